@@ -0,0 +1,123 @@
+//! Cover art for `get_artwork`: a track's embedded picture, or the station logo.
+//!
+//! Only JPEG and PNG are passed on, recognized by their leading bytes rather
+//! than the media type a tag claims. Images over [`MAX_ARTWORK_BYTES`] are
+//! refused; they'd go to the listener in a single RPC response, and byte
+//! arrays grow about elevenfold on the way through the JSON framing.
+
+use log::warn;
+use std::path::Path;
+use symphonia::core::meta::{StandardVisualKey, Visual};
+
+/// Largest image served, embedded or logo; fits an 8 MiB RPC frame with room
+/// to spare once encoded
+pub const MAX_ARTWORK_BYTES: usize = 512 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+}
+
+impl ImageFormat {
+    /// Recognize an image from its first bytes
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Jpeg => "JPEG",
+            Self::Png => "PNG",
+        }
+    }
+}
+
+/// The image's format, if it's one zelfm serves and not too large
+pub fn check(data: &[u8]) -> Result<ImageFormat, String> {
+    if data.len() > MAX_ARTWORK_BYTES {
+        return Err(format!(
+            "image is {} KB, over the {} KB limit",
+            data.len() / 1024,
+            MAX_ARTWORK_BYTES / 1024
+        ));
+    }
+    ImageFormat::detect(data).ok_or_else(|| "not a JPEG or PNG image".to_string())
+}
+
+/// The best picture among a track's visuals: the front cover if it's usable,
+/// otherwise the first usable one
+pub fn from_visuals(visuals: &[Visual]) -> Option<Vec<u8>> {
+    let (covers, others): (Vec<&Visual>, Vec<&Visual>) = visuals
+        .iter()
+        .partition(|visual| visual.usage == Some(StandardVisualKey::FrontCover));
+    covers
+        .into_iter()
+        .chain(others)
+        .find(|visual| match check(&visual.data) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("[Artwork] Skipping embedded {}: {}", visual.media_type, e);
+                false
+            }
+        })
+        .map(|visual| visual.data.to_vec())
+}
+
+/// Read and check a station logo
+pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Can't read logo {}: {}", path.display(), e))?;
+    check(&data).map_err(|e| anyhow::anyhow!("Logo {}: {}", path.display(), e))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visual(usage: Option<StandardVisualKey>, data: &[u8]) -> Visual {
+        Visual {
+            media_type: "image/jpeg".to_string(),
+            dimensions: None,
+            bits_per_pixel: None,
+            color_mode: None,
+            usage,
+            tags: Vec::new(),
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn only_jpeg_and_png_within_the_limit_pass() {
+        assert_eq!(check(b"\xFF\xD8\xFF\xE0rest"), Ok(ImageFormat::Jpeg));
+        assert_eq!(check(b"\x89PNG\r\n\x1a\nrest"), Ok(ImageFormat::Png));
+        assert!(check(b"GIF89a").is_err());
+
+        let mut huge = vec![0u8; MAX_ARTWORK_BYTES + 1];
+        huge[..3].copy_from_slice(&[0xFF, 0xD8, 0xFF]);
+        assert!(check(&huge).is_err());
+    }
+
+    #[test]
+    fn the_front_cover_wins_over_other_pictures() {
+        let back = visual(Some(StandardVisualKey::BackCover), b"\xFF\xD8\xFFback");
+        let front = visual(Some(StandardVisualKey::FrontCover), b"\xFF\xD8\xFFfront");
+        let broken = visual(Some(StandardVisualKey::FrontCover), b"not an image");
+
+        assert_eq!(
+            from_visuals(&[back.clone(), broken.clone(), front]).unwrap(),
+            b"\xFF\xD8\xFFfront"
+        );
+        // An unusable front cover falls back to whatever else there is
+        assert_eq!(from_visuals(&[broken, back]).unwrap(), b"\xFF\xD8\xFFback");
+        assert_eq!(from_visuals(&[]), None);
+    }
+}
@@ -1,7 +1,20 @@
+//! Speaker output for listeners.
+//!
+//! Blocks are queued on a rodio sink, which is held paused until
+//! [`PREBUFFER`] of audio is waiting (showing "Buffering... X%"), so network
+//! jitter doesn't stutter the first seconds. If the queue ever runs dry the
+//! player pauses and refills the same way ("Rebuffering...").
+
 #[cfg(feature = "playback")]
 use rodio::{Decoder, OutputStream, Sink};
 #[cfg(feature = "playback")]
+use std::collections::VecDeque;
+#[cfg(feature = "playback")]
 use std::io::Cursor;
+use std::time::Duration;
+
+/// Audio queued before playback starts or resumes
+pub const PREBUFFER: Duration = Duration::from_millis(500);
 
 #[cfg(feature = "playback")]
 pub struct AudioPlayer {
@@ -9,6 +22,16 @@ pub struct AudioPlayer {
     sink: Sink,
     sample_rate: u32,
     channels: u8,
+    /// Length of each block still in the sink, oldest first
+    queued: VecDeque<Duration>,
+    /// Held paused until the queue reaches [`PREBUFFER`]
+    buffering: bool,
+    /// Playback has started at least once
+    started: bool,
+    /// Times the queue ran dry after playback started
+    underruns: u64,
+    /// Last percentage shown, to redraw only on change
+    shown_percent: Option<u32>,
 }
 
 #[cfg(feature = "playback")]
@@ -16,43 +39,130 @@ impl AudioPlayer {
     pub fn new(sample_rate: u32, channels: u8) -> anyhow::Result<Self> {
         use rodio::OutputStreamBuilder;
 
+        crate::audio_util::check_format(sample_rate, channels as usize)?;
+
         let stream = OutputStreamBuilder::open_default_stream()?;
 
         let mixer = stream.mixer();
         let sink = Sink::connect_new(mixer);
+        sink.pause();
 
         Ok(Self {
             stream,
             sink,
             sample_rate,
             channels,
+            queued: VecDeque::new(),
+            buffering: true,
+            started: false,
+            underruns: 0,
+            shown_percent: None,
         })
     }
 
-    pub fn play_samples(&mut self, samples: &[&[f32]]) -> anyhow::Result<()> {
-        // Convert planar to interleaved
-        let num_channels = samples.len();
-        let num_samples = samples[0].len();
+    /// Audio waiting in the sink
+    pub fn queued(&mut self) -> Duration {
+        // Blocks the sink has finished with are gone from its queue
+        while self.queued.len() > self.sink.len() {
+            self.queued.pop_front();
+        }
+        self.queued.iter().sum()
+    }
+
+    /// Times playback ran out of audio and had to rebuffer
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+
+    /// Pause on an underrun; start or resume once the queue is full enough
+    fn update_buffering(&mut self) {
+        use std::io::Write;
+
+        let queued = self.queued();
+        if !self.buffering {
+            if self.sink.empty() {
+                self.underruns += 1;
+                self.buffering = true;
+                self.shown_percent = None;
+                self.sink.pause();
+                log::warn!("[Player] Underrun #{}, rebuffering", self.underruns);
+                eprint!("\rRebuffering...");
+                let _ = std::io::stderr().flush();
+            }
+            return;
+        }
+
+        if queued >= PREBUFFER {
+            self.buffering = false;
+            self.sink.play();
+            if self.started {
+                log::info!("[Player] Resumed");
+            } else {
+                log::info!("[Player] Buffered {} ms, playing", queued.as_millis());
+            }
+            self.started = true;
+            eprintln!("\rPlaying            ");
+            return;
+        }
 
-        let mut interleaved = Vec::with_capacity(num_channels * num_samples);
-        for i in 0..num_samples {
-            for channel in samples {
-                interleaved.push(channel[i]);
+        if !self.started {
+            let percent = (queued.as_secs_f64() / PREBUFFER.as_secs_f64() * 100.0) as u32;
+            if self.shown_percent != Some(percent) {
+                self.shown_percent = Some(percent);
+                eprint!("\rBuffering... {}%", percent);
+                let _ = std::io::stderr().flush();
             }
         }
+    }
+
+    pub fn play_samples(&mut self, samples: &[&[f32]]) -> anyhow::Result<()> {
+        // Convert planar to interleaved
+        let interleaved = crate::audio_util::planar_to_interleaved(samples);
+        if interleaved.is_empty() {
+            return Ok(());
+        }
 
+        let frames = interleaved.len() / self.channels.max(1) as usize;
         let source =
             rodio::buffer::SamplesBuffer::new(self.channels as u16, self.sample_rate, interleaved);
 
+        // An underrun is only noticed here, as the next block arrives
+        if !self.buffering {
+            self.update_buffering();
+        }
         self.sink.append(source);
+        self.queued.push_back(Duration::from_secs_f64(
+            frames as f64 / self.sample_rate as f64,
+        ));
+        if self.buffering {
+            self.update_buffering();
+        }
         Ok(())
     }
 
     pub fn finish(self) {
+        self.sink.play();
         self.sink.sleep_until_end();
     }
 }
 
+#[cfg(feature = "playback")]
+impl crate::listener::PcmSink for AudioPlayer {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        self.play_samples(samples)?;
+        Ok(true)
+    }
+
+    fn finish(&mut self) {
+        // A stream shorter than the prebuffer still plays out
+        self.sink.play();
+        self.sink.sleep_until_end();
+        if self.underruns > 0 {
+            log::info!("[Player] {} underruns during playback", self.underruns);
+        }
+    }
+}
+
 // Stub when playback disabled
 #[cfg(not(feature = "playback"))]
 pub struct AudioPlayer;
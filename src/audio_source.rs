@@ -1,87 +1,436 @@
-use log::{error, info};
-use std::path::PathBuf;
+use log::{debug, error, info, warn};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use symphonia::core::audio::{AudioBuffer, Signal};
 use tokio::sync::broadcast;
 
+use crate::audio_util::{
+    check_format, conform_channels, interleaved_to_planar, sanitize, PlanarDecodeBuffer,
+    SanitizeLog, SOURCE_CHANNELS,
+};
+use crate::fade::Fader;
+use crate::levels::LevelMeter;
+use crate::playlist::{DirectoryScan, PlaylistEntry, Repeat, TrackSettings};
+use crate::service::{SourceCapabilities, StationEvent};
+use crate::tags::TrackTags;
+
 type AudioBlock = Vec<Vec<f32>>; // [channels][samples]
 
 /// Trait for audio sources that can broadcast PCM audio blocks
 pub trait AudioSource: Send + 'static {
-    fn start(self, pcm_tx: broadcast::Sender<AudioBlock>) -> anyhow::Result<()>;
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()>;
+
+    /// Describe what this source supports (seeking, metadata, native format)
+    fn capabilities(&self) -> SourceCapabilities;
+}
+
+/// Track and pause events kept for a slow `event_stream` subscriber
+const SOURCE_EVENT_CAPACITY: usize = 32;
+
+/// Operator controls shared with a running source (skip, pause), and what
+/// it's playing
+#[derive(Clone)]
+pub struct SourceControl {
+    skip: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    /// Tracks started so far
+    track: Arc<AtomicU64>,
+    /// The current track's cover art
+    artwork: Arc<Mutex<Option<Arc<Vec<u8>>>>>,
+    /// The latest [`StationEvent::TrackChanged`], for new `event_stream`
+    /// subscribers
+    current_track: Arc<Mutex<Option<StationEvent>>>,
+    /// Codec and sample format of the track playing now, for sources that
+    /// change them between tracks
+    capabilities: Arc<Mutex<Option<SourceCapabilities>>>,
+    /// Track changes and pause/resume, for `event_stream`
+    events: broadcast::Sender<StationEvent>,
+}
+
+impl Default for SourceControl {
+    fn default() -> Self {
+        Self {
+            skip: Arc::default(),
+            paused: Arc::default(),
+            track: Arc::default(),
+            artwork: Arc::default(),
+            current_track: Arc::default(),
+            capabilities: Arc::default(),
+            events: broadcast::channel(SOURCE_EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl SourceControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track changes, pauses, and resumes from here on
+    pub fn events(&self) -> broadcast::Receiver<StationEvent> {
+        self.events.subscribe()
+    }
+
+    /// Abandon the current track and move on
+    pub fn skip(&self) {
+        self.skip.store(true, Ordering::Relaxed);
+    }
+
+    /// Hold the source where it is; live input is discarded while paused
+    pub fn pause(&self) {
+        if !self.paused.swap(true, Ordering::Relaxed) {
+            let _ = self.events.send(StationEvent::Paused);
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::Relaxed) {
+            let _ = self.events.send(StationEvent::Resumed);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Changes each time the source starts a track (jingles included)
+    pub fn track(&self) -> u64 {
+        self.track.load(Ordering::Relaxed)
+    }
+
+    /// Cover art embedded in the current track, if it has any
+    pub fn artwork(&self) -> Option<Arc<Vec<u8>>> {
+        self.artwork.lock().unwrap().clone()
+    }
+
+    /// Publish a new track: its cover art (or its lack) for `get_artwork`,
+    /// and a [`StationEvent::TrackChanged`]
+    pub fn start_track(&self, title: Option<String>, artwork: Option<Vec<u8>>) {
+        *self.artwork.lock().unwrap() = artwork.map(Arc::new);
+        let event = StationEvent::TrackChanged { title };
+        *self.current_track.lock().unwrap() = Some(event.clone());
+        let _ = self.events.send(event);
+    }
+
+    /// What the latest track announced, for listeners who tuned in after it
+    /// started (`None` before the first track)
+    pub fn current_track(&self) -> Option<StationEvent> {
+        self.current_track.lock().unwrap().clone()
+    }
+
+    /// The current track's capabilities, once a playlist has started one
+    pub fn capabilities(&self) -> Option<SourceCapabilities> {
+        self.capabilities.lock().unwrap().clone()
+    }
+
+    fn set_capabilities(&self, capabilities: SourceCapabilities) {
+        *self.capabilities.lock().unwrap() = Some(capabilities);
+    }
+
+    /// Consume a pending skip, if any
+    fn take_skip(&self) -> bool {
+        self.skip.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Where decoded blocks go: channel conforming, sanitizing, backpressure,
+/// fades, metering, then the broadcast channel
+struct BlockSender<'a> {
+    pcm_tx: &'a broadcast::Sender<Arc<AudioBlock>>,
+    max_queued: Option<usize>,
+    meter: Option<&'a LevelMeter>,
+    fader: Option<&'a Fader>,
+    control: Option<&'a SourceControl>,
+    /// Hold decoded audio to wall-clock speed (`--realtime`), or to within
+    /// some seconds of it (`--max-decode-ahead-secs`)
+    pacer: Option<Pacer>,
+    /// Drop each track's leading and trailing audio at or below this linear
+    /// level (`--trim-silence`)
+    trim_silence: Option<f32>,
+    sanitized: RefCell<SanitizeLog>,
+}
+
+/// How far ahead of the wall clock a paced source may run, so encoders
+/// never wait on it
+const PACE_LEAD: Duration = Duration::from_millis(200);
+
+/// Releases decoded audio at the rate it plays
+struct Pacer {
+    started: Cell<Instant>,
+    sent: Cell<Duration>,
+    /// How far ahead of the wall clock the source may run
+    lead: Duration,
+}
+
+impl Pacer {
+    fn new() -> Self {
+        Self::with_lead(PACE_LEAD)
+    }
+
+    fn with_lead(lead: Duration) -> Self {
+        Self {
+            started: Cell::new(Instant::now()),
+            sent: Cell::new(Duration::ZERO),
+            lead,
+        }
+    }
+
+    /// `--realtime` holds a source to [`PACE_LEAD`]; otherwise `max_ahead`
+    /// caps it, if set
+    fn for_source(realtime: bool, max_ahead: Option<Duration>) -> Option<Self> {
+        if realtime {
+            Some(Self::new())
+        } else {
+            max_ahead.map(Self::with_lead)
+        }
+    }
+
+    /// Account for `frames` just sent at `rate`, sleeping until the clock catches up
+    fn pace(&self, frames: usize, rate: f64) {
+        let sent = self.sent.get() + Duration::from_secs_f64(frames as f64 / rate);
+        self.sent.set(sent);
+
+        let elapsed = self.started.get().elapsed();
+        if elapsed > sent + PACE_LEAD {
+            // Fell behind (paused, slow decode); restart the clock rather than
+            // bursting to catch up. However long the lead, a source that's
+            // behind is behind by the same measure
+            self.started.set(Instant::now() - sent);
+        } else if let Some(ahead) = sent.checked_sub(elapsed + self.lead) {
+            std::thread::sleep(ahead);
+        }
+    }
+}
+
+impl BlockSender<'_> {
+    /// Announce a new track through the source control, if there is one
+    fn start_track(&self, title: Option<String>, tags: &TrackTags) {
+        if let Some(control) = self.control {
+            control.start_track(title, tags.artwork.clone());
+        }
+    }
+
+    fn set_capabilities(&self, capabilities: SourceCapabilities) {
+        if let Some(control) = self.control {
+            control.set_capabilities(capabilities);
+        }
+    }
+
+    fn send(&self, planar: AudioBlock) {
+        // Mono and multichannel files play on a stereo station
+        let mut planar = conform_channels(planar, SOURCE_CHANNELS);
+        self.sanitized.borrow_mut().record(sanitize(&mut planar));
+
+        // Paused: hold the decoder (a skip still gets through)
+        if let Some(control) = self.control {
+            while control.is_paused() && !control.skip.load(Ordering::Relaxed) {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+        }
+
+        // Backpressure: hold off until the slowest listener has room
+        if let Some(limit) = self.max_queued {
+            while self.pcm_tx.receiver_count() > 0 && self.pcm_tx.len() >= limit {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        }
+
+        if let Some(fader) = self.fader {
+            fader.apply(&mut planar);
+        }
+
+        if let Some(meter) = self.meter {
+            meter.update(&planar);
+        }
+
+        // Send to broadcast channel - it's OK if there are zero receivers
+        let _ = self.pcm_tx.send(Arc::new(planar));
+    }
 }
 
 // ============================================================================
 // File Source (existing functionality)
 // ============================================================================
 
+#[derive(Clone)]
 pub struct FileSource {
     pub path: PathBuf,
+    /// Wait while this many blocks are still queued for the slowest listener
+    pub max_queued: Option<usize>,
+    /// Stop after this many complete passes (`None` loops forever)
+    pub repeat: Option<u32>,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+    pub control: Option<SourceControl>,
+    /// Decode at playback speed instead of as fast as listeners take it
+    pub realtime: bool,
+    /// Never decode more than this far ahead of playback
+    pub max_ahead: Option<Duration>,
+    /// Apply the file's `REPLAYGAIN_TRACK_GAIN` tag
+    pub replay_gain: bool,
+    /// Trim leading and trailing silence below this many dBFS
+    pub trim_silence: Option<f32>,
 }
 
 impl FileSource {
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            max_queued: None,
+            repeat: None,
+            meter: None,
+            fader: None,
+            control: None,
+            realtime: false,
+            max_ahead: None,
+            replay_gain: false,
+            trim_silence: None,
+        }
+    }
+
+    /// Release audio at wall-clock pace instead of decoding ahead
+    pub fn with_realtime(mut self) -> Self {
+        self.realtime = true;
+        self
+    }
+
+    /// Let decoding run at most `ahead` past playback, then wait for the clock
+    pub fn with_max_decode_ahead(mut self, ahead: Duration) -> Self {
+        self.max_ahead = Some(ahead);
+        self
+    }
+
+    /// Normalize with the file's ReplayGain track gain, if it has one
+    pub fn with_replay_gain(mut self) -> Self {
+        self.replay_gain = true;
+        self
+    }
+
+    /// Drop leading and trailing audio quieter than `threshold_db` dBFS
+    pub fn with_silence_trim(mut self, threshold_db: f32) -> Self {
+        self.trim_silence = Some(threshold_db);
+        self
+    }
+
+    /// Let the operator skip or pause the file
+    pub fn with_control(mut self, control: SourceControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade in at the start and out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+
+    /// Play the file `times` times then end; 0 means loop forever
+    pub fn with_repeat(mut self, times: u32) -> Self {
+        self.repeat = (times > 0).then_some(times);
+        self
+    }
+
+    /// Apply backpressure instead of letting slow listeners drop blocks
+    pub fn with_backpressure(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
     }
 }
 
 impl AudioSource for FileSource {
-    fn start(self, pcm_tx: broadcast::Sender<AudioBlock>) -> anyhow::Result<()> {
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
         info!(
             "[FileSource] Starting file decoder for: {}",
             self.path.display()
         );
-        file_decode_loop(&self.path, pcm_tx)
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: self.max_queued,
+            meter: self.meter.as_deref(),
+            fader: self.fader.as_deref(),
+            control: self.control.as_ref(),
+            pacer: Pacer::for_source(self.realtime, self.max_ahead),
+            trim_silence: self.trim_silence.map(db_to_level),
+            sanitized: RefCell::new(SanitizeLog::new("File")),
+        };
+        file_decode_loop(&self.path, self.repeat, self.replay_gain, &sender)
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        file_capabilities(&self.path)
     }
 }
 
-fn file_decode_loop(
-    file_path: &PathBuf,
-    pcm_tx: broadcast::Sender<AudioBlock>,
-) -> anyhow::Result<()> {
-    use std::fs::File;
-    use symphonia::core::audio::SampleBuffer;
-    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
-    use symphonia::core::errors::Error as SymphoniaError;
-    use symphonia::core::formats::FormatOptions;
-    use symphonia::core::io::MediaSourceStream;
-    use symphonia::core::meta::MetadataOptions;
-    use symphonia::core::probe::Hint;
+/// Probe a file's codec and sample format for [`SourceCapabilities`]
+fn file_capabilities(path: &Path) -> SourceCapabilities {
+    // Best effort - an unreadable file is reported when the decode loop starts
+    match open_format(path) {
+        Ok(format) => format_capabilities(format.as_ref()),
+        Err(_) => SourceCapabilities {
+            has_metadata: true,
+            ..Default::default()
+        },
+    }
+}
 
-    info!("[File] Starting decode loop for: {}", file_path.display());
+/// Codec and sample format of an opened file's first audio track
+///
+/// Not seekable: listeners get the station's live output, with no way to
+/// move within the file.
+fn format_capabilities(format: &dyn symphonia::core::formats::FormatReader) -> SourceCapabilities {
+    use symphonia::core::codecs::CODEC_TYPE_NULL;
 
-    loop {
-        info!("[File] Decoding iteration starting...");
+    let mut caps = SourceCapabilities {
+        seekable: false,
+        has_metadata: true,
+        codec: "unknown".to_string(),
+        sample_format: "unknown".to_string(),
+    };
 
-        match decode_file_once(file_path, &pcm_tx) {
-            Ok(true) => {
-                info!("[File] Decode complete, looping...");
-            }
-            Ok(false) => {
-                info!("[File] Channel closed, shutting down...");
-                break;
-            }
-            Err(e) => {
-                error!("[File] Decode error: {}", e);
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
+    if let Some(track) = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    {
+        let params = &track.codec_params;
+        if let Some(desc) = symphonia::default::get_codecs().get_codec(params.codec) {
+            caps.codec = desc.short_name.to_string();
         }
+        caps.sample_format = match (params.sample_format, params.bits_per_sample) {
+            (Some(fmt), _) => format!("{:?}", fmt).to_lowercase(),
+            (None, Some(bits)) => format!("{}-bit", bits),
+            (None, None) => "unknown".to_string(),
+        };
     }
 
-    info!("[File] Decode loop exited");
+    caps
+}
 
-    Ok(())
+/// Open and probe a media file, returning its format reader
+fn open_format(
+    file_path: &Path,
+) -> anyhow::Result<Box<dyn symphonia::core::formats::FormatReader>> {
+    Ok(open_probed(file_path)?.format)
 }
 
-fn decode_file_once(
-    file_path: &PathBuf,
-    pcm_tx: &broadcast::Sender<AudioBlock>,
-) -> anyhow::Result<bool> {
+/// Open and probe a media file, keeping any metadata found while probing
+fn open_probed(file_path: &Path) -> anyhow::Result<symphonia::core::probe::ProbeResult> {
     use std::fs::File;
-    use symphonia::core::audio::SampleBuffer;
-    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
-    use symphonia::core::errors::Error as SymphoniaError;
-    use symphonia::core::formats::FormatOptions;
     use symphonia::core::io::MediaSourceStream;
-    use symphonia::core::meta::MetadataOptions;
     use symphonia::core::probe::Hint;
 
     let file = File::open(file_path)?;
@@ -94,165 +443,2231 @@ fn decode_file_once(
         }
     }
 
-    let probed = symphonia::default::get_probe().format(
-        &hint,
-        mss,
-        &FormatOptions::default(),
-        &MetadataOptions::default(),
-    )?;
+    probe_stream(mss, &hint)
+}
 
-    let mut format = probed.format;
+/// Probe any media stream (file, pipe, ...) for its container format
+fn probe_stream(
+    mss: symphonia::core::io::MediaSourceStream,
+    hint: &symphonia::core::probe::Hint,
+) -> anyhow::Result<symphonia::core::probe::ProbeResult> {
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::meta::MetadataOptions;
+
+    // Some demuxers panic on impossible headers (a 0 Hz WAV, for one) rather
+    // than returning an error
+    let probed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        symphonia::default::get_probe().format(
+            hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+    }))
+    .map_err(|_| anyhow::anyhow!("Malformed audio: unreadable container header"))??;
+
+    Ok(probed)
+}
+
+/// What `zelfm probe` reports about a file
+#[derive(Debug, Clone)]
+pub struct ProbeReport {
+    pub codec: String,
+    pub sample_format: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    pub duration_secs: Option<f64>,
+    /// Tags from the container and any leading ID3/APE blocks, in file order
+    pub tags: Vec<(String, String)>,
+}
+
+/// Tags from any leading ID3/APE blocks, then the container's own (Vorbis
+/// comments for OGG and FLAC)
+fn probed_tags(
+    probed: &mut symphonia::core::probe::ProbeResult,
+) -> Vec<symphonia::core::meta::Tag> {
+    let mut tags = Vec::new();
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            tags.extend_from_slice(revision.tags());
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend_from_slice(revision.tags());
+    }
+    tags
+}
+
+/// Cover art from any leading ID3 block or the container's own pictures
+fn probed_artwork(probed: &mut symphonia::core::probe::ProbeResult) -> Option<Vec<u8>> {
+    let mut visuals = Vec::new();
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            visuals.extend_from_slice(revision.visuals());
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        visuals.extend_from_slice(revision.visuals());
+    }
+    crate::artwork::from_visuals(&visuals)
+}
+
+/// Probe a file and decode its first packets to prove it's playable
+pub fn probe_file(path: impl AsRef<Path>) -> anyhow::Result<ProbeReport> {
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::errors::Error as SymphoniaError;
+
+    /// Give up if this many packets in a row fail to decode
+    const MAX_DECODE_ATTEMPTS: usize = 50;
+
+    let path = path.as_ref();
+    let mut probed = open_probed(path)?;
 
+    let tags = probed_tags(&mut probed)
+        .into_iter()
+        .map(|t| (t.key, t.value.to_string()))
+        .collect();
+
+    let mut format = probed.format;
     let track = format
         .tracks()
         .iter()
         .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
         .ok_or_else(|| anyhow::anyhow!("No audio track"))?;
-
     let track_id = track.id;
-    let codec_params = &track.codec_params;
-
-    let detected_rate = codec_params.sample_rate.unwrap_or(44100);
-    let detected_channels = codec_params.channels.map(|c| c.count()).unwrap_or(2);
-
-    info!(
-        "[File] Detected format: {} Hz, {} ch",
-        detected_rate, detected_channels
-    );
-
-    let mut decoder =
-        symphonia::default::get_codecs().make(&codec_params, &DecoderOptions::default())?;
+    let params = track.codec_params.clone();
+    check_format(
+        params.sample_rate.unwrap_or(44100),
+        params.channels.map_or(2, |c| c.count()),
+    )?;
 
-    let mut sample_buf: Option<SampleBuffer<f32>> = None;
-    let mut audio_spec = None;
+    let caps = file_capabilities(path);
+    let duration_secs = match (params.n_frames, params.time_base, params.sample_rate) {
+        (Some(frames), Some(time_base), _) => {
+            let time = time_base.calc_time(frames);
+            Some(time.seconds as f64 + time.frac)
+        }
+        (Some(frames), None, Some(rate)) => Some(frames as f64 / rate as f64),
+        _ => None,
+    };
 
+    // Probing only reads headers; decode a little to catch unsupported codecs
+    let mut decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+    let mut attempts = 0;
     loop {
         let packet = match format.next_packet() {
             Ok(packet) => packet,
             Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                break
+                anyhow::bail!("No decodable audio before end of file")
             }
-            Err(SymphoniaError::ResetRequired) => break,
             Err(e) => return Err(e.into()),
         };
-
         if packet.track_id() != track_id {
             continue;
         }
-
-        let decoded = match decoder.decode(&packet) {
-            Ok(buf) => buf,
-            Err(SymphoniaError::DecodeError(_)) => continue,
+        match decoder.decode(&packet) {
+            Ok(_) => break,
+            Err(SymphoniaError::DecodeError(e)) => {
+                attempts += 1;
+                if attempts >= MAX_DECODE_ATTEMPTS {
+                    anyhow::bail!("Audio doesn't decode: {}", e);
+                }
+            }
             Err(e) => return Err(e.into()),
-        };
-
-        if sample_buf.is_none() {
-            audio_spec = Some(*decoded.spec());
-            let duration = decoded.capacity() as u64;
-            sample_buf = Some(SampleBuffer::<f32>::new(duration, audio_spec.unwrap()));
         }
+    }
 
-        if let Some(buf) = &mut sample_buf {
-            buf.copy_interleaved_ref(decoded);
+    Ok(ProbeReport {
+        codec: caps.codec,
+        sample_format: caps.sample_format,
+        sample_rate: params.sample_rate,
+        channels: params.channels.map(|c| c.count()),
+        duration_secs,
+        tags,
+    })
+}
 
-            let interleaved = buf.samples();
-            let num_channels = audio_spec.unwrap().channels.count();
-            let frames = interleaved.len() / num_channels;
+/// Retries of a failing track before giving up on it
+pub const DECODE_MAX_RETRIES: u32 = 5;
 
-            let mut planar = vec![Vec::with_capacity(frames); num_channels];
-            for (i, &sample) in interleaved.iter().enumerate() {
-                planar[i % num_channels].push(sample);
-            }
+/// First retry delay after a decode error; doubles per failure
+const DECODE_RETRY_BASE: Duration = Duration::from_secs(1);
 
-            // Send to broadcast channel - it's OK if there are zero receivers
-            let _ = pcm_tx.send(planar);
-        }
-    }
+/// Longest wait between decode retries
+const DECODE_RETRY_MAX: Duration = Duration::from_secs(30);
 
-    Ok(true)
+/// Exponential backoff between retries of a failing decode
+#[derive(Default)]
+struct Backoff {
+    failures: u32,
 }
 
-// ============================================================================
-// Live Source (CPAL input capture)
-// ============================================================================
-
-#[cfg(feature = "live-input")]
-pub struct LiveSource {
-    pub device_name: Option<String>,
-}
+impl Backoff {
+    /// Delay before the next retry, or `None` once retries are used up
+    fn next_delay(&mut self) -> Option<Duration> {
+        if self.failures >= DECODE_MAX_RETRIES {
+            return None;
+        }
+        let delay = DECODE_RETRY_BASE
+            .saturating_mul(1 << self.failures)
+            .min(DECODE_RETRY_MAX);
+        self.failures += 1;
+        Some(delay)
+    }
 
-#[cfg(feature = "live-input")]
-impl LiveSource {
-    pub fn new(device_name: Option<String>) -> Self {
-        Self { device_name }
+    fn reset(&mut self) {
+        self.failures = 0;
     }
 }
 
-#[cfg(feature = "live-input")]
-impl AudioSource for LiveSource {
-    fn start(self, pcm_tx: broadcast::Sender<AudioBlock>) -> anyhow::Result<()> {
-        use crate::devices::find_device_by_name;
-        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-
-        let host = cpal::default_host();
+/// Errors retrying can't fix: missing or unreadable file, unsupported codec
+fn is_permanent(e: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+    use symphonia::core::errors::Error as SymphoniaError;
 
-        // Select device
-        let device = if let Some(name) = &self.device_name {
-            find_device_by_name(&host, name)?
+    let permanent_io = |e: &std::io::Error| {
+        matches!(
+            e.kind(),
+            ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::IsADirectory
+        )
+    };
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<SymphoniaError>() {
+            match e {
+                SymphoniaError::Unsupported(_) => true,
+                SymphoniaError::IoError(e) => permanent_io(e),
+                _ => false,
+            }
         } else {
-            host.default_input_device()
-                .ok_or_else(|| anyhow::anyhow!("No default input device"))?
-        };
+            cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(permanent_io)
+        }
+    })
+}
 
-        let device_name = device.name()?;
-        let config = device.default_input_config()?;
-        let sample_rate = config.sample_rate().0;
-        let channels = config.channels() as usize;
+fn file_decode_loop(
+    file_path: &Path,
+    repeat: Option<u32>,
+    replay_gain: bool,
+    sender: &BlockSender,
+) -> anyhow::Result<()> {
+    info!("[File] Starting decode loop for: {}", file_path.display());
 
-        println!("[Live] Device: {}", device_name);
-        println!("[Live] Format: {} Hz, {} ch", sample_rate, channels);
+    let mut passes = 0;
+    let mut backoff = Backoff::default();
 
-        // Build input stream
-        let stream = device.build_input_stream(
-            &config.into(),
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                // Convert interleaved to planar
-                let frames = data.len() / channels;
-                let mut planar = vec![Vec::with_capacity(frames); channels];
-                for (i, &sample) in data.iter().enumerate() {
-                    planar[i % channels].push(sample);
-                }
+    loop {
+        info!("[File] Decoding iteration starting...");
 
-                // Upmix mono to stereo if needed (broadcaster expects 2 channels)
-                if channels == 1 && planar.len() == 1 {
-                    let mono_channel = planar[0].clone();
-                    planar.push(mono_channel); // Duplicate for stereo
+        match decode_file_once(file_path, replay_gain, sender) {
+            Ok(_) => {
+                backoff.reset();
+                passes += 1;
+                if repeat.is_some_and(|limit| passes >= limit) {
+                    info!("[File] Played {} time(s), finished", passes);
+                    break;
                 }
-
-                // Broadcast to all listeners
-                let _ = pcm_tx.send(planar);
+                info!("[File] Decode complete, looping...");
+            }
+            Err(e) if is_permanent(&e) => {
+                anyhow::bail!("Can't play {}: {}", file_path.display(), e);
+            }
+            Err(e) => match backoff.next_delay() {
+                Some(delay) => {
+                    error!(
+                        "[File] Decode error: {} (retrying in {}s)",
+                        e,
+                        delay.as_secs()
+                    );
+                    std::thread::sleep(delay);
+                }
+                None => anyhow::bail!(
+                    "Giving up on {} after {} retries: {}",
+                    file_path.display(),
+                    DECODE_MAX_RETRIES,
+                    e
+                ),
             },
-            |err| error!("[Live] Stream error: {}", err),
-            None,
-        )?;
+        }
+    }
 
-        stream.play()?;
+    info!("[File] Decode loop exited");
 
-        println!("[Live] Streaming... (Press Ctrl+C to stop)");
+    Ok(())
+}
 
-        // Keep stream alive by moving it into the loop
-        // Process exit will clean it up
-        loop {
-            std::thread::sleep(std::time::Duration::from_millis(100));
+fn decode_file_once(
+    file_path: &Path,
+    replay_gain: bool,
+    sender: &BlockSender,
+) -> anyhow::Result<TrackEnd> {
+    let (format, tags) = open_track(file_path)?;
+    let name = tags.now_playing();
+    if let Some(name) = &name {
+        info!("[File] Now playing: {}", name);
+    }
+    sender.start_track(name, &tags);
+    let settings = track_settings(&TrackSettings::default(), &tags, replay_gain);
+    decode_format(format, sender, &settings)
+}
 
-            // Stream is kept alive by this loop
-            // When main thread exits (Ctrl+C), this thread is terminated
-        }
+/// Open a track for playback along with its tags
+fn open_track(
+    path: &Path,
+) -> anyhow::Result<(Box<dyn symphonia::core::formats::FormatReader>, TrackTags)> {
+    let mut probed = open_probed(path)?;
+    let mut tags = TrackTags::from_tags(&probed_tags(&mut probed));
+    tags.artwork = probed_artwork(&mut probed);
+    Ok((probed.format, tags))
+}
 
-        #[allow(unreachable_code)]
-        {
-            drop(stream);
-            Ok(())
+/// `settings` with the track's ReplayGain added on top, when enabled
+fn track_settings(settings: &TrackSettings, tags: &TrackTags, replay_gain: bool) -> TrackSettings {
+    let mut settings = settings.clone();
+    if let Some(gain_db) = tags.replay_gain_db.filter(|_| replay_gain) {
+        settings.gain_db += gain_db;
+    }
+    settings
+}
+
+/// Pick the first audio track and build a decoder for it
+fn open_decoder(
+    format: &dyn symphonia::core::formats::FormatReader,
+) -> anyhow::Result<(
+    u32,
+    Option<symphonia::core::units::TimeBase>,
+    Box<dyn symphonia::core::codecs::Decoder>,
+)> {
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| anyhow::anyhow!("No audio track"))?;
+    let codec_params = &track.codec_params;
+
+    let detected_rate = codec_params.sample_rate.unwrap_or(44100);
+    let detected_channels = codec_params.channels.map(|c| c.count()).unwrap_or(2);
+    check_format(detected_rate, detected_channels)?;
+
+    info!(
+        "[Decode] Detected format: {} Hz, {} ch",
+        detected_rate, detected_channels
+    );
+
+    let decoder =
+        symphonia::default::get_codecs().make(codec_params, &DecoderOptions::default())?;
+    Ok((track.id, codec_params.time_base, decoder))
+}
+
+/// Whether the track carries a codec that is chained in OGG (Vorbis or Opus)
+fn is_ogg_codec(format: &dyn symphonia::core::formats::FormatReader, track_id: u32) -> bool {
+    use symphonia::core::codecs::{CODEC_TYPE_OPUS, CODEC_TYPE_VORBIS};
+
+    format
+        .tracks()
+        .iter()
+        .find(|t| t.id == track_id)
+        .is_some_and(|t| [CODEC_TYPE_VORBIS, CODEC_TYPE_OPUS].contains(&t.codec_params.codec))
+}
+
+/// How a track's decode ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrackEnd {
+    /// The stream (or trimmed span) was exhausted
+    Finished,
+    /// The operator skipped it
+    Skipped,
+}
+
+/// Decode every packet of the first audio track into planar PCM blocks
+///
+/// `settings` trims the start/end and applies gain.
+fn decode_format(
+    mut format: Box<dyn symphonia::core::formats::FormatReader>,
+    sender: &BlockSender,
+    settings: &TrackSettings,
+) -> anyhow::Result<TrackEnd> {
+    use symphonia::core::errors::Error as SymphoniaError;
+    use symphonia::core::formats::{SeekMode, SeekTo};
+
+    let (mut track_id, mut time_base, mut decoder) = open_decoder(format.as_ref())?;
+    if let Some(control) = sender.control {
+        control.track.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Jump near the intro trim; packets are then trimmed to the exact frame
+    if let Some(start) = settings.start_secs {
+        let seek = format.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time: start.into(),
+                track_id: Some(track_id),
+            },
+        );
+        match seek {
+            Ok(_) => decoder.reset(),
+            Err(e) => warn!(
+                "[Decode] Can't seek to {:.1}s, decoding up to it: {}",
+                start, e
+            ),
+        }
+    } else if is_ogg_codec(format.as_ref(), track_id) {
+        // Probing a seekable chained OGG for its length leaves the demuxer on
+        // the next link's first page, which drops a first link that fits in a
+        // single page; rewinding puts it back on the first audio page
+        if format
+            .seek(SeekMode::Coarse, SeekTo::TimeStamp { ts: 0, track_id })
+            .is_ok()
+        {
+            decoder.reset();
+        }
+    }
+
+    let gain = settings.gain();
+    let mut planar_buf = PlanarDecodeBuffer::default();
+    let mut pending = PendingBlock::default();
+    let mut silence = sender
+        .trim_silence
+        .map(|threshold| SilenceTrim::new(threshold / gain));
+    let mut audio_spec = None;
+    // Fallback clock for formats without a time base
+    let mut next_packet_secs = 0.0;
+
+    loop {
+        // Checked per packet so a skip lands within a few milliseconds
+        if sender.control.is_some_and(|control| control.take_skip()) {
+            info!("[Decode] Skipping to next track");
+            return Ok(TrackEnd::Skipped);
+        }
+
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(SymphoniaError::ResetRequired) => {
+                // The stream changed underneath us (e.g. the next link of a chained
+                // OGG); decode the new track with a fresh decoder
+                info!("[Decode] Stream reset, recreating decoder");
+                pending.send(sender, gain);
+                (track_id, time_base, decoder) = open_decoder(format.as_ref())?;
+                audio_spec = None;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let packet_secs = time_base.map_or(next_packet_secs, |tb| {
+            let time = tb.calc_time(packet.ts());
+            time.seconds as f64 + time.frac
+        });
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(buf) => buf,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = match audio_spec {
+            Some(spec) => spec,
+            None => {
+                let spec = *decoded.spec();
+                check_format(spec.rate, spec.channels.count())?;
+                *audio_spec.insert(spec)
+            }
+        };
+
+        let rate = spec.rate as f64;
+        let frames = decoded.frames();
+        next_packet_secs = packet_secs + frames as f64 / rate;
+        let past_end = settings.end_secs.is_some_and(|end| next_packet_secs >= end);
+
+        // Trim by copying only the kept frames out of the decoder's buffer
+        let (mut from, mut to) = (0, frames);
+        if settings.is_trimmed() {
+            let frame_at =
+                |secs: f64| (((secs - packet_secs) * rate).round().max(0.0) as usize).min(frames);
+            to = settings.end_secs.map_or(frames, frame_at);
+            from = settings.start_secs.map_or(0, frame_at).min(to);
+        }
+
+        if from < to {
+            let buf = planar_buf.as_f32(&decoded);
+            match &mut silence {
+                Some(silence) => silence.append(buf, from, to, &mut pending),
+                None => pending.append(buf, from, to),
+            }
+            if pending.frames() >= DECODE_BLOCK_FRAMES {
+                pending.send(sender, gain);
+            }
+        }
+        if past_end {
+            break;
+        }
+    }
+
+    // Whatever silence is still held is the track's tail
+    if let Some(frames) = silence
+        .map(|silence| silence.held_frames())
+        .filter(|&n| n > 0)
+    {
+        debug!("[Decode] Trimmed {} frames of trailing silence", frames);
+    }
+    pending.send(sender, gain);
+    Ok(TrackEnd::Finished)
+}
+
+/// Frames gathered from decoder packets before a block is sent
+pub const DECODE_BLOCK_FRAMES: usize = 2048;
+
+/// Decoded audio batched into blocks of at least [`DECODE_BLOCK_FRAMES`]
+///
+/// Decoders hand back small packets (1152 frames for MP3, often fewer for
+/// Vorbis and AAC), and every block sent is copied for each listener and
+/// sanitized, faded, and metered on its own. Batching means fewer, larger
+/// blocks, each allocated at full size up front so appending doesn't regrow it.
+#[derive(Default)]
+struct PendingBlock {
+    block: AudioBlock,
+    rate: f64,
+}
+
+impl PendingBlock {
+    fn frames(&self) -> usize {
+        self.block.first().map_or(0, Vec::len)
+    }
+
+    /// Add frames `from..to` of a decoded packet
+    fn append(&mut self, buf: &AudioBuffer<f32>, from: usize, to: usize) {
+        if self.block.is_empty() {
+            // Room for a full batch plus the packet that completes it
+            let capacity = DECODE_BLOCK_FRAMES + buf.capacity();
+            self.block = (0..buf.spec().channels.count())
+                .map(|_| Vec::with_capacity(capacity))
+                .collect();
+            self.rate = buf.spec().rate as f64;
+        }
+        for (channel, samples) in self.block.iter_mut().enumerate() {
+            samples.extend_from_slice(&buf.chan(channel)[from..to]);
+        }
+    }
+
+    /// Add frames at `rate` held back earlier, emptying `held`
+    fn append_held(&mut self, held: &mut AudioBlock, rate: f64) {
+        if self.block.is_empty() {
+            self.block = std::mem::take(held);
+            self.rate = rate;
+        }
+        for (samples, held) in self.block.iter_mut().zip(held.iter_mut()) {
+            samples.append(held);
+        }
+    }
+
+    /// Apply `gain` and send what's gathered so far, if anything
+    ///
+    /// Held silence released in one go can run to [`MAX_HELD_SILENCE`], so
+    /// anything past two batches goes out as [`DECODE_BLOCK_FRAMES`] blocks
+    /// (the last taking the remainder), each paced on its own.
+    fn send(&mut self, sender: &BlockSender, gain: f32) {
+        let planar = std::mem::take(&mut self.block);
+        let frames = planar.first().map_or(0, Vec::len);
+        if frames < 2 * DECODE_BLOCK_FRAMES {
+            self.send_block(planar, sender, gain);
+            return;
+        }
+        let mut start = 0;
+        while start < frames {
+            let end = if frames - start < 2 * DECODE_BLOCK_FRAMES {
+                frames
+            } else {
+                start + DECODE_BLOCK_FRAMES
+            };
+            let block = planar
+                .iter()
+                .map(|samples| samples[start..end].to_vec())
+                .collect();
+            self.send_block(block, sender, gain);
+            start = end;
+        }
+    }
+
+    fn send_block(&self, mut planar: AudioBlock, sender: &BlockSender, gain: f32) {
+        let frames = planar.first().map_or(0, Vec::len);
+        if frames == 0 {
+            return;
+        }
+        if gain != 1.0 {
+            planar
+                .iter_mut()
+                .flatten()
+                .for_each(|sample| *sample *= gain);
+        }
+        sender.send(planar);
+        if let Some(pacer) = &sender.pacer {
+            pacer.pace(frames, self.rate);
+        }
+    }
+}
+
+/// Threshold `--trim-silence` uses unless given one, in dBFS
+pub const DEFAULT_SILENCE_THRESHOLD_DB: f32 = -50.0;
+
+/// Most silence held back in case it's a track's tail; quiet stretches
+/// longer than this inside a track are passed on rather than buffered
+const MAX_HELD_SILENCE: Duration = Duration::from_secs(10);
+
+/// Linear sample level for `db` dBFS
+fn db_to_level(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Drops a track's leading silence and holds back each run of silence
+/// until more sound follows it, so a run that reaches the end of the track
+/// is never sent
+struct SilenceTrim {
+    /// Sample level, before the track's gain, that counts as sound
+    threshold: f32,
+    /// Sound has been heard, so the leading silence is over
+    started: bool,
+    /// Silence since the last sound
+    held: AudioBlock,
+}
+
+impl SilenceTrim {
+    fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            started: false,
+            held: Vec::new(),
+        }
+    }
+
+    fn held_frames(&self) -> usize {
+        self.held.first().map_or(0, Vec::len)
+    }
+
+    /// Pass frames `from..to` of a decoded packet on to `pending`, less any
+    /// silence that might turn out to be leading or trailing
+    fn append(
+        &mut self,
+        buf: &AudioBuffer<f32>,
+        from: usize,
+        to: usize,
+        pending: &mut PendingBlock,
+    ) {
+        let (channels, threshold) = (buf.spec().channels.count(), self.threshold);
+        let loud =
+            |frame: usize| (0..channels).any(|channel| buf.chan(channel)[frame].abs() > threshold);
+        let (first, last) = match (from..to).find(|&frame| loud(frame)) {
+            Some(first) => (
+                first,
+                (first..to).rfind(|&frame| loud(frame)).unwrap_or(first),
+            ),
+            None if self.started => {
+                self.hold(buf, from, to, pending);
+                return;
+            }
+            None => return,
+        };
+
+        // Sound: leading silence ends here, and held silence turns out to
+        // be a pause within the track
+        let from = if self.started { from } else { first };
+        self.started = true;
+        let rate = buf.spec().rate as f64;
+        pending.append_held(&mut self.held, rate);
+        pending.append(buf, from, last + 1);
+        self.hold(buf, last + 1, to, pending);
+    }
+
+    fn hold(&mut self, buf: &AudioBuffer<f32>, from: usize, to: usize, pending: &mut PendingBlock) {
+        if from == to {
+            return;
+        }
+        let rate = buf.spec().rate as f64;
+        if self.held_frames() + (to - from) > (MAX_HELD_SILENCE.as_secs_f64() * rate) as usize {
+            pending.append_held(&mut self.held, rate);
+        }
+        self.held.resize_with(buf.spec().channels.count(), Vec::new);
+        for (channel, held) in self.held.iter_mut().enumerate() {
+            held.extend_from_slice(&buf.chan(channel)[from..to]);
+        }
+    }
+}
+
+// ============================================================================
+// Playlist Source (M3U / PLS)
+// ============================================================================
+
+/// Plays playlist entries in order, looping over the whole list
+#[derive(Clone)]
+pub struct PlaylistSource {
+    pub entries: Vec<PlaylistEntry>,
+    /// Wait while this many blocks are still queued for the slowest listener
+    pub max_queued: Option<usize>,
+    /// Loop the list, replay one track, or stop at the end
+    pub repeat: Repeat,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+    pub control: Option<SourceControl>,
+    /// Reorder the entries randomly at the start of every pass
+    pub shuffle: Option<StdRng>,
+    /// Rescan this directory after each track and update the queue
+    pub watch: Option<DirectoryScan>,
+    /// Decode at playback speed instead of as fast as listeners take it
+    pub realtime: bool,
+    /// Never decode more than this far ahead of playback
+    pub max_ahead: Option<Duration>,
+    /// Station ID clip played between tracks
+    pub jingle: Option<Jingle>,
+    /// Apply each track's `REPLAYGAIN_TRACK_GAIN` tag on top of its manifest gain
+    pub replay_gain: bool,
+    /// Trim each track's leading and trailing silence below this many dBFS
+    pub trim_silence: Option<f32>,
+    /// How far through the list playback has got, shared with clones so a
+    /// restarted copy carries on instead of starting over
+    position: Arc<Mutex<PlaylistPosition>>,
+}
+
+/// Where a [`PlaylistSource`] is in its list
+#[derive(Debug, Default)]
+struct PlaylistPosition {
+    /// Passes finished
+    passes: u32,
+    /// The rest of the pass in progress, if one is
+    queue: Option<VecDeque<PlaylistEntry>>,
+    played: HashSet<PathBuf>,
+    played_any: bool,
+    last: Option<PathBuf>,
+}
+
+/// A short clip (station ID, bumper) played between playlist tracks
+///
+/// It goes through the same path as the tracks, so it's conformed to the
+/// station's channels and metered like any other audio.
+#[derive(Debug, Clone)]
+pub struct Jingle {
+    pub path: PathBuf,
+    /// Play after every this many tracks
+    pub every: u32,
+}
+
+impl PlaylistSource {
+    pub fn new(entries: Vec<PlaylistEntry>) -> Self {
+        Self {
+            entries,
+            max_queued: None,
+            repeat: Repeat::All,
+            meter: None,
+            fader: None,
+            control: None,
+            shuffle: None,
+            watch: None,
+            realtime: false,
+            max_ahead: None,
+            jingle: None,
+            replay_gain: false,
+            trim_silence: None,
+            position: Arc::default(),
+        }
+    }
+
+    /// Load an `.m3u`/`.m3u8`/`.pls` file, skipping entries that don't exist
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Self::new(crate::playlist::load(path.as_ref())?))
+    }
+
+    /// Apply backpressure instead of letting slow listeners drop blocks
+    pub fn with_backpressure(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+
+    /// Loop the list (the default), replay one track, or stop after some passes
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade in at the start and out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+
+    /// Let the operator skip to the next entry or pause
+    pub fn with_control(mut self, control: SourceControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Play each pass in a random order; a fixed `seed` gives the same
+    /// sequence of orders every run
+    pub fn with_shuffle(mut self, seed: Option<u64>) -> Self {
+        self.shuffle = Some(match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        });
+        self
+    }
+
+    /// Release audio at wall-clock pace instead of decoding ahead
+    pub fn with_realtime(mut self) -> Self {
+        self.realtime = true;
+        self
+    }
+
+    /// Let decoding run at most `ahead` past playback, then wait for the clock
+    pub fn with_max_decode_ahead(mut self, ahead: Duration) -> Self {
+        self.max_ahead = Some(ahead);
+        self
+    }
+
+    /// Pick up files added to or removed from a `--dir` source while playing
+    pub fn with_watch(mut self, scan: DirectoryScan) -> Self {
+        self.watch = Some(scan);
+        self
+    }
+
+    /// Normalize each track with its ReplayGain track gain, if it has one
+    pub fn with_replay_gain(mut self) -> Self {
+        self.replay_gain = true;
+        self
+    }
+
+    /// Drop each track's leading and trailing audio quieter than
+    /// `threshold_db` dBFS, so tracks follow each other without gaps
+    pub fn with_silence_trim(mut self, threshold_db: f32) -> Self {
+        self.trim_silence = Some(threshold_db);
+        self
+    }
+
+    /// Play `path` between tracks, after every `every` of them
+    pub fn with_jingle(mut self, path: impl Into<PathBuf>, every: u32) -> Self {
+        self.jingle = Some(Jingle {
+            path: path.into(),
+            every: every.max(1),
+        });
+        self
+    }
+
+    /// Entries for the next pass, shuffled if enabled
+    ///
+    /// A shuffled pass never opens with `last`, the track that just ended.
+    fn pass_order(&mut self, last: Option<&Path>) -> VecDeque<PlaylistEntry> {
+        let mut order = self.entries.clone();
+        if let Some(rng) = &mut self.shuffle {
+            order.shuffle(rng);
+            if order.len() > 1 && last.is_some_and(|last| order[0].path == last) {
+                let swap = rng.random_range(1..order.len());
+                order.swap(0, swap);
+            }
+        }
+        order.into()
+    }
+
+    /// Rescan the watched directory, dropping deleted files from `queue` and
+    /// queueing new ones that haven't played this pass
+    fn refresh(&mut self, queue: &mut VecDeque<PlaylistEntry>, played: &HashSet<PathBuf>) {
+        let Some(scan) = &self.watch else {
+            return;
+        };
+        let current = match scan.rescan() {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("[Playlist] Rescan failed: {}", e);
+                return;
+            }
+        };
+
+        queue.retain(|queued| {
+            let present = current.iter().any(|entry| entry.path == queued.path);
+            if !present {
+                info!("[Playlist] Removed: {}", queued.display_name());
+            }
+            present
+        });
+        for entry in &current {
+            let known = played.contains(&entry.path)
+                || queue.iter().any(|queued| queued.path == entry.path);
+            if !known {
+                info!("[Playlist] Added: {}", entry.display_name());
+                queue.push_back(entry.clone());
+            }
+        }
+        self.entries = current;
+    }
+}
+
+impl AudioSource for PlaylistSource {
+    fn start(mut self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
+        let meter = self.meter.clone();
+        let fader = self.fader.clone();
+        let control = self.control.clone();
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: self.max_queued,
+            meter: meter.as_deref(),
+            fader: fader.as_deref(),
+            control: control.as_ref(),
+            pacer: Pacer::for_source(self.realtime, self.max_ahead),
+            trim_silence: self.trim_silence.map(db_to_level),
+            sanitized: RefCell::new(SanitizeLog::new("Playlist")),
+        };
+
+        info!("[Playlist] {} entries", self.entries.len());
+        // A restart picks up after the track that was playing when the last
+        // run failed, in case that track is what brought it down
+        let position = self.position.clone();
+        let resumed = std::mem::take(&mut *position.lock().unwrap());
+        let mut passes = resumed.passes;
+        let mut last = resumed.last;
+        let mut resume = resumed
+            .queue
+            .map(|queue| (queue, resumed.played, resumed.played_any));
+        if resume.is_some() {
+            info!("[Playlist] Resuming pass {} where it stopped", passes + 1);
+        }
+        let mut failed_passes = Backoff::default();
+        // Tracks played since the last jingle (carries across passes)
+        let mut since_jingle = 0;
+
+        loop {
+            let (mut queue, mut played, mut played_any) = match resume.take() {
+                Some(resumed) => resumed,
+                None => (self.pass_order(last.as_deref()), HashSet::new(), false),
+            };
+
+            while let Some(entry) = queue.pop_front() {
+                {
+                    let mut position = position.lock().unwrap();
+                    position.passes = passes;
+                    position.queue = Some(queue.clone());
+                    position.played = played.clone();
+                    position.played.insert(entry.path.clone());
+                    position.played_any = played_any;
+                    position.last = Some(entry.path.clone());
+                }
+                if let Some(jingle) = self.jingle.as_ref().filter(|j| since_jingle >= j.every) {
+                    play_jingle(&jingle.path, &sender);
+                    since_jingle = 0;
+                }
+
+                let mut backoff = Backoff::default();
+                let mut entry_played = false;
+                loop {
+                    let decoded = open_track(&entry.path).and_then(|(format, tags)| {
+                        // The playlist's own title wins over the file's tags
+                        let name = entry
+                            .title
+                            .clone()
+                            .or_else(|| tags.now_playing())
+                            .unwrap_or_else(|| entry.display_name());
+                        info!("[Playlist] Now playing: {}", name);
+                        sender.start_track(Some(name), &tags);
+                        sender.set_capabilities(format_capabilities(format.as_ref()));
+                        let settings = track_settings(&entry.settings, &tags, self.replay_gain);
+                        decode_format(format, &sender, &settings)
+                    });
+                    let ended = match decoded {
+                        Ok(ended) => {
+                            played_any = true;
+                            entry_played = true;
+                            backoff.reset();
+                            ended
+                        }
+                        Err(e) => {
+                            // Transient failures get a few retries before moving on
+                            match backoff.next_delay().filter(|_| !is_permanent(&e)) {
+                                Some(delay) => {
+                                    warn!(
+                                        "[Playlist] {} failed: {} (retrying in {}s)",
+                                        entry.path.display(),
+                                        e,
+                                        delay.as_secs()
+                                    );
+                                    std::thread::sleep(delay);
+                                    continue;
+                                }
+                                None => {
+                                    warn!("[Playlist] Skipping {}: {}", entry.path.display(), e);
+                                    break;
+                                }
+                            }
+                        }
+                    };
+                    // Repeat-one holds the current track until the operator skips
+                    if self.repeat != Repeat::One || ended == TrackEnd::Skipped {
+                        break;
+                    }
+                }
+                if entry_played {
+                    since_jingle += 1;
+                }
+                played.insert(entry.path.clone());
+                last = Some(entry.path);
+                self.refresh(&mut queue, &played);
+            }
+
+            if !played_any && self.entries.is_empty() && self.watch.is_some() {
+                // Waiting for files to appear in the watched directory; an
+                // empty pass doesn't count towards the repeat limit
+                std::thread::sleep(DECODE_RETRY_BASE);
+                self.refresh(&mut VecDeque::new(), &played);
+                continue;
+            }
+
+            passes += 1;
+            if self.repeat.passes().is_some_and(|limit| passes >= limit) {
+                info!("[Playlist] Played {} time(s), finished", passes);
+                break;
+            }
+            if played_any {
+                failed_passes.reset();
+            } else {
+                // Every entry failed; back off, then give up on the whole list
+                match failed_passes.next_delay() {
+                    Some(delay) => std::thread::sleep(delay),
+                    None => anyhow::bail!(
+                        "No playable entries after {} attempts",
+                        DECODE_MAX_RETRIES + 1
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The track playing now, or before playback the first entry
+    ///
+    /// Once playing, the live value is on [`SourceControl::capabilities`].
+    fn capabilities(&self) -> SourceCapabilities {
+        let playing = self.position.lock().unwrap().last.clone();
+        playing
+            .or_else(|| self.entries.first().map(|entry| entry.path.clone()))
+            .map(|path| file_capabilities(&path))
+            .unwrap_or_default()
+    }
+}
+
+/// Play a jingle through the tracks' path; a jingle that won't play is
+/// skipped rather than holding up the next track
+fn play_jingle(path: &Path, sender: &BlockSender) {
+    info!("[Playlist] Jingle: {}", path.display());
+    let decoded = open_probed(path)
+        .and_then(|probed| decode_format(probed.format, sender, &TrackSettings::default()));
+    if let Err(e) = decoded {
+        warn!("[Playlist] Jingle {} failed: {}", path.display(), e);
+    }
+}
+
+// ============================================================================
+// Stdin Source (piped media stream)
+// ============================================================================
+
+/// Decodes a media stream piped into standard input, ending at EOF
+#[derive(Clone, Default)]
+pub struct StdinSource {
+    /// Wait while this many blocks are still queued for the slowest listener
+    pub max_queued: Option<usize>,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+}
+
+impl StdinSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade in at the start and out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+
+    /// Apply backpressure instead of letting slow listeners drop blocks
+    pub fn with_backpressure(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+}
+
+impl AudioSource for StdinSource {
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
+        use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
+        use symphonia::core::probe::Hint;
+
+        info!("[StdinSource] Reading media stream from stdin");
+
+        // Pipes can't seek, so symphonia reads through a forward-only adapter
+        let source = ReadOnlySource::new(std::io::stdin());
+        let mss = MediaSourceStream::new(Box::new(source), Default::default());
+        let format = probe_stream(mss, &Hint::new())?.format;
+
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: self.max_queued,
+            meter: self.meter.as_deref(),
+            fader: self.fader.as_deref(),
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("StdinSource")),
+        };
+        decode_format(format, &sender, &TrackSettings::default())?;
+        info!("[StdinSource] End of input");
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        // Probing would consume the pipe, so the format is only known once decoding starts
+        SourceCapabilities {
+            seekable: false,
+            has_metadata: false,
+            ..Default::default()
+        }
+    }
+}
+
+// ============================================================================
+// Raw PCM Source (named pipe or file of headerless samples)
+// ============================================================================
+
+/// Sample encoding of a [`RawPcmSource`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RawPcmFormat {
+    /// Signed 16-bit little-endian
+    #[default]
+    S16,
+    /// 32-bit float little-endian
+    F32,
+}
+
+impl RawPcmFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::S16 => 2,
+            Self::F32 => 4,
+        }
+    }
+
+    fn sample(self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::S16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Self::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+impl std::fmt::Display for RawPcmFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::S16 => write!(f, "s16le"),
+            Self::F32 => write!(f, "f32le"),
+        }
+    }
+}
+
+/// Frames per block read from a raw PCM pipe
+const RAW_PCM_BLOCK: usize = 1024;
+
+/// Interleaved PCM in a known format from a named pipe (or a plain file),
+/// sent as-is at wall-clock pace; ends when the writer closes the pipe
+///
+/// For tools that can write samples but can't speak zelfm: a DAW, or another
+/// encoder's raw output.
+#[derive(Clone)]
+pub struct RawPcmSource {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: RawPcmFormat,
+    /// Wait while this many blocks are still queued for the slowest listener
+    pub max_queued: Option<usize>,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+    pub control: Option<SourceControl>,
+}
+
+impl RawPcmSource {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        sample_rate: u32,
+        channels: u16,
+        format: RawPcmFormat,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            sample_rate,
+            channels,
+            format,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+        }
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade in at the start and out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+
+    /// Let the operator pause the stream (the writer is held up meanwhile)
+    pub fn with_control(mut self, control: SourceControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Apply backpressure instead of letting slow listeners drop blocks
+    pub fn with_backpressure(mut self, max_queued: usize) -> Self {
+        self.max_queued = Some(max_queued);
+        self
+    }
+}
+
+impl AudioSource for RawPcmSource {
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
+        use std::io::Read;
+
+        let channels = self.channels as usize;
+        check_format(self.sample_rate, channels)?;
+
+        // Opening a FIFO blocks until something opens it for writing
+        info!("[RawPcm] Waiting for audio on {}", self.path.display());
+        let mut input = std::fs::File::open(&self.path)
+            .map_err(|e| anyhow::anyhow!("Can't open {}: {}", self.path.display(), e))?;
+        info!(
+            "[RawPcm] Reading {} Hz, {} ch, {} from {}",
+            self.sample_rate,
+            channels,
+            self.format,
+            self.path.display()
+        );
+
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: self.max_queued,
+            meter: self.meter.as_deref(),
+            fader: self.fader.as_deref(),
+            control: self.control.as_ref(),
+            pacer: Some(Pacer::new()),
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("RawPcm")),
+        };
+
+        let sample_bytes = self.format.bytes_per_sample();
+        let frame_bytes = sample_bytes * channels;
+        let mut bytes = vec![0u8; RAW_PCM_BLOCK * frame_bytes];
+        let mut samples = Vec::with_capacity(RAW_PCM_BLOCK * channels);
+        let rate = self.sample_rate as f64;
+        loop {
+            // Pipes hand over whatever the writer has; fill a whole block
+            // unless the input ends first
+            let mut filled = 0;
+            while filled < bytes.len() {
+                match input.read(&mut bytes[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            let whole = filled - filled % frame_bytes;
+            if whole > 0 {
+                samples.clear();
+                samples.extend(
+                    bytes[..whole]
+                        .chunks_exact(sample_bytes)
+                        .map(|sample| self.format.sample(sample)),
+                );
+                sender.send(interleaved_to_planar(&samples, channels));
+                if let Some(pacer) = &sender.pacer {
+                    pacer.pace(whole / frame_bytes, rate);
+                }
+            }
+            if filled < bytes.len() {
+                if whole < filled {
+                    warn!(
+                        "[RawPcm] Dropped {} bytes of a partial frame at the end",
+                        filled - whole
+                    );
+                }
+                break;
+            }
+        }
+        info!("[RawPcm] End of input");
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities {
+            seekable: false,
+            has_metadata: false,
+            codec: "pcm".to_string(),
+            sample_format: self.format.to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// Tone Source (placeholder after the program ends)
+// ============================================================================
+
+/// Placeholder tone level: quiet enough to talk over, loud enough to notice
+const TONE_LEVEL: f32 = 0.1;
+
+/// Frames per generated block
+const TONE_BLOCK: usize = 1024;
+
+/// A steady sine tone (or silence at 0 Hz) at wall-clock pace, until the
+/// broadcast stops; keeps a station on the air after its program runs out
+#[derive(Clone)]
+pub struct ToneSource {
+    pub sample_rate: u32,
+    pub frequency: f32,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+    pub control: Option<SourceControl>,
+}
+
+impl ToneSource {
+    pub fn new(sample_rate: u32, frequency: f32) -> Self {
+        Self {
+            sample_rate,
+            frequency,
+            meter: None,
+            fader: None,
+            control: None,
+        }
+    }
+
+    /// Let the operator pause the tone
+    pub fn with_control(mut self, control: SourceControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+}
+
+impl AudioSource for ToneSource {
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
+        check_format(self.sample_rate, 1)?;
+        if self.frequency > 0.0 {
+            info!("[Tone] Playing a {} Hz tone", self.frequency);
+        } else {
+            info!("[Tone] Playing silence");
+        }
+
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: self.meter.as_deref(),
+            fader: self.fader.as_deref(),
+            control: self.control.as_ref(),
+            pacer: Some(Pacer::new()),
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Tone")),
+        };
+
+        let rate = self.sample_rate as f64;
+        let step = std::f64::consts::TAU * self.frequency as f64 / rate;
+        let mut phase = 0.0f64;
+        loop {
+            let block: Vec<f32> = (0..TONE_BLOCK)
+                .map(|_| {
+                    let sample = phase.sin() as f32 * TONE_LEVEL;
+                    phase = (phase + step) % std::f64::consts::TAU;
+                    sample
+                })
+                .collect();
+            sender.send(vec![block]);
+            if let Some(pacer) = &sender.pacer {
+                pacer.pace(TONE_BLOCK, rate);
+            }
+        }
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities {
+            seekable: false,
+            has_metadata: false,
+            codec: "pcm".to_string(),
+            sample_format: "f32".to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// Live Source (CPAL input capture)
+// ============================================================================
+
+#[cfg(feature = "live-input")]
+#[derive(Clone)]
+pub struct LiveSource {
+    pub device_name: Option<String>,
+    /// Format to open the device in when it supports it (the station's)
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub meter: Option<Arc<LevelMeter>>,
+    pub fader: Option<Arc<Fader>>,
+    pub control: Option<SourceControl>,
+}
+
+#[cfg(feature = "live-input")]
+impl LiveSource {
+    pub fn new(device_name: Option<String>) -> Self {
+        Self {
+            device_name,
+            sample_rate: 44100,
+            channels: SOURCE_CHANNELS as u16,
+            meter: None,
+            fader: None,
+            control: None,
+        }
+    }
+
+    /// Open the device at this rate and channel count if it supports them
+    pub fn with_format(mut self, sample_rate: u32, channels: u16) -> Self {
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self
+    }
+
+    /// Let the operator pause (mute) the input
+    pub fn with_control(mut self, control: SourceControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Report output levels to `meter`
+    pub fn with_meter(mut self, meter: Arc<LevelMeter>) -> Self {
+        self.meter = Some(meter);
+        self
+    }
+
+    /// Fade in at the start and out on [`Fader::fade_out`]
+    pub fn with_fader(mut self, fader: Arc<Fader>) -> Self {
+        self.fader = Some(fader);
+        self
+    }
+}
+
+#[cfg(feature = "live-input")]
+impl AudioSource for LiveSource {
+    fn start(self, pcm_tx: broadcast::Sender<Arc<AudioBlock>>) -> anyhow::Result<()> {
+        use crate::devices::{best_input_config, select_input_device};
+        use cpal::traits::{DeviceTrait, StreamTrait};
+
+        let host = cpal::default_host();
+        let device = select_input_device(&host, self.device_name.as_deref())?;
+
+        let device_name = device.name()?;
+        let config = best_input_config(&device, self.sample_rate, self.channels)?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels() as usize;
+
+        info!("[Live] Device: {}", device_name);
+        info!(
+            "[Live] Format: {} Hz, {} ch, {:?}",
+            sample_rate,
+            channels,
+            config.sample_format()
+        );
+
+        let meter = self.meter;
+        let fader = self.fader;
+        let control = self.control;
+        let mut sanitized = SanitizeLog::new("Live");
+
+        // Build input stream
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                if control.as_ref().is_some_and(|c| c.is_paused()) {
+                    return;
+                }
+
+                let mut planar =
+                    conform_channels(interleaved_to_planar(data, channels), SOURCE_CHANNELS);
+                sanitized.record(sanitize(&mut planar));
+
+                if let Some(fader) = &fader {
+                    fader.apply(&mut planar);
+                }
+
+                if let Some(meter) = &meter {
+                    meter.update(&planar);
+                }
+
+                // Broadcast to all listeners
+                let _ = pcm_tx.send(Arc::new(planar));
+            },
+            |err| error!("[Live] Stream error: {}", err),
+            None,
+        )?;
+
+        stream.play()?;
+
+        info!("[Live] Streaming...");
+
+        // Keep stream alive by moving it into the loop
+        // Process exit will clean it up
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+
+            // Stream is kept alive by this loop
+            // When main thread exits (Ctrl+C), this thread is terminated
+        }
+
+        #[allow(unreachable_code)]
+        {
+            drop(stream);
+            Ok(())
+        }
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        // Live input can't be rewound and carries no track tags
+        SourceCapabilities {
+            seekable: false,
+            has_metadata: false,
+            codec: "pcm".to_string(),
+            sample_format: "f32".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::{NonZeroU32, NonZeroU8};
+
+    const RATE: u32 = 44100;
+
+    /// Append one second of a 440 Hz tone as its own logical OGG Vorbis stream
+    fn vorbis_link(serial: i32, out: &mut Vec<u8>) {
+        tagged_vorbis_link(serial, &[], out);
+    }
+
+    /// [`vorbis_link`] with these Vorbis comments
+    fn tagged_vorbis_link(serial: i32, tags: &[(&str, &str)], out: &mut Vec<u8>) {
+        let tone: Vec<f32> = (0..RATE)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / RATE as f32).sin() * 0.5)
+            .collect();
+
+        let mut builder = vorbis_rs::VorbisEncoderBuilder::new_with_serial(
+            NonZeroU32::new(RATE).unwrap(),
+            NonZeroU8::new(1).unwrap(),
+            out,
+            serial,
+        );
+        builder.comment_tags(tags.iter().copied()).unwrap();
+        let mut encoder = builder.build().unwrap();
+        encoder.encode_audio_block([&tone[..]]).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    /// A native FLAC file with these Vorbis comments and one frame of
+    /// stereo silence
+    fn tagged_flac(tags: &[(&str, &str)]) -> Vec<u8> {
+        fn crc8(data: &[u8]) -> u8 {
+            data.iter().fold(0u8, |crc, &byte| {
+                (0..8).fold(crc ^ byte, |crc, _| {
+                    if crc & 0x80 != 0 {
+                        (crc << 1) ^ 0x07
+                    } else {
+                        crc << 1
+                    }
+                })
+            })
+        }
+        fn crc16(data: &[u8]) -> u16 {
+            data.iter().fold(0u16, |crc, &byte| {
+                (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+                    if crc & 0x8000 != 0 {
+                        (crc << 1) ^ 0x8005
+                    } else {
+                        crc << 1
+                    }
+                })
+            })
+        }
+
+        let mut out = b"fLaC".to_vec();
+
+        // STREAMINFO: 192-sample blocks, 44.1 kHz, 2 channels, 16-bit, length unknown
+        out.extend_from_slice(&[0x00, 0, 0, 34]);
+        out.extend_from_slice(&192u16.to_be_bytes());
+        out.extend_from_slice(&192u16.to_be_bytes());
+        out.extend_from_slice(&[0; 6]);
+        let packed = (RATE as u64) << 44 | 1 << 41 | 15 << 36;
+        out.extend_from_slice(&packed.to_be_bytes());
+        out.extend_from_slice(&[0; 16]);
+
+        // VORBIS_COMMENT (last metadata block); its fields are little-endian
+        let mut comments = Vec::new();
+        let vendor = b"zelfm test";
+        comments.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        comments.extend_from_slice(vendor);
+        comments.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+        for (key, value) in tags {
+            let comment = format!("{}={}", key, value);
+            comments.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            comments.extend_from_slice(comment.as_bytes());
+        }
+        out.push(0x80 | 4);
+        out.extend_from_slice(&(comments.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(&comments);
+
+        // One frame: fixed blocking, 192 samples, 44.1 kHz, independent
+        // stereo, 16-bit, frame 0, then two constant-zero subframes
+        let mut frame = vec![0xFF, 0xF8, 0x19, 0x18, 0x00];
+        frame.push(crc8(&frame));
+        frame.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let footer = crc16(&frame);
+        frame.extend_from_slice(&footer.to_be_bytes());
+        out.extend_from_slice(&frame);
+        out
+    }
+
+    fn tags_of(bytes: Vec<u8>, extension: &str) -> TrackTags {
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::probe::Hint;
+
+        let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension(extension);
+        let mut probed = probe_stream(mss, &hint).unwrap();
+        TrackTags::from_tags(&probed_tags(&mut probed))
+    }
+
+    const TAGS: &[(&str, &str)] = &[
+        ("TITLE", "Night Drive"),
+        ("ARTIST", "The Zels"),
+        ("ALBUM", "Static"),
+        ("REPLAYGAIN_TRACK_GAIN", "-6.48 dB"),
+    ];
+
+    fn assert_tagged(tags: &TrackTags) {
+        assert_eq!(tags.album.as_deref(), Some("Static"));
+        assert_eq!(
+            tags.now_playing().as_deref(),
+            Some("The Zels - Night Drive")
+        );
+        assert_eq!(tags.replay_gain_db, Some(-6.48));
+
+        let settings = track_settings(&TrackSettings::default(), tags, true);
+        assert_eq!(settings.gain_db, -6.48);
+        let settings = track_settings(&TrackSettings::default(), tags, false);
+        assert_eq!(settings.gain_db, 0.0);
+    }
+
+    #[test]
+    fn vorbis_comments_in_ogg_name_and_normalize_the_track() {
+        let mut ogg = Vec::new();
+        tagged_vorbis_link(1, TAGS, &mut ogg);
+        assert_tagged(&tags_of(ogg, "ogg"));
+    }
+
+    #[test]
+    fn vorbis_comments_in_flac_name_and_normalize_the_track() {
+        // Lowercase keys match too, as Vorbis comment keys are case-insensitive
+        let lowercase: Vec<(String, &str)> = TAGS
+            .iter()
+            .map(|(key, value)| (key.to_lowercase(), *value))
+            .collect();
+        let lowercase: Vec<(&str, &str)> = lowercase
+            .iter()
+            .map(|(key, value)| (key.as_str(), *value))
+            .collect();
+        assert_tagged(&tags_of(tagged_flac(&lowercase), "flac"));
+    }
+
+    #[test]
+    fn mono_file_decodes_to_stereo_blocks() {
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::probe::Hint;
+
+        let mut mono = Vec::new();
+        vorbis_link(1, &mut mono);
+
+        let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(mono)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("ogg");
+        let format = probe_stream(mss, &hint).unwrap().format;
+
+        // What a stereo broadcaster's encoders subscribe to
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(10_000);
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Test")),
+        };
+        decode_format(format, &sender, &TrackSettings::default()).unwrap();
+
+        let mut blocks = 0;
+        while let Ok(block) = pcm_rx.try_recv() {
+            assert_eq!(block.len(), 2, "block has {} channels", block.len());
+            assert_eq!(block[0], block[1]);
+            blocks += 1;
+        }
+        assert!(blocks > 0);
+    }
+
+    #[test]
+    fn raw_pcm_is_read_as_is_until_the_writer_stops() {
+        let path = std::env::temp_dir().join(format!("zelfm-raw-pcm-{}", std::process::id()));
+        // 2.5 blocks of stereo s16 ramping up on the left, down on the right,
+        // then half a frame the writer never finished
+        let frames = RAW_PCM_BLOCK * 5 / 2;
+        let mut raw = Vec::new();
+        for i in 0..frames as i16 {
+            raw.extend_from_slice(&i.to_le_bytes());
+            raw.extend_from_slice(&(-i).to_le_bytes());
+        }
+        raw.extend_from_slice(&[0, 0]);
+        std::fs::write(&path, &raw).unwrap();
+
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(100);
+        let source = RawPcmSource::new(&path, RATE, 2, RawPcmFormat::S16);
+        source.start(pcm_tx).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        while let Ok(block) = pcm_rx.try_recv() {
+            left.extend_from_slice(&block[0]);
+            right.extend_from_slice(&block[1]);
+        }
+        assert_eq!(left.len(), frames);
+        assert_eq!(left[100], 100.0 / 32768.0);
+        assert_eq!(right[100], -100.0 / 32768.0);
+    }
+
+    #[test]
+    fn pacer_runs_at_most_its_lead_ahead() {
+        let pacer = Pacer::with_lead(Duration::from_millis(100));
+        let started = Instant::now();
+        // The first 100 ms go out at once; the rest waits on the clock
+        pacer.pace(RATE as usize / 10, RATE as f64);
+        assert!(started.elapsed() < Duration::from_millis(50));
+        pacer.pace(RATE as usize / 5, RATE as f64);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn a_long_lead_still_restarts_the_clock_after_a_stall() {
+        let pacer = Pacer::with_lead(Duration::from_secs(10));
+        // Two seconds with nothing sent, as after a pause
+        pacer.started.set(Instant::now() - Duration::from_secs(2));
+        pacer.pace(RATE as usize / 10, RATE as f64);
+        // The clock restarted at what was sent, so there's no burst to catch up
+        assert!(pacer.started.get().elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn small_packets_are_batched_into_blocks() {
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::probe::Hint;
+
+        let mut ogg = Vec::new();
+        vorbis_link(1, &mut ogg);
+
+        let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(ogg)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("ogg");
+        let format = probe_stream(mss, &hint).unwrap().format;
+
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(10_000);
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Test")),
+        };
+        decode_format(format, &sender, &TrackSettings::default()).unwrap();
+
+        let mut lengths = Vec::new();
+        while let Ok(block) = pcm_rx.try_recv() {
+            lengths.push(block[0].len());
+        }
+        // Vorbis packets are at most 1024 frames; only the track's tail is short
+        let (_, full) = lengths.split_last().unwrap();
+        assert!(
+            full.iter().all(|&frames| frames >= DECODE_BLOCK_FRAMES),
+            "{:?}",
+            lengths
+        );
+        assert!(lengths.iter().sum::<usize>() > RATE as usize * 9 / 10);
+    }
+
+    #[test]
+    fn zero_rate_wav_is_an_error_not_a_panic() {
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::probe::Hint;
+
+        // A 16-bit stereo WAV header claiming 0 Hz, then one frame of silence
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&40u32.to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&2u16.to_le_bytes()); // channels
+        wav.extend_from_slice(&0u32.to_le_bytes()); // sample rate
+        wav.extend_from_slice(&0u32.to_le_bytes()); // byte rate
+        wav.extend_from_slice(&4u16.to_le_bytes()); // block align
+        wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&4u32.to_le_bytes());
+        wav.extend_from_slice(&[0; 4]);
+
+        let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(wav)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("wav");
+        let error = probe_stream(mss, &hint).err().expect("probe should fail");
+        assert!(error.to_string().contains("Malformed"), "{}", error);
+    }
+
+    #[test]
+    fn extinf_title_is_announced_when_the_track_starts() {
+        let dir = std::env::temp_dir().join(format!("zelfm-extinf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut track = Vec::new();
+        vorbis_link(1, &mut track);
+        std::fs::write(dir.join("track.ogg"), track).unwrap();
+        let playlist = dir.join("list.m3u");
+        std::fs::write(&playlist, "#EXTM3U\n#EXTINF:1,Artist - Title\ntrack.ogg\n").unwrap();
+
+        let control = SourceControl::new();
+        let mut events = control.events();
+        let (pcm_tx, _pcm_rx) = broadcast::channel(10_000);
+        PlaylistSource::load(&playlist)
+            .unwrap()
+            .with_repeat(Repeat::None)
+            .with_control(control)
+            .start(pcm_tx)
+            .unwrap();
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            StationEvent::TrackChanged {
+                title: Some("Artist - Title".to_string())
+            }
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tone_plays_at_its_level_and_zero_hz_is_silence() {
+        for (frequency, peak) in [(440.0, TONE_LEVEL), (0.0, 0.0)] {
+            let (pcm_tx, mut pcm_rx) = broadcast::channel(16);
+            // Runs until the process exits, as it would on the air
+            std::thread::spawn(move || ToneSource::new(RATE, frequency).start(pcm_tx));
+
+            // A few blocks cover whole cycles of the 440 Hz tone
+            let mut loudest = 0.0f32;
+            for _ in 0..4 {
+                let block = pcm_rx.blocking_recv().unwrap();
+                assert_eq!(block.len(), 2);
+                assert_eq!(block[0], block[1]);
+                assert_eq!(block[0].len(), TONE_BLOCK);
+                loudest = block[0].iter().fold(loudest, |max, s| max.max(s.abs()));
+            }
+            assert!(
+                (loudest - peak).abs() < 0.001,
+                "{frequency} Hz peaked at {loudest}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_watched_directory_that_starts_empty_picks_up_new_files() {
+        let dir = std::env::temp_dir().join(format!("zelfm-watch-empty-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let scan = DirectoryScan {
+            root: dir.clone(),
+            recursive: false,
+            order: crate::playlist::PlayOrder::Name,
+        };
+
+        let control = SourceControl::new();
+        let mut events = control.events();
+        let (pcm_tx, _pcm_rx) = broadcast::channel(10_000);
+        let source = PlaylistSource::new(scan.scan().unwrap())
+            .with_repeat(Repeat::None)
+            .with_watch(scan)
+            .with_control(control);
+        let player = std::thread::spawn(move || source.start(pcm_tx));
+
+        // Appear in one step, so a rescan never sees a half-written file
+        std::thread::sleep(Duration::from_millis(100));
+        let mut track = Vec::new();
+        vorbis_link(1, &mut track);
+        std::fs::write(dir.join("track.part"), track).unwrap();
+        std::fs::rename(dir.join("track.part"), dir.join("track.ogg")).unwrap();
+
+        player.join().unwrap().unwrap();
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            StationEvent::TrackChanged { .. }
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entry_trims_and_gain_apply_to_the_exact_frames() {
+        // One second of a mono 8 kHz ramp, so each sample gives its position
+        let rate = 8000u32;
+        let data: Vec<u8> = (0..rate as i16).flat_map(i16::to_le_bytes).collect();
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // channels
+        wav.extend_from_slice(&rate.to_le_bytes());
+        wav.extend_from_slice(&(rate * 2).to_le_bytes()); // byte rate
+        wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+        wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        let path = std::env::temp_dir().join(format!("zelfm-trim-{}.wav", std::process::id()));
+        std::fs::write(&path, wav).unwrap();
+
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(100);
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Test")),
+        };
+        let settings = TrackSettings {
+            gain_db: 20.0 * 2f32.log10(),
+            start_secs: Some(0.25),
+            end_secs: Some(0.5),
+        };
+        let (format, _) = open_track(&path).unwrap();
+        decode_format(format, &sender, &settings).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut left = Vec::new();
+        while let Ok(block) = pcm_rx.try_recv() {
+            assert_eq!(block[0], block[1]);
+            left.extend_from_slice(&block[0]);
+        }
+        // Frames 2000..4000, doubled
+        let expected = |frame: i32| frame as f32 / 32768.0 * 2.0;
+        assert_eq!(left.len(), 2000);
+        assert!((left[0] - expected(2000)).abs() < 1e-4, "{}", left[0]);
+        assert!((left[1999] - expected(3999)).abs() < 1e-4, "{}", left[1999]);
+    }
+
+    #[test]
+    fn chained_ogg_plays_past_the_reset() {
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::core::probe::Hint;
+
+        let mut chained = Vec::new();
+        vorbis_link(1, &mut chained);
+        vorbis_link(2, &mut chained);
+
+        let mss =
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(chained)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("ogg");
+        let format = probe_stream(mss, &hint).unwrap().format;
+
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(10_000);
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Test")),
+        };
+        let ended = decode_format(format, &sender, &TrackSettings::default()).unwrap();
+        assert_eq!(ended, TrackEnd::Finished);
+
+        let mut frames = 0;
+        while let Ok(block) = pcm_rx.try_recv() {
+            frames += block[0].len();
+        }
+        // Both links decoded, not just the first
+        assert!(
+            frames > RATE as usize * 3 / 2,
+            "only {} frames decoded",
+            frames
+        );
+    }
+
+    #[test]
+    fn silence_is_trimmed_from_track_edges_only() {
+        use symphonia::core::audio::{Channels, SignalSpec};
+
+        let level = db_to_level(DEFAULT_SILENCE_THRESHOLD_DB);
+        // Hiss under the threshold, a tone, a pause, the tone again, a fade-out tail
+        let track: Vec<f32> = [
+            (level * 0.5, 300),
+            (0.5, 200),
+            (0.0, 100),
+            (0.5, 200),
+            (level * 0.1, 400),
+        ]
+        .into_iter()
+        .flat_map(|(sample, frames)| std::iter::repeat_n(sample, frames))
+        .collect();
+
+        let spec = SignalSpec::new(RATE, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut silence = SilenceTrim::new(level);
+        let mut pending = PendingBlock::default();
+        for packet in track.chunks(128) {
+            let mut buf = AudioBuffer::<f32>::new(128, spec);
+            buf.render_reserved(Some(packet.len()));
+            buf.chan_mut(0).copy_from_slice(packet);
+            buf.chan_mut(1).copy_from_slice(packet);
+            silence.append(&buf, 0, packet.len(), &mut pending);
+        }
+
+        // The pause inside the track stays; the tail is still held when it ends
+        let kept = [vec![0.5; 200], vec![0.0; 100], vec![0.5; 200]].concat();
+        assert_eq!(pending.block, [kept.clone(), kept]);
+        assert_eq!(pending.rate, RATE as f64);
+        assert_eq!(silence.held_frames(), 400);
+    }
+
+    #[test]
+    fn a_long_pause_goes_out_in_ordinary_blocks() {
+        use symphonia::core::audio::{Channels, SignalSpec};
+
+        let level = db_to_level(DEFAULT_SILENCE_THRESHOLD_DB);
+        // A tone, five seconds of silence, the tone again
+        let track: Vec<f32> = [(0.5, 200), (0.0, RATE as usize * 5), (0.5, 200)]
+            .into_iter()
+            .flat_map(|(sample, frames)| std::iter::repeat_n(sample, frames))
+            .collect();
+
+        let spec = SignalSpec::new(RATE, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut silence = SilenceTrim::new(level);
+        let mut pending = PendingBlock::default();
+        let (pcm_tx, mut pcm_rx) = broadcast::channel(10_000);
+        let sender = BlockSender {
+            pcm_tx: &pcm_tx,
+            max_queued: None,
+            meter: None,
+            fader: None,
+            control: None,
+            pacer: None,
+            trim_silence: None,
+            sanitized: RefCell::new(SanitizeLog::new("Test")),
+        };
+        for packet in track.chunks(1024) {
+            let mut buf = AudioBuffer::<f32>::new(1024, spec);
+            buf.render_reserved(Some(packet.len()));
+            buf.chan_mut(0).copy_from_slice(packet);
+            buf.chan_mut(1).copy_from_slice(packet);
+            silence.append(&buf, 0, packet.len(), &mut pending);
+            if pending.frames() >= DECODE_BLOCK_FRAMES {
+                pending.send(&sender, 1.0);
+            }
+        }
+        pending.send(&sender, 1.0);
+
+        let mut sent = Vec::new();
+        while let Ok(block) = pcm_rx.try_recv() {
+            assert!(
+                block[0].len() < 2 * DECODE_BLOCK_FRAMES,
+                "a {} frame block",
+                block[0].len()
+            );
+            sent.extend_from_slice(&block[0]);
+        }
+        assert_eq!(sent, track);
+    }
+
+    #[test]
+    fn playlist_capabilities_follow_the_playing_track() {
+        let dir = std::env::temp_dir().join(format!("zelfm-caps-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let flac = dir.join("a.flac");
+        std::fs::write(&flac, tagged_flac(&[])).unwrap();
+        let mut track = Vec::new();
+        vorbis_link(1, &mut track);
+        let ogg = dir.join("b.ogg");
+        std::fs::write(&ogg, track).unwrap();
+        let entries = [flac, ogg].map(|path| PlaylistEntry {
+            path,
+            ..Default::default()
+        });
+
+        let control = SourceControl::new();
+        let source = PlaylistSource::new(entries.to_vec())
+            .with_repeat(Repeat::None)
+            .with_control(control.clone());
+        let caps = source.capabilities();
+        assert_eq!(caps.codec, "flac");
+        assert!(!caps.seekable);
+        assert!(control.capabilities().is_none());
+
+        let (pcm_tx, _pcm_rx) = broadcast::channel(10_000);
+        source.clone().start(pcm_tx).unwrap();
+        assert_eq!(control.capabilities().unwrap().codec, "vorbis");
+        assert_eq!(source.capabilities().codec, "vorbis");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_restarted_playlist_carries_on_after_the_interrupted_track() {
+        let dir = std::env::temp_dir().join(format!("zelfm-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entries: Vec<PlaylistEntry> = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(serial, name)| {
+                let mut track = Vec::new();
+                vorbis_link(serial as i32, &mut track);
+                let path = dir.join(format!("{}.ogg", name));
+                std::fs::write(&path, track).unwrap();
+                PlaylistEntry {
+                    path,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let control = SourceControl::new();
+        let mut events = control.events();
+        let source = PlaylistSource::new(entries.clone())
+            .with_repeat(Repeat::None)
+            .with_control(control);
+        // Where the first run had got to when "a.ogg" brought it down
+        *source.position.lock().unwrap() = PlaylistPosition {
+            queue: Some(entries[1..].iter().cloned().collect()),
+            played: HashSet::from([entries[0].path.clone()]),
+            last: Some(entries[0].path.clone()),
+            ..Default::default()
+        };
+
+        let (pcm_tx, _pcm_rx) = broadcast::channel(10_000);
+        source.clone().start(pcm_tx).unwrap();
+        let mut titles = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let StationEvent::TrackChanged { title } = event {
+                titles.push(title.unwrap());
+            }
+        }
+        assert_eq!(titles, ["b.ogg", "c.ogg"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Pass orders of a seeded shuffled playlist, feeding each pass's last track back in
+    fn shuffled_passes(seed: u64, passes: usize) -> Vec<Vec<PathBuf>> {
+        let entries = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| PlaylistEntry {
+                path: PathBuf::from(format!("{}.ogg", name)),
+                ..Default::default()
+            })
+            .collect();
+        let mut source = PlaylistSource::new(entries).with_shuffle(Some(seed));
+
+        let mut last: Option<PathBuf> = None;
+        (0..passes)
+            .map(|_| {
+                let order: Vec<PathBuf> = source
+                    .pass_order(last.as_deref())
+                    .into_iter()
+                    .map(|entry| entry.path)
+                    .collect();
+                last = order.last().cloned();
+                order
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeded_shuffle_is_deterministic_without_immediate_repeats() {
+        let passes = shuffled_passes(42, 50);
+        assert_eq!(passes, shuffled_passes(42, 50));
+        assert_ne!(passes, shuffled_passes(7, 50));
+
+        let mut sorted_original: Vec<PathBuf> = passes[0].clone();
+        sorted_original.sort();
+        for pass in &passes {
+            // Every pass plays each entry exactly once
+            let mut sorted = pass.clone();
+            sorted.sort();
+            assert_eq!(sorted, sorted_original);
+        }
+        for pair in passes.windows(2) {
+            assert_ne!(
+                pair[0].last(),
+                pair[1].first(),
+                "track repeated across passes"
+            );
         }
+        // Reshuffled each pass, not fixed once
+        assert!(passes.windows(2).any(|pair| pair[0] != pair[1]));
     }
 }
@@ -0,0 +1,350 @@
+//! Sample layout helpers shared by sources, sinks, and the player.
+//!
+//! Blocks move through zelfm as planar `[channel][frame]` vectors; devices and
+//! decoders mostly speak interleaved `[frame][channel]`.
+
+use log::warn;
+use std::time::{Duration, Instant};
+use symphonia::core::audio::{AudioBuffer, AudioBufferRef};
+
+/// Split interleaved samples into one vector per channel
+///
+/// A trailing partial frame is dropped so every channel comes out the same length.
+pub fn interleaved_to_planar(data: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    if channels == 0 {
+        return Vec::new();
+    }
+
+    let frames = data.len() / channels;
+    // Not `vec![..; channels]`: cloning a `Vec` doesn't keep its capacity
+    let mut planar: Vec<Vec<f32>> = (0..channels).map(|_| Vec::with_capacity(frames)).collect();
+    for frame in data.chunks_exact(channels) {
+        for (channel, &sample) in planar.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+    planar
+}
+
+/// Reads decoded packets as planar f32, whatever format the decoder outputs
+///
+/// f32 packets are read in place. Other formats (16-bit FLAC, say) are
+/// converted into one buffer kept across packets, so neither case allocates
+/// or interleaves per packet the way a `SampleBuffer` round trip does.
+#[derive(Default)]
+pub struct PlanarDecodeBuffer {
+    converted: Option<AudioBuffer<f32>>,
+}
+
+impl PlanarDecodeBuffer {
+    /// `decoded` as f32, valid until the next call
+    pub fn as_f32<'a>(&'a mut self, decoded: &'a AudioBufferRef<'_>) -> &'a AudioBuffer<f32> {
+        if let AudioBufferRef::F32(buf) = decoded {
+            return buf;
+        }
+        let spec = *decoded.spec();
+        let fits = self
+            .converted
+            .as_ref()
+            .is_some_and(|buf| *buf.spec() == spec && buf.capacity() >= decoded.capacity());
+        if !fits {
+            self.converted = Some(AudioBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let converted = self.converted.as_mut().unwrap();
+        decoded.convert(converted);
+        converted
+    }
+}
+
+/// Interleave planar channels, tolerating empty and ragged blocks.
+///
+/// Decoders can hand back zero-length or uneven channels at stream edges;
+/// frames beyond the shortest channel are dropped rather than indexed.
+pub fn planar_to_interleaved<C: AsRef<[f32]>>(planar: &[C]) -> Vec<f32> {
+    let frames = planar.iter().map(|c| c.as_ref().len()).min().unwrap_or(0);
+
+    let mut interleaved = Vec::with_capacity(planar.len() * frames);
+    for i in 0..frames {
+        for channel in planar {
+            interleaved.push(channel.as_ref()[i]);
+        }
+    }
+    interleaved
+}
+
+/// Average all channels into one (-6 dB per channel for two, so a full-scale
+/// center image stays at full scale and hard-panned peaks can't clip)
+pub fn sum_to_mono<C: AsRef<[f32]>>(planar: &[C]) -> Vec<f32> {
+    let frames = planar.iter().map(|c| c.as_ref().len()).min().unwrap_or(0);
+    if planar.is_empty() {
+        return Vec::new();
+    }
+
+    let gain = 1.0 / planar.len() as f32;
+    (0..frames)
+        .map(|i| planar.iter().map(|c| c.as_ref()[i]).sum::<f32>() * gain)
+        .collect()
+}
+
+/// Channel count every source hands the broadcaster (mono stations mix down
+/// in the encoder)
+pub const SOURCE_CHANNELS: usize = 2;
+
+/// Make a block exactly `channels` wide
+///
+/// Narrower blocks repeat their channels (mono plays on both sides); a mono
+/// target gets [`sum_to_mono`]; otherwise channels past `channels` are
+/// averaged and mixed equally into every kept channel at half level each.
+pub fn conform_channels(planar: Vec<Vec<f32>>, channels: usize) -> Vec<Vec<f32>> {
+    let have = planar.len();
+    if have == channels || have == 0 || channels == 0 {
+        return planar;
+    }
+    if channels == 1 {
+        return vec![sum_to_mono(&planar)];
+    }
+
+    if have < channels {
+        let mut planar = planar;
+        while planar.len() < channels {
+            planar.push(planar[planar.len() - have].clone());
+        }
+        return planar;
+    }
+
+    let extras = sum_to_mono(&planar[channels..]);
+    planar
+        .into_iter()
+        .take(channels)
+        .map(|channel| {
+            channel
+                .iter()
+                .zip(&extras)
+                .map(|(&kept, &extra)| (kept + extra) * 0.5)
+                .collect()
+        })
+        .collect()
+}
+
+/// Highest sample rate accepted from files and stations (buffers and
+/// resamplers are sized from it)
+pub const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Reject a stream format nothing downstream can play
+///
+/// Malformed files can claim zero channels or a zero sample rate; encoders,
+/// players and resamplers all assume both are positive, and size buffers
+/// from the rate.
+pub fn check_format(sample_rate: u32, channels: usize) -> anyhow::Result<()> {
+    if sample_rate == 0 {
+        anyhow::bail!("Malformed audio: sample rate is 0 Hz");
+    }
+    if sample_rate > MAX_SAMPLE_RATE {
+        anyhow::bail!(
+            "Malformed audio: sample rate {} Hz is above {} Hz",
+            sample_rate,
+            MAX_SAMPLE_RATE
+        );
+    }
+    if channels == 0 {
+        anyhow::bail!("Malformed audio: no channels");
+    }
+    Ok(())
+}
+
+/// Samples [`sanitize`] had to fix in one block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sanitized {
+    /// NaN or infinite, replaced with silence
+    pub non_finite: usize,
+    /// Beyond full scale, clamped to it
+    pub clamped: usize,
+}
+
+impl Sanitized {
+    pub fn total(&self) -> usize {
+        self.non_finite + self.clamped
+    }
+}
+
+/// Make a block safe for the encoders: NaN and infinite samples (driver
+/// glitches) become 0.0 and everything else is clamped to [-1.0, 1.0]
+pub fn sanitize(planar: &mut [Vec<f32>]) -> Sanitized {
+    let mut fixed = Sanitized::default();
+    for sample in planar.iter_mut().flatten() {
+        if !sample.is_finite() {
+            *sample = 0.0;
+            fixed.non_finite += 1;
+        } else if sample.abs() > 1.0 {
+            *sample = sample.clamp(-1.0, 1.0);
+            fixed.clamped += 1;
+        }
+    }
+    fixed
+}
+
+/// How often [`SanitizeLog`] reports
+const SANITIZE_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Running totals of sanitized samples for one source, logged when the
+/// first bad sample turns up and then at most every 10 seconds
+pub struct SanitizeLog {
+    source: &'static str,
+    non_finite: u64,
+    clamped: u64,
+    last_logged: Option<Instant>,
+}
+
+impl SanitizeLog {
+    pub fn new(source: &'static str) -> Self {
+        Self {
+            source,
+            non_finite: 0,
+            clamped: 0,
+            last_logged: None,
+        }
+    }
+
+    pub fn record(&mut self, fixed: Sanitized) {
+        if fixed.total() == 0 {
+            return;
+        }
+        self.non_finite += fixed.non_finite as u64;
+        self.clamped += fixed.clamped as u64;
+
+        if self
+            .last_logged
+            .is_some_and(|at| at.elapsed() < SANITIZE_LOG_INTERVAL)
+        {
+            return;
+        }
+        self.last_logged = Some(Instant::now());
+        warn!(
+            "[{}] Sanitized input so far: {} NaN/infinite samples silenced, {} clamped to full scale",
+            self.source, self.non_finite, self.clamped
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mono_passes_through() {
+        let data = [0.1, 0.2, 0.3];
+        let planar = interleaved_to_planar(&data, 1);
+        assert_eq!(planar, vec![vec![0.1, 0.2, 0.3]]);
+        assert_eq!(planar_to_interleaved(&planar), data);
+    }
+
+    #[test]
+    fn stereo_round_trip() {
+        let data = [1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+        let planar = interleaved_to_planar(&data, 2);
+        assert_eq!(planar, vec![vec![1.0, 2.0, 3.0], vec![-1.0, -2.0, -3.0]]);
+        assert_eq!(planar_to_interleaved(&planar), data);
+    }
+
+    #[test]
+    fn six_channels_keep_their_order() {
+        // Two frames of 5.1, each sample tagged frame * 10 + channel
+        let data: Vec<f32> = (0..2)
+            .flat_map(|f| (0..6).map(move |c| (f * 10 + c) as f32))
+            .collect();
+        let planar = interleaved_to_planar(&data, 6);
+        assert_eq!(planar.len(), 6);
+        for (c, channel) in planar.iter().enumerate() {
+            assert_eq!(channel, &vec![c as f32, (10 + c) as f32]);
+        }
+        assert_eq!(planar_to_interleaved(&planar), data);
+    }
+
+    #[test]
+    fn decoded_packets_come_out_planar_f32() {
+        use std::borrow::Cow;
+        use symphonia::core::audio::{Channels, Signal, SignalSpec};
+
+        let spec = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut pcm16 = AudioBuffer::<i16>::new(4, spec);
+        pcm16.render_reserved(Some(4));
+        pcm16.chan_mut(0).copy_from_slice(&[0, 16384, -16384, 0]);
+        pcm16.chan_mut(1).copy_from_slice(&[0, -8192, 8192, 0]);
+        let decoded = AudioBufferRef::S16(Cow::Borrowed(&pcm16));
+
+        let mut planar_buf = PlanarDecodeBuffer::default();
+        let converted = planar_buf.as_f32(&decoded);
+        assert_eq!(converted.chan(0), [0.0, 0.5, -0.5, 0.0]);
+        assert_eq!(converted.chan(1), [0.0, -0.25, 0.25, 0.0]);
+        // Later packets convert into the same buffer
+        let converted = planar_buf.as_f32(&decoded) as *const AudioBuffer<f32>;
+        assert_eq!(planar_buf.as_f32(&decoded) as *const _, converted);
+
+        // f32 packets are read where they are
+        let mut float = AudioBuffer::<f32>::new(4, spec);
+        float.render_reserved(Some(4));
+        let decoded = AudioBufferRef::F32(Cow::Borrowed(&float));
+        assert!(std::ptr::eq(planar_buf.as_f32(&decoded), &float));
+    }
+
+    #[test]
+    fn mono_sum_keeps_center_and_halves_sides() {
+        let left = [1.0, 1.0, 0.5];
+        let right = [1.0, 0.0, -0.5];
+        assert_eq!(sum_to_mono(&[&left[..], &right[..]]), vec![1.0, 0.5, 0.0]);
+        assert!(sum_to_mono::<&[f32]>(&[]).is_empty());
+    }
+
+    #[test]
+    fn channels_conform_to_the_station() {
+        let mono = vec![vec![0.5, -0.5]];
+        assert_eq!(conform_channels(mono.clone(), 2), vec![mono[0].clone(); 2]);
+
+        let stereo = vec![vec![1.0, 1.0], vec![1.0, 0.0]];
+        assert_eq!(conform_channels(stereo.clone(), 2), stereo);
+        assert_eq!(conform_channels(stereo, 1), vec![vec![1.0, 0.5]]);
+
+        // Third and fourth channels average in at half level
+        let quad = vec![vec![1.0], vec![-1.0], vec![0.5], vec![0.5]];
+        assert_eq!(conform_channels(quad, 2), vec![vec![0.75], vec![-0.25]]);
+    }
+
+    #[test]
+    fn sanitize_silences_garbage_and_clamps_overs() {
+        let mut block = vec![
+            vec![0.5, f32::NAN, 1.5],
+            vec![f32::NEG_INFINITY, -2.0, -1.0],
+        ];
+        let fixed = sanitize(&mut block);
+        assert_eq!(block, vec![vec![0.5, 0.0, 1.0], vec![0.0, -1.0, -1.0]]);
+        assert_eq!(
+            fixed,
+            Sanitized {
+                non_finite: 2,
+                clamped: 2
+            }
+        );
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert_eq!(interleaved_to_planar(&[], 2), vec![Vec::<f32>::new(); 2]);
+        assert!(interleaved_to_planar(&[1.0, 2.0], 0).is_empty());
+        assert!(planar_to_interleaved::<&[f32]>(&[]).is_empty());
+        assert!(planar_to_interleaved::<&[f32]>(&[&[], &[]]).is_empty());
+    }
+
+    #[test]
+    fn ragged_inputs_are_truncated() {
+        // Partial trailing frame is dropped
+        let planar = interleaved_to_planar(&[1.0, -1.0, 2.0], 2);
+        assert_eq!(planar, vec![vec![1.0], vec![-1.0]]);
+
+        let left = [1.0, 2.0, 3.0];
+        let right = [-1.0, -2.0];
+        assert_eq!(
+            planar_to_interleaved(&[&left[..], &right[..]]),
+            vec![1.0, -1.0, 2.0, -2.0]
+        );
+    }
+}
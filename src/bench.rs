@@ -0,0 +1,163 @@
+//! Encode and decode throughput, for `zelfm bench`.
+//!
+//! Synthetic PCM goes through the same Vorbis encoder setup the broadcaster
+//! gives each listener, and the resulting OGG is decoded the way a listener
+//! decodes it. Nothing touches the network or an audio device, so the numbers
+//! only move when the codec path does.
+
+use std::time::{Duration, Instant};
+
+use vorbis_rs::VorbisDecoder;
+
+use crate::broadcaster::{vorbis_encoder, QUALITY_TIERS};
+
+/// Frames per block fed to the encoder, like a file source sends
+const BLOCK_FRAMES: usize = 2048;
+
+pub struct BenchOptions {
+    /// Length of the synthetic audio
+    pub duration: Duration,
+    /// Vorbis quality, as for `--min-quality` / `--max-quality`
+    pub quality: f32,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(60),
+            quality: QUALITY_TIERS[0],
+            sample_rate: 44100,
+            channels: 2,
+        }
+    }
+}
+
+/// One timed pass over the audio
+pub struct PassReport {
+    pub elapsed: Duration,
+    /// Frames that went in (encode) or came out (decode)
+    pub frames: u64,
+}
+
+impl PassReport {
+    /// Seconds of audio handled per second of wall time
+    pub fn realtime(&self, sample_rate: u32) -> f64 {
+        self.frames as f64 / sample_rate as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn samples_per_sec(&self, channels: u8) -> f64 {
+        (self.frames * u64::from(channels)) as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Planar f32 PCM handled per second, in MB
+    pub fn pcm_mb_per_sec(&self, channels: u8) -> f64 {
+        self.samples_per_sec(channels) * 4.0 / 1_000_000.0
+    }
+}
+
+pub struct BenchReport {
+    pub encode: PassReport,
+    pub decode: PassReport,
+    /// Size of the encoded OGG stream
+    pub encoded_bytes: usize,
+}
+
+impl BenchReport {
+    /// Bits per second of audio the encoder produced
+    pub fn bitrate(&self, sample_rate: u32) -> u64 {
+        let secs = self.encode.frames as f64 / sample_rate as f64;
+        (self.encoded_bytes as f64 * 8.0 / secs) as u64
+    }
+}
+
+/// Encode `options.duration` of synthetic audio, then decode it again
+pub fn run(options: &BenchOptions) -> anyhow::Result<BenchReport> {
+    let frames = (options.duration.as_secs_f64() * options.sample_rate as f64) as usize;
+    // Generated up front so only the codec is timed
+    let planar = synthetic_audio(frames, options.channels as usize, options.sample_rate);
+
+    let started = Instant::now();
+    let mut encoder = vorbis_encoder(
+        options.sample_rate,
+        options.channels,
+        Vec::new(),
+        options.quality,
+        None,
+    )
+    .map_err(|e| anyhow::anyhow!(e))?;
+    for start in (0..frames).step_by(BLOCK_FRAMES) {
+        let end = (start + BLOCK_FRAMES).min(frames);
+        let block: Vec<&[f32]> = planar.iter().map(|c| &c[start..end]).collect();
+        encoder.encode_audio_block(&block)?;
+    }
+    let ogg = encoder.finish()?;
+    let encode = PassReport {
+        elapsed: started.elapsed(),
+        frames: frames as u64,
+    };
+
+    let started = Instant::now();
+    let mut decoder = VorbisDecoder::new(std::io::Cursor::new(&ogg))?;
+    let mut decoded = 0;
+    while let Some(block) = decoder.decode_audio_block()? {
+        decoded += block.samples().first().map_or(0, |c| c.len()) as u64;
+    }
+    let decode = PassReport {
+        elapsed: started.elapsed(),
+        frames: decoded,
+    };
+
+    Ok(BenchReport {
+        encode,
+        decode,
+        encoded_bytes: ogg.len(),
+    })
+}
+
+/// A slowly swelling chord over quiet noise: busy enough that VBR has to work,
+/// and the same on every run
+fn synthetic_audio(frames: usize, channels: usize, sample_rate: u32) -> Vec<Vec<f32>> {
+    const CHORD_HZ: [f32; 3] = [220.0, 277.2, 329.6];
+    let mut seed: u32 = 0x2545_f491;
+    (0..channels)
+        .map(|channel| {
+            (0..frames)
+                .map(|i| {
+                    let t = i as f32 / sample_rate as f32;
+                    let swell = 0.5 + 0.4 * (t * 0.25 * std::f32::consts::TAU).sin();
+                    let tone: f32 = CHORD_HZ
+                        .iter()
+                        .map(|hz| {
+                            (t * hz * (1.0 + channel as f32 * 0.002) * std::f32::consts::TAU).sin()
+                        })
+                        .sum();
+                    seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                    swell * tone / CHORD_HZ.len() as f32 * 0.8 + noise * 0.05
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_what_it_encoded() {
+        let options = BenchOptions {
+            duration: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let report = run(&options).unwrap();
+
+        assert_eq!(report.encode.frames, 44100);
+        // Padding at the end of the last Vorbis block aside
+        assert!(report.decode.frames.abs_diff(report.encode.frames) < BLOCK_FRAMES as u64);
+        assert!(report.encoded_bytes > 0);
+        assert!(report.encode.realtime(options.sample_rate) > 0.0);
+    }
+}
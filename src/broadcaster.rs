@@ -1,82 +1,1860 @@
 use async_trait::async_trait;
 use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::{NonZeroU32, NonZeroU8};
 use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+    atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex, RwLock, Weak,
 };
-use tokio::io::AsyncWriteExt;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, OwnedSemaphorePermit, Semaphore};
 use tokio::time::{timeout, Duration};
-use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+use tokio_util::sync::CancellationToken;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoder, VorbisEncoderBuilder};
 
-use crate::service::{ChatMessage, RadioServiceServer, StationInfo};
+use crate::audio_source::SourceControl;
+use crate::chat_log::ChatLog;
+#[cfg(feature = "flac")]
+use crate::flac_stream::FlacStreamEncoder;
+use crate::levels::{LevelMeter, MeterMode};
+use crate::pcm_frame::PcmFrameWriter;
+use crate::rewind::{PageSplitter, RewindBuffer};
+use crate::service::{
+    ChannelLevels, ChatMessage, HealthStatus, RadioError, RadioServiceServer, SignedStationInfo,
+    SkipVote, SourceCapabilities, StationEvent, StationInfo, StationInfoUpdate, StreamCodec,
+    TrackRequest, FLAC_BITS_PER_SAMPLE,
+};
 use zel_core::protocol::RequestContext;
 
 type AudioBlock = Vec<Vec<f32>>;
 
+/// Default encoded bytes buffered before a chunk is sent to a listener
+pub const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+/// Chunk size with [`BroadcastOptions::low_latency`] unless one is set
+pub const LOW_LATENCY_CHUNK_SIZE: usize = 1024;
+
+/// Smallest OGG page body with [`BroadcastOptions::low_latency`]; libogg
+/// otherwise fills pages to about 4 KB, a quarter second at 128 kbps
+pub const LOW_LATENCY_PAGE_SIZE: u16 = 256;
+
+/// Shortest [`BroadcastOptions::flush_interval`] accepted; a page holds at
+/// least one Vorbis packet, up to about 25 ms of audio, so shorter gains nothing
+pub const MIN_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Longest [`BroadcastOptions::flush_interval`] accepted
+pub const MAX_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of recent chat messages kept for late joiners and resubscribers,
+/// unless configured
+pub const CHAT_HISTORY_LEN: usize = 100;
+
+/// Default memory budget for chat history (see [`chat_size`])
+pub const DEFAULT_CHAT_HISTORY_BYTES: usize = 256 * 1024;
+
+/// Default chat messages buffered for each live `chat_stream` subscriber
+pub const DEFAULT_CHAT_CAPACITY: usize = 100;
+
+/// Most concurrent subscriptions of one kind (chat, events) a connection may hold
+pub const MAX_SUBSCRIPTIONS: usize = 1;
+
+/// Join and leave events kept for a slow `event_stream` subscriber
+const EVENT_CAPACITY: usize = 64;
+
+/// Most track requests kept; the oldest are dropped beyond this
+pub const MAX_TRACK_REQUESTS: usize = 50;
+
+/// Minimum time between track requests from one listener
+pub const REQUEST_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Longest track request accepted, in characters
+pub const MAX_REQUEST_LEN: usize = 200;
+
+/// OGG pages sent to a new listener unbuffered when fast start is on: the
+/// Vorbis header pages (identification, then comment + setup, which can span
+/// two pages) and the first audio page
+pub const FAST_START_PAGES: usize = 4;
+
+/// Default time a listener may go without accepting data before it's dropped
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default QUIC send priority of listener audio. Chat, events, and RPC
+/// replies go out at QUIC's default of 0, so audio waiting to be sent goes
+/// first on a congested connection
+pub const DEFAULT_AUDIO_PRIORITY: i32 = 1;
+
+/// Vorbis quality of each adaptive bitrate tier, best first (roughly 160,
+/// 80, and 48 kbps for stereo)
+pub const QUALITY_TIERS: &[f32] = &[0.5, 0.1, -0.1];
+
+/// Lowest and highest Vorbis quality an operator can allow for `listen_at`
+pub const MIN_QUALITY: f32 = -0.1;
+pub const MAX_QUALITY: f32 = 1.0;
+
+/// Rough Vorbis bitrate at `quality` for `channels`: the encoder's nominal
+/// stereo rates at 44.1 kHz, scaled per channel
+pub fn vorbis_bitrate(quality: f32, channels: u8) -> u32 {
+    // Stereo kbps at quality -0.1, 0.0, 0.1, ... 1.0
+    const STEREO_KBPS: [u32; 12] = [45, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 500];
+    let index = ((quality * 10.0).round() as i32 + 1).clamp(0, STEREO_KBPS.len() as i32 - 1);
+    STEREO_KBPS[index as usize] * 1000 * u32::from(channels.max(1)) / 2
+}
+
+/// Pages a shared `listen_at` encoder keeps for listeners catching up
+const SHARED_ENCODER_WINDOW: Duration = Duration::from_secs(5);
+
+/// A write slower than this counts as backpressure for adaptive bitrate
+pub const SLOW_SEND: Duration = Duration::from_millis(500);
+
+/// Consecutive slow writes before a listener drops one quality tier
+pub const SLOW_SENDS_TO_DEGRADE: u32 = 3;
+
+/// Time without a slow write before a listener goes back up one tier
+pub const RECOVER_AFTER: Duration = Duration::from_secs(30);
+
+/// Steps a listener down a [`QUALITY_TIERS`] level after
+/// [`SLOW_SENDS_TO_DEGRADE`] slow writes in a row, and back up one after
+/// [`RECOVER_AFTER`] without one
+struct TierController {
+    slow_sends: u32,
+    last_change: std::time::Instant,
+}
+
+impl TierController {
+    fn new(now: std::time::Instant) -> Self {
+        Self {
+            slow_sends: 0,
+            last_change: now,
+        }
+    }
+
+    /// Count a write that took `took` while at tier `current`, returning the
+    /// tier to switch to, if any
+    fn record(&mut self, took: Duration, current: usize, now: std::time::Instant) -> Option<usize> {
+        if took >= SLOW_SEND {
+            self.slow_sends += 1;
+        } else {
+            self.slow_sends = 0;
+        }
+        if self.slow_sends >= SLOW_SENDS_TO_DEGRADE && current + 1 < QUALITY_TIERS.len() {
+            self.slow_sends = 0;
+            self.last_change = now;
+            Some(current + 1)
+        } else if self.slow_sends > 0 {
+            // Still struggling; don't count this as recovery time
+            self.last_change = now;
+            None
+        } else if current > 0 && now - self.last_change >= RECOVER_AFTER {
+            self.last_change = now;
+            Some(current - 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Default number of PCM blocks buffered in the broadcast channel
+pub const DEFAULT_PCM_CAPACITY: usize = 100;
+
+/// Default time a departed listener still counts in the reported listener
+/// count, so quick reconnects don't make the number flicker
+pub const DEFAULT_LISTENER_GRACE: Duration = Duration::from_secs(3);
+
+/// Audio the measured [`ChannelLevels::encoded_bitrate`] averages over, so
+/// VBR swings between loud and quiet passages even out
+pub const BITRATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Audio older than this means the source has stopped, as far as `health` goes
+pub const AUDIO_STALE_AFTER: Duration = Duration::from_secs(5);
+
+/// What happens when a listener's encoder falls behind the PCM broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Slow encoders skip the oldest blocks and keep going
+    #[default]
+    DropOldest,
+    /// File sources wait for the slowest encoder (live input still drops)
+    Backpressure,
+}
+
+/// Tunables for the per-listener encoding pipeline
+#[derive(Debug, Clone)]
+pub struct BroadcastOptions {
+    /// Flush threshold for encoded OGG data (smaller = lower latency, more writes)
+    pub chunk_size: usize,
+    /// Capacity of the PCM broadcast channel, in blocks
+    pub pcm_capacity: usize,
+    pub overflow: OverflowPolicy,
+    /// Disconnect listeners after this long so busy stations rotate fairly
+    pub max_session: Option<Duration>,
+    /// Drop listeners whose connection accepts nothing for this long
+    pub stall_timeout: Duration,
+    /// QUIC send priority of audio streams, against 0 for everything else
+    /// on the connection (higher goes first; equal priorities share)
+    pub audio_priority: i32,
+    /// Send the header pages and first audio page as soon as they're encoded
+    /// instead of waiting for a full chunk
+    pub fast_start: bool,
+    pub meter_mode: MeterMode,
+    /// Keep this much encoded audio so listeners can start in the past (`None` = live only)
+    pub rewind: Option<Duration>,
+    /// Post "… joined" / "… left" to chat as listeners come and go
+    pub announce_joins: bool,
+    /// Re-encode a struggling listener's stream at a lower [`QUALITY_TIERS`]
+    /// level instead of letting it stall out; each switch starts a new link
+    /// of a chained OGG stream, so only `listen_adaptive` streams do this
+    pub adaptive_bitrate: bool,
+    /// Refuse listeners beyond this many with [`RadioError::StationFull`].
+    /// Every listener runs its own encoder on the blocking pool, so this also
+    /// caps concurrent encoders (`None` = unlimited)
+    pub max_listeners: Option<usize>,
+    /// Listener stream encoding; FLAC streams can't be rewound, relayed, or
+    /// adapted, since those work on OGG pages
+    pub codec: StreamCodec,
+    /// Let listeners pick a Vorbis quality in this range with `listen_at`
+    pub quality_range: Option<(f32, f32)>,
+    /// Keep counting a departed listener for this long; a listener joining
+    /// meanwhile takes its place instead of raising the count
+    pub listener_grace: Duration,
+    /// Cap on total upload to listeners, in kbps: only as many listeners as
+    /// fit at the best quality each can get are admitted, the rest get
+    /// [`RadioError::StationFull`] (combines with `max_listeners`)
+    pub max_bandwidth: Option<u32>,
+    /// Let listeners vote the current track off: it's skipped once more than
+    /// this share of listeners (0 to 1) vote for it. Needs a source that can
+    /// skip, given with [`RadioBroadcaster::with_source_control`]
+    pub vote_skip: Option<f32>,
+    /// Finish OGG pages after [`LOW_LATENCY_PAGE_SIZE`] bytes so audio leaves
+    /// the encoder sooner, for a little more page overhead. The bitrate
+    /// strategy needs no change: unmanaged quality VBR, which every encoder
+    /// here uses, is already Vorbis' lowest-latency mode, where the ABR modes
+    /// hold packets back in the bitrate management reservoir
+    pub low_latency: bool,
+    /// Finish OGG pages once they hold about this much audio at the stream
+    /// bitrate, in place of `low_latency`'s fixed page size. vorbis_rs has
+    /// no flush call, so this sets the encoder's minimum page size instead;
+    /// VBR makes it approximate, and a page still holds at least one packet
+    pub flush_interval: Option<Duration>,
+    /// Chat messages buffered per `chat_stream` subscriber; one that falls
+    /// further behind skips ahead (and can catch up from history)
+    pub chat_capacity: usize,
+    /// Chat history keeps at most this many messages...
+    pub chat_history_len: usize,
+    /// ...and at most this many bytes of them, dropping the oldest first
+    pub chat_history_bytes: usize,
+}
+
+impl Default for BroadcastOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            pcm_capacity: DEFAULT_PCM_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            max_session: None,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            audio_priority: DEFAULT_AUDIO_PRIORITY,
+            fast_start: true,
+            meter_mode: MeterMode::default(),
+            rewind: None,
+            announce_joins: true,
+            adaptive_bitrate: false,
+            max_listeners: None,
+            codec: StreamCodec::default(),
+            quality_range: None,
+            listener_grace: DEFAULT_LISTENER_GRACE,
+            max_bandwidth: None,
+            vote_skip: None,
+            low_latency: false,
+            flush_interval: None,
+            chat_capacity: DEFAULT_CHAT_CAPACITY,
+            chat_history_len: CHAT_HISTORY_LEN,
+            chat_history_bytes: DEFAULT_CHAT_HISTORY_BYTES,
+        }
+    }
+}
+
+/// Custom Write impl that sends encoded OGG data to a listener's channel
+struct ChannelWriter {
+    tx: tokio::sync::mpsc::Sender<Vec<u8>>,
+    buffer: Vec<u8>,
+    chunk_size: usize,
+    /// Pages to pass straight through before chunking kicks in
+    eager_pages: usize,
+    pages_seen: usize,
+    /// Every byte the encoder has written, for [`BitrateWindow`]
+    written: Arc<AtomicU64>,
+}
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // libogg hands over each page as a header write then a body write
+        if buf.starts_with(b"OggS") {
+            self.pages_seen += 1;
+        }
+        self.written.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= self.chunk_size || self.pages_seen <= self.eager_pages {
+            let chunk = self.buffer.clone();
+            self.buffer.clear();
+            // If send fails, listener disconnected - return error to stop encoder
+            self.tx.blocking_send(chunk).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Listener disconnected")
+            })?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            let chunk = self.buffer.clone();
+            self.buffer.clear();
+            // If send fails, listener disconnected - return error to stop encoder
+            self.tx.blocking_send(chunk).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Listener disconnected")
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        let _ = std::io::Write::flush(self);
+    }
+}
+
+/// A listener's encoder, by [`StreamCodec`]
+enum ListenerEncoder<W: std::io::Write> {
+    // Boxed: the libvorbis state dwarfs the other encoders
+    Vorbis(Box<VorbisEncoder<W>>),
+    #[cfg(feature = "flac")]
+    Flac(FlacStreamEncoder<W>),
+    Pcm(PcmFrameWriter<W>),
+}
+
+impl<W: std::io::Write> ListenerEncoder<W> {
+    /// `quality` and `page_size` only apply to Vorbis
+    fn new(
+        codec: StreamCodec,
+        sample_rate: u32,
+        channels: u8,
+        writer: W,
+        quality: f32,
+        page_size: Option<u16>,
+    ) -> Result<Self, String> {
+        match codec {
+            StreamCodec::Vorbis => Ok(Self::Vorbis(Box::new(vorbis_encoder(
+                sample_rate,
+                channels,
+                writer,
+                quality,
+                page_size,
+            )?))),
+            #[cfg(feature = "flac")]
+            StreamCodec::Flac => Ok(Self::Flac(
+                FlacStreamEncoder::new(sample_rate, channels, writer)
+                    .map_err(|e| format!("Encoder setup: {}", e))?,
+            )),
+            #[cfg(not(feature = "flac"))]
+            StreamCodec::Flac => {
+                let _ = writer;
+                Err("FLAC broadcasting requires the `flac` feature".to_string())
+            }
+            StreamCodec::Pcm => Ok(Self::Pcm(PcmFrameWriter::new(channels, writer))),
+        }
+    }
+
+    fn encode_audio_block(&mut self, block: &[&[f32]]) -> Result<(), String> {
+        match self {
+            Self::Vorbis(encoder) => encoder.encode_audio_block(block).map_err(|e| e.to_string()),
+            #[cfg(feature = "flac")]
+            Self::Flac(encoder) => encoder.encode_audio_block(block).map_err(|e| e.to_string()),
+            Self::Pcm(encoder) => encoder.encode_audio_block(block).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// The listener missed `blocks`; only PCM frames can tell it so
+    fn skip(&mut self, blocks: u64) {
+        if let Self::Pcm(encoder) = self {
+            encoder.skip(blocks);
+        }
+    }
+
+    /// Write out the end of the stream and hand back the writer
+    fn finish(self) -> Result<W, String> {
+        match self {
+            Self::Vorbis(encoder) => (*encoder).finish().map_err(|e| e.to_string()),
+            #[cfg(feature = "flac")]
+            Self::Flac(encoder) => encoder.finish().map_err(|e| e.to_string()),
+            Self::Pcm(encoder) => encoder.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
+
+pub(crate) fn vorbis_encoder<W: std::io::Write>(
+    sample_rate: u32,
+    channels: u8,
+    writer: W,
+    target_quality: f32,
+    page_size: Option<u16>,
+) -> Result<VorbisEncoder<W>, String> {
+    let sample_rate = NonZeroU32::new(sample_rate).ok_or("Encoder setup: sample rate is 0 Hz")?;
+    let channels = NonZeroU8::new(channels).ok_or("Encoder setup: no channels")?;
+    VorbisEncoderBuilder::new(sample_rate, channels, writer)
+        .map_err(|e| format!("Encoder setup: {}", e))?
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr { target_quality })
+        .minimum_page_data_size(page_size)
+        .build()
+        .map_err(|e| format!("Encoder build: {}", e))
+}
+
+/// Page body size holding about `interval` of audio at `bitrate` bits per second
+pub fn flush_page_size(interval: Duration, bitrate: u32) -> u16 {
+    let bytes = bitrate as f64 / 8.0 * interval.as_secs_f64();
+    bytes.round().clamp(1.0, u16::MAX as f64) as u16
+}
+
+/// An encoder whose pages any number of `listen_at` listeners replay; stops
+/// once the last of them lets go
+struct SharedEncoder {
+    pages: Arc<RewindBuffer>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for SharedEncoder {
+    fn drop(&mut self) {
+        // Dropping the chunk receiver ends the encoder at its next send
+        self.task.abort();
+    }
+}
+
+/// Map key for a quality, so near-identical requests share an encoder
+fn quality_key(quality: f32) -> i32 {
+    (quality * 10.0).round() as i32
+}
+
+/// Ring buffer of recent chat plus the sequence counter that orders it,
+/// bounded by both message count and total size
+struct ChatHistory {
+    messages: VecDeque<ChatMessage>,
+    next_seq: u64,
+    /// [`chat_size`] of everything in `messages`
+    bytes: usize,
+    max_len: usize,
+    max_bytes: usize,
+}
+
+impl ChatHistory {
+    fn new(max_len: usize, max_bytes: usize) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            next_seq: 0,
+            bytes: 0,
+            max_len,
+            max_bytes,
+        }
+    }
+
+    /// Add a message, then drop the oldest until both limits hold again
+    fn push(&mut self, chat: ChatMessage) {
+        self.bytes += chat_size(&chat);
+        self.messages.push_back(chat);
+        while self.messages.len() > self.max_len || self.bytes > self.max_bytes {
+            let Some(oldest) = self.messages.pop_front() else {
+                break;
+            };
+            self.bytes -= chat_size(&oldest);
+        }
+    }
+}
+
+/// Memory a history entry holds: the message itself plus its text
+fn chat_size(chat: &ChatMessage) -> usize {
+    std::mem::size_of::<ChatMessage>()
+        + chat.message.len()
+        + chat.nickname.as_ref().map_or(0, String::len)
+}
+
+/// Active subscriptions of one kind per connection (keyed by connection stable ID)
+#[derive(Clone, Default)]
+struct Subscriptions {
+    active: Arc<Mutex<HashMap<usize, usize>>>,
+}
+
+impl Subscriptions {
+    /// Claim a slot for `connection`, or `None` if it's already at the limit
+    fn acquire(&self, connection: usize) -> Option<SubscriptionSlot> {
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(connection).or_default();
+        if *count >= MAX_SUBSCRIPTIONS {
+            return None;
+        }
+        *count += 1;
+        Some(SubscriptionSlot {
+            subscriptions: self.clone(),
+            connection,
+        })
+    }
+}
+
+/// Releases its slot when the subscription ends, however it ends
+struct SubscriptionSlot {
+    subscriptions: Subscriptions,
+    connection: usize,
+}
+
+impl Drop for SubscriptionSlot {
+    fn drop(&mut self) {
+        let mut active = self.subscriptions.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.connection) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.connection);
+            }
+        }
+    }
+}
+
+/// Bytes sent to listeners, with a rate sampled at most once a second
+struct Throughput {
+    sent: AtomicU64,
+    /// When the rate was last sampled, `sent` at that point, and the rate
+    sample: Mutex<(std::time::Instant, u64, u64)>,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            sample: Mutex::new((std::time::Instant::now(), 0, 0)),
+        }
+    }
+
+    fn add(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Average rate since the previous sample
+    fn bytes_per_sec(&self) -> u64 {
+        let mut sample = self.sample.lock().unwrap();
+        let (at, sent_then, rate) = *sample;
+        let elapsed = at.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return rate;
+        }
+        let sent = self.sent.load(Ordering::Relaxed);
+        let rate = ((sent - sent_then) as f64 / elapsed.as_secs_f64()) as u64;
+        *sample = (std::time::Instant::now(), sent, rate);
+        rate
+    }
+}
+
+/// Encoded bytes for the most recent [`BITRATE_WINDOW`] of audio, block by block
+struct BitrateWindow {
+    /// Frames and encoded bytes of each block
+    blocks: VecDeque<(usize, u64)>,
+    frames: usize,
+    bytes: u64,
+    sample_rate: u32,
+}
+
+impl BitrateWindow {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            blocks: VecDeque::new(),
+            frames: 0,
+            bytes: 0,
+            sample_rate,
+        }
+    }
+
+    fn push(&mut self, frames: usize, bytes: u64) {
+        self.blocks.push_back((frames, bytes));
+        self.frames += frames;
+        self.bytes += bytes;
+        let max_frames = (BITRATE_WINDOW.as_secs_f64() * self.sample_rate as f64) as usize;
+        while let Some(&(oldest, oldest_bytes)) = self.blocks.front() {
+            if self.frames - oldest < max_frames {
+                break;
+            }
+            self.blocks.pop_front();
+            self.frames -= oldest;
+            self.bytes -= oldest_bytes;
+        }
+    }
+
+    /// Bits per second of audio across the window (0 when it's empty)
+    fn bitrate(&self) -> u32 {
+        if self.frames == 0 {
+            return 0;
+        }
+        (self.bytes as f64 * 8.0 * self.sample_rate as f64 / self.frames as f64) as u32
+    }
+}
+
+/// Upload to charge each listener against `max_bandwidth`: for Vorbis, the
+/// best quality a listener can get (the top adaptive tier, or the top of
+/// `quality_range`), otherwise the stream's `bitrate`
+fn listener_bitrate(options: &BroadcastOptions, bitrate: u32, channels: u8) -> u32 {
+    match options.codec {
+        StreamCodec::Vorbis => {
+            let best = options
+                .quality_range
+                .map_or(QUALITY_TIERS[0], |(_, max)| max.max(QUALITY_TIERS[0]));
+            vorbis_bitrate(best, channels)
+        }
+        StreamCodec::Flac | StreamCodec::Pcm => bitrate,
+    }
+}
+
+/// Listeners that fit in `max_listeners` and in `max_bandwidth` at `bitrate` each
+fn listener_limit(options: &BroadcastOptions, bitrate: u32) -> Option<usize> {
+    let by_bandwidth = options
+        .max_bandwidth
+        .map(|kbps| (u64::from(kbps) * 1000 / u64::from(bitrate.max(1))) as usize);
+    match (options.max_listeners, by_bandwidth) {
+        (Some(max), Some(fit)) => Some(max.min(fit)),
+        (max, fit) => max.or(fit),
+    }
+}
+
+/// Recent departures still included in the reported listener count
+struct Departures {
+    grace: Duration,
+    recent: VecDeque<std::time::Instant>,
+}
+
+impl Departures {
+    fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            recent: VecDeque::new(),
+        }
+    }
+
+    fn expire(&mut self, now: std::time::Instant) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&left| now.duration_since(left) >= self.grace)
+        {
+            self.recent.pop_front();
+        }
+    }
+
+    fn left(&mut self, now: std::time::Instant) {
+        self.expire(now);
+        if !self.grace.is_zero() {
+            self.recent.push_back(now);
+        }
+    }
+
+    /// A new listener stands in for the oldest pending departure, if any
+    fn joined(&mut self, now: std::time::Instant) {
+        self.expire(now);
+        self.recent.pop_front();
+    }
+
+    fn pending(&mut self, now: std::time::Instant) -> usize {
+        self.expire(now);
+        self.recent.len()
+    }
+}
+
+/// A listener's request text with control characters removed, so it can't
+/// rewrite the operator's terminal; errors if it's empty or too long
+fn clean_request(query: &str) -> Result<String, RadioError> {
+    let query: String = query.chars().filter(|c| !c.is_control()).collect();
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(RadioError::InvalidRequest("request is empty".to_string()));
+    }
+    if query.chars().count() > MAX_REQUEST_LEN {
+        return Err(RadioError::InvalidRequest(format!(
+            "request is longer than {} characters",
+            MAX_REQUEST_LEN
+        )));
+    }
+    Ok(query.to_string())
+}
+
+/// Listener track requests plus when each listener last asked
+#[derive(Default)]
+struct TrackRequests {
+    queue: VecDeque<TrackRequest>,
+    last_request: HashMap<usize, std::time::Instant>,
+}
+
+/// Listeners who voted to skip the current track
+#[derive(Default)]
+struct SkipVotes {
+    /// [`SourceControl::track`] the votes are for
+    track: u64,
+    voters: HashSet<usize>,
+}
+
+/// Votes a track needs to be skipped: more than `share` of `listeners`, and
+/// never more than there are listeners to vote
+fn votes_needed(share: f32, listeners: usize) -> usize {
+    let listeners = listeners.max(1);
+    ((share as f64 * listeners as f64).floor() as usize + 1).min(listeners)
+}
+
+/// A connected listener, as shown by the operator console
+#[derive(Debug, Clone)]
+pub struct ListenerSession {
+    pub id: usize,
+    /// Node ID for iroh listeners, socket address for HTTP ones
+    pub peer: String,
+    pub nickname: Option<String>,
+    pub connected_at: std::time::Instant,
+}
+
+impl ListenerSession {
+    /// How join/leave notices refer to this listener
+    fn display_name(&self) -> &str {
+        self.nickname.as_deref().unwrap_or("A listener")
+    }
+
+    /// How operator logs identify this listener: ID, nickname, and peer
+    fn label(&self) -> String {
+        match &self.nickname {
+            Some(nickname) => format!("{} ({}, {})", self.id, nickname, self.peer),
+            None => format!("{} ({})", self.id, self.peer),
+        }
+    }
+}
+
+/// Average send rate over a stream so far, for slow-listener warnings
+pub(crate) fn send_rate(sent_bytes: u64, since: std::time::Instant) -> String {
+    let secs = since.elapsed().as_secs_f64();
+    if secs < 1.0 {
+        return "just started".to_string();
+    }
+    format!(
+        "{:.0} kbps average",
+        sent_bytes as f64 * 8.0 / secs / 1000.0
+    )
+}
+
+/// Longest name, description, genre, tag, or website `set_station_info` accepts
+pub const MAX_INFO_FIELD_LEN: usize = 256;
+
+/// What `get_info` says about the station; operators can change it on the
+/// air with `set_station_info`
+#[derive(Debug, Clone, Default)]
+struct StationMetadata {
+    name: String,
+    description: String,
+    genre: Option<String>,
+    tags: Vec<String>,
+    website: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct RadioBroadcaster {
-    station_name: String,
-    station_desc: String,
+    metadata: Arc<RwLock<StationMetadata>>,
+    /// Node IDs allowed to call `set_station_info`
+    operators: Arc<HashSet<iroh::PublicKey>>,
     sample_rate: u32,
     channels: u8,
-    pcm_broadcast_tx: broadcast::Sender<AudioBlock>, // Broadcast PCM audio blocks
-    chat_broadcast_tx: broadcast::Sender<ChatMessage>, // Broadcast chat messages
+    capabilities: SourceCapabilities,
+    options: BroadcastOptions,
+    pcm_broadcast_tx: broadcast::Sender<Arc<AudioBlock>>, // Broadcast PCM audio blocks
+    chat_broadcast_tx: broadcast::Sender<ChatMessage>,    // Broadcast chat messages
+    chat_history: Arc<Mutex<ChatHistory>>,
+    /// Where chat is appended as it's posted, when it's kept on disk
+    chat_log: Option<Arc<Mutex<ChatLog>>>,
+    chat_subscriptions: Subscriptions,
+    /// Listener joins and leaves for `event_stream`; the source's own events
+    /// come through [`SourceControl::events`]
+    event_broadcast_tx: broadcast::Sender<StationEvent>,
+    event_subscriptions: Subscriptions,
+    track_requests: Arc<Mutex<TrackRequests>>,
+    /// The playing source's track and artwork, and its skip for
+    /// [`BroadcastOptions::vote_skip`]
+    source_control: Option<SourceControl>,
+    /// Served by `get_artwork` when the track has no cover of its own
+    logo: Option<Arc<Vec<u8>>>,
+    skip_votes: Arc<Mutex<SkipVotes>>,
+    levels: Arc<LevelMeter>,
+    rewind: Option<Arc<RewindBuffer>>,
+    /// Listeners get the upstream's pages from `rewind` instead of an encoder
+    relay: bool,
+    bitrate: u32,
+    /// Upload each listener is charged against `max_bandwidth`
+    listener_bitrate: u32,
+    /// Node key used to sign [`StationInfo`] for `signed_info`
+    signing_key: Option<iroh::SecretKey>,
+    /// A relay's upstream node ID and signed info, passed through by `signed_info`
+    upstream_signature: Option<(iroh::PublicKey, Box<SignedStationInfo>)>,
+    /// Listeners actually connected; see [`RadioBroadcaster::listener_count`]
+    /// for the smoothed figure
     listener_count: Arc<AtomicUsize>,
+    departures: Arc<Mutex<Departures>>,
+    next_listener_id: Arc<AtomicUsize>,
+    /// One permit per listener when `max_listeners` or `max_bandwidth` is set
+    listener_slots: Option<Arc<Semaphore>>,
+    /// Encoded audio sent to listeners over iroh and HTTP
+    throughput: Arc<Throughput>,
+    /// What the encoders at the advertised quality actually produce; see
+    /// [`RadioBroadcaster::encoded_bitrate`]
+    encoded_bitrate: Arc<AtomicU32>,
+    /// Running `listen_at` encoders by [`quality_key`]
+    shared_encoders: Arc<Mutex<HashMap<i32, Weak<SharedEncoder>>>>,
+    sessions: Arc<Mutex<HashMap<usize, ListenerSession>>>,
+    shutdown: CancellationToken,
+    started_at: std::time::Instant,
+}
+
+impl RadioBroadcaster {
+    pub fn new(
+        name: impl Into<String>,
+        desc: impl Into<String>,
+        sample_rate: u32,
+        channels: u8,
+    ) -> (Self, broadcast::Sender<Arc<AudioBlock>>) {
+        Self::with_options(
+            name,
+            desc,
+            sample_rate,
+            channels,
+            BroadcastOptions::default(),
+        )
+    }
+
+    pub fn with_options(
+        name: impl Into<String>,
+        desc: impl Into<String>,
+        sample_rate: u32,
+        channels: u8,
+        options: BroadcastOptions,
+    ) -> (Self, broadcast::Sender<Arc<AudioBlock>>) {
+        // Broadcast channel for PCM audio blocks
+        let (pcm_broadcast_tx, _) = broadcast::channel(options.pcm_capacity);
+        let tx_clone = pcm_broadcast_tx.clone();
+
+        // Broadcast channel for chat messages
+        let (chat_broadcast_tx, _) = broadcast::channel(options.chat_capacity.max(1));
+
+        let levels = Arc::new(LevelMeter::with_mode(options.meter_mode, sample_rate));
+
+        // FLAC's true rate depends on the audio; advertise the uncompressed ceiling
+        let bitrate = match options.codec {
+            StreamCodec::Vorbis => 128000,
+            StreamCodec::Flac => sample_rate * channels as u32 * FLAC_BITS_PER_SAMPLE,
+            StreamCodec::Pcm => sample_rate * channels as u32 * 32,
+        };
+        let listener_bitrate = listener_bitrate(&options, bitrate, channels);
+        let listener_slots =
+            listener_limit(&options, listener_bitrate).map(|max| Arc::new(Semaphore::new(max)));
+        let departures = Departures::new(options.listener_grace);
+
+        let chat_history = ChatHistory::new(options.chat_history_len, options.chat_history_bytes);
+
+        let mut broadcaster = Self {
+            metadata: Arc::new(RwLock::new(StationMetadata {
+                name: name.into(),
+                description: desc.into(),
+                ..Default::default()
+            })),
+            operators: Arc::default(),
+            sample_rate,
+            channels,
+            capabilities: SourceCapabilities::default(),
+            options,
+            pcm_broadcast_tx,
+            chat_broadcast_tx,
+            chat_history: Arc::new(Mutex::new(chat_history)),
+            chat_log: None,
+            chat_subscriptions: Subscriptions::default(),
+            event_broadcast_tx: broadcast::channel(EVENT_CAPACITY).0,
+            event_subscriptions: Subscriptions::default(),
+            track_requests: Arc::new(Mutex::new(TrackRequests::default())),
+            source_control: None,
+            logo: None,
+            skip_votes: Arc::new(Mutex::new(SkipVotes::default())),
+            levels,
+            rewind: None,
+            relay: false,
+            bitrate,
+            listener_bitrate,
+            signing_key: None,
+            upstream_signature: None,
+            listener_count: Arc::new(AtomicUsize::new(0)),
+            departures: Arc::new(Mutex::new(departures)),
+            next_listener_id: Arc::new(AtomicUsize::new(0)),
+            listener_slots,
+            throughput: Arc::new(Throughput::new()),
+            encoded_bitrate: Arc::new(AtomicU32::new(0)),
+            shared_encoders: Arc::new(Mutex::new(HashMap::new())),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            shutdown: CancellationToken::new(),
+            started_at: std::time::Instant::now(),
+        };
+        if let Some(window) = broadcaster.options.rewind {
+            broadcaster.rewind = Some(broadcaster.spawn_rewind_encoder(window));
+        }
+
+        (broadcaster, tx_clone)
+    }
+
+    /// Claim a listener slot, held until the returned permit drops
+    ///
+    /// Fails fast rather than queueing: a waiting listener would just see a
+    /// silent stream.
+    pub(crate) fn reserve_slot(&self) -> Result<Option<OwnedSemaphorePermit>, RadioError> {
+        match &self.listener_slots {
+            Some(slots) => match slots.clone().try_acquire_owned() {
+                Ok(permit) => Ok(Some(permit)),
+                Err(_) => {
+                    warn!("[Broadcaster] Station full, refusing listener");
+                    Err(RadioError::StationFull)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Smallest OGG page body for Vorbis encoders: `flush_interval`'s worth
+    /// at the stream bitrate, else [`LOW_LATENCY_PAGE_SIZE`] with
+    /// `low_latency`, else libogg's own choice (about 4 KB)
+    pub fn page_size(&self) -> Option<u16> {
+        match self.options.flush_interval {
+            Some(interval) => Some(flush_page_size(interval, self.bitrate)),
+            None => self.options.low_latency.then_some(LOW_LATENCY_PAGE_SIZE),
+        }
+    }
+
+    /// Encoded bytes buffered per send: `chunk_size`, but with
+    /// `flush_interval` no more than a page, so each page goes out as soon as
+    /// it's finished rather than waiting on the next
+    pub fn chunk_size(&self) -> usize {
+        match self.options.flush_interval {
+            Some(interval) => self
+                .options
+                .chunk_size
+                .min(flush_page_size(interval, self.bitrate) as usize),
+            None => self.options.chunk_size,
+        }
+    }
+
+    /// Build a listener encoder and run a second of silence through it, for
+    /// checking a setup without serving; returns the encoded size in bytes
+    pub fn check_encoder(&self) -> Result<usize, String> {
+        let mut encoder = ListenerEncoder::new(
+            self.options.codec,
+            self.sample_rate,
+            self.channels,
+            Vec::new(),
+            QUALITY_TIERS[0],
+            self.page_size(),
+        )?;
+        let silence = vec![0.0f32; self.sample_rate as usize];
+        let block: Vec<&[f32]> = (0..self.channels).map(|_| &silence[..]).collect();
+        encoder.encode_audio_block(&block)?;
+        Ok(encoder.finish()?.len())
+    }
+
+    pub fn codec(&self) -> StreamCodec {
+        self.options.codec
+    }
+
+    /// Most listeners admitted at once, from `max_listeners` and `max_bandwidth`
+    pub fn listener_limit(&self) -> Option<usize> {
+        listener_limit(&self.options, self.listener_bitrate)
+    }
+
+    /// A listener as operator logs name it (see [`ListenerSession::label`]);
+    /// just the ID once it's gone
+    pub(crate) fn listener_label(&self, listener_id: usize) -> String {
+        if listener_id == crate::service::STATION_LISTENER_ID {
+            return "shared".to_string();
+        }
+        match self.sessions.lock().unwrap().get(&listener_id) {
+            Some(session) => session.label(),
+            None => listener_id.to_string(),
+        }
+    }
+
+    /// Count encoded bytes delivered to a listener toward [`Self::outbound_bytes_per_sec`]
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.throughput.add(bytes);
+    }
+
+    /// Current total upload to listeners
+    pub fn outbound_bytes_per_sec(&self) -> u64 {
+        self.throughput.bytes_per_sec()
+    }
+
+    /// Nominal bitrate advertised in [`StationInfo`]
+    pub fn bitrate(&self) -> u32 {
+        self.bitrate
+    }
+
+    /// Bits per second the stream's encoder actually produced over the last
+    /// [`BITRATE_WINDOW`] of audio, to set against the nominal [`StationInfo`]
+    /// bitrate
+    ///
+    /// Only measured while an encoder runs at the advertised quality (a
+    /// listener on the default stream, or the rewind buffer); 0 otherwise,
+    /// including on a relay.
+    pub fn encoded_bitrate(&self) -> u32 {
+        self.encoded_bitrate.load(Ordering::Relaxed)
+    }
+
+    /// Listeners to report: connected ones plus any that left within the
+    /// grace period, so a quick reconnect doesn't bounce the number
+    pub fn listener_count(&self) -> usize {
+        let pending = self
+            .departures
+            .lock()
+            .unwrap()
+            .pending(std::time::Instant::now());
+        self.listener_count.load(Ordering::Relaxed) + pending
+    }
+
+    /// Uptime, listeners, and whether audio arrived within [`AUDIO_STALE_AFTER`]
+    ///
+    /// A relay has no local source, so it goes by the upstream's last page.
+    pub fn health(&self) -> HealthStatus {
+        let since_audio = match (&self.rewind, self.relay) {
+            (Some(pages), true) => pages.since_last_page(),
+            _ => self.levels.since_last_block(),
+        };
+        HealthStatus {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            listeners: self.listener_count(),
+            audio_flowing: since_audio.is_some_and(|age| age <= AUDIO_STALE_AFTER),
+            last_audio_ms: since_audio.map(|age| age.as_millis() as u64),
+        }
+    }
+
+    /// The buffer `listen_from` serves, if rewind is configured
+    ///
+    /// A relay always buffers the upstream's pages, but only offers them
+    /// for rewinding when asked to.
+    fn offered_rewind(&self) -> Option<&Arc<RewindBuffer>> {
+        self.rewind
+            .as_ref()
+            .filter(|_| self.options.rewind.is_some())
+    }
+
+    pub fn station_name(&self) -> String {
+        self.metadata.read().unwrap().name.clone()
+    }
+
+    pub fn station_desc(&self) -> String {
+        self.metadata.read().unwrap().description.clone()
+    }
+
+    /// Apply an operator's metadata change. `get_info`, the directory
+    /// listing, and new HTTP listeners see it from now on, and
+    /// `event_stream` subscribers get a [`StationEvent::InfoChanged`].
+    pub fn update_station_info(&self, update: StationInfoUpdate) -> Result<(), RadioError> {
+        let invalid = |reason: String| Err(RadioError::InvalidRequest(reason));
+        if update.is_empty() {
+            return invalid("nothing to change".to_string());
+        }
+        if update
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return invalid("the station name can't be empty".to_string());
+        }
+        let fields = [
+            ("name", &update.name),
+            ("description", &update.description),
+            ("genre", &update.genre),
+            ("website", &update.website),
+        ];
+        let tags = update.tags.iter().flatten().map(|tag| ("tag", tag));
+        for (field, value) in fields
+            .into_iter()
+            .filter_map(|(field, value)| Some((field, value.as_ref()?)))
+            .chain(tags)
+        {
+            if value.chars().count() > MAX_INFO_FIELD_LEN {
+                return invalid(format!(
+                    "{} is longer than {} characters",
+                    field, MAX_INFO_FIELD_LEN
+                ));
+            }
+        }
+
+        // Blank genre or website clears it
+        let cleared = |value: String| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        let event = {
+            let mut metadata = self.metadata.write().unwrap();
+            if let Some(name) = update.name {
+                metadata.name = name.trim().to_string();
+            }
+            if let Some(description) = update.description {
+                metadata.description = description.trim().to_string();
+            }
+            if let Some(genre) = update.genre {
+                metadata.genre = cleared(genre);
+            }
+            if let Some(tags) = update.tags {
+                metadata.tags = tags.into_iter().filter_map(cleared).collect();
+            }
+            if let Some(website) = update.website {
+                metadata.website = cleared(website);
+            }
+            StationEvent::InfoChanged {
+                name: metadata.name.clone(),
+                description: metadata.description.clone(),
+                genre: metadata.genre.clone(),
+                tags: metadata.tags.clone(),
+                website: metadata.website.clone(),
+            }
+        };
+
+        info!("[Station] Info updated: {}", event);
+        let _ = self.event_broadcast_tx.send(event);
+        Ok(())
+    }
+
+    /// How long a listener may stall before it's disconnected
+    pub(crate) fn stall_timeout(&self) -> Duration {
+        self.options.stall_timeout
+    }
+
+    /// Meter for sources to update as they send blocks (read back by `get_levels`)
+    pub fn level_meter(&self) -> Arc<LevelMeter> {
+        self.levels.clone()
+    }
+
+    /// Sequence, record, and broadcast a chat message
+    fn post_chat(&self, mut chat: ChatMessage) {
+        // One lock so history and live order agree
+        let mut history = self.chat_history.lock().unwrap();
+        history.next_seq += 1;
+        chat.seq = history.next_seq;
+        history.push(chat.clone());
+        if let Some(log) = &self.chat_log {
+            let mut log = log.lock().unwrap();
+            if let Err(e) = log.append(&chat) {
+                warn!("[Chat] Couldn't log to {}: {}", log.path().display(), e);
+            }
+        }
+
+        // Broadcast to all chat subscribers
+        let _ = self.chat_broadcast_tx.send(chat);
+    }
+
+    /// Post a message to chat as the station itself
+    pub fn announce(&self, message: impl Into<String>) {
+        use std::time::SystemTime;
+
+        self.post_chat(ChatMessage {
+            listener_id: crate::service::STATION_LISTENER_ID,
+            nickname: Some("Station".to_string()),
+            message: message.into(),
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            seq: 0,
+        });
+    }
+
+    /// Pending track requests, oldest first
+    pub fn track_requests(&self) -> Vec<TrackRequest> {
+        let requests = self.track_requests.lock().unwrap();
+        requests.queue.iter().cloned().collect()
+    }
+
+    /// Ask every encoder to finish its stream and every subscription to end
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Wait (up to `limit`) for connected listeners to drain after shutdown
+    pub async fn wait_for_listeners(&self, limit: Duration) {
+        let drained = async {
+            while self.listener_count.load(Ordering::Relaxed) > 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        if timeout(limit, drained).await.is_err() {
+            warn!(
+                "[Broadcaster] {} listener(s) still connected at shutdown",
+                self.listener_count.load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    /// Connected listeners, oldest first
+    pub fn listeners(&self) -> Vec<ListenerSession> {
+        let mut sessions: Vec<_> = self.sessions.lock().unwrap().values().cloned().collect();
+        sessions.sort_by_key(|s| s.id);
+        sessions
+    }
+
+    /// Register a newly connected listener, returning its ID
+    pub(crate) fn listener_connected(
+        &self,
+        peer: impl Into<String>,
+        nickname: Option<String>,
+    ) -> usize {
+        let listener_id = self.next_listener_id.fetch_add(1, Ordering::Relaxed);
+        let session = ListenerSession {
+            id: listener_id,
+            peer: peer.into(),
+            nickname,
+            connected_at: std::time::Instant::now(),
+        };
+        info!(
+            "[Broadcaster] Listener {} connected from {}",
+            listener_id, session.peer
+        );
+        if self.options.announce_joins {
+            self.announce(format!("{} joined", session.display_name()));
+        }
+        let nickname = session.nickname.clone();
+
+        self.sessions.lock().unwrap().insert(listener_id, session);
+        self.departures
+            .lock()
+            .unwrap()
+            .joined(std::time::Instant::now());
+        let listeners = self.listener_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.event_broadcast_tx.send(StationEvent::ListenerJoined {
+            listener_id,
+            nickname,
+            listeners,
+        });
+        listener_id
+    }
+
+    pub(crate) fn listener_disconnected(&self, listener_id: usize) {
+        let session = self.sessions.lock().unwrap().remove(&listener_id);
+        self.departures
+            .lock()
+            .unwrap()
+            .left(std::time::Instant::now());
+        let listeners = self.listener_count.fetch_sub(1, Ordering::Relaxed) - 1;
+        info!("[Broadcaster] Listener {} disconnected", listener_id);
+        let _ = self.event_broadcast_tx.send(StationEvent::ListenerLeft {
+            listener_id,
+            nickname: session.as_ref().and_then(|s| s.nickname.clone()),
+            listeners,
+        });
+        if let Some(session) = session.filter(|_| self.options.announce_joins) {
+            self.announce(format!("{} left", session.display_name()));
+        }
+    }
+
+    /// A listener's OGG chunk stream: relayed pages in relay mode, otherwise
+    /// a fresh encoder (following `tier` when given)
+    pub(crate) fn spawn_stream(
+        &self,
+        listener_id: usize,
+        tier: Option<Arc<AtomicUsize>>,
+    ) -> (
+        tokio::sync::mpsc::Receiver<Vec<u8>>,
+        tokio::task::JoinHandle<Result<(), String>>,
+    ) {
+        match &self.rewind {
+            Some(pages) if self.relay => spawn_replay(pages.clone(), Duration::ZERO),
+            _ => self.spawn_encoder(listener_id, QUALITY_TIERS[0], tier),
+        }
+    }
+
+    /// Spawn an encoder for one listener, returning its encoded chunk stream
+    ///
+    /// With `tier`, a Vorbis encoder restarts at that [`QUALITY_TIERS`] level
+    /// whenever it changes.
+    pub(crate) fn spawn_encoder(
+        &self,
+        listener_id: usize,
+        quality: f32,
+        tier: Option<Arc<AtomicUsize>>,
+    ) -> (
+        tokio::sync::mpsc::Receiver<Vec<u8>>,
+        tokio::task::JoinHandle<Result<(), String>>,
+    ) {
+        // Subscribe to PCM broadcast - each listener gets ALL audio blocks
+        let mut pcm_rx = self.pcm_broadcast_tx.subscribe();
+
+        let sample_rate = self.sample_rate;
+        let channels = self.channels;
+        let chunk_size = self.chunk_size();
+        let codec = self.options.codec;
+        let page_size = self.page_size();
+        let label = self.listener_label(listener_id);
+        let eager_pages = if self.options.fast_start {
+            FAST_START_PAGES
+        } else {
+            0
+        };
+        let shutdown = self.shutdown.clone();
+        // Only the advertised quality speaks for the station's bitrate
+        let encoded_bitrate = (quality_key(quality) == quality_key(QUALITY_TIERS[0]))
+            .then(|| self.encoded_bitrate.clone());
+        let written = Arc::new(AtomicU64::new(0));
+
+        let (ogg_tx, ogg_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+
+        let encoder_task = tokio::task::spawn_blocking(move || {
+            let writer = ChannelWriter {
+                tx: ogg_tx,
+                buffer: Vec::with_capacity(chunk_size),
+                chunk_size,
+                eager_pages,
+                // FLAC and PCM have no pages to count, so chunking starts right away
+                pages_seen: match codec {
+                    StreamCodec::Vorbis => 0,
+                    StreamCodec::Flac | StreamCodec::Pcm => eager_pages + 1,
+                },
+                written: written.clone(),
+            };
+
+            let mut current_tier = 0;
+            let mut encoder =
+                ListenerEncoder::new(codec, sample_rate, channels, writer, quality, page_size)?;
+            // Headers aren't audio, so the window starts after them
+            let mut counted = written.load(Ordering::Relaxed);
+            let mut window = BitrateWindow::new(sample_rate);
+
+            // Encode PCM blocks as they arrive
+            info!("[Encoder {}] Starting encoding loop", listener_id);
+            let mut block_count = 0;
+            let mut skipped_blocks: u64 = 0;
+            while !shutdown.is_cancelled() {
+                let pcm_block = match pcm_rx.blocking_recv() {
+                    Ok(block) => block,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // Fell behind the source - skip ahead rather than dropping the listener
+                        skipped_blocks += skipped;
+                        encoder.skip(skipped);
+                        warn!(
+                            "[Encoder {}] Lagged, skipped {} blocks ({} total); listener {}",
+                            listener_id, skipped, skipped_blocks, label
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                // Guard against empty or ragged blocks from sources at stream edges
+                let frames = pcm_block.iter().map(|c| c.len()).min().unwrap_or(0);
+                if frames == 0 {
+                    continue;
+                }
+                let ragged = pcm_block.iter().any(|c| c.len() != frames);
+                let pcm_block: Vec<&[f32]> = pcm_block.iter().map(|c| &c[..frames]).collect();
+                if ragged {
+                    warn!(
+                        "[Encoder {}] Ragged block, truncated to {} frames",
+                        listener_id, frames
+                    );
+                }
+
+                // Mono stations collapse whatever the source delivers
+                let mono;
+                let pcm_block = if channels == 1 && pcm_block.len() > 1 {
+                    mono = crate::audio_util::sum_to_mono(&pcm_block);
+                    vec![&mono[..]]
+                } else {
+                    pcm_block
+                };
+
+                // Tier change: end this link and start the next one at the new quality
+                let wanted = tier.as_ref().map_or(current_tier, |tier| {
+                    tier.load(Ordering::Relaxed).min(QUALITY_TIERS.len() - 1)
+                });
+                if wanted != current_tier {
+                    info!(
+                        "[Encoder {}] Switching to quality tier {} ({})",
+                        listener_id, wanted, QUALITY_TIERS[wanted]
+                    );
+                    let writer = encoder
+                        .finish()
+                        .map_err(|e| format!("Encoder finish: {}", e))?;
+                    encoder = ListenerEncoder::Vorbis(Box::new(vorbis_encoder(
+                        sample_rate,
+                        channels,
+                        writer,
+                        QUALITY_TIERS[wanted],
+                        page_size,
+                    )?));
+                    current_tier = wanted;
+                }
+
+                block_count += 1;
+                if block_count % 100 == 0 {
+                    info!("[Encoder {}] Encoded {} blocks", listener_id, block_count);
+                }
+                if let Err(e) = encoder.encode_audio_block(&pcm_block) {
+                    error!("[Encoder {}] Encoding error: {}", listener_id, e);
+                    break;
+                }
+
+                let total = written.load(Ordering::Relaxed);
+                window.push(frames, total - counted);
+                counted = total;
+                if let Some(encoded_bitrate) =
+                    encoded_bitrate.as_ref().filter(|_| current_tier == 0)
+                {
+                    encoded_bitrate.store(window.bitrate(), Ordering::Relaxed);
+                }
+            }
+            // Any other encoder still running takes over with its next block
+            if let Some(encoded_bitrate) = &encoded_bitrate {
+                encoded_bitrate.store(0, Ordering::Relaxed);
+            }
+            info!(
+                "[Encoder {}] Encoding loop ended, total blocks: {}, skipped: {}",
+                listener_id, block_count, skipped_blocks
+            );
+
+            // Finish encoder - writes the final page or frame so players end cleanly
+            let _ = encoder.finish();
+
+            Ok::<_, String>(())
+        });
+
+        (ogg_rx, encoder_task)
+    }
+
+    /// Encode the station once more into a [`RewindBuffer`] for `listen_from`
+    fn spawn_rewind_encoder(&self, window: Duration) -> Arc<RewindBuffer> {
+        info!(
+            "[Broadcaster] Keeping {}s of audio for rewind",
+            window.as_secs()
+        );
+        let (buffer, _task) = self.spawn_paged_encoder(window, QUALITY_TIERS[0]);
+        buffer
+    }
+
+    /// Encode the station into a [`RewindBuffer`] that listeners replay from;
+    /// aborting the returned task stops the encoder
+    fn spawn_paged_encoder(
+        &self,
+        window: Duration,
+        quality: f32,
+    ) -> (Arc<RewindBuffer>, tokio::task::JoinHandle<()>) {
+        let buffer = Arc::new(RewindBuffer::new(window));
+        let (mut ogg_rx, _encoder_task) =
+            self.spawn_encoder(crate::service::STATION_LISTENER_ID, quality, None);
+
+        let pages = buffer.clone();
+        let task = tokio::spawn(async move {
+            let mut splitter = PageSplitter::default();
+            while let Some(chunk) = ogg_rx.recv().await {
+                for page in splitter.push(&chunk) {
+                    pages.push_page(page);
+                }
+            }
+            pages.close();
+        });
+
+        (buffer, task)
+    }
+
+    /// The running encoder for `quality`, started if no listener has it yet
+    fn shared_encoder(&self, quality: f32) -> Arc<SharedEncoder> {
+        let key = quality_key(quality);
+        let mut encoders = self.shared_encoders.lock().unwrap();
+        if let Some(encoder) = encoders.get(&key).and_then(Weak::upgrade) {
+            return encoder;
+        }
+
+        info!(
+            "[Broadcaster] Starting shared encoder at quality {:.1}",
+            quality
+        );
+        let (pages, task) = self.spawn_paged_encoder(SHARED_ENCODER_WINDOW, quality);
+        let encoder = Arc::new(SharedEncoder { pages, task });
+        encoders.retain(|_, encoder| encoder.strong_count() > 0);
+        encoders.insert(key, Arc::downgrade(&encoder));
+        encoder
+    }
+
+    /// Stream live audio to a listener; with `adaptive` (and adaptive bitrate
+    /// on), through an encoder that follows the listener's quality tier
+    async fn listen_live(
+        &self,
+        ctx: RequestContext,
+        send: iroh::endpoint::SendStream,
+        adaptive: bool,
+    ) -> Result<(), RadioError> {
+        let _slot = self.reserve_slot()?;
+        let listener_id = self.listener_connected(ctx.remote_id().to_string(), nickname(&ctx));
+
+        // Spawn encoder task for THIS listener
+        let tier = (adaptive
+            && self.options.adaptive_bitrate
+            && self.options.codec == StreamCodec::Vorbis)
+            .then(|| Arc::new(AtomicUsize::new(0)));
+        let (ogg_rx, encoder_task) = self.spawn_stream(listener_id, tier.clone());
+        // An abrupt drop closes the connection long before a write would stall
+        tokio::select! {
+            _ = self.stream_to_listener(listener_id, send, ogg_rx, tier.as_deref()) => {}
+            _ = ctx.connection().closed() => {}
+        }
+        encoder_task.abort();
+
+        self.listener_disconnected(listener_id);
+
+        Ok(())
+    }
+
+    /// Send a listener's OGG chunks until the source ends, the session limit
+    /// hits, or the listener stalls
+    ///
+    /// With `tier`, sustained slow writes step the listener's encoder down a
+    /// quality tier and a long enough run without one steps it back up.
+    ///
+    /// Chat and events have their own streams and tasks, so nothing here
+    /// waits on them. The stream goes out at [`BroadcastOptions::audio_priority`],
+    /// ahead of them when the connection can't keep up with everything.
+    async fn stream_to_listener(
+        &self,
+        listener_id: usize,
+        mut send: iroh::endpoint::SendStream,
+        mut ogg_rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+        tier: Option<&AtomicUsize>,
+    ) {
+        // Only fails if the listener already closed the stream
+        let _ = send.set_priority(self.options.audio_priority);
+
+        // Send encoded OGG chunks to client with stall detection
+        let stall_timeout = self.options.stall_timeout;
+
+        let max_session = self.options.max_session;
+        let session_limit = async move {
+            match max_session {
+                Some(limit) => tokio::time::sleep(limit).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(session_limit);
+        // Set when the listener is cut off, so it can tell the user why
+        let mut reset_code = None;
+        let mut tiers = TierController::new(std::time::Instant::now());
+        let streaming_since = std::time::Instant::now();
+        let mut sent_bytes: u64 = 0;
+
+        loop {
+            let chunk = tokio::select! {
+                chunk = ogg_rx.recv() => match chunk {
+                    Some(chunk) => chunk,
+                    None => break,
+                },
+                _ = &mut session_limit => {
+                    info!(
+                        "Listener {} reached the max session length ({}s), disconnecting",
+                        listener_id,
+                        max_session.unwrap_or_default().as_secs()
+                    );
+                    reset_code = Some(crate::service::RESET_SESSION_LIMIT);
+                    break;
+                }
+            };
+
+            let started = std::time::Instant::now();
+            match timeout(stall_timeout, send.write_all(&chunk)).await {
+                Ok(Ok(())) => {
+                    self.record_sent(chunk.len());
+                    sent_bytes += chunk.len() as u64;
+                    let Some(tier) = tier else { continue };
+                    let current = tier.load(Ordering::Relaxed);
+                    let now = std::time::Instant::now();
+                    let Some(next) = tiers.record(now - started, current, now) else {
+                        continue;
+                    };
+                    if next > current {
+                        info!(
+                            "Listener {} is falling behind ({}), lowering quality to tier {}",
+                            self.listener_label(listener_id),
+                            send_rate(sent_bytes, streaming_since),
+                            next
+                        );
+                    } else {
+                        info!(
+                            "Listener {} recovered, raising quality to tier {}",
+                            listener_id, next
+                        );
+                    }
+                    tier.store(next, Ordering::Relaxed);
+                }
+                Ok(Err(iroh::endpoint::WriteError::Stopped(code)))
+                    if code.into_inner() == crate::service::STOP_LISTENER_DONE as u64 =>
+                {
+                    info!("Listener {} stopped listening", listener_id);
+                    break;
+                }
+                Ok(Err(e)) => {
+                    error!(
+                        "Send error to listener {}: {}",
+                        self.listener_label(listener_id),
+                        e
+                    );
+                    break;
+                }
+                Err(_) => {
+                    warn!(
+                        "Listener {} stalled (no progress for {} seconds; {}), disconnecting",
+                        self.listener_label(listener_id),
+                        stall_timeout.as_secs(),
+                        send_rate(sent_bytes, streaming_since)
+                    );
+                    reset_code = Some(crate::service::RESET_STALLED);
+                    break;
+                }
+            }
+        }
+
+        // Cleanup; the reset code tells the listener why the stream ended
+        match reset_code {
+            Some(code) => {
+                let _ = send.reset(iroh::endpoint::VarInt::from_u32(code));
+            }
+            None => {
+                let _ = send.finish();
+            }
+        }
+    }
+
+    /// Rebroadcast an already-encoded stream fed into `pages` (see [`crate::relay`])
+    /// instead of encoding PCM; `bitrate` is advertised in [`StationInfo`]
+    ///
+    /// `pages` are only offered to `listen_from` when `rewind` is set.
+    pub fn with_relay(mut self, pages: Arc<RewindBuffer>, bitrate: u32, rewind: bool) -> Self {
+        self.options.rewind = rewind.then(|| pages.window());
+        self.rewind = Some(pages);
+        self.relay = true;
+        self.bitrate = bitrate;
+        self.listener_bitrate = bitrate;
+        // The bandwidth budget goes further or less far at the upstream's bitrate
+        self.listener_slots =
+            listener_limit(&self.options, bitrate).map(|max| Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// Sign [`StationInfo`] with the endpoint's node key so listeners can verify it
+    pub fn with_signing_key(mut self, key: iroh::SecretKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Pass a relay's upstream `signed` info (made by `station`) through to listeners
+    pub fn with_upstream_signature(
+        mut self,
+        station: iroh::PublicKey,
+        signed: SignedStationInfo,
+    ) -> Self {
+        self.upstream_signature = Some((station, Box::new(signed)));
+        self
+    }
+
+    /// Advertise the capabilities of the active audio source
+    pub fn with_capabilities(mut self, capabilities: SourceCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Follow the source's tracks for cover art, and skip them when a
+    /// listener vote passes
+    pub fn with_source_control(mut self, control: SourceControl) -> Self {
+        self.source_control = Some(control);
+        self
+    }
+
+    /// Serve a station logo (checked with [`crate::artwork::check`]) from
+    /// `get_artwork` whenever the track has no cover art
+    pub fn with_logo(mut self, logo: Vec<u8>) -> Self {
+        self.logo = Some(Arc::new(logo));
+        self
+    }
+
+    /// Append chat to `log` from now on, and start chat history with the
+    /// newest messages already in it so history survives a restart
+    pub fn with_chat_log(mut self, log: ChatLog) -> Self {
+        let restored = log.tail(self.options.chat_history_len);
+        if !restored.is_empty() {
+            info!(
+                "[Chat] Restored {} message(s) from {}",
+                restored.len(),
+                log.path().display()
+            );
+        }
+        {
+            let mut history = self.chat_history.lock().unwrap();
+            // Sequence numbers carry on from the log
+            history.next_seq = restored.iter().map(|chat| chat.seq).max().unwrap_or(0);
+            restored.into_iter().for_each(|chat| history.push(chat));
+        }
+        self.chat_log = Some(Arc::new(Mutex::new(log)));
+        self
+    }
+
+    /// Advertise genre, tags, and website in [`StationInfo`]
+    pub fn with_metadata(
+        self,
+        genre: Option<String>,
+        tags: Vec<String>,
+        website: Option<String>,
+    ) -> Self {
+        {
+            let mut metadata = self.metadata.write().unwrap();
+            metadata.genre = genre;
+            metadata.tags = tags;
+            metadata.website = website;
+        }
+        self
+    }
+
+    /// Let these node IDs change the station's info with `set_station_info`
+    pub fn with_operators(mut self, operators: impl IntoIterator<Item = iroh::PublicKey>) -> Self {
+        self.operators = Arc::new(operators.into_iter().collect());
+        self
+    }
 }
 
-impl RadioBroadcaster {
-    pub fn new(
-        name: impl Into<String>,
-        desc: impl Into<String>,
-        sample_rate: u32,
-        channels: u8,
-    ) -> (Self, broadcast::Sender<AudioBlock>) {
-        // Broadcast channel for PCM audio blocks
-        let (pcm_broadcast_tx, _) = broadcast::channel(100);
-        let tx_clone = pcm_broadcast_tx.clone();
+/// Feed a listener from `rewind`: header pages, then every page from `ago` back on
+fn spawn_replay(
+    rewind: Arc<RewindBuffer>,
+    ago: Duration,
+) -> (
+    tokio::sync::mpsc::Receiver<Vec<u8>>,
+    tokio::task::JoinHandle<Result<(), String>>,
+) {
+    let (headers, mut cursor) = rewind.start(ago);
+    let (ogg_tx, ogg_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+
+    let replay_task = tokio::spawn(async move {
+        for page in headers {
+            if ogg_tx.send(page.to_vec()).await.is_err() {
+                return Ok(());
+            }
+        }
+        loop {
+            let pages = rewind.pages_from(&mut cursor).await;
+            if pages.is_empty() {
+                return Ok(());
+            }
+            for page in pages {
+                if ogg_tx.send(page.to_vec()).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    });
+
+    (ogg_rx, replay_task)
+}
 
-        // Broadcast channel for chat messages
-        let (chat_broadcast_tx, _) = broadcast::channel(100);
+/// The nickname a listener's connection carries, if any
+fn nickname(ctx: &RequestContext) -> Option<String> {
+    ctx.connection_extensions()
+        .get::<crate::service::ListenerInfo>()
+        .and_then(|info| info.nickname.clone())
+}
 
-        let broadcaster = Self {
-            station_name: name.into(),
-            station_desc: desc.into(),
-            sample_rate,
-            channels,
-            pcm_broadcast_tx,
-            chat_broadcast_tx,
-            listener_count: Arc::new(AtomicUsize::new(0)),
-        };
+/// Next chat message for a subscriber, skipping ahead if it fell behind
+///
+/// Only returns `None` once the channel is closed; a lagging subscriber loses
+/// the overwritten messages (recoverable through chat history) but stays on.
+async fn next_chat(chat_rx: &mut broadcast::Receiver<ChatMessage>) -> Option<ChatMessage> {
+    loop {
+        match chat_rx.recv().await {
+            Ok(msg) => return Some(msg),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("[Chat] Slow subscriber missed {} message(s)", missed);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
 
-        (broadcaster, tx_clone)
+/// The next station event, skipping over any a slow subscriber missed
+async fn next_event(event_rx: &mut broadcast::Receiver<StationEvent>) -> Option<StationEvent> {
+    loop {
+        match event_rx.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("[Events] Slow subscriber missed {} event(s)", missed);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
     }
 }
 
 #[async_trait]
 impl RadioServiceServer for RadioBroadcaster {
-    async fn get_info(&self, _ctx: RequestContext) -> Result<StationInfo, String> {
+    async fn get_info(&self, _ctx: RequestContext) -> Result<StationInfo, RadioError> {
+        let metadata = self.metadata.read().unwrap().clone();
         Ok(StationInfo {
-            name: self.station_name.clone(),
-            description: self.station_desc.clone(),
-            bitrate: 128000,
+            name: metadata.name,
+            description: metadata.description,
+            bitrate: self.bitrate,
             sample_rate: self.sample_rate,
             channels: self.channels,
-            listeners: self.listener_count.load(Ordering::Relaxed),
+            listeners: self.listener_count(),
+            protocol_version: crate::service::PROTOCOL_VERSION,
+            genre: metadata.genre,
+            tags: metadata.tags,
+            website: metadata.website,
+            rewind_secs: self
+                .offered_rewind()
+                .map_or(0, |rewind| rewind.window().as_secs() as u32),
+            codec: self.options.codec,
+            quality_range: self.options.quality_range.filter(|_| !self.relay),
         })
     }
 
-    async fn send_chat(&self, ctx: RequestContext, message: String) -> Result<(), String> {
+    async fn get_signed_info(&self, ctx: RequestContext) -> Result<SignedStationInfo, RadioError> {
+        let key = self.signing_key.as_ref().ok_or_else(|| {
+            RadioError::InvalidRequest("This station doesn't sign its info".to_string())
+        })?;
+        let info = self.get_info(ctx).await?;
+        let mut signed = SignedStationInfo::sign(&info, key);
+        signed.upstream = self.upstream_signature.clone();
+        Ok(signed)
+    }
+
+    async fn capabilities(&self, _ctx: RequestContext) -> Result<SourceCapabilities, RadioError> {
+        // A playlist reports each track's codec as it starts
+        Ok(self
+            .source_control
+            .as_ref()
+            .and_then(SourceControl::capabilities)
+            .unwrap_or_else(|| self.capabilities.clone()))
+    }
+
+    async fn set_station_info(
+        &self,
+        ctx: RequestContext,
+        update: StationInfoUpdate,
+    ) -> Result<StationInfo, RadioError> {
+        let peer = ctx.remote_id();
+        if !self.operators.contains(&peer) {
+            warn!(
+                "[Station] Refused set_station_info from {}: not an operator",
+                peer
+            );
+            return Err(RadioError::Unauthorized);
+        }
+        self.update_station_info(update)?;
+        self.get_info(ctx).await
+    }
+
+    async fn send_chat(&self, ctx: RequestContext, message: String) -> Result<(), RadioError> {
         use std::time::SystemTime;
 
         // Get listener info from connection extensions
         let listener_info = ctx
             .connection_extensions()
             .get::<crate::service::ListenerInfo>()
-            .ok_or("Listener info not found")?;
+            .ok_or(RadioError::UnknownListener)?;
 
-        let chat = ChatMessage {
+        self.post_chat(ChatMessage {
             listener_id: listener_info.id,
             nickname: listener_info.nickname.clone(),
             message,
@@ -84,21 +1862,178 @@ impl RadioServiceServer for RadioBroadcaster {
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            seq: 0,
+        });
+        Ok(())
+    }
+
+    async fn get_chat_history(
+        &self,
+        _ctx: RequestContext,
+        since_timestamp: Option<u64>,
+    ) -> Result<Vec<ChatMessage>, RadioError> {
+        let history = self.chat_history.lock().unwrap();
+        Ok(history
+            .messages
+            .iter()
+            .filter(|m| since_timestamp.is_none_or(|since| m.timestamp >= since))
+            .cloned()
+            .collect())
+    }
+
+    async fn health(&self, _ctx: RequestContext) -> Result<HealthStatus, RadioError> {
+        Ok(RadioBroadcaster::health(self))
+    }
+
+    async fn get_levels(&self, _ctx: RequestContext) -> Result<ChannelLevels, RadioError> {
+        Ok(ChannelLevels {
+            outbound_bytes_per_sec: self.outbound_bytes_per_sec(),
+            encoded_bitrate: self.encoded_bitrate(),
+            ..self.levels.snapshot()
+        })
+    }
+
+    async fn request_track(&self, ctx: RequestContext, query: String) -> Result<(), RadioError> {
+        use std::time::{Instant, SystemTime};
+
+        let listener_info = ctx
+            .connection_extensions()
+            .get::<crate::service::ListenerInfo>()
+            .ok_or(RadioError::UnknownListener)?;
+
+        let query = clean_request(&query)?;
+
+        let mut requests = self.track_requests.lock().unwrap();
+        // Only listeners still cooling down need remembering
+        requests
+            .last_request
+            .retain(|_, last| last.elapsed() < REQUEST_COOLDOWN);
+        if let Some(last) = requests.last_request.get(&listener_info.id) {
+            let wait = REQUEST_COOLDOWN.saturating_sub(last.elapsed());
+            if !wait.is_zero() {
+                return Err(RadioError::RateLimited {
+                    retry_after_secs: wait.as_secs().max(1),
+                });
+            }
+        }
+        requests
+            .last_request
+            .insert(listener_info.id, Instant::now());
+
+        let request = TrackRequest {
+            listener_id: listener_info.id,
+            nickname: listener_info.nickname.clone(),
+            query,
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
         };
 
-        // Broadcast to all chat subscribers
-        let _ = self.chat_broadcast_tx.send(chat);
+        // Surface on the operator's console
+        info!(
+            "[Request] {}: {}",
+            request
+                .nickname
+                .clone()
+                .unwrap_or_else(|| format!("Listener {}", request.listener_id)),
+            request.query
+        );
+
+        requests.queue.push_back(request);
+        if requests.queue.len() > MAX_TRACK_REQUESTS {
+            requests.queue.pop_front();
+        }
         Ok(())
     }
 
+    async fn get_requests(&self, _ctx: RequestContext) -> Result<Vec<TrackRequest>, RadioError> {
+        Ok(self.track_requests())
+    }
+
+    async fn get_artwork(&self, _ctx: RequestContext) -> Result<Option<Vec<u8>>, RadioError> {
+        let artwork = self
+            .source_control
+            .as_ref()
+            .and_then(SourceControl::artwork)
+            .or_else(|| self.logo.clone());
+        Ok(artwork.map(|image| image.to_vec()))
+    }
+
+    async fn vote_skip(&self, ctx: RequestContext) -> Result<SkipVote, RadioError> {
+        let listener_info = ctx
+            .connection_extensions()
+            .get::<crate::service::ListenerInfo>()
+            .ok_or(RadioError::UnknownListener)?;
+        let (Some(share), Some(control)) = (self.options.vote_skip, &self.source_control) else {
+            return Err(RadioError::InvalidRequest(
+                "This station doesn't take skip votes".to_string(),
+            ));
+        };
+
+        let needed = votes_needed(share, self.listener_count());
+        let vote = {
+            let mut votes = self.skip_votes.lock().unwrap();
+            // A new track starts a new vote
+            let track = control.track();
+            if votes.track != track {
+                votes.track = track;
+                votes.voters.clear();
+            }
+            if !votes.voters.insert(listener_info.id) {
+                return Err(RadioError::InvalidRequest(
+                    "You already voted to skip this track".to_string(),
+                ));
+            }
+
+            let skipped = votes.voters.len() >= needed;
+            let vote = SkipVote {
+                votes: votes.voters.len(),
+                needed,
+                skipped,
+            };
+            if skipped {
+                votes.voters.clear();
+                control.skip();
+            }
+            vote
+        };
+
+        if vote.skipped {
+            info!("[Broadcaster] Listeners voted to skip the track");
+            self.announce(format!(
+                "{}/{} votes to skip, skipping this track",
+                vote.votes, vote.needed
+            ));
+        } else {
+            self.announce(format!("{}/{} votes to skip", vote.votes, vote.needed));
+        }
+        Ok(vote)
+    }
+
     async fn chat_stream(
         &self,
-        _ctx: RequestContext,
+        ctx: RequestContext,
         mut sink: crate::service::RadioServiceChatStreamSink,
-    ) -> Result<(), String> {
+    ) -> Result<(), RadioError> {
+        let connection = ctx.connection();
+        let Some(_subscription) = self.chat_subscriptions.acquire(connection.stable_id()) else {
+            return Err(RadioError::InvalidRequest(
+                "Already subscribed to chat on this connection".to_string(),
+            ));
+        };
         let mut chat_rx = self.chat_broadcast_tx.subscribe();
 
-        while let Ok(msg) = chat_rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                msg = next_chat(&mut chat_rx) => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                // Don't wait for the next message to notice the listener is gone
+                _ = connection.closed() => break,
+                _ = self.shutdown.cancelled() => break,
+            };
             if sink.send(msg).await.is_err() {
                 break;
             }
@@ -107,141 +2042,559 @@ impl RadioServiceServer for RadioBroadcaster {
         Ok(())
     }
 
+    async fn event_stream(
+        &self,
+        ctx: RequestContext,
+        mut sink: crate::service::RadioServiceEventStreamSink,
+    ) -> Result<(), RadioError> {
+        let connection = ctx.connection();
+        let Some(_subscription) = self.event_subscriptions.acquire(connection.stable_id()) else {
+            return Err(RadioError::InvalidRequest(
+                "Already subscribed to events on this connection".to_string(),
+            ));
+        };
+        let mut station_rx = self.event_broadcast_tx.subscribe();
+        // Without source control (a relay) there are no track or pause events
+        let mut source_rx = self.source_control.as_ref().map(SourceControl::events);
+
+        // Start with what's playing, so a listener who tuned in mid-track
+        // knows it too (a track starting just now may come through twice)
+        let current = self
+            .source_control
+            .as_ref()
+            .and_then(SourceControl::current_track);
+        if let Some(event) = current {
+            if sink.send(event).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        loop {
+            let event = tokio::select! {
+                event = next_event(&mut station_rx) => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                Some(event) = async {
+                    match source_rx.as_mut() {
+                        Some(rx) => next_event(rx).await,
+                        None => std::future::pending().await,
+                    }
+                } => event,
+                _ = connection.closed() => break,
+                _ = self.shutdown.cancelled() => {
+                    let _ = sink.send(StationEvent::OffAir).await;
+                    break;
+                }
+            };
+            if sink.send(event).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn listen(
         &self,
-        _ctx: RequestContext,
-        mut send: iroh::endpoint::SendStream,
+        ctx: RequestContext,
+        send: iroh::endpoint::SendStream,
         _recv: iroh::endpoint::RecvStream,
-    ) -> Result<(), String> {
-        let listener_id = self.listener_count.fetch_add(1, Ordering::Relaxed);
-        info!("[Broadcaster] Listener {} connected", listener_id);
+    ) -> Result<(), RadioError> {
+        self.listen_live(ctx, send, false).await
+    }
 
-        // Subscribe to PCM broadcast - each listener gets ALL audio blocks
-        let mut pcm_rx = self.pcm_broadcast_tx.subscribe();
+    async fn listen_adaptive(
+        &self,
+        ctx: RequestContext,
+        send: iroh::endpoint::SendStream,
+        _recv: iroh::endpoint::RecvStream,
+    ) -> Result<(), RadioError> {
+        self.listen_live(ctx, send, true).await
+    }
 
-        // Spawn encoder task for THIS listener
-        let sample_rate = self.sample_rate;
-        let channels = self.channels;
+    async fn listen_from(
+        &self,
+        ctx: RequestContext,
+        send: iroh::endpoint::SendStream,
+        _recv: iroh::endpoint::RecvStream,
+        seconds_ago: u32,
+    ) -> Result<(), RadioError> {
+        let rewind = self.offered_rewind().cloned().ok_or_else(|| {
+            RadioError::InvalidRequest("This station doesn't keep a rewind buffer".to_string())
+        })?;
+        let ago = Duration::from_secs(seconds_ago.into()).min(rewind.window());
 
-        let (ogg_tx, mut ogg_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+        let _slot = self.reserve_slot()?;
+        let listener_id = self.listener_connected(ctx.remote_id().to_string(), nickname(&ctx));
+        info!(
+            "[Broadcaster] Listener {} rewinding {}s",
+            listener_id,
+            ago.as_secs()
+        );
 
-        let encoder_task = tokio::task::spawn_blocking(move || {
-            // Custom Write impl that sends to channel
-            struct ChannelWriter {
-                tx: tokio::sync::mpsc::Sender<Vec<u8>>,
-                buffer: Vec<u8>,
-            }
-
-            impl std::io::Write for ChannelWriter {
-                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-                    self.buffer.extend_from_slice(buf);
-                    if self.buffer.len() >= 8192 {
-                        let chunk = self.buffer.clone();
-                        self.buffer.clear();
-                        // If send fails, listener disconnected - return error to stop encoder
-                        self.tx.blocking_send(chunk).map_err(|_| {
-                            std::io::Error::new(
-                                std::io::ErrorKind::BrokenPipe,
-                                "Listener disconnected",
-                            )
-                        })?;
-                    }
-                    Ok(buf.len())
-                }
+        let (ogg_rx, replay_task) = spawn_replay(rewind, ago);
+        tokio::select! {
+            _ = self.stream_to_listener(listener_id, send, ogg_rx, None) => {}
+            _ = ctx.connection().closed() => {}
+        }
+        replay_task.abort();
 
-                fn flush(&mut self) -> std::io::Result<()> {
-                    if !self.buffer.is_empty() {
-                        let chunk = self.buffer.clone();
-                        self.buffer.clear();
-                        // If send fails, listener disconnected - return error to stop encoder
-                        self.tx.blocking_send(chunk).map_err(|_| {
-                            std::io::Error::new(
-                                std::io::ErrorKind::BrokenPipe,
-                                "Listener disconnected",
-                            )
-                        })?;
-                    }
-                    Ok(())
-                }
-            }
+        self.listener_disconnected(listener_id);
 
-            impl Drop for ChannelWriter {
-                fn drop(&mut self) {
-                    let _ = std::io::Write::flush(self);
-                }
-            }
+        Ok(())
+    }
 
-            let writer = ChannelWriter {
-                tx: ogg_tx,
-                buffer: Vec::new(),
+    async fn listen_at(
+        &self,
+        ctx: RequestContext,
+        send: iroh::endpoint::SendStream,
+        _recv: iroh::endpoint::RecvStream,
+        quality: f32,
+    ) -> Result<(), RadioError> {
+        let (min, max) = self
+            .options
+            .quality_range
+            .filter(|_| !self.relay)
+            .ok_or_else(|| {
+                RadioError::InvalidRequest(
+                    "This station doesn't offer a choice of quality".to_string(),
+                )
+            })?;
+        if !(min..=max).contains(&quality) {
+            return Err(RadioError::InvalidRequest(format!(
+                "Quality must be between {} and {}",
+                min, max
+            )));
+        }
+
+        let _slot = self.reserve_slot()?;
+        let listener_id = self.listener_connected(ctx.remote_id().to_string(), nickname(&ctx));
+        info!(
+            "[Broadcaster] Listener {} asked for quality {:.1}",
+            listener_id, quality
+        );
+
+        let encoder = self.shared_encoder(quality);
+        let (ogg_rx, replay_task) = spawn_replay(encoder.pages.clone(), Duration::ZERO);
+        tokio::select! {
+            _ = self.stream_to_listener(listener_id, send, ogg_rx, None) => {}
+            _ = ctx.connection().closed() => {}
+        }
+        replay_task.abort();
+        drop(encoder);
+
+        self.listener_disconnected(listener_id);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(seq: u64) -> ChatMessage {
+        ChatMessage {
+            listener_id: 0,
+            nickname: None,
+            message: format!("message {}", seq),
+            timestamp: 0,
+            seq,
+        }
+    }
+
+    #[tokio::test]
+    async fn lagging_chat_subscriber_recovers() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for seq in 1..=5 {
+            tx.send(chat(seq)).unwrap();
+        }
+
+        // Messages 1-3 were overwritten; the subscriber resumes at the oldest kept
+        assert_eq!(next_chat(&mut rx).await.unwrap().seq, 4);
+        assert_eq!(next_chat(&mut rx).await.unwrap().seq, 5);
+
+        tx.send(chat(6)).unwrap();
+        assert_eq!(next_chat(&mut rx).await.unwrap().seq, 6);
+
+        drop(tx);
+        assert!(next_chat(&mut rx).await.is_none());
+    }
+
+    #[test]
+    fn tiers_step_down_on_slow_sends_and_recover_after_a_quiet_spell() {
+        let start = std::time::Instant::now();
+        let mut tiers = TierController::new(start);
+        let slow = SLOW_SEND;
+        let fast = Duration::from_millis(1);
+
+        // A fast write between slow ones starts the count over
+        assert_eq!(tiers.record(slow, 0, start), None);
+        assert_eq!(tiers.record(fast, 0, start), None);
+        for _ in 1..SLOW_SENDS_TO_DEGRADE {
+            assert_eq!(tiers.record(slow, 0, start), None);
+        }
+        assert_eq!(tiers.record(slow, 0, start), Some(1));
+
+        // Never below the last tier
+        let last = QUALITY_TIERS.len() - 1;
+        for _ in 0..SLOW_SENDS_TO_DEGRADE {
+            assert_eq!(tiers.record(slow, last, start), None);
+        }
+
+        // Recovery waits for RECOVER_AFTER since the last slow write
+        let later = start + RECOVER_AFTER;
+        assert_eq!(tiers.record(fast, 1, later - fast), None);
+        assert_eq!(tiers.record(fast, 1, later), Some(0));
+        assert_eq!(tiers.record(fast, 0, later + RECOVER_AFTER), None);
+    }
+
+    #[test]
+    fn chat_history_stays_within_its_byte_budget() {
+        let max_bytes = 64 * 1024;
+        let mut history = ChatHistory::new(CHAT_HISTORY_LEN, max_bytes);
+
+        // Sizes from a few bytes up to a message bigger than the whole budget
+        for seq in 1..=2000u64 {
+            let mut message = chat(seq);
+            let len = match seq % 4 {
+                0 => 3,
+                1 => 900,
+                2 => 8 * 1024,
+                _ => 1 + (seq as usize * 37) % 4096,
             };
+            message.message = "x".repeat(if seq == 1500 { max_bytes * 2 } else { len });
+            history.push(message);
 
-            let mut encoder = VorbisEncoderBuilder::new(
-                NonZeroU32::new(sample_rate).unwrap(),
-                NonZeroU8::new(channels).unwrap(),
-                writer,
-            )
-            .map_err(|e| format!("Encoder setup: {}", e))?
-            .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
-                target_quality: 0.5,
+            assert!(history.bytes <= max_bytes);
+            assert!(history.messages.len() <= CHAT_HISTORY_LEN);
+            assert_eq!(
+                history.bytes,
+                history.messages.iter().map(chat_size).sum::<usize>()
+            );
+            // Only a message too big to keep at all leaves history empty
+            if seq != 1500 {
+                assert_eq!(history.messages.back().unwrap().seq, seq);
+            }
+        }
+    }
+
+    #[test]
+    fn station_info_changes_on_the_air() {
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Day FM", "Talk", 44100, 2);
+        let broadcaster =
+            broadcaster.with_metadata(Some("Talk".to_string()), vec!["news".to_string()], None);
+        let mut events = broadcaster.event_broadcast_tx.subscribe();
+
+        broadcaster
+            .update_station_info(StationInfoUpdate {
+                name: Some("  Night FM ".to_string()),
+                genre: Some(String::new()),
+                tags: Some(vec!["ambient".to_string(), " ".to_string()]),
+                ..Default::default()
             })
-            .build()
-            .map_err(|e| format!("Encoder build: {}", e))?;
+            .unwrap();
+        assert_eq!(broadcaster.station_name(), "Night FM");
+        // Untouched fields stay; a blank genre clears it
+        assert_eq!(broadcaster.station_desc(), "Talk");
+        let metadata = broadcaster.metadata.read().unwrap().clone();
+        assert_eq!(metadata.genre, None);
+        assert_eq!(metadata.tags, ["ambient"]);
 
-            // Encode PCM blocks as they arrive
-            info!("[Encoder {}] Starting encoding loop", listener_id);
-            let mut block_count = 0;
-            while let Ok(pcm_block) = pcm_rx.blocking_recv() {
-                block_count += 1;
-                if block_count % 100 == 0 {
-                    info!("[Encoder {}] Encoded {} blocks", listener_id, block_count);
-                }
-                if let Err(e) = encoder.encode_audio_block(&pcm_block) {
-                    error!("[Encoder {}] Encoding error: {}", listener_id, e);
-                    break;
-                }
+        // Clones share the change, and subscribers hear about it
+        assert_eq!(broadcaster.clone().station_name(), "Night FM");
+        match events.try_recv().unwrap() {
+            StationEvent::InfoChanged { name, tags, .. } => {
+                assert_eq!(name, "Night FM");
+                assert_eq!(tags, ["ambient"]);
             }
-            info!(
-                "[Encoder {}] Encoding loop ended, total blocks: {}",
-                listener_id, block_count
-            );
+            other => panic!("unexpected event {:?}", other),
+        }
 
-            // Finish encoder
-            let _ = encoder.finish();
+        let rejected = [
+            StationInfoUpdate::default(),
+            StationInfoUpdate {
+                name: Some(" ".to_string()),
+                ..Default::default()
+            },
+            StationInfoUpdate {
+                description: Some("x".repeat(MAX_INFO_FIELD_LEN + 1)),
+                ..Default::default()
+            },
+        ];
+        for update in rejected {
+            assert!(matches!(
+                broadcaster.update_station_info(update),
+                Err(RadioError::InvalidRequest(_))
+            ));
+        }
+        assert_eq!(broadcaster.station_name(), "Night FM");
+        assert!(events.try_recv().is_err());
+    }
 
-            Ok::<_, String>(())
-        });
+    #[test]
+    fn subscriptions_are_capped_and_released() {
+        let subscriptions = Subscriptions::default();
 
-        // Send encoded OGG chunks to client with stall detection
-        const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+        for _ in 0..1000 {
+            let first = subscriptions.acquire(7).expect("slot should be free");
+            assert!(subscriptions.acquire(7).is_none());
+            // Other connections have their own slot
+            assert!(subscriptions.acquire(8).is_some());
+            drop(first);
+        }
 
-        while let Some(chunk) = ogg_rx.recv().await {
-            match timeout(SEND_TIMEOUT, send.write_all(&chunk)).await {
-                Ok(Ok(())) => {
-                    // Successfully sent chunk
-                }
-                Ok(Err(e)) => {
-                    error!("Send error to listener {}: {}", listener_id, e);
-                    break;
-                }
-                Err(_) => {
-                    warn!(
-                        "Listener {} stalled (no progress for {} seconds), disconnecting",
-                        listener_id,
-                        SEND_TIMEOUT.as_secs()
-                    );
-                    break;
-                }
+        assert!(subscriptions.active.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn track_requests_are_cleaned_and_bounded() {
+        assert_eq!(
+            clean_request("  Blue\x1b[2J Monday\r\n").unwrap(),
+            "Blue[2J Monday"
+        );
+        assert!(clean_request("\x07\t ").is_err());
+        assert!(clean_request(&"x".repeat(MAX_REQUEST_LEN)).is_ok());
+        assert!(clean_request(&"x".repeat(MAX_REQUEST_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn skipping_takes_more_than_the_share_of_listeners() {
+        // Half: a majority
+        assert_eq!(votes_needed(0.5, 1), 1);
+        assert_eq!(votes_needed(0.5, 4), 3);
+        assert_eq!(votes_needed(0.5, 5), 3);
+        // Everyone is as many as there can be
+        assert_eq!(votes_needed(1.0, 5), 5);
+        // An empty count still takes the voter's own vote
+        assert_eq!(votes_needed(0.5, 0), 1);
+    }
+
+    /// Frames fed to a Vorbis encoder before its first audio page comes out
+    fn frames_to_first_audio_page(page_size: Option<u16>) -> usize {
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
             }
         }
 
-        // Cleanup
-        let _ = send.finish();
-        encoder_task.abort();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut encoder = vorbis_encoder(
+            44100,
+            2,
+            Shared(written.clone()),
+            QUALITY_TIERS[0],
+            page_size,
+        )
+        .unwrap();
+        let headers = written.lock().unwrap().len();
 
-        self.listener_count.fetch_sub(1, Ordering::Relaxed);
-        info!("[Broadcaster] Listener {} disconnected", listener_id);
+        // A 440 Hz tone in 256-frame blocks, about what a live input delivers
+        let mut fed = 0;
+        while written.lock().unwrap().len() == headers {
+            let tone: Vec<f32> = (fed..fed + 256)
+                .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin() * 0.5)
+                .collect();
+            encoder.encode_audio_block([&tone, &tone]).unwrap();
+            fed += 256;
+            assert!(fed < 44100 * 10, "no audio page after 10 seconds");
+        }
+        fed
+    }
 
-        Ok(())
+    #[test]
+    fn low_latency_pages_leave_the_encoder_sooner() {
+        let default = frames_to_first_audio_page(None);
+        let low_latency = frames_to_first_audio_page(Some(LOW_LATENCY_PAGE_SIZE));
+        assert!(low_latency < default);
+    }
+
+    #[test]
+    fn flush_interval_bounds_page_latency() {
+        assert_eq!(flush_page_size(Duration::from_millis(50), 128_000), 800);
+        assert_eq!(flush_page_size(Duration::ZERO, 128_000), 1);
+
+        let default = frames_to_first_audio_page(None);
+        let slow =
+            frames_to_first_audio_page(Some(flush_page_size(Duration::from_millis(200), 128_000)));
+        let fast =
+            frames_to_first_audio_page(Some(flush_page_size(Duration::from_millis(25), 128_000)));
+        assert!(fast <= slow);
+        assert!(slow <= default);
+    }
+
+    #[test]
+    fn flush_interval_caps_the_chunk_at_a_page() {
+        let options = BroadcastOptions {
+            flush_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let (broadcaster, _pcm_tx) =
+            RadioBroadcaster::with_options("Quick FM", "test", 44100, 2, options);
+        assert_eq!(
+            broadcaster.chunk_size(),
+            broadcaster.page_size().unwrap() as usize
+        );
+
+        let options = BroadcastOptions {
+            chunk_size: 256,
+            flush_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let (broadcaster, _pcm_tx) =
+            RadioBroadcaster::with_options("Quick FM", "test", 44100, 2, options);
+        assert_eq!(broadcaster.chunk_size(), 256);
+
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Steady FM", "test", 44100, 2);
+        assert_eq!(broadcaster.chunk_size(), DEFAULT_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn max_listeners_refuses_listeners_until_one_leaves() {
+        let options = BroadcastOptions {
+            max_listeners: Some(2),
+            ..Default::default()
+        };
+        let (broadcaster, _pcm_tx) =
+            RadioBroadcaster::with_options("Full FM", "test", 44100, 2, options);
+
+        let first = broadcaster.reserve_slot().unwrap();
+        let _second = broadcaster.reserve_slot().unwrap();
+        assert_eq!(
+            broadcaster.reserve_slot().unwrap_err(),
+            RadioError::StationFull
+        );
+
+        drop(first);
+        assert!(broadcaster.reserve_slot().unwrap().is_some());
+
+        // Unlimited by default
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Open FM", "test", 44100, 2);
+        assert!(broadcaster.reserve_slot().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn listeners_at_the_same_quality_share_an_encoder() {
+        let options = BroadcastOptions {
+            quality_range: Some((0.0, 0.8)),
+            ..Default::default()
+        };
+        let (broadcaster, _pcm_tx) =
+            RadioBroadcaster::with_options("Shared FM", "test", 44100, 2, options);
+
+        let first = broadcaster.shared_encoder(0.4);
+        // Quality is matched to a tenth, as Vorbis can't tell finer apart
+        let same = broadcaster.shared_encoder(0.41);
+        let other = broadcaster.shared_encoder(0.8);
+        assert!(Arc::ptr_eq(&first, &same));
+        assert!(!Arc::ptr_eq(&first, &other));
+
+        // The encoder goes away with its last listener and restarts for the next
+        let stale = Arc::downgrade(&first);
+        drop((first, same));
+        assert!(stale.upgrade().is_none());
+        let restarted = broadcaster.shared_encoder(0.4);
+        assert_eq!(broadcaster.shared_encoders.lock().unwrap().len(), 2);
+        assert!(Arc::ptr_eq(&broadcaster.shared_encoder(0.8), &other));
+        drop(restarted);
+    }
+
+    #[test]
+    fn bandwidth_budget_limits_listeners() {
+        let options = |max_listeners, max_bandwidth| BroadcastOptions {
+            max_listeners,
+            max_bandwidth,
+            ..Default::default()
+        };
+
+        assert_eq!(listener_limit(&options(None, None), 128000), None);
+        // 1000 kbps fits seven 128 kbps streams, not eight
+        assert_eq!(listener_limit(&options(None, Some(1000)), 128000), Some(7));
+        assert_eq!(
+            listener_limit(&options(Some(3), Some(1000)), 128000),
+            Some(3)
+        );
+        assert_eq!(
+            listener_limit(&options(Some(20), Some(1000)), 128000),
+            Some(7)
+        );
+        assert_eq!(listener_limit(&options(None, Some(100)), 128000), Some(0));
+    }
+
+    #[test]
+    fn vorbis_listeners_are_charged_at_their_best_quality() {
+        let vorbis = BroadcastOptions::default();
+        assert_eq!(
+            listener_bitrate(&vorbis, 128000, 2),
+            vorbis_bitrate(QUALITY_TIERS[0], 2)
+        );
+        assert_eq!(vorbis_bitrate(0.5, 2), 160_000);
+        assert_eq!(vorbis_bitrate(0.5, 1), 80_000);
+
+        // listen_at can go above the top tier, but a low range doesn't go below it
+        let high = BroadcastOptions {
+            quality_range: Some((0.0, 1.0)),
+            ..Default::default()
+        };
+        assert_eq!(listener_bitrate(&high, 128000, 2), 500_000);
+        let low = BroadcastOptions {
+            quality_range: Some((-0.1, 0.2)),
+            ..Default::default()
+        };
+        assert_eq!(listener_bitrate(&low, 128000, 2), 160_000);
+
+        let flac = BroadcastOptions {
+            codec: StreamCodec::Flac,
+            ..Default::default()
+        };
+        assert_eq!(listener_bitrate(&flac, 1_411_200, 2), 1_411_200);
+    }
+
+    #[test]
+    fn departures_hold_the_count_through_a_reconnect() {
+        let start = std::time::Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut departures = Departures::new(Duration::from_secs(3));
+
+        departures.left(at(0));
+        assert_eq!(departures.pending(at(1)), 1);
+        // Reconnecting takes the departed listener's place
+        departures.joined(at(2));
+        assert_eq!(departures.pending(at(2)), 0);
+
+        // A listener who doesn't come back drops out after the grace period
+        departures.left(at(10));
+        departures.left(at(11));
+        assert_eq!(departures.pending(at(12)), 2);
+        assert_eq!(departures.pending(at(13)), 1);
+        assert_eq!(departures.pending(at(14)), 0);
+
+        let mut immediate = Departures::new(Duration::ZERO);
+        immediate.left(at(0));
+        assert_eq!(immediate.pending(at(0)), 0);
+    }
+
+    #[test]
+    fn encoded_bitrate_averages_over_the_window() {
+        let mut window = BitrateWindow::new(1000);
+        assert_eq!(window.bitrate(), 0);
+
+        // A loud second at 16 kB/s then a quiet one at 4 kB/s average out
+        window.push(1000, 16_000);
+        assert_eq!(window.bitrate(), 128_000);
+        window.push(1000, 4_000);
+        assert_eq!(window.bitrate(), 80_000);
+
+        // Once the loud second is more than a window old it stops counting
+        for _ in 0..BITRATE_WINDOW.as_secs() {
+            window.push(1000, 4_000);
+        }
+        assert_eq!(window.bitrate(), 32_000);
     }
 }
@@ -0,0 +1,192 @@
+//! Durable chat log (`--chat-log`).
+//!
+//! Every chat message is appended to the log as one JSON line as it's posted.
+//! Once the file would pass its size limit it's renamed to `<path>.1`
+//! (replacing the previous one) and a fresh file is started, so at most about
+//! twice the limit stays on disk. On startup the newest messages are read
+//! back to seed chat history, so `chat_history` survives a restart.
+
+use log::warn;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::service::ChatMessage;
+
+/// Size a log grows to before it's rotated, unless configured
+pub const DEFAULT_CHAT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+pub struct ChatLog {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    /// Bytes in the current file
+    size: u64,
+}
+
+impl ChatLog {
+    /// Open the log at `path` for appending, creating it if needed
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64) -> anyhow::Result<Self> {
+        let path = path.into();
+        let mut file = open_append(&path)?;
+        let mut size = file.metadata()?.len();
+        // Start on a fresh line after one cut short by a crash
+        if size > 0 && !ends_with_newline(&path)? {
+            file.write_all(b"\n")?;
+            size += 1;
+        }
+        Ok(Self {
+            path,
+            max_bytes,
+            file,
+            size,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Up to `count` of the newest logged messages, oldest first (reaching
+    /// into the rotated file when the current one is short)
+    pub fn tail(&self, count: usize) -> Vec<ChatMessage> {
+        let mut messages = VecDeque::with_capacity(count);
+        if count > 0 {
+            read_tail(&rotated_path(&self.path), &mut messages, count);
+            read_tail(&self.path, &mut messages, count);
+        }
+        messages.into()
+    }
+
+    /// Append a message, rotating first if it would take the file past the limit
+    pub fn append(&mut self, chat: &ChatMessage) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(chat)?;
+        line.push(b'\n');
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let rotated = rotated_path(&self.path);
+        std::fs::rename(&self.path, &rotated).map_err(|e| {
+            anyhow::anyhow!("Can't rotate chat log to {}: {}", rotated.display(), e)
+        })?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Can't open chat log {}: {}", path.display(), e))
+}
+
+fn ends_with_newline(path: &Path) -> anyhow::Result<bool> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// `<path>.1`, where the previous log goes on rotation
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Add the messages in `path` to `messages`, keeping only the newest `count`;
+/// unreadable lines (say, one cut short by a crash) are skipped
+fn read_tail(path: &Path, messages: &mut VecDeque<ChatMessage>, count: usize) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("[Chat log] Can't read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("[Chat log] Stopped reading {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(chat) => {
+                if messages.len() == count {
+                    messages.pop_front();
+                }
+                messages.push_back(chat);
+            }
+            Err(e) => warn!(
+                "[Chat log] Skipping {} line {}: {}",
+                path.display(),
+                number + 1,
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(seq: u64) -> ChatMessage {
+        ChatMessage {
+            listener_id: 1,
+            nickname: None,
+            message: format!("message {}", seq),
+            timestamp: seq,
+            seq,
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_the_tail_readable() {
+        let path = std::env::temp_dir().join(format!("zelfm-chat-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(rotated_path(&path));
+
+        // Small enough to rotate every few messages
+        let mut log = ChatLog::open(&path, 200).unwrap();
+        for seq in 1..=10 {
+            log.append(&chat(seq)).unwrap();
+        }
+        assert!(rotated_path(&path).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() <= 200);
+
+        // A torn last line from a crash doesn't lose the rest
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"listener_id\": 1, \"mess")
+            .unwrap();
+
+        let reopened = ChatLog::open(&path, 200).unwrap();
+        let tail = reopened.tail(4);
+        let seqs: Vec<u64> = tail.iter().map(|chat| chat.seq).collect();
+        assert_eq!(seqs, [7, 8, 9, 10]);
+        assert!(reopened.tail(0).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(rotated_path(&path)).unwrap();
+    }
+}
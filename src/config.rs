@@ -0,0 +1,882 @@
+//! Broadcaster settings loaded from a TOML file and/or CLI flags.
+//!
+//! Every field is optional so a file can set only what it cares about; CLI
+//! flags are converted into the same struct and layered on top with
+//! [`BroadcastConfig::merge`].
+//!
+//! ```toml
+//! name = "Night Shift FM"
+//! description = "Ambient until dawn"
+//! genre = "Ambient"
+//! tags = ["chill", "drone"]
+//! website = "https://example.com"
+//! operators = ["<node ID>"]    # may change name, description, etc. on the air
+//! logo = "art/logo.png"        # JPEG or PNG, when a track has no cover art
+//! file = "music/ambient.ogg"   # or: playlist = "sets/night.m3u", dir = "music/", input = "USB Audio", relay = "<ticket>", pipe = "/tmp/zelfm.pcm"
+//! pcm_rate = 48000             # with pipe: raw samples, also pcm_channels = 2, pcm_format = "s16" or "f32"
+//! jingle = "ids/station-id.ogg"  # between playlist or dir tracks
+//! jingle_every = 3             # tracks
+//! on_end = "fallback"          # or "stop", "loop" (default for files), "tone"
+//! fallback = "music/standby.ogg"  # file or playlist, looped once the source runs out
+//! max_decode_ahead_secs = 10   # don't decode files more than this far ahead of playback
+//! trim_silence = true          # skip dead air at the start and end of tracks
+//! silence_threshold_db = -50   # dBFS
+//! strict_format = true         # refuse to start if sources or the jingle aren't at the station's rate
+//! chunk_size = 4096
+//! flush_ms = 50                # OGG pages (and chunks) of about this much audio, for lower latency
+//! codec = "vorbis"             # or "flac" (lossless, for LANs), "pcm" (no encoder delay)
+//! overflow = "drop-oldest"     # or "backpressure"
+//! meter_mode = "loudness"      # or "basic"
+//! http_addr = "0.0.0.0:8000"
+//! relay_urls = ["https://relay.example.com"]  # or: lan_only = true
+//! bind = "0.0.0.0:4433"
+//! identity = "station.key"     # keeps the node ID across restarts
+//! announce_interval = 600      # seconds
+//! announce_text = "You're listening to {station} with {listeners} others"
+//! vote_skip = 0.5              # listeners skip a track once more than half vote
+//! chat_log = "chat.jsonl"      # append chat here and reload history on restart
+//! chat_log_max_mb = 10         # then rotate to chat.jsonl.1
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::audio_source::{RawPcmFormat, DEFAULT_SILENCE_THRESHOLD_DB};
+use crate::broadcaster::{
+    OverflowPolicy, CHAT_HISTORY_LEN, DEFAULT_AUDIO_PRIORITY, DEFAULT_CHAT_CAPACITY,
+    DEFAULT_CHAT_HISTORY_BYTES, DEFAULT_CHUNK_SIZE, DEFAULT_LISTENER_GRACE, DEFAULT_PCM_CAPACITY,
+    DEFAULT_STALL_TIMEOUT, LOW_LATENCY_CHUNK_SIZE, MAX_FLUSH_INTERVAL, MAX_QUALITY,
+    MIN_FLUSH_INTERVAL, MIN_QUALITY,
+};
+use crate::chat_log::DEFAULT_CHAT_LOG_MAX_BYTES;
+use crate::fade::DEFAULT_FADE_SECS;
+use crate::levels::MeterMode;
+use crate::network::NetworkOptions;
+use crate::playlist::{OnEnd, PlayOrder, Repeat};
+use crate::service::StreamCodec;
+
+pub const DEFAULT_STATION_NAME: &str = "ZelFM Demo";
+pub const DEFAULT_STATION_DESC: &str = "Live P2P Radio Stream";
+pub const DEFAULT_ANNOUNCE_TEXT: &str = "You're listening to {station}";
+/// Placeholder tone for `on_end = "tone"`, in Hz
+pub const DEFAULT_END_TONE_HZ: f32 = 440.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BroadcastConfig {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub website: Option<String>,
+    /// JPEG or PNG served as artwork when the track has no cover of its own
+    pub logo: Option<String>,
+    pub chunk_size: Option<usize>,
+    /// Small OGG pages and chunks for the least encoder-side delay
+    pub low_latency_encode: Option<bool>,
+    /// Finish OGG pages after about this many milliseconds of audio
+    pub flush_ms: Option<u64>,
+    pub pcm_capacity: Option<usize>,
+    pub overflow: Option<OverflowPolicy>,
+    /// Sum the source to a single-channel stream
+    pub mono: Option<bool>,
+    /// Listener stream encoding (`vorbis`, lossless `flac`, or uncompressed `pcm`)
+    pub codec: Option<StreamCodec>,
+    /// Lowest Vorbis quality listeners may ask for (enables `listen_at`)
+    pub min_quality: Option<f32>,
+    /// Highest Vorbis quality listeners may ask for (enables `listen_at`)
+    pub max_quality: Option<f32>,
+    /// Decode file and playlist sources at playback speed instead of ahead of it
+    pub realtime: Option<bool>,
+    /// Hold file and playlist decoding to at most this many seconds ahead of playback
+    pub max_decode_ahead_secs: Option<f32>,
+    /// Normalize file and playlist tracks with their ReplayGain track gain tags
+    pub replay_gain: Option<bool>,
+    /// Drop leading and trailing silence from file and playlist tracks
+    pub trim_silence: Option<bool>,
+    /// Level below which `trim_silence` counts audio as silent (default -50 dBFS)
+    pub silence_threshold_db: Option<f32>,
+    /// Refuse to start when the source's (or jingle's) sample rate isn't the
+    /// station's; `pipe` and `relay` set the station's rate, and `stdin`
+    /// can't be checked before it plays, so it's refused
+    pub strict_format: Option<bool>,
+    pub duration: Option<u64>,
+    /// Disconnect each listener after this many seconds
+    pub max_session_secs: Option<u64>,
+    /// Refuse listeners beyond this many (each one costs an encoder)
+    pub max_listeners: Option<usize>,
+    /// Cap on total upload to listeners in kbps; admits as many as fit at the stream bitrate
+    pub max_bandwidth: Option<u32>,
+    /// Disconnect listeners that accept no data for this many seconds (default 30)
+    pub stall_timeout_secs: Option<u64>,
+    /// QUIC send priority of audio against 0 for chat and everything else (default 1)
+    pub audio_priority: Option<i32>,
+    /// Seconds a departed listener still counts in `info`, smoothing reconnects (default 3, 0 = off)
+    pub listener_grace_secs: Option<u64>,
+    /// Seconds to fade in at startup and out at shutdown (default 1.5, 0 = off)
+    pub fade_secs: Option<f32>,
+    /// Send headers and the first audio page to new listeners unbuffered (default on)
+    pub fast_start: Option<bool>,
+    /// `basic` (peak + RMS) or `loudness` (adds A-weighted RMS and true peak)
+    pub meter_mode: Option<MeterMode>,
+    /// Keep this many seconds of encoded audio so listeners can rewind (default: live only)
+    pub rewind_secs: Option<u64>,
+    pub http_addr: Option<SocketAddr>,
+    /// Relay servers to use instead of the public n0 relays
+    pub relay_urls: Option<Vec<String>>,
+    /// Disable relays and DNS discovery; listeners must connect directly with a ticket
+    pub lan_only: Option<bool>,
+    /// Local UDP address for the iroh endpoint
+    pub bind: Option<SocketAddr>,
+    /// Secret key file that keeps the station's node ID across restarts
+    pub identity: Option<String>,
+    /// Post a station announcement to chat every this many seconds
+    pub announce_interval: Option<u64>,
+    /// Announcement template; `{station}` and `{listeners}` are filled in
+    pub announce_text: Option<String>,
+    /// Don't post "… joined" / "… left" to chat (for busy stations)
+    pub quiet_joins: Option<bool>,
+    /// Chat messages buffered per live chat subscriber (default 100)
+    pub chat_capacity: Option<usize>,
+    /// Chat messages kept for late joiners (default 100)
+    pub chat_history_count: Option<usize>,
+    /// Memory chat history may use, in bytes, before the oldest messages are
+    /// dropped (default 256 KiB)
+    pub chat_history_bytes: Option<usize>,
+    /// JSONL file chat is appended to, and history reloaded from on start
+    pub chat_log: Option<String>,
+    /// Rotate `chat_log` to `<chat_log>.1` past this many megabytes (default 10)
+    pub chat_log_max_mb: Option<u64>,
+    /// Skip a `file`, `playlist`, or `dir` track once more than this share of listeners vote to
+    pub vote_skip: Option<f32>,
+    /// Re-encode listeners whose sends keep stalling at a lower quality
+    pub adaptive_bitrate: Option<bool>,
+    /// Node ID of a directory to register with
+    pub directory: Option<String>,
+    /// Node IDs allowed to change the station's info on the air
+    pub operators: Option<Vec<String>>,
+    pub file: Option<String>,
+    /// M3U or PLS playlist to play in order
+    pub playlist: Option<String>,
+    /// TOML/JSON sidecar with per-track title, gain, and trims for `playlist`
+    pub manifest: Option<String>,
+    /// Directory of audio files to play as a playlist
+    pub dir: Option<String>,
+    /// `name`, `mtime`, or `shuffle` ordering for `dir`
+    pub order: Option<PlayOrder>,
+    /// Include subdirectories of `dir`
+    pub recursive: Option<bool>,
+    /// Rescan `dir` between tracks so added and removed files update the queue
+    pub watch: Option<bool>,
+    /// `none`, `one`, `all` (default, unless `on_end` or `fallback` is set), or a
+    /// number of passes over `file` or the playlist
+    pub repeat: Option<Repeat>,
+    /// Play the playlist or `dir` in a random order, reshuffled each pass
+    pub shuffle: Option<bool>,
+    /// Fixed shuffle seed, for a reproducible order
+    pub seed: Option<u64>,
+    /// Short clip played between `playlist` or `dir` tracks
+    pub jingle: Option<String>,
+    /// Play `jingle` after every this many tracks (default 1)
+    pub jingle_every: Option<u32>,
+    /// `stop`, `loop`, `tone`, or `fallback` once the source runs out
+    pub on_end: Option<OnEnd>,
+    /// Placeholder tone for `on_end = "tone"`, in Hz (default 440, 0 = silence)
+    pub end_tone_hz: Option<f32>,
+    /// File or M3U/PLS playlist looped for `on_end = "fallback"`
+    pub fallback: Option<String>,
+    pub input: Option<String>,
+    /// Decode a media stream piped into stdin
+    pub stdin: Option<bool>,
+    /// Named pipe (or file) of raw interleaved PCM, sent as-is
+    pub pipe: Option<String>,
+    /// Sample rate of `pipe` in Hz, which the station runs at (default 44100)
+    pub pcm_rate: Option<u32>,
+    /// Channels in `pipe` (default 2)
+    pub pcm_channels: Option<u16>,
+    /// `s16` (default) or `f32` little-endian samples in `pipe`
+    pub pcm_format: Option<RawPcmFormat>,
+    /// Rebroadcast another station (node ID or ticket) without re-encoding
+    pub relay: Option<String>,
+}
+
+impl BroadcastConfig {
+    /// Load and parse a TOML config file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Can't read config {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Write as TOML that [`BroadcastConfig::load`] reads back
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| anyhow::anyhow!("Can't serialize config: {}", e))?;
+        std::fs::write(path, text)
+            .map_err(|e| anyhow::anyhow!("Can't write config {}: {}", path.display(), e))
+    }
+
+    /// The same settings with every default spelled out, so a saved copy
+    /// keeps meaning the same thing if defaults change
+    ///
+    /// Source-specific options are only filled in for sources they apply to,
+    /// so the result still passes [`BroadcastConfig::validate`].
+    pub fn resolved(&self) -> Self {
+        let plays_files = self.file.is_some() || self.playlist.is_some() || self.dir.is_some();
+        let shuffles = self.playlist.is_some() || self.dir.is_some();
+        Self {
+            name: Some(self.name().to_string()),
+            description: Some(self.description().to_string()),
+            chunk_size: Some(self.chunk_size()),
+            low_latency_encode: Some(self.low_latency_encode()),
+            pcm_capacity: Some(self.pcm_capacity()),
+            overflow: Some(self.overflow()),
+            mono: Some(self.mono()),
+            codec: Some(self.codec()),
+            realtime: Some(self.realtime()),
+            max_decode_ahead_secs: self.max_decode_ahead_secs,
+            replay_gain: plays_files.then(|| self.replay_gain()),
+            trim_silence: plays_files.then(|| self.trim_silence().is_some()),
+            silence_threshold_db: self.trim_silence(),
+            strict_format: Some(self.strict_format()),
+            stall_timeout_secs: Some(self.stall_timeout().as_secs()),
+            audio_priority: Some(self.audio_priority()),
+            listener_grace_secs: Some(self.listener_grace().as_secs()),
+            fade_secs: Some(self.fade_secs()),
+            fast_start: Some(self.fast_start()),
+            meter_mode: Some(self.meter_mode()),
+            announce_text: self
+                .announce_interval
+                .map(|_| self.announce_text().to_string()),
+            quiet_joins: Some(self.quiet_joins()),
+            chat_capacity: Some(self.chat_capacity()),
+            chat_history_count: Some(self.chat_history_count()),
+            chat_history_bytes: Some(self.chat_history_bytes()),
+            chat_log_max_mb: self
+                .chat_log
+                .as_ref()
+                .map(|_| self.chat_log_max_bytes() / (1024 * 1024)),
+            adaptive_bitrate: Some(self.adaptive_bitrate()),
+            repeat: plays_files.then(|| self.repeat()),
+            shuffle: shuffles.then(|| self.shuffle()),
+            order: self.dir.as_ref().map(|_| self.order()),
+            recursive: self.dir.as_ref().map(|_| self.recursive()),
+            watch: self.dir.as_ref().map(|_| self.watch()),
+            jingle_every: self.jingle.as_ref().map(|_| self.jingle_every()),
+            on_end: Some(self.on_end()),
+            end_tone_hz: (self.on_end() == OnEnd::Tone).then(|| self.end_tone_hz()),
+            pcm_rate: self.pipe.as_ref().map(|_| self.pcm_rate()),
+            pcm_channels: self.pipe.as_ref().map(|_| self.pcm_channels()),
+            pcm_format: self.pipe.as_ref().map(|_| self.pcm_format()),
+            ..self.clone()
+        }
+    }
+
+    /// Layer `overrides` on top of `self`; any value set in `overrides` wins
+    pub fn merge(self, overrides: BroadcastConfig) -> Self {
+        // A source given on the command line replaces the file's source entirely
+        let cli_source = overrides.file.is_some()
+            || overrides.playlist.is_some()
+            || overrides.dir.is_some()
+            || overrides.input.is_some()
+            || overrides.stdin.is_some()
+            || overrides.pipe.is_some()
+            || overrides.relay.is_some();
+        let (file, playlist, dir, input, stdin, pipe, relay) = if cli_source {
+            (
+                overrides.file,
+                overrides.playlist,
+                overrides.dir,
+                overrides.input,
+                overrides.stdin,
+                overrides.pipe,
+                overrides.relay,
+            )
+        } else {
+            (
+                self.file,
+                self.playlist,
+                self.dir,
+                self.input,
+                self.stdin,
+                self.pipe,
+                self.relay,
+            )
+        };
+
+        Self {
+            name: overrides.name.or(self.name),
+            description: overrides.description.or(self.description),
+            genre: overrides.genre.or(self.genre),
+            tags: overrides.tags.or(self.tags),
+            website: overrides.website.or(self.website),
+            logo: overrides.logo.or(self.logo),
+            chunk_size: overrides.chunk_size.or(self.chunk_size),
+            low_latency_encode: overrides.low_latency_encode.or(self.low_latency_encode),
+            flush_ms: overrides.flush_ms.or(self.flush_ms),
+            pcm_capacity: overrides.pcm_capacity.or(self.pcm_capacity),
+            overflow: overrides.overflow.or(self.overflow),
+            mono: overrides.mono.or(self.mono),
+            codec: overrides.codec.or(self.codec),
+            min_quality: overrides.min_quality.or(self.min_quality),
+            max_quality: overrides.max_quality.or(self.max_quality),
+            realtime: overrides.realtime.or(self.realtime),
+            max_decode_ahead_secs: overrides
+                .max_decode_ahead_secs
+                .or(self.max_decode_ahead_secs),
+            replay_gain: overrides.replay_gain.or(self.replay_gain),
+            trim_silence: overrides.trim_silence.or(self.trim_silence),
+            silence_threshold_db: overrides.silence_threshold_db.or(self.silence_threshold_db),
+            strict_format: overrides.strict_format.or(self.strict_format),
+            duration: overrides.duration.or(self.duration),
+            max_session_secs: overrides.max_session_secs.or(self.max_session_secs),
+            max_listeners: overrides.max_listeners.or(self.max_listeners),
+            max_bandwidth: overrides.max_bandwidth.or(self.max_bandwidth),
+            stall_timeout_secs: overrides.stall_timeout_secs.or(self.stall_timeout_secs),
+            audio_priority: overrides.audio_priority.or(self.audio_priority),
+            listener_grace_secs: overrides.listener_grace_secs.or(self.listener_grace_secs),
+            fade_secs: overrides.fade_secs.or(self.fade_secs),
+            fast_start: overrides.fast_start.or(self.fast_start),
+            meter_mode: overrides.meter_mode.or(self.meter_mode),
+            rewind_secs: overrides.rewind_secs.or(self.rewind_secs),
+            http_addr: overrides.http_addr.or(self.http_addr),
+            relay_urls: overrides.relay_urls.or(self.relay_urls),
+            lan_only: overrides.lan_only.or(self.lan_only),
+            bind: overrides.bind.or(self.bind),
+            identity: overrides.identity.or(self.identity),
+            announce_interval: overrides.announce_interval.or(self.announce_interval),
+            announce_text: overrides.announce_text.or(self.announce_text),
+            quiet_joins: overrides.quiet_joins.or(self.quiet_joins),
+            chat_capacity: overrides.chat_capacity.or(self.chat_capacity),
+            chat_history_count: overrides.chat_history_count.or(self.chat_history_count),
+            chat_history_bytes: overrides.chat_history_bytes.or(self.chat_history_bytes),
+            chat_log: overrides.chat_log.or(self.chat_log),
+            chat_log_max_mb: overrides.chat_log_max_mb.or(self.chat_log_max_mb),
+            vote_skip: overrides.vote_skip.or(self.vote_skip),
+            adaptive_bitrate: overrides.adaptive_bitrate.or(self.adaptive_bitrate),
+            directory: overrides.directory.or(self.directory),
+            operators: overrides.operators.or(self.operators),
+            repeat: overrides.repeat.or(self.repeat),
+            shuffle: overrides.shuffle.or(self.shuffle),
+            seed: overrides.seed.or(self.seed),
+            jingle: overrides.jingle.or(self.jingle),
+            jingle_every: overrides.jingle_every.or(self.jingle_every),
+            on_end: overrides.on_end.or(self.on_end),
+            end_tone_hz: overrides.end_tone_hz.or(self.end_tone_hz),
+            fallback: overrides.fallback.or(self.fallback),
+            manifest: overrides.manifest.or(self.manifest),
+            order: overrides.order.or(self.order),
+            recursive: overrides.recursive.or(self.recursive),
+            watch: overrides.watch.or(self.watch),
+            pcm_rate: overrides.pcm_rate.or(self.pcm_rate),
+            pcm_channels: overrides.pcm_channels.or(self.pcm_channels),
+            pcm_format: overrides.pcm_format.or(self.pcm_format),
+            file,
+            playlist,
+            dir,
+            input,
+            stdin,
+            pipe,
+            relay,
+        }
+    }
+
+    /// Check the merged settings for values that can't work
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.chunk_size() == 0 {
+            anyhow::bail!("chunk_size must be greater than zero");
+        }
+        if self.pcm_capacity() == 0 {
+            anyhow::bail!("pcm_capacity must be greater than zero");
+        }
+        let sources = [
+            self.file.is_some(),
+            self.playlist.is_some(),
+            self.dir.is_some(),
+            self.input.is_some(),
+            self.stdin(),
+            self.pipe.is_some(),
+            self.relay.is_some(),
+        ]
+        .iter()
+        .filter(|&&set| set)
+        .count();
+        match sources {
+            0 => anyhow::bail!(
+                "No audio source specified (set `file`, `playlist`, `dir`, `input`, `stdin`, `pipe`, or `relay`)"
+            ),
+            1 => {}
+            _ => anyhow::bail!(
+                "Specify only one of `file`, `playlist`, `dir`, `input`, `stdin`, `pipe`, or `relay`"
+            ),
+        }
+        if self.pipe.is_none()
+            && (self.pcm_rate.is_some() || self.pcm_channels.is_some() || self.pcm_format.is_some())
+        {
+            anyhow::bail!(
+                "`pcm_rate`, `pcm_channels`, and `pcm_format` only apply to a `pipe` source"
+            );
+        }
+        if !(8000..=192000).contains(&self.pcm_rate()) {
+            anyhow::bail!(
+                "pcm_rate must be between 8000 and 192000 Hz (got {})",
+                self.pcm_rate()
+            );
+        }
+        if !(1..=8).contains(&self.pcm_channels()) {
+            anyhow::bail!(
+                "pcm_channels must be between 1 and 8 (got {})",
+                self.pcm_channels()
+            );
+        }
+        if self.dir.is_none()
+            && (self.order.is_some() || self.recursive.is_some() || self.watch.is_some())
+        {
+            anyhow::bail!("`order`, `recursive`, and `watch` only apply to a `dir` source");
+        }
+        if self.shuffle.is_some() && self.playlist.is_none() && self.dir.is_none() {
+            anyhow::bail!("`shuffle` only applies to a `playlist` or `dir` source");
+        }
+        if self.replay_gain.is_some()
+            && self.file.is_none()
+            && self.playlist.is_none()
+            && self.dir.is_none()
+        {
+            anyhow::bail!("`replay_gain` only applies to a `file`, `playlist`, or `dir` source");
+        }
+        if self.max_decode_ahead_secs.is_some()
+            && self.file.is_none()
+            && self.playlist.is_none()
+            && self.dir.is_none()
+        {
+            anyhow::bail!(
+                "`max_decode_ahead_secs` only applies to a `file`, `playlist`, or `dir` source"
+            );
+        }
+        if let Some(secs) = self.max_decode_ahead_secs {
+            if !(secs.is_finite() && secs > 0.0) {
+                anyhow::bail!("max_decode_ahead_secs must be more than zero, got {secs}");
+            }
+            if self.realtime == Some(true) {
+                anyhow::bail!(
+                    "`max_decode_ahead_secs` has no effect with `realtime`, which already paces decoding"
+                );
+            }
+        }
+        if self.trim_silence.is_some()
+            && self.file.is_none()
+            && self.playlist.is_none()
+            && self.dir.is_none()
+        {
+            anyhow::bail!("`trim_silence` only applies to a `file`, `playlist`, or `dir` source");
+        }
+        if self.silence_threshold_db.is_some() && self.trim_silence != Some(true) {
+            anyhow::bail!("`silence_threshold_db` needs `trim_silence = true`");
+        }
+        if let Some(db) = self.silence_threshold_db {
+            if !(-120.0..0.0).contains(&db) {
+                anyhow::bail!(
+                    "silence_threshold_db must be below 0 and at least -120 (got {})",
+                    db
+                );
+            }
+        }
+        if self.vote_skip.is_some()
+            && self.file.is_none()
+            && self.playlist.is_none()
+            && self.dir.is_none()
+        {
+            anyhow::bail!("`vote_skip` only applies to a `file`, `playlist`, or `dir` source");
+        }
+        if self
+            .vote_skip
+            .is_some_and(|share| !(share > 0.0 && share <= 1.0))
+        {
+            anyhow::bail!("vote_skip must be above 0 and at most 1");
+        }
+        if self.jingle.is_some() && self.playlist.is_none() && self.dir.is_none() {
+            anyhow::bail!("`jingle` only applies to a `playlist` or `dir` source");
+        }
+        if self.strict_format() && self.stdin() {
+            anyhow::bail!("`strict_format` can't check a `stdin` stream's rate before it plays");
+        }
+        if self.jingle_every.is_some() && self.jingle.is_none() {
+            anyhow::bail!("`jingle_every` needs a `jingle`");
+        }
+        if self.jingle_every == Some(0) {
+            anyhow::bail!("jingle_every must be greater than zero");
+        }
+        let plays_files = self.file.is_some() || self.playlist.is_some() || self.dir.is_some();
+        if self.on_end == Some(OnEnd::Loop) {
+            if !plays_files {
+                anyhow::bail!("`on_end = \"loop\"` needs a `file`, `playlist`, or `dir` source");
+            }
+            if self.repeat().passes().is_some() {
+                anyhow::bail!(
+                    "`on_end = \"loop\"` contradicts `repeat = \"{}\"`",
+                    self.repeat()
+                );
+            }
+        }
+        if plays_files && self.on_end() != OnEnd::Loop && self.repeat().passes().is_none() {
+            anyhow::bail!(
+                "`repeat = \"{}\"` never ends, so the source would never reach its `on_end`",
+                self.repeat()
+            );
+        }
+        if self.relay.is_some() && matches!(self.on_end(), OnEnd::Tone | OnEnd::Fallback) {
+            anyhow::bail!("A `relay` station passes the upstream's pages through; it can't play a tone or fallback after it ends");
+        }
+        if self.on_end() == OnEnd::Fallback && self.fallback.is_none() {
+            anyhow::bail!("`on_end = \"fallback\"` needs a `fallback` file or playlist");
+        }
+        if self.fallback.is_some() && self.on_end() != OnEnd::Fallback {
+            anyhow::bail!("`fallback` only applies with `on_end = \"fallback\"`");
+        }
+        if self.end_tone_hz.is_some() && self.on_end() != OnEnd::Tone {
+            anyhow::bail!("`end_tone_hz` only applies with `on_end = \"tone\"`");
+        }
+        if self
+            .end_tone_hz
+            .is_some_and(|hz| !(0.0..=20000.0).contains(&hz))
+        {
+            anyhow::bail!("end_tone_hz must be between 0 and 20000");
+        }
+        if self.manifest.is_some() && self.playlist.is_none() {
+            anyhow::bail!("`manifest` only applies to a `playlist` source");
+        }
+        self.network()?;
+        if self.duration == Some(0) {
+            anyhow::bail!("duration must be greater than zero");
+        }
+        if self.announce_interval == Some(0) {
+            anyhow::bail!("announce_interval must be greater than zero");
+        }
+        if self.chat_capacity == Some(0) {
+            anyhow::bail!("chat_capacity must be greater than zero");
+        }
+        for operator in self.operators.iter().flatten() {
+            if operator.trim().parse::<iroh::PublicKey>().is_err() {
+                anyhow::bail!("operator '{}' is not a valid node ID", operator);
+            }
+        }
+        if self.chat_history_count == Some(0) {
+            anyhow::bail!("chat_history_count must be greater than zero");
+        }
+        if self.chat_history_bytes == Some(0) {
+            anyhow::bail!("chat_history_bytes must be greater than zero");
+        }
+        if self.chat_log_max_mb.is_some() && self.chat_log.is_none() {
+            anyhow::bail!("`chat_log_max_mb` needs a `chat_log`");
+        }
+        if self.chat_log_max_mb == Some(0) {
+            anyhow::bail!("chat_log_max_mb must be greater than zero");
+        }
+        if let Some(ms) = self.flush_ms {
+            let (min, max) = (
+                MIN_FLUSH_INTERVAL.as_millis(),
+                MAX_FLUSH_INTERVAL.as_millis(),
+            );
+            if !(min..=max).contains(&(ms as u128)) {
+                anyhow::bail!("flush_ms must be between {} and {}", min, max);
+            }
+            if self.relay.is_some() {
+                anyhow::bail!("A `relay` station passes the upstream's pages through; `flush_ms` has nothing to flush");
+            }
+        }
+        if self.rewind_secs == Some(0) {
+            anyhow::bail!("rewind_secs must be greater than zero");
+        }
+        if self.stall_timeout_secs == Some(0) {
+            anyhow::bail!("stall_timeout_secs must be greater than zero");
+        }
+        if self.max_session_secs == Some(0) {
+            anyhow::bail!("max_session_secs must be greater than zero");
+        }
+        if self.max_listeners == Some(0) {
+            anyhow::bail!("max_listeners must be greater than zero");
+        }
+        if self.max_bandwidth == Some(0) {
+            anyhow::bail!("max_bandwidth must be greater than zero");
+        }
+        if self
+            .fade_secs
+            .is_some_and(|secs| !secs.is_finite() || secs < 0.0)
+        {
+            anyhow::bail!("fade_secs must be zero or more");
+        }
+        if let Some((min, max)) = self.quality_range() {
+            let allowed = MIN_QUALITY..=MAX_QUALITY;
+            if !allowed.contains(&min) || !allowed.contains(&max) {
+                anyhow::bail!(
+                    "min_quality and max_quality must be between {} and {}",
+                    MIN_QUALITY,
+                    MAX_QUALITY
+                );
+            }
+            if min > max {
+                anyhow::bail!("min_quality can't be above max_quality");
+            }
+            if self.codec() != StreamCodec::Vorbis {
+                anyhow::bail!("Listener-chosen quality only applies to the Vorbis codec");
+            }
+            if self.relay.is_some() {
+                anyhow::bail!("A `relay` station can't re-encode at listener-chosen quality");
+            }
+        }
+        let codec = self.codec();
+        if codec == StreamCodec::Flac && !cfg!(feature = "flac") {
+            anyhow::bail!("The flac codec needs zelfm built with the `flac` feature");
+        }
+        if codec != StreamCodec::Vorbis {
+            // These all pass OGG pages around
+            if self.rewind_secs.is_some() {
+                anyhow::bail!("`rewind_secs` doesn't work with the {} codec", codec);
+            }
+            if self.flush_ms.is_some() {
+                anyhow::bail!("`flush_ms` doesn't work with the {} codec", codec);
+            }
+            if self.adaptive_bitrate() {
+                anyhow::bail!("`adaptive_bitrate` doesn't work with the {} codec", codec);
+            }
+            if self.relay.is_some() {
+                anyhow::bail!("A `relay` station passes the upstream's stream through unchanged; it can't use the {} codec", codec);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn network(&self) -> anyhow::Result<NetworkOptions> {
+        let network = NetworkOptions::new(
+            self.relay_urls.as_deref().unwrap_or_default(),
+            self.lan_only.unwrap_or(false),
+            self.bind,
+        )?;
+        Ok(match &self.identity {
+            Some(path) => network.with_identity(path),
+            None => network,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_STATION_NAME)
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.as_deref().unwrap_or(DEFAULT_STATION_DESC)
+    }
+
+    pub fn chat_capacity(&self) -> usize {
+        self.chat_capacity.unwrap_or(DEFAULT_CHAT_CAPACITY)
+    }
+
+    pub fn chat_history_count(&self) -> usize {
+        self.chat_history_count.unwrap_or(CHAT_HISTORY_LEN)
+    }
+
+    pub fn chat_history_bytes(&self) -> usize {
+        self.chat_history_bytes
+            .unwrap_or(DEFAULT_CHAT_HISTORY_BYTES)
+    }
+
+    pub fn chat_log_max_bytes(&self) -> u64 {
+        self.chat_log_max_mb
+            .map_or(DEFAULT_CHAT_LOG_MAX_BYTES, |mb| mb * 1024 * 1024)
+    }
+
+    pub fn announce_text(&self) -> &str {
+        self.announce_text
+            .as_deref()
+            .unwrap_or(DEFAULT_ANNOUNCE_TEXT)
+    }
+
+    pub fn quiet_joins(&self) -> bool {
+        self.quiet_joins.unwrap_or(false)
+    }
+
+    pub fn adaptive_bitrate(&self) -> bool {
+        self.adaptive_bitrate.unwrap_or(false)
+    }
+
+    /// Range for `listen_at`, if either end is set (the other defaults to
+    /// the widest Vorbis allows)
+    pub fn quality_range(&self) -> Option<(f32, f32)> {
+        if self.min_quality.is_none() && self.max_quality.is_none() {
+            return None;
+        }
+        Some((
+            self.min_quality.unwrap_or(MIN_QUALITY),
+            self.max_quality.unwrap_or(MAX_QUALITY),
+        ))
+    }
+
+    pub fn codec(&self) -> StreamCodec {
+        self.codec.unwrap_or_default()
+    }
+
+    pub fn mono(&self) -> bool {
+        self.mono.unwrap_or(false)
+    }
+
+    pub fn realtime(&self) -> bool {
+        self.realtime.unwrap_or(false)
+    }
+
+    /// How far file and playlist sources may decode ahead (`None`: no limit)
+    pub fn max_decode_ahead(&self) -> Option<std::time::Duration> {
+        self.max_decode_ahead_secs
+            .map(std::time::Duration::from_secs_f32)
+    }
+
+    pub fn replay_gain(&self) -> bool {
+        self.replay_gain.unwrap_or(false)
+    }
+
+    /// Silence threshold in dBFS when `trim_silence` is on
+    pub fn trim_silence(&self) -> Option<f32> {
+        (self.trim_silence == Some(true)).then(|| {
+            self.silence_threshold_db
+                .unwrap_or(DEFAULT_SILENCE_THRESHOLD_DB)
+        })
+    }
+
+    pub fn strict_format(&self) -> bool {
+        self.strict_format.unwrap_or(false)
+    }
+
+    pub fn stall_timeout(&self) -> std::time::Duration {
+        self.stall_timeout_secs
+            .map_or(DEFAULT_STALL_TIMEOUT, std::time::Duration::from_secs)
+    }
+
+    pub fn audio_priority(&self) -> i32 {
+        self.audio_priority.unwrap_or(DEFAULT_AUDIO_PRIORITY)
+    }
+
+    pub fn listener_grace(&self) -> std::time::Duration {
+        self.listener_grace_secs
+            .map_or(DEFAULT_LISTENER_GRACE, std::time::Duration::from_secs)
+    }
+
+    pub fn fade_secs(&self) -> f32 {
+        self.fade_secs.unwrap_or(DEFAULT_FADE_SECS)
+    }
+
+    pub fn meter_mode(&self) -> MeterMode {
+        self.meter_mode.unwrap_or_default()
+    }
+
+    pub fn fast_start(&self) -> bool {
+        self.fast_start.unwrap_or(true)
+    }
+
+    /// An explicit `repeat`, else one pass when something else is meant to
+    /// follow the source (`on_end` other than `loop`, or a `fallback`), else
+    /// looping the whole list
+    pub fn repeat(&self) -> Repeat {
+        let ends =
+            self.fallback.is_some() || self.on_end.is_some_and(|on_end| on_end != OnEnd::Loop);
+        self.repeat.unwrap_or(if ends {
+            Repeat::None
+        } else {
+            Repeat::default()
+        })
+    }
+
+    /// Shuffling was asked for directly or through `order = "shuffle"`
+    pub fn shuffle(&self) -> bool {
+        self.shuffle.unwrap_or(false) || self.order == Some(PlayOrder::Shuffle)
+    }
+
+    pub fn jingle_every(&self) -> u32 {
+        self.jingle_every.unwrap_or(1)
+    }
+
+    /// An explicit `on_end`, else `fallback` when one is configured; otherwise
+    /// files and playlists keep looping as they always have unless `repeat`
+    /// limits the passes, and every other source stops
+    pub fn on_end(&self) -> OnEnd {
+        if let Some(on_end) = self.on_end {
+            return on_end;
+        }
+        if self.fallback.is_some() {
+            return OnEnd::Fallback;
+        }
+        let plays_files = self.file.is_some() || self.playlist.is_some() || self.dir.is_some();
+        if plays_files && self.repeat().passes().is_none() {
+            OnEnd::Loop
+        } else {
+            OnEnd::Stop
+        }
+    }
+
+    pub fn end_tone_hz(&self) -> f32 {
+        self.end_tone_hz.unwrap_or(DEFAULT_END_TONE_HZ)
+    }
+
+    pub fn order(&self) -> PlayOrder {
+        self.order.unwrap_or_default()
+    }
+
+    pub fn recursive(&self) -> bool {
+        self.recursive.unwrap_or(false)
+    }
+
+    pub fn watch(&self) -> bool {
+        self.watch.unwrap_or(false)
+    }
+
+    pub fn stdin(&self) -> bool {
+        self.stdin.unwrap_or(false)
+    }
+
+    pub fn pcm_rate(&self) -> u32 {
+        self.pcm_rate.unwrap_or(44100)
+    }
+
+    pub fn pcm_channels(&self) -> u16 {
+        self.pcm_channels.unwrap_or(2)
+    }
+
+    pub fn pcm_format(&self) -> RawPcmFormat {
+        self.pcm_format.unwrap_or_default()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.clone().unwrap_or_default()
+    }
+
+    /// Parsed `operators`; [`BroadcastConfig::validate`] rejects bad ones
+    pub fn operators(&self) -> Vec<iroh::PublicKey> {
+        self.operators
+            .iter()
+            .flatten()
+            .filter_map(|operator| operator.trim().parse().ok())
+            .collect()
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        let default = if self.low_latency_encode() {
+            LOW_LATENCY_CHUNK_SIZE
+        } else {
+            DEFAULT_CHUNK_SIZE
+        };
+        self.chunk_size.unwrap_or(default)
+    }
+
+    pub fn low_latency_encode(&self) -> bool {
+        self.low_latency_encode.unwrap_or(false)
+    }
+
+    pub fn pcm_capacity(&self) -> usize {
+        self.pcm_capacity.unwrap_or(DEFAULT_PCM_CAPACITY)
+    }
+
+    pub fn overflow(&self) -> OverflowPolicy {
+        self.overflow.unwrap_or_default()
+    }
+}
@@ -41,3 +41,108 @@ pub fn find_device_by_name(host: &cpal::Host, search: &str) -> anyhow::Result<cp
         })
         .ok_or_else(|| anyhow::anyhow!("No device matching '{}' found", search))
 }
+
+/// The device live input opens: the first match for `search`, or the default
+#[cfg(feature = "live-input")]
+pub fn select_input_device(
+    host: &cpal::Host,
+    search: Option<&str>,
+) -> anyhow::Result<cpal::Device> {
+    match search {
+        Some(name) => find_device_by_name(host, name),
+        None => host
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No default input device")),
+    }
+}
+
+/// The input config that best matches `sample_rate` and `channels`, or the
+/// device default (with a warning) when none runs at that rate
+///
+/// Live input is captured as f32 and isn't resampled, so only f32 configs
+/// count, and the rate must match exactly. Among those, an exact channel count
+/// wins, then more channels (mixed down), then fewer.
+#[cfg(feature = "live-input")]
+pub fn best_input_config(
+    device: &cpal::Device,
+    sample_rate: u32,
+    channels: u16,
+) -> anyhow::Result<cpal::SupportedStreamConfig> {
+    let ranges: Vec<_> = device.supported_input_configs()?.collect();
+    if let Some(config) = closest_config(&ranges, sample_rate, channels) {
+        return Ok(config);
+    }
+
+    let config = device.default_input_config()?;
+    log::warn!(
+        "[Live] No f32 input config at {} Hz; using the default ({} Hz, {} ch, {:?})",
+        sample_rate,
+        config.sample_rate().0,
+        config.channels(),
+        config.sample_format()
+    );
+    Ok(config)
+}
+
+#[cfg(feature = "live-input")]
+fn closest_config(
+    ranges: &[cpal::SupportedStreamConfigRange],
+    sample_rate: u32,
+    channels: u16,
+) -> Option<cpal::SupportedStreamConfig> {
+    let channel_penalty = |count: u16| match count.cmp(&channels) {
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+        std::cmp::Ordering::Less => 2,
+    };
+    ranges
+        .iter()
+        .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+        .filter_map(|range| {
+            range
+                .clone()
+                .try_with_sample_rate(cpal::SampleRate(sample_rate))
+        })
+        .min_by_key(|config| (channel_penalty(config.channels()), config.channels()))
+}
+
+#[cfg(all(test, feature = "live-input"))]
+mod tests {
+    use super::*;
+    use cpal::{SampleFormat, SampleRate, SupportedBufferSize, SupportedStreamConfigRange};
+
+    fn range(
+        channels: u16,
+        min: u32,
+        max: u32,
+        format: SampleFormat,
+    ) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            channels,
+            SampleRate(min),
+            SampleRate(max),
+            SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    #[test]
+    fn picks_the_target_rate_and_nearest_channel_count() {
+        let ranges = [
+            range(1, 8000, 96000, SampleFormat::F32),
+            range(2, 48000, 48000, SampleFormat::F32),
+            range(2, 44100, 44100, SampleFormat::I16),
+            range(4, 44100, 48000, SampleFormat::F32),
+        ];
+
+        // Stereo at 44.1 kHz is i16 only; four channels beat mono
+        let config = closest_config(&ranges, 44100, 2).unwrap();
+        assert_eq!((config.channels(), config.sample_rate().0), (4, 44100));
+
+        let config = closest_config(&ranges, 48000, 2).unwrap();
+        assert_eq!((config.channels(), config.sample_rate().0), (2, 48000));
+
+        // Nothing at the rate: the caller falls back to the default
+        assert!(closest_config(&ranges[1..], 22050, 2).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! Opt-in station directory: broadcasters register name + node ID with a
+//! well-known directory node, and listeners browse it instead of trading raw IDs.
+
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zel_core::protocol::{zel_service, RequestContext};
+
+/// ALPN for directory nodes (separate from the radio protocol)
+pub const DIRECTORY_ALPN: &[u8] = b"zelfm-directory/1";
+
+/// Entries not refreshed within this window are dropped
+pub const ENTRY_TTL: Duration = Duration::from_secs(90);
+
+/// How often broadcasters re-register
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationEntry {
+    pub name: String,
+    pub description: String,
+    /// Filled in by the directory from the connection, not trusted from the client
+    pub node_id: String,
+    pub listeners: usize,
+}
+
+#[zel_service(name = "directory")]
+pub trait DirectoryService {
+    /// Register or refresh the calling node's station
+    #[method(name = "register")]
+    async fn register(&self, entry: StationEntry) -> Result<(), String>;
+
+    /// All live stations
+    #[method(name = "list")]
+    async fn list(&self) -> Result<Vec<StationEntry>, String>;
+
+    /// Stations whose name or description contains `query` (case-insensitive)
+    #[method(name = "search")]
+    async fn search(&self, query: String) -> Result<Vec<StationEntry>, String>;
+}
+
+#[derive(Clone, Default)]
+pub struct Directory {
+    stations: Arc<Mutex<HashMap<String, (StationEntry, Instant)>>>,
+}
+
+impl Directory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn live_entries(&self) -> Vec<StationEntry> {
+        let mut stations = self.stations.lock().unwrap();
+        stations.retain(|_, (_, seen)| seen.elapsed() < ENTRY_TTL);
+
+        let mut entries: Vec<_> = stations.values().map(|(e, _)| e.clone()).collect();
+        entries.sort_by_cached_key(|e| e.name.to_lowercase());
+        entries
+    }
+}
+
+#[async_trait]
+impl DirectoryServiceServer for Directory {
+    async fn register(&self, ctx: RequestContext, mut entry: StationEntry) -> Result<(), String> {
+        // Key by the authenticated remote so nobody can register on another node's behalf
+        entry.node_id = ctx.remote_id().to_string();
+
+        let mut stations = self.stations.lock().unwrap();
+        if !stations.contains_key(&entry.node_id) {
+            info!(
+                "[Directory] Registered '{}' ({})",
+                entry.name, entry.node_id
+            );
+        }
+        stations.insert(entry.node_id.clone(), (entry, Instant::now()));
+        Ok(())
+    }
+
+    async fn list(&self, _ctx: RequestContext) -> Result<Vec<StationEntry>, String> {
+        Ok(self.live_entries())
+    }
+
+    async fn search(
+        &self,
+        _ctx: RequestContext,
+        query: String,
+    ) -> Result<Vec<StationEntry>, String> {
+        let query = query.to_lowercase();
+        Ok(self
+            .live_entries()
+            .into_iter()
+            .filter(|e| {
+                e.name.to_lowercase().contains(&query)
+                    || e.description.to_lowercase().contains(&query)
+            })
+            .collect())
+    }
+}
+
+/// Open a directory client from `endpoint` to the directory node
+pub async fn connect(
+    endpoint: &iroh::Endpoint,
+    directory: iroh::PublicKey,
+) -> anyhow::Result<DirectoryServiceClient> {
+    let connection = tokio::time::timeout(
+        Duration::from_secs(15),
+        endpoint.connect(directory, DIRECTORY_ALPN),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("Directory {} unreachable", directory))??;
+
+    let rpc_client = zel_core::protocol::client::RpcClient::new(connection).await?;
+    Ok(DirectoryServiceClient::new(rpc_client))
+}
+
+/// Keep a station registered, re-announcing every [`HEARTBEAT_INTERVAL`]
+///
+/// `entry` is called before each heartbeat so listener counts stay fresh.
+pub async fn heartbeat(
+    endpoint: iroh::Endpoint,
+    directory: iroh::PublicKey,
+    entry: impl Fn() -> StationEntry,
+) {
+    let mut client = None;
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if client.is_none() {
+            match connect(&endpoint, directory).await {
+                Ok(c) => client = Some(c),
+                Err(e) => {
+                    warn!("[Directory] {}", e);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(c) = &client {
+            if let Err(e) = c.register(entry()).await {
+                warn!("[Directory] Registration failed, will reconnect: {}", e);
+                client = None;
+            }
+        }
+    }
+}
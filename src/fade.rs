@@ -0,0 +1,114 @@
+//! Fade-in when a broadcast starts and fade-out when it stops.
+//!
+//! Sources run their blocks through [`Fader::apply`] right before the
+//! broadcast channel, next to the level meter, so the ramp is in the audio
+//! every encoder sees. The position counts frames, not wall time, so a source
+//! decoding ahead of playback still gets a ramp of the right length.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Default fade length for `--fade-secs`
+pub const DEFAULT_FADE_SECS: f32 = 1.5;
+
+pub struct Fader {
+    /// Ramp length in frames; zero disables fading
+    length: u64,
+    state: Mutex<FadeState>,
+}
+
+#[derive(Default)]
+struct FadeState {
+    /// Frames passed through so far
+    position: u64,
+    /// Position the fade-out started at
+    fade_out_from: Option<u64>,
+}
+
+impl Fader {
+    pub fn new(length: Duration, sample_rate: u32) -> Self {
+        Self {
+            length: (length.as_secs_f64() * sample_rate as f64).round() as u64,
+            state: Mutex::new(FadeState::default()),
+        }
+    }
+
+    /// Start ramping down from the next block
+    pub fn fade_out(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.fade_out_from.is_none() {
+            state.fade_out_from = Some(state.position);
+        }
+    }
+
+    /// Whether the fade-out has reached silence (immediately, with fading off)
+    pub fn faded_out(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .fade_out_from
+            .is_some_and(|from| state.position - from >= self.length)
+    }
+
+    /// Scale a planar block by the ramp at its place in the stream
+    pub fn apply(&self, planar: &mut [Vec<f32>]) {
+        let frames = planar.iter().map(Vec::len).min().unwrap_or(0) as u64;
+        let (start, fade_out_from) = {
+            let mut state = self.state.lock().unwrap();
+            let start = state.position;
+            state.position += frames;
+            (start, state.fade_out_from)
+        };
+
+        // Past the fade-in and not fading out: leave the samples alone
+        if self.length == 0 || (start >= self.length && fade_out_from.is_none()) {
+            return;
+        }
+
+        for i in 0..frames {
+            let position = start + i;
+            let mut gain = (position as f64 / self.length as f64).min(1.0);
+            if let Some(from) = fade_out_from {
+                gain *= 1.0 - ((position - from) as f64 / self.length as f64).min(1.0);
+            }
+            if gain < 1.0 {
+                for channel in planar.iter_mut() {
+                    channel[i as usize] *= gain as f32;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_in_then_out_to_silence() {
+        // 4 frames at 4 Hz = a 1 second ramp
+        let fader = Fader::new(Duration::from_secs(1), 4);
+
+        let mut block = vec![vec![1.0; 6]; 2];
+        fader.apply(&mut block);
+        assert_eq!(block[0], [0.0, 0.25, 0.5, 0.75, 1.0, 1.0]);
+        assert_eq!(block[0], block[1]);
+
+        assert!(!fader.faded_out());
+        fader.fade_out();
+        let mut block = vec![vec![1.0; 6]];
+        fader.apply(&mut block);
+        assert_eq!(block[0], [1.0, 0.75, 0.5, 0.25, 0.0, 0.0]);
+        assert!(fader.faded_out());
+    }
+
+    #[test]
+    fn zero_length_passes_audio_through() {
+        let fader = Fader::new(Duration::ZERO, 44100);
+        let mut block = vec![vec![0.5; 3]];
+        fader.apply(&mut block);
+        assert_eq!(block[0], [0.5; 3]);
+
+        fader.fade_out();
+        assert!(fader.faded_out());
+    }
+}
@@ -0,0 +1,211 @@
+//! Streaming FLAC encoder for lossless broadcasts.
+//!
+//! `flacenc` encodes whole sources at once; this drives it a frame at a time
+//! so a listener's stream can start before the audio ends. Output is native
+//! FLAC: the `fLaC` marker and a STREAMINFO block (total length unknown), then
+//! fixed-size frames as enough samples arrive. Samples are quantized to 16 bits,
+//! the same as the FLAC recorder.
+
+use flacenc::component::{BitRepr, Stream, StreamInfo};
+use flacenc::error::Verify;
+use flacenc::source::Fill;
+use std::io::Write;
+
+use crate::service::FLAC_BITS_PER_SAMPLE;
+
+pub struct FlacStreamEncoder<W: Write> {
+    writer: W,
+    config: flacenc::error::Verified<flacenc::config::Encoder>,
+    stream_info: StreamInfo,
+    channels: usize,
+    /// Interleaved samples waiting for a full frame
+    pending: Vec<i32>,
+    frame_number: usize,
+    /// Samples per channel written out in frames so far
+    total_samples: usize,
+}
+
+impl<W: Write> FlacStreamEncoder<W> {
+    /// Write the stream header and get ready for audio
+    pub fn new(sample_rate: u32, channels: u8, mut writer: W) -> anyhow::Result<Self> {
+        let config = flacenc::config::Encoder::default()
+            .into_verified()
+            .map_err(|(_, e)| anyhow::anyhow!("FLAC config: {:?}", e))?;
+        let mut stream_info = StreamInfo::new(
+            sample_rate as usize,
+            channels as usize,
+            FLAC_BITS_PER_SAMPLE as usize,
+        )
+        .map_err(|e| anyhow::anyhow!("FLAC stream info: {:?}", e))?;
+        // Written before any frame exists, so the fixed block size is stated
+        // up front; decoders refuse a STREAMINFO with zero block sizes
+        stream_info
+            .set_block_sizes(config.block_size, config.block_size)
+            .map_err(|e| anyhow::anyhow!("FLAC stream info: {:?}", e))?;
+
+        // A stream with no frames yet is just the marker and metadata
+        let header = Stream::with_stream_info(stream_info.clone());
+        writer.write_all(&to_bytes(&header)?)?;
+
+        Ok(Self {
+            writer,
+            config,
+            stream_info,
+            channels: channels as usize,
+            pending: Vec::new(),
+            frame_number: 0,
+            total_samples: 0,
+        })
+    }
+
+    /// Queue a planar block, writing out every frame it completes
+    pub fn encode_audio_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<()> {
+        let frames = samples.iter().map(|c| c.len()).min().unwrap_or(0);
+        for i in 0..frames {
+            for channel in samples.iter().take(self.channels) {
+                let sample = channel[i].clamp(-1.0, 1.0) * i16::MAX as f32;
+                self.pending.push(sample as i16 as i32);
+            }
+        }
+
+        let frame_len = self.config.block_size * self.channels;
+        while self.pending.len() >= frame_len {
+            let samples: Vec<i32> = self.pending.drain(..frame_len).collect();
+            self.write_frame(&samples)?;
+        }
+        Ok(())
+    }
+
+    /// Write the final (possibly short) frame and return the writer
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        if !self.pending.is_empty() {
+            let samples = std::mem::take(&mut self.pending);
+            self.write_frame(&samples)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// [`finish`](Self::finish), then go back and fill in the header's total
+    /// length, for a stream that's done once it's written (a recording)
+    pub fn finish_seekable(self) -> anyhow::Result<W>
+    where
+        W: std::io::Seek,
+    {
+        use std::io::SeekFrom;
+
+        let mut stream_info = self.stream_info.clone();
+        let total_samples = self.total_samples + self.pending.len() / self.channels;
+        let mut writer = self.finish()?;
+        stream_info.set_total_samples(total_samples);
+        // The same metadata as before, so the header keeps its length
+        let header = Stream::with_stream_info(stream_info);
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&to_bytes(&header)?)?;
+        writer.seek(SeekFrom::End(0))?;
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    fn write_frame(&mut self, interleaved: &[i32]) -> anyhow::Result<()> {
+        let mut framebuf =
+            flacenc::source::FrameBuf::with_size(self.channels, interleaved.len() / self.channels)
+                .map_err(|e| anyhow::anyhow!("FLAC frame buffer: {:?}", e))?;
+        framebuf
+            .fill_interleaved(interleaved)
+            .map_err(|e| anyhow::anyhow!("FLAC frame buffer: {:?}", e))?;
+        let frame = flacenc::encode_fixed_size_frame(
+            &self.config,
+            &framebuf,
+            self.frame_number,
+            &self.stream_info,
+        )
+        .map_err(|e| anyhow::anyhow!("FLAC encode: {:?}", e))?;
+        self.frame_number += 1;
+        self.total_samples += interleaved.len() / self.channels;
+
+        self.writer.write_all(&to_bytes(&frame)?)?;
+        Ok(())
+    }
+}
+
+fn to_bytes(component: &impl BitRepr) -> anyhow::Result<Vec<u8>> {
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    component
+        .write(&mut sink)
+        .map_err(|e| anyhow::anyhow!("FLAC write: {:?}", e))?;
+    Ok(sink.as_slice().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::formats::{FormatOptions, FormatReader};
+    use symphonia::core::io::MediaSourceStream;
+
+    #[test]
+    fn stream_decodes_back_to_the_input() {
+        let rate = 8000;
+        let left: Vec<f32> = (0..5000).map(|i| (i as f32 * 0.03).sin() * 0.5).collect();
+        let right: Vec<f32> = left.iter().map(|sample| -sample).collect();
+
+        // Uneven blocks, so frames straddle them and the last one is short
+        let mut encoder = FlacStreamEncoder::new(rate, 2, Vec::new()).unwrap();
+        encoder
+            .encode_audio_block(&[&left[..1234], &right[..1234]])
+            .unwrap();
+        encoder
+            .encode_audio_block(&[&left[1234..], &right[1234..]])
+            .unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let source =
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes)), Default::default());
+        let mut reader =
+            symphonia::default::formats::FlacReader::try_new(source, &FormatOptions::default())
+                .unwrap();
+        let params = reader.default_track().unwrap().codec_params.clone();
+        assert_eq!(params.sample_rate, Some(rate));
+        assert_eq!(params.channels.map(|channels| channels.count()), Some(2));
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .unwrap();
+
+        let mut decoded = Vec::new();
+        while let Ok(packet) = reader.next_packet() {
+            let buf = decoder.decode(&packet).unwrap();
+            let mut samples = SampleBuffer::<f32>::new(buf.capacity() as u64, *buf.spec());
+            samples.copy_interleaved_ref(buf);
+            decoded.extend_from_slice(samples.samples());
+        }
+
+        // A live stream's length is unknown
+        assert_eq!(params.n_frames, None);
+
+        // 16-bit quantization is the only loss
+        assert_eq!(decoded.len(), 2 * left.len());
+        for (i, pair) in decoded.chunks(2).enumerate() {
+            assert!((pair[0] - left[i]).abs() < 1e-3, "left sample {}", i);
+            assert!((pair[1] - right[i]).abs() < 1e-3, "right sample {}", i);
+        }
+    }
+
+    #[test]
+    fn a_finished_recording_states_its_length() {
+        let tone: Vec<f32> = (0..3000).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let mut encoder =
+            FlacStreamEncoder::new(8000, 1, std::io::Cursor::new(Vec::new())).unwrap();
+        encoder.encode_audio_block(&[&tone]).unwrap();
+        let bytes = encoder.finish_seekable().unwrap().into_inner();
+
+        let source =
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes)), Default::default());
+        let reader =
+            symphonia::default::formats::FlacReader::try_new(source, &FormatOptions::default())
+                .unwrap();
+        let params = &reader.default_track().unwrap().codec_params;
+        assert_eq!(params.n_frames, Some(tone.len() as u64));
+    }
+}
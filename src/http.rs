@@ -0,0 +1,210 @@
+//! Minimal Shoutcast/Icecast-style HTTP endpoint so ordinary media players
+//! (VLC, browsers) can tune in without the iroh client.
+//!
+//! `GET /healthz` returns the station's [`HealthStatus`](crate::service::HealthStatus)
+//! as JSON instead of audio, with 503 when the source has stopped producing.
+
+use log::{error, info, warn};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+
+use crate::broadcaster::RadioBroadcaster;
+use crate::service::StreamCodec;
+
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// Accept HTTP listeners on `addr` until the task is dropped
+pub async fn serve(addr: SocketAddr, broadcaster: RadioBroadcaster) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("HTTP stream: http://{}/", listener.local_addr()?);
+
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        let broadcaster = broadcaster.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(socket, peer, broadcaster).await {
+                warn!("[HTTP] Client {} error: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_client(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    broadcaster: RadioBroadcaster,
+) -> anyhow::Result<()> {
+    let request = read_request_head(&mut socket).await?;
+    let mut lines = request.lines();
+    let request_line = lines.next().unwrap_or_default();
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or("/");
+    let version = parts.next().unwrap_or("HTTP/1.0");
+
+    if method != "GET" {
+        socket
+            .write_all(
+                b"HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await?;
+        return Ok(());
+    }
+
+    if path == "/healthz" {
+        return write_health(&mut socket, &broadcaster).await;
+    }
+
+    let Ok(_slot) = broadcaster.reserve_slot() else {
+        socket
+            .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Ok(());
+    };
+
+    // Players ask for ICY metadata with `Icy-MetaData: 1`
+    let wants_icy = lines.any(|line| {
+        line.split_once(':')
+            .map(|(k, v)| k.trim().eq_ignore_ascii_case("icy-metadata") && v.trim() == "1")
+            .unwrap_or(false)
+    });
+
+    // HTTP/1.0 clients don't understand chunked transfer; end-of-stream is connection close
+    let chunked = version != "HTTP/1.0";
+
+    let mut head = String::from("HTTP/1.1 200 OK\r\n");
+    head.push_str(match broadcaster.codec() {
+        StreamCodec::Vorbis => "Content-Type: application/ogg\r\n",
+        StreamCodec::Flac => "Content-Type: audio/flac\r\n",
+        StreamCodec::Pcm => "Content-Type: application/octet-stream\r\n",
+    });
+    head.push_str("Cache-Control: no-cache, no-store\r\n");
+    head.push_str("Connection: close\r\n");
+    if chunked {
+        head.push_str("Transfer-Encoding: chunked\r\n");
+    }
+    if wants_icy {
+        // OGG carries its own in-band tags, so no icy-metaint interleaving
+        head.push_str(&format!(
+            "icy-name: {}\r\n",
+            header_safe(&broadcaster.station_name())
+        ));
+        head.push_str(&format!(
+            "icy-description: {}\r\n",
+            header_safe(&broadcaster.station_desc())
+        ));
+        // kbps: what the encoder is producing once it's measured, else the nominal rate
+        let bitrate = match broadcaster.encoded_bitrate() {
+            0 => broadcaster.bitrate(),
+            measured => measured,
+        };
+        head.push_str(&format!("icy-br: {}\r\n", bitrate / 1000));
+        head.push_str("icy-pub: 0\r\n");
+    }
+    head.push_str("\r\n");
+    socket.write_all(head.as_bytes()).await?;
+
+    let listener_id = broadcaster.listener_connected(format!("http://{}", peer), None);
+    let (mut ogg_rx, encoder_task) = broadcaster.spawn_stream(listener_id, None);
+
+    let stall_timeout = broadcaster.stall_timeout();
+    let streaming_since = std::time::Instant::now();
+    let mut sent_bytes: u64 = 0;
+
+    while let Some(chunk) = ogg_rx.recv().await {
+        let write = async {
+            if chunked {
+                socket
+                    .write_all(format!("{:X}\r\n", chunk.len()).as_bytes())
+                    .await?;
+                socket.write_all(&chunk).await?;
+                socket.write_all(b"\r\n").await
+            } else {
+                socket.write_all(&chunk).await
+            }
+        };
+
+        match timeout(stall_timeout, write).await {
+            Ok(Ok(())) => {
+                broadcaster.record_sent(chunk.len());
+                sent_bytes += chunk.len() as u64;
+            }
+            Ok(Err(e)) => {
+                info!("[HTTP] Listener {} closed: {}", listener_id, e);
+                break;
+            }
+            Err(_) => {
+                warn!(
+                    "[HTTP] Listener {} stalled (no progress for {} seconds; {}), disconnecting",
+                    broadcaster.listener_label(listener_id),
+                    stall_timeout.as_secs(),
+                    crate::broadcaster::send_rate(sent_bytes, streaming_since)
+                );
+                break;
+            }
+        }
+    }
+
+    if chunked {
+        let _ = socket.write_all(b"0\r\n\r\n").await;
+    }
+    let _ = socket.shutdown().await;
+    encoder_task.abort();
+
+    broadcaster.listener_disconnected(listener_id);
+
+    Ok(())
+}
+
+/// Answer a monitor: 200 while audio is flowing, 503 once the source stops
+async fn write_health(
+    socket: &mut TcpStream,
+    broadcaster: &RadioBroadcaster,
+) -> anyhow::Result<()> {
+    let health = broadcaster.health();
+    let status = if health.audio_flowing {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    let body = serde_json::to_string(&health)?;
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nCache-Control: no-cache\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    let _ = socket.shutdown().await;
+    Ok(())
+}
+
+/// Read the request line and headers (up to the blank line)
+async fn read_request_head(socket: &mut TcpStream) -> anyhow::Result<String> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut byte = [0u8; 1];
+
+    while !buf.ends_with(b"\r\n\r\n") {
+        if buf.len() >= MAX_REQUEST_BYTES {
+            error!("[HTTP] Request header too large");
+            anyhow::bail!("request header too large");
+        }
+        match timeout(Duration::from_secs(10), socket.read(&mut byte)).await {
+            Ok(Ok(0)) => anyhow::bail!("connection closed before request completed"),
+            Ok(Ok(_)) => buf.push(byte[0]),
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => anyhow::bail!("timed out reading request"),
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Strip characters that would break an HTTP header line
+fn header_safe(value: &str) -> String {
+    value.chars().filter(|c| *c != '\r' && *c != '\n').collect()
+}
@@ -0,0 +1,90 @@
+//! Persistent node identity.
+//!
+//! A node's ID is the public half of its endpoint's secret key. Iroh makes a
+//! new key on every start, so a station's ID would change with each restart;
+//! `--identity <path>` keeps the key in a file instead, generating it the
+//! first time.
+//!
+//! The file holds the 32-byte ed25519 secret key as 64 lowercase hex digits
+//! and a newline. Anyone with the file can impersonate the node, so it is
+//! created readable by its owner only (mode 0600 on Unix).
+
+use iroh::SecretKey;
+use std::io::Write;
+use std::path::Path;
+
+/// Load the key at `path`, or generate one and save it there
+pub fn load_or_create(path: &Path) -> anyhow::Result<SecretKey> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid identity file {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = SecretKey::generate(&mut rand::rng());
+            save(path, &key)?;
+            Ok(key)
+        }
+        Err(e) => Err(anyhow::anyhow!(
+            "Can't read identity file {}: {}",
+            path.display(),
+            e
+        )),
+    }
+}
+
+fn save(path: &Path, key: &SecretKey) -> anyhow::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    // Never overwrite: an existing key is someone's station ID
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let hex: String = key
+        .to_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let mut file = options
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Can't create identity file {}: {}", path.display(), e))?;
+    writeln!(file, "{}", hex)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_saved_identity_loads_back_unchanged() {
+        let path = std::env::temp_dir().join(format!("zelfm-identity-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let created = load_or_create(&path).unwrap();
+        let loaded = load_or_create(&path).unwrap();
+        assert_eq!(created.public(), loaded.public());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_corrupt_identity_is_an_error() {
+        let path = std::env::temp_dir().join(format!("zelfm-bad-identity-{}", std::process::id()));
+        std::fs::write(&path, "not a key\n").unwrap();
+
+        assert!(load_or_create(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
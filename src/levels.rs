@@ -0,0 +1,359 @@
+//! Output level metering for remote monitoring.
+//!
+//! Sources feed each block through [`LevelMeter::update`] right before it goes
+//! to the broadcast channel, so metering reads the samples in place and never
+//! copies them.
+//!
+//! [`MeterMode::Loudness`] adds A-weighted RMS and inter-sample true peak
+//! (4x oversampled) for stations that have to meet loudness specs.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::service::ChannelLevels;
+
+/// Samples at or beyond full scale count as clipped
+const CLIP_THRESHOLD: f32 = 1.0;
+
+/// True-peak oversampling factor (ITU-R BS.1770 uses 4x)
+const OVERSAMPLE: usize = 4;
+
+/// Interpolation taps per oversampled phase
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// A-weighting pole frequencies in Hz (IEC 61672)
+const A_WEIGHT_POLES: [f64; 4] = [20.598997, 107.65265, 737.86223, 12194.217];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MeterMode {
+    /// Sample peak and RMS
+    #[default]
+    Basic,
+    /// Also A-weighted RMS and 4x oversampled true peak
+    Loudness,
+}
+
+#[derive(Default)]
+pub struct LevelMeter {
+    levels: Mutex<ChannelLevels>,
+    loudness: Option<Mutex<LoudnessState>>,
+    last_block: Mutex<Option<Instant>>,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Meter in the given mode; loudness metering needs the sample rate for its filters
+    pub fn with_mode(mode: MeterMode, sample_rate: u32) -> Self {
+        Self {
+            levels: Mutex::default(),
+            loudness: (mode == MeterMode::Loudness)
+                .then(|| Mutex::new(LoudnessState::new(sample_rate))),
+            last_block: Mutex::default(),
+        }
+    }
+
+    /// Measure one planar block; levels reflect the most recent block
+    pub fn update<C: AsRef<[f32]>>(&self, block: &[C]) {
+        let mut peak = Vec::with_capacity(block.len());
+        let mut rms = Vec::with_capacity(block.len());
+        let mut clipped = 0u64;
+
+        for channel in block {
+            let samples = channel.as_ref();
+            let mut channel_peak = 0.0f32;
+            let mut sum_squares = 0.0f32;
+            for &sample in samples {
+                let magnitude = sample.abs();
+                channel_peak = channel_peak.max(magnitude);
+                sum_squares += sample * sample;
+                if magnitude >= CLIP_THRESHOLD {
+                    clipped += 1;
+                }
+            }
+            peak.push(channel_peak);
+            rms.push(if samples.is_empty() {
+                0.0
+            } else {
+                (sum_squares / samples.len() as f32).sqrt()
+            });
+        }
+
+        let loudness = self
+            .loudness
+            .as_ref()
+            .map(|state| state.lock().unwrap().measure(block));
+
+        *self.last_block.lock().unwrap() = Some(Instant::now());
+        let mut levels = self.levels.lock().unwrap();
+        levels.peak = peak;
+        levels.rms = rms;
+        levels.clipped_samples += clipped;
+        if let Some((rms_a, true_peak)) = loudness {
+            levels.rms_a = rms_a;
+            levels.true_peak = true_peak;
+        }
+    }
+
+    pub fn snapshot(&self) -> ChannelLevels {
+        self.levels.lock().unwrap().clone()
+    }
+
+    /// Time since the last metered block (`None` before the first)
+    pub fn since_last_block(&self) -> Option<Duration> {
+        self.last_block.lock().unwrap().map(|at| at.elapsed())
+    }
+}
+
+/// Filter and interpolator history carried across blocks, per channel
+struct LoudnessState {
+    weighting: AWeighting,
+    filters: Vec<Vec<FirstOrder>>,
+    history: Vec<Vec<f32>>,
+    phases: [[f32; TRUE_PEAK_TAPS]; OVERSAMPLE - 1],
+}
+
+impl LoudnessState {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            weighting: AWeighting::new(sample_rate),
+            filters: Vec::new(),
+            history: Vec::new(),
+            phases: true_peak_phases(),
+        }
+    }
+
+    /// A-weighted RMS and true peak per channel
+    fn measure<C: AsRef<[f32]>>(&mut self, block: &[C]) -> (Vec<f32>, Vec<f32>) {
+        // Channel count can change between sources; start those channels fresh
+        if self.filters.len() != block.len() {
+            self.filters = vec![self.weighting.sections.clone(); block.len()];
+            self.history = vec![vec![0.0; TRUE_PEAK_TAPS - 1]; block.len()];
+        }
+
+        let mut rms_a = Vec::with_capacity(block.len());
+        let mut true_peak = Vec::with_capacity(block.len());
+        for (c, channel) in block.iter().enumerate() {
+            let samples = channel.as_ref();
+
+            let mut sum_squares = 0.0f64;
+            for &sample in samples {
+                let weighted = self.filters[c]
+                    .iter_mut()
+                    .fold(sample as f64, |x, section| section.process(x))
+                    * self.weighting.gain;
+                sum_squares += weighted * weighted;
+            }
+            rms_a.push(if samples.is_empty() {
+                0.0
+            } else {
+                (sum_squares / samples.len() as f64).sqrt() as f32
+            });
+
+            true_peak.push(self.true_peak(c, samples));
+        }
+        (rms_a, true_peak)
+    }
+
+    /// Largest magnitude among the samples and the points interpolated between them
+    fn true_peak(&mut self, channel: usize, samples: &[f32]) -> f32 {
+        let history = &mut self.history[channel];
+        let mut window = std::mem::take(history);
+        window.extend_from_slice(samples);
+
+        let mut peak = samples.iter().fold(0.0f32, |p, s| p.max(s.abs()));
+        for taps in window.windows(TRUE_PEAK_TAPS) {
+            for phase in &self.phases {
+                let value: f32 = taps.iter().zip(phase).map(|(x, h)| x * h).sum();
+                peak = peak.max(value.abs());
+            }
+        }
+
+        let keep = window.len() - (TRUE_PEAK_TAPS - 1);
+        *history = window.split_off(keep);
+        peak
+    }
+}
+
+/// Hann-windowed sinc taps for the three in-between points of 4x oversampling
+fn true_peak_phases() -> [[f32; TRUE_PEAK_TAPS]; OVERSAMPLE - 1] {
+    let half = (TRUE_PEAK_TAPS / 2) as f64;
+    let mut phases = [[0.0; TRUE_PEAK_TAPS]; OVERSAMPLE - 1];
+    for (p, phase) in phases.iter_mut().enumerate() {
+        let offset = (p + 1) as f64 / OVERSAMPLE as f64;
+        for (k, tap) in phase.iter_mut().enumerate() {
+            // Distance from the interpolated point, which sits between taps half-1 and half
+            let t = k as f64 - (half - 1.0) - offset;
+            let sinc = if t == 0.0 {
+                1.0
+            } else {
+                (PI * t).sin() / (PI * t)
+            };
+            let window = 0.5 * (1.0 + (PI * t / (half + 1.0)).cos());
+            *tap = (sinc * window) as f32;
+        }
+    }
+    phases
+}
+
+/// A-weighting as a cascade of bilinear-transformed first-order sections,
+/// normalized to unity gain at 1 kHz
+struct AWeighting {
+    sections: Vec<FirstOrder>,
+    gain: f64,
+}
+
+impl AWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let fs = sample_rate as f64;
+        let [f1, f2, f3, f4] = A_WEIGHT_POLES;
+        // s^4 / ((s + w1)^2 (s + w2)(s + w3)) * w4^2 / (s + w4)^2
+        let sections = vec![
+            FirstOrder::high_pass(f1, fs),
+            FirstOrder::high_pass(f1, fs),
+            FirstOrder::high_pass(f2, fs),
+            FirstOrder::high_pass(f3, fs),
+            FirstOrder::low_pass(f4, fs),
+            FirstOrder::low_pass(f4, fs),
+        ];
+
+        let omega = 2.0 * PI * 1000.0 / fs;
+        let response: f64 = sections.iter().map(|s| s.magnitude(omega)).product();
+        Self {
+            sections,
+            gain: 1.0 / response,
+        }
+    }
+}
+
+/// `y[n] = b0 x[n] + b1 x[n-1] - a1 y[n-1]`
+#[derive(Clone)]
+struct FirstOrder {
+    b0: f64,
+    b1: f64,
+    a1: f64,
+    x1: f64,
+    y1: f64,
+}
+
+impl FirstOrder {
+    /// Bilinear `s / (s + w)` with the pole prewarped to land at `freq`
+    fn high_pass(freq: f64, fs: f64) -> Self {
+        let (k, w) = Self::prewarp(freq, fs);
+        Self::new(k / (k + w), -k / (k + w), -(k - w) / (k + w))
+    }
+
+    /// Bilinear `w / (s + w)` with the pole prewarped to land at `freq`
+    fn low_pass(freq: f64, fs: f64) -> Self {
+        let (k, w) = Self::prewarp(freq, fs);
+        Self::new(w / (k + w), w / (k + w), -(k - w) / (k + w))
+    }
+
+    fn prewarp(freq: f64, fs: f64) -> (f64, f64) {
+        let k = 2.0 * fs;
+        // Poles above Nyquist can't be prewarped; clamp just below it
+        let freq = freq.min(fs * 0.49);
+        (k, k * (PI * freq / fs).tan())
+    }
+
+    fn new(b0: f64, b1: f64, a1: f64) -> Self {
+        Self {
+            b0,
+            b1,
+            a1,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+
+    /// Gain at normalized angular frequency `omega`
+    fn magnitude(&self, omega: f64) -> f64 {
+        let (sin, cos) = omega.sin_cos();
+        let num = ((self.b0 + self.b1 * cos).powi(2) + (self.b1 * sin).powi(2)).sqrt();
+        let den = ((1.0 + self.a1 * cos).powi(2) + (self.a1 * sin).powi(2)).sqrt();
+        num / den
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, phase: f64, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|n| (2.0 * PI * freq * n as f64 / sample_rate as f64 + phase).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn basic_levels_and_clipping() {
+        let meter = LevelMeter::new();
+        meter.update(&[vec![0.5, -1.0, 0.0, 0.0], vec![0.25; 4]]);
+        meter.update(&[vec![1.5; 2], vec![0.0; 2]]);
+
+        let levels = meter.snapshot();
+        // Levels are the latest block's; clipping counts up across blocks
+        assert_eq!(levels.peak, [1.5, 0.0]);
+        assert_eq!(levels.rms, [1.5, 0.0]);
+        assert_eq!(levels.clipped_samples, 3);
+        assert!(levels.rms_a.is_empty() && levels.true_peak.is_empty());
+    }
+
+    #[test]
+    fn a_weighting_is_flat_at_1khz_and_cuts_lows() {
+        let weighting = AWeighting::new(48000);
+        let db = |freq: f64| {
+            let omega = 2.0 * PI * freq / 48000.0;
+            let response: f64 = weighting
+                .sections
+                .iter()
+                .map(|s| s.magnitude(omega))
+                .product();
+            20.0 * (response * weighting.gain).log10()
+        };
+        assert!(db(1000.0).abs() < 0.01);
+        // IEC 61672: -19.1 dB at 100 Hz, -50.5 dB at 20 Hz
+        assert!((db(100.0) + 19.1).abs() < 0.2, "{}", db(100.0));
+        assert!((db(20.0) + 50.5).abs() < 0.5, "{}", db(20.0));
+    }
+
+    #[test]
+    fn loudness_mode_finds_peaks_between_samples() {
+        let meter = LevelMeter::with_mode(MeterMode::Loudness, 48000);
+        // A quarter of the sample rate, offset so every sample lands at 0.707
+        // while the waveform itself peaks at 1.0 in between
+        let tone = sine(12000.0, PI / 4.0, 48000, 4800);
+        meter.update(&[&tone[..]]);
+        let levels = meter.snapshot();
+        assert!((levels.peak[0] - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001);
+        assert!(
+            (levels.true_peak[0] - 1.0).abs() < 0.05,
+            "{:?}",
+            levels.true_peak
+        );
+
+        // After the filters settle, a 1 kHz tone reads its plain RMS
+        let meter = LevelMeter::with_mode(MeterMode::Loudness, 48000);
+        let tone = sine(1000.0, 0.0, 48000, 4800);
+        meter.update(&[&tone[..]]);
+        meter.update(&[&tone[..]]);
+        let levels = meter.snapshot();
+        assert!(
+            (levels.rms_a[0] - levels.rms[0]).abs() < 0.01,
+            "{:?}",
+            levels
+        );
+    }
+}
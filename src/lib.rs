@@ -0,0 +1,38 @@
+//! ZelFM - P2P internet radio over iroh.
+//!
+//! The `zelfm` binary is a thin CLI over these modules; embedders can use
+//! [`listener::RadioListener`] directly to receive decoded PCM.
+
+pub mod artwork;
+pub mod audio_player;
+pub mod audio_source;
+pub mod audio_util;
+pub mod bench;
+pub mod broadcaster;
+pub mod chat_log;
+pub mod config;
+pub mod devices;
+pub mod directory;
+pub mod fade;
+#[cfg(feature = "flac")]
+pub mod flac_stream;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod identity;
+pub mod levels;
+pub mod listener;
+pub mod logging;
+#[cfg(any(test, feature = "test-util"))]
+pub mod loopback;
+pub mod network;
+pub mod night_mode;
+pub mod pcm_frame;
+pub mod playlist;
+pub mod recorder;
+pub mod relay;
+pub mod resample;
+pub mod rewind;
+pub mod service;
+pub mod spectrum;
+pub mod tags;
+pub mod ticket;
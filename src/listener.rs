@@ -1,156 +1,1327 @@
-use log::info;
-use std::io::Cursor;
+use log::{error, info, warn};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use vorbis_rs::VorbisDecoder;
 
-use crate::service::RadioServiceClient;
+use crate::audio_util::check_format;
+use crate::night_mode::NightMode;
+use crate::pcm_frame::PcmFrameReader;
+use crate::recorder::{pcm_recorder, RecordFormat};
+use crate::resample::Resampler;
+use crate::rewind::PageSplitter;
+use crate::service::{
+    reset_reason, RadioError, RadioServiceClient, StationInfo, StreamCodec, ADAPTIVE_VERSION,
+    CODEC_VERSION, ENCODED_BITRATE_VERSION, PROTOCOL_VERSION, SIGNED_INFO_VERSION,
+    SIGNING_CONTEXT_VERSION, STOP_LISTENER_DONE,
+};
+use crate::spectrum::{render_bars, SpectrumAnalyzer, DECIMATION};
 
 #[cfg(feature = "playback")]
 use crate::audio_player::AudioPlayer;
 
+pub type AudioBlock = Vec<Vec<f32>>; // [channels][samples]
+
+/// How long the receive task gets to stop after decoding ends
+const RECV_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Tries at the first `info` call before giving up; a station that just
+/// accepted the connection may not be serving yet
+const INFO_ATTEMPTS: u32 = 4;
+
+/// How long one `info` attempt may take
+const INFO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause before the first `info` retry, doubling after each
+const INFO_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Receive path sizing when the station's bitrate doesn't call for more:
+/// largest single read from the stream, and received chunks waiting for the
+/// decoder (10 x 8 KB is about 5 seconds at 128 kbps)
+pub const DEFAULT_READ_CHUNK: usize = 8192;
+pub const DEFAULT_RECV_QUEUE: usize = 10;
+
+/// Largest read chunk picked from the bitrate
+const MAX_AUTO_READ_CHUNK: usize = 64 * 1024;
+
+/// Read chunk and queue depth for a stream at `bitrate` bits per second
+///
+/// A chunk takes about 1/16 s of the stream, so a lossless or PCM stream isn't
+/// read a few kilobytes at a time, and the queue holds at least a second.
+pub fn receive_buffers(bitrate: u32) -> (usize, usize) {
+    let bytes_per_sec = bitrate as usize / 8;
+    let chunk = (bytes_per_sec / 16)
+        .next_power_of_two()
+        .clamp(DEFAULT_READ_CHUNK, MAX_AUTO_READ_CHUNK);
+    let queue = bytes_per_sec.div_ceil(chunk).max(DEFAULT_RECV_QUEUE);
+    (chunk, queue)
+}
+
+/// Blocks queued for each output when several run at once (a few seconds)
+const OUTPUT_QUEUE_BLOCKS: usize = 256;
+
+/// Builds an output on the thread that will run it
+type MakeSink = Box<dyn FnOnce() -> anyhow::Result<Box<dyn PcmSink>> + Send>;
+
+/// Format of the decoded stream, known once the OGG headers arrive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+/// Destination for decoded planar PCM blocks
+pub trait PcmSink {
+    /// Handle one decoded block; return `Ok(false)` to stop listening
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool>;
+
+    /// Called once after the last block
+    fn finish(&mut self) {}
+}
+
+/// Forwards decoded blocks to a channel, for embedding zelfm in other apps
+struct ChannelSink {
+    tx: tokio::sync::mpsc::Sender<AudioBlock>,
+}
+
+impl PcmSink for ChannelSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        let block = samples.iter().map(|channel| channel.to_vec()).collect();
+        // Receiver dropped - the embedding app is done listening
+        Ok(self.tx.blocking_send(block).is_ok())
+    }
+}
+
+/// Counts samples when there's nothing to play them on
+struct CountingSink {
+    total_samples: usize,
+}
+
+impl PcmSink for CountingSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        self.total_samples += samples.first().map_or(0, |c| c.len());
+        Ok(true)
+    }
+
+    fn finish(&mut self) {
+        info!("[Listener] Processed {} samples", self.total_samples);
+    }
+}
+
+/// Sample encoding for `--pcm-out`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PcmOutFormat {
+    /// Signed 16-bit little-endian
+    #[default]
+    S16,
+    /// 32-bit float little-endian
+    F32,
+}
+
+/// Writes interleaved little-endian PCM to stdout for piping into other tools
+struct StdoutSink {
+    out: std::io::BufWriter<std::io::Stdout>,
+    format: PcmOutFormat,
+}
+
+impl StdoutSink {
+    fn new(format: PcmOutFormat, stream: StreamFormat) -> Self {
+        // Status goes to stderr; stdout carries nothing but samples
+        info!(
+            "[Listener] PCM out: {}, {} Hz, {} ch, interleaved",
+            match format {
+                PcmOutFormat::S16 => "s16le",
+                PcmOutFormat::F32 => "f32le",
+            },
+            stream.sample_rate,
+            stream.channels
+        );
+        Self {
+            out: std::io::BufWriter::new(std::io::stdout()),
+            format,
+        }
+    }
+}
+
+impl PcmSink for StdoutSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        use std::io::{ErrorKind, Write};
+
+        let mut bytes = Vec::new();
+        for sample in crate::audio_util::planar_to_interleaved(samples) {
+            match self.format {
+                PcmOutFormat::S16 => bytes.extend_from_slice(
+                    &((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes(),
+                ),
+                PcmOutFormat::F32 => bytes.extend_from_slice(&sample.to_le_bytes()),
+            }
+        }
+
+        match self.out.write_all(&bytes) {
+            Ok(()) => Ok(true),
+            // The reading end of the pipe went away; stop quietly
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn finish(&mut self) {
+        use std::io::Write;
+        let _ = self.out.flush();
+    }
+}
+
+/// Feeds each decoded block to several sinks (e.g. speakers and a recording);
+/// a sink that's done is finished and dropped, and listening goes on while
+/// any remain
+struct FanoutSink {
+    sinks: Vec<Box<dyn PcmSink>>,
+}
+
+impl PcmSink for FanoutSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        let mut i = 0;
+        while i < self.sinks.len() {
+            if self.sinks[i].write_block(samples)? {
+                i += 1;
+            } else {
+                self.sinks.remove(i).finish();
+            }
+        }
+        Ok(!self.sinks.is_empty())
+    }
+
+    fn finish(&mut self) {
+        for sink in &mut self.sinks {
+            sink.finish();
+        }
+    }
+}
+
+/// Runs an output on its own thread behind a bounded queue, so a slow one (a
+/// full pipe, a busy disk) drops its own blocks instead of holding up the rest
+struct DetachedSink {
+    name: &'static str,
+    tx: Option<std::sync::mpsc::SyncSender<AudioBlock>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+    dropped: u64,
+}
+
+impl DetachedSink {
+    /// Build the output with `make` on a new thread; errors come back here
+    fn spawn(name: &'static str, make: MakeSink) -> anyhow::Result<Self> {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<AudioBlock>(OUTPUT_QUEUE_BLOCKS);
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name(format!("zelfm-{}", name))
+            .spawn(move || {
+                let mut sink = match make() {
+                    Ok(sink) => {
+                        let _ = ready_tx.send(Ok(()));
+                        sink
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                while let Ok(block) = rx.recv() {
+                    let samples: Vec<&[f32]> = block.iter().map(Vec::as_slice).collect();
+                    match sink.write_block(&samples) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(e) => {
+                            error!("[Listener] {} output failed: {}", name, e);
+                            break;
+                        }
+                    }
+                }
+                sink.finish();
+            })?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow::anyhow!("{} output thread exited", name))??;
+        Ok(Self {
+            name,
+            tx: Some(tx),
+            thread: Some(thread),
+            dropped: 0,
+        })
+    }
+}
+
+impl PcmSink for DetachedSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        use std::sync::mpsc::TrySendError;
+
+        let Some(tx) = &self.tx else {
+            return Ok(false);
+        };
+        let block = samples.iter().map(|channel| channel.to_vec()).collect();
+        match tx.try_send(block) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                if self.dropped.is_power_of_two() {
+                    warn!(
+                        "[Listener] {} output is falling behind, {} blocks dropped",
+                        self.name, self.dropped
+                    );
+                }
+                Ok(true)
+            }
+            // The output stopped on its own (closed pipe, write error)
+            Err(TrySendError::Disconnected(_)) => Ok(false),
+        }
+    }
+
+    fn finish(&mut self) {
+        // Closing the queue lets the thread drain it and finish the output
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if self.dropped > 0 {
+            info!(
+                "[Listener] {} output dropped {} blocks in total",
+                self.name, self.dropped
+            );
+        }
+    }
+}
+
+/// Converts blocks to `inner`'s sample rate (`--output-rate`)
+struct ResampleSink {
+    inner: Box<dyn PcmSink>,
+    resampler: Resampler,
+}
+
+impl ResampleSink {
+    fn new(inner: Box<dyn PcmSink>, resampler: Resampler) -> Self {
+        info!(
+            "[Listener] Resampling {} Hz -> {} Hz",
+            resampler.from_rate(),
+            resampler.to_rate()
+        );
+        Self { inner, resampler }
+    }
+}
+
+impl PcmSink for ResampleSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        let resampled = self.resampler.process(samples);
+        if resampled.first().is_none_or(Vec::is_empty) {
+            return Ok(true);
+        }
+        let resampled: Vec<&[f32]> = resampled.iter().map(Vec::as_slice).collect();
+        self.inner.write_block(&resampled)
+    }
+
+    fn finish(&mut self) {
+        let tail = self.resampler.flush();
+        let tail: Vec<&[f32]> = tail.iter().map(Vec::as_slice).collect();
+        if let Err(e) = self.inner.write_block(&tail) {
+            warn!("[Listener] Couldn't write the resampler's tail: {}", e);
+        }
+        self.inner.finish();
+    }
+}
+
+/// Runs blocks through [`NightMode`] on their way to `inner`
+struct NightModeSink {
+    inner: Box<dyn PcmSink>,
+    night: NightMode,
+    /// Reused copy of each block to process in place
+    block: Vec<Vec<f32>>,
+}
+
+impl PcmSink for NightModeSink {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        self.block.resize_with(samples.len(), Vec::new);
+        for (copy, channel) in self.block.iter_mut().zip(samples) {
+            copy.clear();
+            copy.extend_from_slice(channel);
+        }
+        self.night.process(&mut self.block);
+        let block: Vec<&[f32]> = self.block.iter().map(Vec::as_slice).collect();
+        self.inner.write_block(&block)
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+/// `sink` behind night mode when it's on, at the stream's `sample_rate`
+fn night_mode(sink: Box<dyn PcmSink>, enabled: bool, sample_rate: u32) -> Box<dyn PcmSink> {
+    if !enabled {
+        return sink;
+    }
+    Box::new(NightModeSink {
+        inner: sink,
+        night: NightMode::new(sample_rate),
+        block: Vec::new(),
+    })
+}
+
+/// Passes blocks through to `inner` while handing a decimated mono copy to
+/// the spectrum thread. Never blocks: frames are dropped if the analyzer is busy.
+struct SpectrumTap {
+    inner: Box<dyn PcmSink>,
+    tx: std::sync::mpsc::SyncSender<Vec<f32>>,
+}
+
+impl PcmSink for SpectrumTap {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        if !samples.is_empty() {
+            let frames = samples.iter().map(|c| c.len()).min().unwrap_or(0);
+            let gain = 1.0 / samples.len() as f32;
+            let mono: Vec<f32> = (0..frames)
+                .step_by(DECIMATION)
+                .map(|i| samples.iter().map(|c| c[i]).sum::<f32>() * gain)
+                .collect();
+            let _ = self.tx.try_send(mono);
+        }
+
+        self.inner.write_block(samples)
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+/// Wrap `inner` so a background thread prints a text spectrum of the audio
+fn spectrum_tap(inner: Box<dyn PcmSink>, fft_size: usize) -> anyhow::Result<Box<dyn PcmSink>> {
+    let mut analyzer = SpectrumAnalyzer::new(fft_size)?;
+    let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<f32>>(4);
+
+    std::thread::spawn(move || {
+        use std::io::Write;
+
+        let mut last_draw = std::time::Instant::now();
+        while let Ok(mono) = rx.recv() {
+            if let Some(bins) = analyzer.push(&mono) {
+                // ~10 redraws per second is plenty for a terminal
+                if last_draw.elapsed() >= std::time::Duration::from_millis(100) {
+                    print!("\r[{}] ", render_bars(&bins, 32));
+                    let _ = std::io::stdout().flush();
+                    last_draw = std::time::Instant::now();
+                }
+            }
+        }
+    });
+
+    Ok(Box::new(SpectrumTap { inner, tx }))
+}
+
+/// Build an output for `rate` (the stream's own when `None`), resampling into it
+fn at_rate(
+    make: impl FnOnce(StreamFormat) -> anyhow::Result<Box<dyn PcmSink>>,
+    format: StreamFormat,
+    rate: Option<u32>,
+) -> anyhow::Result<Box<dyn PcmSink>> {
+    let output_format = StreamFormat {
+        sample_rate: rate.unwrap_or(format.sample_rate),
+        ..format
+    };
+    let sink = make(output_format)?;
+    if output_format.sample_rate == format.sample_rate {
+        return Ok(sink);
+    }
+    let resampler = Resampler::new(
+        format.sample_rate,
+        output_format.sample_rate,
+        format.channels as usize,
+    )?;
+    Ok(Box::new(ResampleSink::new(sink, resampler)))
+}
+
+/// Shown the first time a listen wants speakers this build can't drive
+static NO_PLAYBACK_NOTICE: std::sync::Once = std::sync::Once::new();
+
+/// The default output: speakers, or a sample counter without `playback` or
+/// an output device (headless servers can still record the stream)
+fn default_output(format: StreamFormat) -> anyhow::Result<Box<dyn PcmSink>> {
+    #[cfg(feature = "playback")]
+    {
+        match AudioPlayer::new(format.sample_rate, format.channels) {
+            Ok(player) => {
+                info!("[Listener] Output ready, buffering...");
+                Ok(Box::new(player))
+            }
+            Err(e) => {
+                warn!("[Listener] No audio output: {}", e);
+                eprintln!(
+                    "Warning: no audio output device ({}); continuing without playback. \
+                     Use --record or --pcm-out to keep the audio.",
+                    e
+                );
+                Ok(Box::new(CountingSink { total_samples: 0 }))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "playback"))]
+    {
+        let _ = format;
+        info!("[Listener] Playback disabled, counting samples...");
+        Ok(Box::new(CountingSink { total_samples: 0 }))
+    }
+}
+
 pub struct RadioListener {
     client: RadioServiceClient,
+    spectrum_fft_size: Option<usize>,
+    recording: Option<(PathBuf, RecordFormat)>,
+    pcm_out: Option<PcmOutFormat>,
+    /// Play through speakers (default: unless `pcm_out` is set)
+    playback: Option<bool>,
+    /// Also deliver decoded blocks here
+    pcm_channel: Option<tokio::sync::mpsc::Sender<AudioBlock>>,
+    /// Resample playback and PCM-out to this rate
+    output_rate: Option<u32>,
+    /// Compress and limit playback and PCM-out
+    night_mode: bool,
+    /// Receive path sizing; see [`RadioListener::with_read_chunk`]
+    read_chunk: Option<usize>,
+    recv_queue: Option<usize>,
+    rewind: Option<u32>,
+    /// Vorbis quality to ask the station for
+    quality: Option<f32>,
+    /// Node connected to, for verifying its signed station info
+    station_id: Option<iroh::PublicKey>,
 }
 
 impl RadioListener {
     pub fn new(client: RadioServiceClient) -> Self {
-        Self { client }
+        Self {
+            client,
+            spectrum_fft_size: None,
+            recording: None,
+            pcm_out: None,
+            playback: None,
+            pcm_channel: None,
+            output_rate: None,
+            night_mode: false,
+            read_chunk: None,
+            recv_queue: None,
+            rewind: None,
+            quality: None,
+            station_id: None,
+        }
+    }
+
+    /// Verify station info against this node ID (the one connected to)
+    pub fn with_station_id(mut self, station_id: iroh::PublicKey) -> Self {
+        self.station_id = Some(station_id);
+        self
+    }
+
+    /// Save the stream to `path` while listening
+    pub fn with_recording(mut self, path: impl Into<PathBuf>, format: RecordFormat) -> Self {
+        self.recording = Some((path.into(), format));
+        self
+    }
+
+    /// Start `seconds` behind live using the station's rewind buffer
+    pub fn with_rewind(mut self, seconds: u32) -> Self {
+        self.rewind = Some(seconds);
+        self
+    }
+
+    /// Ask for a custom Vorbis quality within the station's `quality_range`
+    pub fn with_quality(mut self, quality: f32) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Show a live text spectrum while playing, using an FFT of `fft_size` points
+    pub fn with_spectrum(mut self, fft_size: usize) -> Self {
+        self.spectrum_fft_size = Some(fft_size);
+        self
+    }
+
+    /// The station's info, retried a few times (with a timeout on each try)
+    /// so a slow or still-starting station doesn't end the listen
+    pub async fn fetch_info(&self) -> anyhow::Result<StationInfo> {
+        let mut delay = INFO_RETRY_DELAY;
+        for attempt in 1..=INFO_ATTEMPTS {
+            let error = match tokio::time::timeout(INFO_TIMEOUT, self.client.get_info()).await {
+                Ok(Ok(info)) => return Ok(info),
+                Ok(Err(e)) => RadioError::describe(&e),
+                Err(_) => format!("no answer after {}s", INFO_TIMEOUT.as_secs()),
+            };
+            if attempt == INFO_ATTEMPTS {
+                anyhow::bail!(
+                    "Couldn't get station info after {} tries: {}",
+                    INFO_ATTEMPTS,
+                    error
+                );
+            }
+            warn!(
+                "[Listener] Station info failed ({}), retrying in {} ms ({}/{})",
+                error,
+                delay.as_millis(),
+                attempt,
+                INFO_ATTEMPTS
+            );
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        unreachable!("the last attempt returns")
     }
 
     pub async fn get_station_info(&self) -> anyhow::Result<()> {
-        let info = self.client.get_info().await?;
+        let mut info = self.fetch_info().await?;
+        let mut relaying = None;
+        let verified = match self.station_id {
+            Some(station_id) if info.supports(SIGNING_CONTEXT_VERSION) => {
+                match self.verified_info(&station_id).await {
+                    Ok((signed, upstream)) => {
+                        info = signed;
+                        relaying = upstream;
+                        "yes (signed by the station's node key)".to_string()
+                    }
+                    Err(e) => {
+                        warn!("[Listener] Station info failed verification: {}", e);
+                        format!("NO ({})", e)
+                    }
+                }
+            }
+            Some(_) if info.supports(SIGNED_INFO_VERSION) => {
+                "no (station signs its info in an older format)".to_string()
+            }
+            _ => "no (station doesn't sign its info)".to_string(),
+        };
         println!("\n=== Station Info ===");
         println!("Name: {}", info.name);
+        println!("Verified: {}", verified);
+        if let Some(relaying) = relaying {
+            println!("Relaying: {}", relaying);
+        }
         println!("Description: {}", info.description);
+        if info.supports(CODEC_VERSION) {
+            println!("Codec: {}", info.codec);
+        }
         println!("Bitrate: {} kbps", info.bitrate / 1000);
+        if info.supports(ENCODED_BITRATE_VERSION) {
+            if let Ok(levels) = self.client.get_levels().await {
+                if levels.encoded_bitrate > 0 {
+                    println!(
+                        "Achieved Bitrate: {} kbps (last 10s)",
+                        levels.encoded_bitrate / 1000
+                    );
+                }
+            }
+        }
         println!("Sample Rate: {} Hz", info.sample_rate);
         println!("Channels: {}", info.channels);
         println!("Listeners: {}", info.listeners);
+        if let Some(genre) = &info.genre {
+            println!("Genre: {}", genre);
+        }
+        if !info.tags.is_empty() {
+            println!("Tags: {}", info.tags.join(", "));
+        }
+        if let Some(website) = &info.website {
+            println!("Website: {}", website);
+        }
+        if let Some((min, max)) = info.quality_range {
+            println!("Quality: choose {} to {} (listen --quality <q>)", min, max);
+        }
+        if info.rewind_secs > 0 {
+            println!(
+                "Rewind: up to {}s (listen --rewind <secs>)",
+                info.rewind_secs
+            );
+        }
+
+        if info.protocol_version > PROTOCOL_VERSION {
+            println!(
+                "Note: station speaks protocol v{} (this listener is v{}); newer features are hidden",
+                info.protocol_version, PROTOCOL_VERSION
+            );
+        } else if !info.supports(PROTOCOL_VERSION) {
+            println!("Note: station runs an older zelfm; some features may be unavailable");
+        }
+
+        // Older broadcasters don't expose capabilities
+        if let Ok(caps) = self.client.capabilities().await {
+            println!("Source Codec: {} ({})", caps.codec, caps.sample_format);
+            println!(
+                "Seekable: {}",
+                if caps.seekable { "yes" } else { "no (live)" }
+            );
+        }
         println!("====================\n");
         Ok(())
     }
 
+    /// The station's signed info, and a description of the upstream it
+    /// relays (if it passed one through)
+    async fn verified_info(
+        &self,
+        station_id: &iroh::PublicKey,
+    ) -> anyhow::Result<(StationInfo, Option<String>)> {
+        let signed = self
+            .client
+            .get_signed_info()
+            .await
+            .map_err(|e| anyhow::anyhow!(RadioError::describe(&e)))?;
+        let info = signed.verify(station_id)?;
+        let upstream = signed.verify_upstream().map(|upstream| match upstream {
+            Ok((station, upstream)) => format!("'{}' (signed by {})", upstream.name, station),
+            Err(e) => format!("unverified upstream ({})", e),
+        });
+        Ok((info, upstream))
+    }
+
+    /// Listen and play through the default output device
+    ///
+    /// Every configured output (speakers, PCM-out, a recording, a channel)
+    /// gets each decoded block. With more than one, each runs on its own
+    /// thread, so a slow output drops blocks rather than stalling the others.
     pub async fn listen(&self, duration_secs: Option<u64>) -> anyhow::Result<()> {
+        let spectrum_fft_size = self.spectrum_fft_size;
+        let recording = self.recording.clone();
+        let pcm_out = self.pcm_out;
+        let playback = self.playback.unwrap_or(pcm_out.is_none());
+        if playback && !cfg!(feature = "playback") {
+            NO_PLAYBACK_NOTICE.call_once(|| {
+                eprintln!(
+                    "Built without playback support; audio will not be played. \
+                     Rebuild with --features playback or use --pcm-out/--record."
+                )
+            });
+        }
+        let pcm_channel = self.pcm_channel.clone();
+        let output_rate = self.output_rate;
+        let night = self.night_mode;
+
+        self.decode_stream(duration_secs, move |format| {
+            let mut outputs: Vec<(&'static str, MakeSink)> = Vec::new();
+
+            // Playback and PCM-out run at the requested rate, through night
+            // mode when it's on; recordings and the channel keep the station's
+            // audio as sent
+            if playback {
+                outputs.push((
+                    "speakers",
+                    Box::new(move || {
+                        let sink = at_rate(default_output, format, output_rate)?;
+                        Ok(night_mode(sink, night, format.sample_rate))
+                    }),
+                ));
+            }
+            if let Some(pcm_format) = pcm_out {
+                outputs.push((
+                    "pcm-out",
+                    Box::new(move || {
+                        let sink = at_rate(
+                            |format| Ok(Box::new(StdoutSink::new(pcm_format, format))),
+                            format,
+                            output_rate,
+                        )?;
+                        Ok(night_mode(sink, night, format.sample_rate))
+                    }),
+                ));
+            }
+            // OGG recordings copy pages as they arrive and take no decoded audio
+            if let Some((path, record_format)) = recording {
+                if record_format != RecordFormat::Ogg {
+                    outputs.push((
+                        "recording",
+                        Box::new(move || {
+                            pcm_recorder(&path, record_format, format)?
+                                .ok_or_else(|| anyhow::anyhow!("No PCM recorder for this format"))
+                        }),
+                    ));
+                }
+            }
+            if let Some(tx) = pcm_channel {
+                outputs.push((
+                    "channel",
+                    Box::new(move || Ok(Box::new(ChannelSink { tx }))),
+                ));
+            }
+
+            let sink: Box<dyn PcmSink> = match outputs.len() {
+                0 => Box::new(CountingSink { total_samples: 0 }),
+                1 => {
+                    let (_, make) = outputs.pop().expect("one output");
+                    make()?
+                }
+                _ => Box::new(FanoutSink {
+                    sinks: outputs
+                        .into_iter()
+                        .map(|(name, make)| {
+                            DetachedSink::spawn(name, make).map(|s| Box::new(s) as Box<dyn PcmSink>)
+                        })
+                        .collect::<anyhow::Result<_>>()?,
+                }),
+            };
+
+            match spectrum_fft_size {
+                Some(fft_size) => spectrum_tap(sink, fft_size),
+                None => Ok(sink),
+            }
+        })
+        .await
+    }
+
+    /// Write decoded PCM to stdout instead of the output device
+    pub fn with_pcm_out(mut self, format: PcmOutFormat) -> Self {
+        self.pcm_out = Some(format);
+        self
+    }
+
+    /// Play through the output device or not, whatever other outputs are set
+    pub fn with_playback(mut self, enabled: bool) -> Self {
+        self.playback = Some(enabled);
+        self
+    }
+
+    /// Also deliver decoded blocks to `tx`, at the station's rate, alongside
+    /// the other outputs
+    pub fn with_pcm_channel(mut self, tx: tokio::sync::mpsc::Sender<AudioBlock>) -> Self {
+        self.pcm_channel = Some(tx);
+        self
+    }
+
+    /// Resample playback and PCM-out to `sample_rate`, whatever the station's rate
+    pub fn with_output_rate(mut self, sample_rate: u32) -> Self {
+        self.output_rate = Some(sample_rate);
+        self
+    }
+
+    /// Even out loudness on playback and PCM-out with gentle compression and
+    /// a peak limiter (see [`NightMode`]); recordings are left as sent
+    pub fn with_night_mode(mut self) -> Self {
+        self.night_mode = true;
+        self
+    }
+
+    /// Read at most `bytes` from the stream at a time (default: from the
+    /// station's bitrate, see [`receive_buffers`])
+    ///
+    /// A read returns as soon as any data arrives, so a smaller chunk doesn't
+    /// get audio out sooner; it only means more, smaller reads. Larger chunks
+    /// keep high-bitrate FLAC and PCM streams from being read piecemeal.
+    pub fn with_read_chunk(mut self, bytes: usize) -> Self {
+        self.read_chunk = Some(bytes.max(1));
+        self
+    }
+
+    /// Queue at most `chunks` received chunks for the decoder (default: from
+    /// the station's bitrate, see [`receive_buffers`])
+    ///
+    /// The queue only fills when decoding falls behind; a full one stops
+    /// reading, and QUIC flow control then holds the station back. It sits
+    /// ahead of the player's jitter buffer ([`crate::audio_player::PREBUFFER`]
+    /// of decoded audio, which absorbs network hiccups): a deep queue rides
+    /// out decoder stalls at the cost of audio piling up behind them, and a
+    /// shallow one keeps latency down for setups where decoding keeps pace.
+    pub fn with_recv_queue(mut self, chunks: usize) -> Self {
+        self.recv_queue = Some(chunks.max(1));
+        self
+    }
+
+    /// Listen and deliver decoded planar PCM blocks to `sink` instead of playing them.
+    ///
+    /// Blocks use the station's sample rate and channel count (see `get_info`).
+    /// Listening stops when `sink`'s receiver is dropped or `duration_secs` elapses.
+    pub async fn listen_with_sink(
+        &self,
+        sink: tokio::sync::mpsc::Sender<AudioBlock>,
+        duration_secs: Option<u64>,
+    ) -> anyhow::Result<()> {
+        self.decode_stream(duration_secs, move |_format| {
+            Ok(Box::new(ChannelSink { tx: sink }) as Box<dyn PcmSink>)
+        })
+        .await
+    }
+
+    /// Open the audio stream, decode it, and feed every block to the sink built by `make_sink`
+    async fn decode_stream<F>(&self, duration_secs: Option<u64>, make_sink: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(StreamFormat) -> anyhow::Result<Box<dyn PcmSink>> + Send + 'static,
+    {
         info!("[Listener] Connecting...");
 
-        let (_send, mut recv) = self.client.listen().await?;
+        // Older stations don't say, and only stream Vorbis
+        let (codec, sample_rate, bitrate, adaptive) = match self.fetch_info().await {
+            Ok(info) => (
+                info.codec,
+                info.sample_rate,
+                info.bitrate,
+                info.supports(ADAPTIVE_VERSION),
+            ),
+            Err(e) => {
+                warn!("[Listener] {}; assuming a Vorbis stream", e);
+                (StreamCodec::Vorbis, 44100, 128000, false)
+            }
+        };
+        let (auto_chunk, auto_queue) = receive_buffers(bitrate);
+        let read_chunk = self.read_chunk.unwrap_or(auto_chunk);
+        let recv_queue = self.recv_queue.unwrap_or(auto_queue);
+        info!(
+            "[Listener] Receive buffers: {} byte reads, {} chunks queued",
+            read_chunk, recv_queue
+        );
+
+        let requested_at = std::time::Instant::now();
+        let stream = match (self.rewind, self.quality) {
+            (Some(seconds), _) => self.client.listen_from(seconds).await,
+            (None, Some(quality)) => self.client.listen_at(quality).await,
+            // Chained links from quality changes are handled by ChannelReader
+            (None, None) if adaptive => self.client.listen_adaptive().await,
+            (None, None) => self.client.listen().await,
+        };
+        let (mut send, mut recv) = stream.map_err(|e| anyhow::anyhow!(RadioError::describe(&e)))?;
 
         info!("[Listener] Stream opened, buffering OGG data...");
 
-        // Spawn a task to collect streaming data
-        // Small buffer (10 chunks = ~80KB = ~5 seconds at 128kbps) for responsive shutdown
-        let (data_tx, data_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+        // Spawn a task to collect streaming data; a short queue keeps shutdown
+        // responsive
+        let (data_tx, data_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(recv_queue);
+
+        // OGG recording is a straight copy of the received bytes
+        let mut ogg_file = match &self.recording {
+            Some((path, RecordFormat::Ogg)) => {
+                match codec {
+                    StreamCodec::Vorbis => info!("[Record] Writing OGG to {}", path.display()),
+                    StreamCodec::Flac => info!(
+                        "[Record] Writing the station's FLAC stream to {}",
+                        path.display()
+                    ),
+                    StreamCodec::Pcm => info!(
+                        "[Record] Writing the station's PCM frames to {}",
+                        path.display()
+                    ),
+                }
+                Some(tokio::fs::File::create(path).await?)
+            }
+            _ => None,
+        };
 
+        // Stops the receive task between reads, however decoding ends
+        let stop = CancellationToken::new();
+        let _stop_on_exit = stop.clone().drop_guard();
+
+        let recv_stop = stop.clone();
         let recv_task = tokio::spawn(async move {
-            let mut chunk = vec![0u8; 8192];
+            use tokio::io::AsyncWriteExt;
+
+            let mut chunk = vec![0u8; read_chunk];
+            let mut first_data = true;
             loop {
-                match recv.read(&mut chunk).await {
+                let read = tokio::select! {
+                    // Reads are cancel-safe: nothing is lost by dropping one
+                    _ = recv_stop.cancelled() => {
+                        // Tell the station we're done so it closes the stream
+                        // now instead of waiting out its stall timeout
+                        let _ = recv.stop(iroh::endpoint::VarInt::from_u32(STOP_LISTENER_DONE));
+                        break;
+                    }
+                    read = recv.read(&mut chunk) => read,
+                };
+                match read {
                     Ok(Some(n)) => {
+                        if first_data {
+                            first_data = false;
+                            info!(
+                                "[Listener] First stream data after {} ms",
+                                requested_at.elapsed().as_millis()
+                            );
+                        }
+                        if let Some(file) = &mut ogg_file {
+                            if let Err(e) = file.write_all(&chunk[..n]).await {
+                                error!("[Record] Write failed, recording stopped: {}", e);
+                                ogg_file = None;
+                            }
+                        }
                         if data_tx.send(chunk[..n].to_vec()).await.is_err() {
                             break;
                         }
                     }
-                    Ok(None) => break,
-                    Err(_) => break,
+                    Ok(None) => {
+                        eprintln!("\nStream ended by the station");
+                        break;
+                    }
+                    Err(iroh::endpoint::ReadError::Reset(code)) => {
+                        let reason = reset_reason(code.into_inner());
+                        info!("[Listener] Stream reset: {}", reason);
+                        eprintln!("\nDisconnected: {}", reason);
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("\nDisconnected: {}", e);
+                        break;
+                    }
                 }
             }
         });
 
-        // Decode and play in blocking task
+        // Decode in blocking task
         let result = tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
-            // Create a streaming reader that pulls from the channel
-            struct ChannelReader {
-                rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
-                buffer: Vec<u8>,
-                position: usize,
-            }
-
-            impl ChannelReader {
-                fn new(rx: tokio::sync::mpsc::Receiver<Vec<u8>>) -> Self {
-                    Self {
-                        rx,
-                        buffer: Vec::new(),
-                        position: 0,
-                    }
+            let mut reader = ChannelReader::new(data_rx, codec == StreamCodec::Vorbis);
+            match codec {
+                StreamCodec::Flac => return decode_flac(reader, make_sink, duration_secs),
+                StreamCodec::Pcm => {
+                    return decode_pcm(reader, sample_rate, make_sink, duration_secs)
                 }
+                StreamCodec::Vorbis => {}
             }
 
-            impl std::io::Read for ChannelReader {
-                fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-                    // Fill from current buffer first
-                    if self.position < self.buffer.len() {
-                        let available = self.buffer.len() - self.position;
-                        let to_copy = available.min(buf.len());
-                        buf[..to_copy]
-                            .copy_from_slice(&self.buffer[self.position..self.position + to_copy]);
-                        self.position += to_copy;
-                        return Ok(to_copy);
+            let mut make_sink = Some(make_sink);
+            let mut sink: Option<(StreamFormat, Box<dyn PcmSink>)> = None;
+            let start = std::time::Instant::now();
+
+            'links: loop {
+                let mut decoder = VorbisDecoder::new(&mut reader)?;
+                let format = StreamFormat {
+                    sample_rate: decoder.sampling_frequency().get(),
+                    channels: decoder.channels().get(),
+                };
+
+                match &sink {
+                    Some((first, _)) if *first != format => anyhow::bail!(
+                        "Stream format changed from {} Hz, {} ch to {} Hz, {} ch",
+                        first.sample_rate,
+                        first.channels,
+                        format.sample_rate,
+                        format.channels
+                    ),
+                    Some(_) => info!("[Listener] Station switched stream quality"),
+                    None => {
+                        info!(
+                            "[Listener] Format: {} Hz, {} ch",
+                            format.sample_rate, format.channels
+                        );
+                        let make_sink = make_sink.take().expect("sink is made once");
+                        sink = Some((format, make_sink(format)?));
+                    }
+                }
+                let (_, output) = sink.as_mut().expect("sink made above");
+
+                while let Some(samples) = decoder.decode_audio_block()? {
+                    if !output.write_block(samples.samples())? {
+                        break 'links;
                     }
 
-                    // Need more data from channel
-                    match self.rx.blocking_recv() {
-                        Some(chunk) => {
-                            self.buffer = chunk;
-                            self.position = 0;
-                            self.read(buf) // Try again with new buffer
+                    if let Some(max) = duration_secs {
+                        if start.elapsed().as_secs() >= max {
+                            break 'links;
                         }
-                        None => Ok(0), // EOF
                     }
                 }
+
+                drop(decoder);
+                if !reader.next_link() {
+                    break;
+                }
             }
 
-            let reader = ChannelReader::new(data_rx);
-            let mut decoder = VorbisDecoder::new(reader)?;
+            if let Some((_, sink)) = &mut sink {
+                sink.finish();
+            }
 
-            let sample_rate = decoder.sampling_frequency().get();
-            let channels = decoder.channels().get();
-            info!("[Listener] Format: {} Hz, {} ch", sample_rate, channels);
+            Ok(())
+        })
+        .await;
 
-            #[cfg(feature = "playback")]
-            {
-                let mut player = AudioPlayer::new(sample_rate, channels)?;
-                info!("[Listener] Playing...");
+        stop.cancel();
+        let _ = send.finish();
+        if tokio::time::timeout(RECV_SHUTDOWN_TIMEOUT, recv_task)
+            .await
+            .is_err()
+        {
+            warn!("[Listener] Receive task didn't stop in time");
+        }
 
-                let start = std::time::Instant::now();
+        result?
+    }
+}
 
-                while let Some(samples) = decoder.decode_audio_block()? {
-                    player.play_samples(samples.samples())?;
+/// Header-type flag marking the first page of a logical stream
+const BEGIN_OF_STREAM: u8 = 0x02;
 
-                    if let Some(max) = duration_secs {
-                        if start.elapsed().as_secs() >= max {
-                            break;
-                        }
-                    }
-                }
+/// A streaming reader that pulls pages from the channel, ending each link of
+/// a chained stream (the station switches links when it changes quality) as
+/// its own EOF. Unpaged (FLAC, PCM) streams pass chunks through whole.
+struct ChannelReader {
+    rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    paged: bool,
+    splitter: PageSplitter,
+    pages: VecDeque<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+    /// The current link's first page has been read
+    link_started: bool,
+    /// Stopped at the next link's first page
+    link_ended: bool,
+}
+
+impl ChannelReader {
+    fn new(rx: tokio::sync::mpsc::Receiver<Vec<u8>>, paged: bool) -> Self {
+        Self {
+            rx,
+            paged,
+            splitter: PageSplitter::default(),
+            pages: VecDeque::new(),
+            buffer: Vec::new(),
+            position: 0,
+            link_started: false,
+            link_ended: false,
+        }
+    }
+
+    /// Move past the end of a link; false at the end of the whole stream
+    fn next_link(&mut self) -> bool {
+        let more = self.link_ended;
+        self.link_started = false;
+        self.link_ended = false;
+        more
+    }
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Fill from current page first
+        if self.position < self.buffer.len() {
+            let available = self.buffer.len() - self.position;
+            let to_copy = available.min(buf.len());
+            buf[..to_copy].copy_from_slice(&self.buffer[self.position..self.position + to_copy]);
+            self.position += to_copy;
+            return Ok(to_copy);
+        }
+        if self.link_ended {
+            return Ok(0);
+        }
 
-                player.finish();
+        // Need another page, from the channel if none are waiting
+        while self.pages.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) if self.paged => self.pages.extend(self.splitter.push(&chunk)),
+                Some(chunk) => self.pages.push_back(chunk),
+                None => return Ok(0), // EOF
             }
+        }
+        let page = self.pages.pop_front().unwrap();
+        let begins_link = self.paged
+            && page
+                .get(5)
+                .is_some_and(|&flags| flags & BEGIN_OF_STREAM != 0);
+        if begins_link && self.link_started {
+            self.pages.push_front(page);
+            self.link_ended = true;
+            return Ok(0);
+        }
+        self.link_started = true;
+        self.buffer = page;
+        self.position = 0;
+        self.read(buf) // Try again with new page
+    }
+}
 
-            #[cfg(not(feature = "playback"))]
-            {
-                info!("[Listener] Playback disabled, counting samples...");
+/// Unpack a framed PCM stream (see [`crate::pcm_frame`]) into the sink built
+/// by `make_sink`, reporting blocks that went missing on the way
+fn decode_pcm<R, F>(
+    reader: R,
+    sample_rate: u32,
+    make_sink: F,
+    duration_secs: Option<u64>,
+) -> anyhow::Result<()>
+where
+    R: std::io::Read,
+    F: FnOnce(StreamFormat) -> anyhow::Result<Box<dyn PcmSink>>,
+{
+    let mut frames = PcmFrameReader::new(reader);
+    let Some(first) = frames.next_frame()? else {
+        return Ok(());
+    };
+    let format = StreamFormat {
+        sample_rate,
+        channels: first.samples.len() as u8,
+    };
+    check_format(format.sample_rate, format.channels as usize)?;
+    info!(
+        "[Listener] Format: {} Hz, {} ch (PCM)",
+        format.sample_rate, format.channels
+    );
 
-                let mut total_samples = 0;
-                let start = std::time::Instant::now();
+    let mut sink = make_sink(format)?;
+    let start = std::time::Instant::now();
+    let mut next = Some(first);
 
-                while let Some(samples) = decoder.decode_audio_block()? {
-                    total_samples += samples.samples()[0].len();
+    while let Some(frame) = next {
+        if frame.dropped > 0 {
+            warn!(
+                "[Listener] {} PCM blocks missing before #{} ({} so far)",
+                frame.dropped, frame.sequence, frames.dropped
+            );
+        }
+        if frame.samples.len() != format.channels as usize {
+            warn!(
+                "[Listener] Skipping PCM frame #{} with {} channels",
+                frame.sequence,
+                frame.samples.len()
+            );
+        } else {
+            let samples: Vec<&[f32]> = frame.samples.iter().map(Vec::as_slice).collect();
+            if !sink.write_block(&samples)? {
+                break;
+            }
+        }
 
-                    if let Some(max) = duration_secs {
-                        if start.elapsed().as_secs() >= max {
-                            break;
-                        }
-                    }
-                }
+        if let Some(max) = duration_secs {
+            if start.elapsed().as_secs() >= max {
+                break;
+            }
+        }
+        next = frames.next_frame()?;
+    }
+
+    if frames.dropped > 0 || frames.skipped_bytes > 0 {
+        info!(
+            "[Listener] PCM stream: {} blocks missing, {} bytes skipped resyncing",
+            frames.dropped, frames.skipped_bytes
+        );
+    }
+    sink.finish();
+    Ok(())
+}
+
+/// Decode a native FLAC stream into the sink built by `make_sink`
+fn decode_flac<R, F>(reader: R, make_sink: F, duration_secs: Option<u64>) -> anyhow::Result<()>
+where
+    R: std::io::Read + Send + Sync + 'static,
+    F: FnOnce(StreamFormat) -> anyhow::Result<Box<dyn PcmSink>>,
+{
+    use symphonia::core::audio::Signal;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::errors::Error as SymphoniaError;
+    use symphonia::core::formats::{FormatOptions, FormatReader};
+    use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
 
-                info!("[Listener] Processed {} samples", total_samples);
+    let source = MediaSourceStream::new(Box::new(ReadOnlySource::new(reader)), Default::default());
+    let mut stream =
+        symphonia::default::formats::FlacReader::try_new(source, &FormatOptions::default())?;
+    let params = stream
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("FLAC stream has no audio track"))?
+        .codec_params
+        .clone();
+    let format = StreamFormat {
+        sample_rate: params.sample_rate.unwrap_or(44100),
+        channels: params.channels.map_or(2, |c| c.count() as u8),
+    };
+    check_format(format.sample_rate, format.channels as usize)?;
+    info!(
+        "[Listener] Format: {} Hz, {} ch (FLAC)",
+        format.sample_rate, format.channels
+    );
+
+    let mut decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+    let mut sink = make_sink(format)?;
+    let mut planar_buf = crate::audio_util::PlanarDecodeBuffer::default();
+    let start = std::time::Instant::now();
+
+    loop {
+        let packet = match stream.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
             }
+            Err(e) => return Err(e.into()),
+        };
+        let decoded = match decoder.decode(&packet) {
+            Ok(buf) => buf,
+            Err(SymphoniaError::DecodeError(e)) => {
+                warn!("[Listener] Skipping bad FLAC frame: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-            Ok(())
+        // The sink only borrows the block, so read it straight from the decoder
+        let buf = planar_buf.as_f32(&decoded);
+        let samples: Vec<&[f32]> = (0..buf.spec().channels.count())
+            .map(|channel| buf.chan(channel))
+            .collect();
+        if !sink.write_block(&samples)? {
+            break;
+        }
+
+        if let Some(max) = duration_secs {
+            if start.elapsed().as_secs() >= max {
+                break;
+            }
+        }
+    }
+
+    sink.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::num::{NonZeroU32, NonZeroU8};
+
+    /// A short OGG Vorbis stream, as one link of a chained stream
+    fn link(serial: i32, quality: f32) -> Vec<u8> {
+        let tone: Vec<f32> = (0..4410).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let mut out = Vec::new();
+        let mut encoder = vorbis_rs::VorbisEncoderBuilder::new_with_serial(
+            NonZeroU32::new(44100).unwrap(),
+            NonZeroU8::new(1).unwrap(),
+            &mut out,
+            serial,
+        )
+        .bitrate_management_strategy(vorbis_rs::VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: quality,
         })
-        .await??;
+        .build()
+        .unwrap();
+        encoder.encode_audio_block([&tone[..]]).unwrap();
+        encoder.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn chained_links_read_as_separate_streams() {
+        let first = link(1, 0.5);
+        let second = link(2, -0.1);
+
+        // Chunk boundaries that don't line up with pages or links
+        let mut chained = first.clone();
+        chained.extend_from_slice(&second);
+        let (tx, rx) = tokio::sync::mpsc::channel(chained.len());
+        for chunk in chained.chunks(700) {
+            tx.try_send(chunk.to_vec()).unwrap();
+        }
+        drop(tx);
+
+        let mut reader = ChannelReader::new(rx, true);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, first);
 
-        recv_task.abort();
+        assert!(reader.next_link());
+        let mut decoder = VorbisDecoder::new(&mut reader).unwrap();
+        let mut frames = 0;
+        while let Some(block) = decoder.decode_audio_block().unwrap() {
+            frames += block.samples()[0].len();
+        }
+        assert_eq!(frames, 4410);
+        drop(decoder);
 
-        Ok(result)
+        assert!(!reader.next_link());
     }
 }
@@ -0,0 +1,150 @@
+//! Logger setup: timestamped `env_logger` output, optionally teed to a rotating file.
+//!
+//! `RUST_LOG` still controls verbosity. Without it, stderr shows errors only,
+//! while `--log-file` raises the default to `info` so long-running stations
+//! keep a record of connects, track changes, and encoder problems.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Rotate once the active log file reaches this size
+pub const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept alongside the active one (`zelfm.log.1` is the newest)
+pub const KEEP_LOG_FILES: usize = 5;
+
+/// Initialize the global logger
+pub fn init(log_file: Option<&Path>) -> anyhow::Result<()> {
+    let mut builder = env_logger::Builder::new();
+    builder.format_timestamp_millis();
+
+    match log_file {
+        Some(path) => {
+            let file = RotatingFile::open(path)
+                .map_err(|e| anyhow::anyhow!("Can't open log file {}: {}", path.display(), e))?;
+            builder
+                .filter_level(log::LevelFilter::Info)
+                .parse_default_env()
+                .target(env_logger::Target::Pipe(Box::new(Tee { file })));
+        }
+        None => {
+            builder
+                .filter_level(log::LevelFilter::Error)
+                .parse_default_env();
+        }
+    }
+
+    builder.try_init()?;
+    Ok(())
+}
+
+/// Writes every record to stderr and the log file
+struct Tee {
+    file: RotatingFile,
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Append-only log file that rolls over to numbered backups by size
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    /// Size that triggers a rotation ([`MAX_LOG_BYTES`])
+    max_bytes: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes: MAX_LOG_BYTES,
+        })
+    }
+
+    fn backup(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for n in (1..KEEP_LOG_FILES).rev() {
+            let from = self.backup(n);
+            if from.exists() {
+                std::fs::rename(&from, self.backup(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.backup(1))?;
+
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > self.max_bytes && self.written > 0 {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_log_rolls_over_and_keeps_a_bounded_history() {
+        let dir = std::env::temp_dir().join(format!("zelfm-logs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("zelfm.log");
+
+        let mut log = RotatingFile::open(&path).unwrap();
+        log.max_bytes = 10;
+        for n in 0..KEEP_LOG_FILES + 3 {
+            log.write_all(format!("line {:04}\n", n).as_bytes())
+                .unwrap();
+        }
+        log.flush().unwrap();
+
+        // Each 10-byte line fills a file, so the newest is active and the
+        // ones before it shift down the backups
+        let last = KEEP_LOG_FILES + 2;
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(path.clone()), format!("line {:04}\n", last));
+        assert_eq!(read(log.backup(1)), format!("line {:04}\n", last - 1));
+        assert_eq!(
+            read(log.backup(KEEP_LOG_FILES)),
+            format!("line {:04}\n", last - KEEP_LOG_FILES)
+        );
+        assert!(!log.backup(KEEP_LOG_FILES + 1).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+//! In-process station for tests: a broadcaster served on a loopback-only iroh
+//! endpoint, with a client connected to it directly.
+//!
+//! `zel_core` clients run over a real iroh [`Connection`], so there's no
+//! in-memory transport to swap in. Instead both endpoints bind `127.0.0.1` in
+//! LAN-only mode (no relays, no discovery) and the client dials the server's
+//! bound socket, so RPCs never leave the machine. Enabled for this crate's own
+//! tests and, for embedders, by the `test-util` feature.
+
+use iroh::endpoint::Connection;
+use iroh::{Endpoint, EndpointAddr};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zel_core::protocol::client::RpcClient;
+use zel_core::protocol::{Extensions, RpcServerBuilder};
+use zel_core::IrohBundle;
+
+use crate::broadcaster::RadioBroadcaster;
+use crate::network::{self, NetworkOptions};
+use crate::service::{ListenerInfo, RadioServiceClient, RadioServiceServer, ALPN};
+
+/// Network options that keep an endpoint on the loopback interface
+pub fn options() -> NetworkOptions {
+    NetworkOptions {
+        relay_urls: Vec::new(),
+        lan_only: true,
+        bind: Some(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))),
+        identity: None,
+    }
+}
+
+/// A served broadcaster and one client connected to it
+///
+/// Keep this alive for the duration of the test: dropping either bundle
+/// closes its endpoint.
+pub struct LoopbackStation {
+    pub server: IrohBundle,
+    pub client_bundle: IrohBundle,
+    pub client: RadioServiceClient,
+}
+
+impl LoopbackStation {
+    /// Serve `broadcaster` and connect a client to it
+    pub async fn start(broadcaster: RadioBroadcaster) -> anyhow::Result<Self> {
+        let server = serve(broadcaster).await?;
+        let (client_bundle, connection) = connect(&server.endpoint, ALPN).await?;
+        let client = RadioServiceClient::new(RpcClient::new(connection).await?);
+        Ok(Self {
+            server,
+            client_bundle,
+            client,
+        })
+    }
+
+    /// Another client on its own endpoint, as a second listener would have
+    pub async fn connect_client(&self) -> anyhow::Result<(IrohBundle, RadioServiceClient)> {
+        let (bundle, connection) = connect(&self.server.endpoint, ALPN).await?;
+        Ok((
+            bundle,
+            RadioServiceClient::new(RpcClient::new(connection).await?),
+        ))
+    }
+}
+
+/// Serve `broadcaster` on a loopback endpoint, assigning listener IDs the way
+/// the binary does
+pub async fn serve(broadcaster: RadioBroadcaster) -> anyhow::Result<IrohBundle> {
+    let endpoint = options().bind().await?;
+    let next_id = Arc::new(AtomicUsize::new(0));
+    let server = RpcServerBuilder::new(ALPN, endpoint.clone())
+        .with_connection_hook(move |_conn, _server_ext| {
+            let id = next_id.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async move { Ok(Extensions::new().with(ListenerInfo { id, nickname: None })) })
+        })
+        .service("radio");
+    let server = broadcaster.into_service_builder(server).build().build();
+    Ok(network::serve(endpoint, ALPN, server))
+}
+
+/// Dial `server` over loopback from a fresh endpoint
+pub async fn connect(server: &Endpoint, alpn: &[u8]) -> anyhow::Result<(IrohBundle, Connection)> {
+    let bundle = options().client_bundle().await?;
+    // LAN-only endpoints have no discovery, so give the direct address
+    let addr = server
+        .bound_sockets()
+        .into_iter()
+        .fold(EndpointAddr::new(server.id()), |addr, socket| {
+            addr.with_ip_addr(socket)
+        });
+    let connection = bundle
+        .endpoint
+        .connect(addr, alpn)
+        .await
+        .map_err(|e| anyhow::anyhow!("Loopback connect failed: {}", e))?;
+    Ok((bundle, connection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::{RadioError, StationEvent, StationInfoUpdate};
+    use futures::StreamExt;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(10);
+
+    #[tokio::test]
+    async fn chat_and_info_round_trip_over_loopback() {
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster).await.unwrap();
+
+        let info = station.client.get_info().await.unwrap();
+        assert_eq!(info.name, "Loopback FM");
+
+        let mut chat = station.client.chat_stream().await.unwrap();
+        station.client.send_chat("hello".to_string()).await.unwrap();
+        let message = timeout(WAIT, chat.next())
+            .await
+            .expect("chat message within timeout")
+            .expect("chat stream open")
+            .unwrap();
+        assert_eq!(message.message, "hello");
+    }
+
+    #[tokio::test]
+    async fn listen_streams_ogg_over_loopback() {
+        let (broadcaster, pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster).await.unwrap();
+
+        let (_send, mut recv) = station.client.listen().await.unwrap();
+        let feed = tokio::spawn(async move {
+            let block = Arc::new(vec![vec![0.0f32; 4410]; 2]);
+            loop {
+                // Errors until the encoder subscribes; keep feeding
+                let _ = pcm_tx.send(block.clone());
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let mut head = [0u8; 4];
+        timeout(WAIT, recv.read_exact(&mut head))
+            .await
+            .expect("stream data within timeout")
+            .unwrap();
+        assert_eq!(&head, b"OggS");
+        feed.abort();
+    }
+
+    #[tokio::test]
+    async fn a_relay_only_advertises_rewind_when_configured() {
+        let pages = || Arc::new(crate::rewind::RewindBuffer::new(Duration::from_secs(30)));
+
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Relay FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster.with_relay(pages(), 128_000, false))
+            .await
+            .unwrap();
+        assert_eq!(station.client.get_info().await.unwrap().rewind_secs, 0);
+
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Relay FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster.with_relay(pages(), 128_000, true))
+            .await
+            .unwrap();
+        assert_eq!(station.client.get_info().await.unwrap().rewind_secs, 30);
+    }
+
+    #[tokio::test]
+    async fn largest_artwork_fits_in_one_response() {
+        // Every byte 0xFF encodes as the longest JSON number
+        let mut cover = vec![0xFF; crate::artwork::MAX_ARTWORK_BYTES];
+        cover[1] = 0xD8;
+        assert!(crate::artwork::check(&cover).is_ok());
+
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster.with_logo(cover.clone()))
+            .await
+            .unwrap();
+        let artwork = timeout(WAIT, station.client.get_artwork())
+            .await
+            .expect("artwork within timeout")
+            .unwrap();
+        assert_eq!(artwork, Some(cover));
+    }
+
+    #[tokio::test]
+    async fn late_subscribers_hear_the_current_track_first() {
+        let control = crate::audio_source::SourceControl::new();
+        control.start_track(Some("Night Drive".to_string()), None);
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster.with_source_control(control.clone()))
+            .await
+            .unwrap();
+
+        let mut events = station.client.event_stream().await.unwrap();
+        let first = timeout(WAIT, events.next())
+            .await
+            .expect("event within timeout")
+            .expect("event stream open")
+            .unwrap();
+        assert_eq!(
+            first,
+            StationEvent::TrackChanged {
+                title: Some("Night Drive".to_string())
+            }
+        );
+
+        // Later tracks follow as they start
+        control.start_track(None, None);
+        let next = timeout(WAIT, events.next())
+            .await
+            .expect("event within timeout")
+            .expect("event stream open")
+            .unwrap();
+        assert_eq!(next, StationEvent::TrackChanged { title: None });
+    }
+
+    #[tokio::test]
+    async fn station_errors_reach_the_client_typed() {
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster).await.unwrap();
+
+        let error = station
+            .client
+            .request_track("   ".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            RadioError::from_client_error(&error),
+            Some(RadioError::InvalidRequest("request is empty".to_string()))
+        );
+        assert_eq!(
+            RadioError::describe(&error),
+            "invalid request: request is empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn only_operators_change_station_info() {
+        let (broadcaster, _pcm_tx) = RadioBroadcaster::new("Loopback FM", "test", 44100, 2);
+        let station = LoopbackStation::start(broadcaster.clone()).await.unwrap();
+
+        let update = StationInfoUpdate {
+            name: Some("Hijacked FM".to_string()),
+            ..Default::default()
+        };
+        let error = station.client.set_station_info(update).await.unwrap_err();
+        assert_eq!(
+            RadioError::from_client_error(&error),
+            Some(RadioError::Unauthorized)
+        );
+        assert_eq!(RadioError::describe(&error), "not authorized");
+        assert_eq!(station.client.get_info().await.unwrap().name, "Loopback FM");
+    }
+}
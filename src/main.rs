@@ -1,226 +1,2505 @@
 use clap::{Args, Parser, Subcommand};
-use log::info;
+use log::{error, info, warn};
 use std::time::Duration;
 
-use futures::future::BoxFuture;
+use futures::future::{BoxFuture, FutureExt};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use zel_core::protocol::{Extensions, RpcServerBuilder};
 use zel_core::IrohBundle;
 
-mod audio_player;
-mod audio_source;
-mod broadcaster;
-mod devices;
-mod listener;
-mod service;
+use zelfm::audio_source::{
+    AudioSource, FileSource, PlaylistSource, RawPcmFormat, RawPcmSource, SourceControl,
+    StdinSource, ToneSource,
+};
+use zelfm::bench::BenchOptions;
+use zelfm::broadcaster::{
+    BroadcastOptions, OverflowPolicy, RadioBroadcaster, MAX_QUALITY, MIN_QUALITY,
+};
+use zelfm::config::BroadcastConfig;
+use zelfm::directory::{Directory, DirectoryServiceServer, StationEntry, DIRECTORY_ALPN};
+use zelfm::fade::Fader;
+use zelfm::levels::MeterMode;
+use zelfm::listener::{PcmOutFormat, RadioListener};
+use zelfm::network::NetworkOptions;
+use zelfm::playlist::{DirectoryScan, OnEnd, PlayOrder, PlaylistEntry, Repeat};
+use zelfm::recorder::RecordFormat;
+use zelfm::rewind::RewindBuffer;
+use zelfm::service::{
+    ListenerInfo, RadioError, RadioServiceClient, RadioServiceServer, SignedStationInfo,
+    SourceCapabilities, StationEvent, StationInfo, StationInfoUpdate, StreamCodec, ALPN,
+    EVENTS_VERSION, SIGNING_CONTEXT_VERSION,
+};
+use zelfm::ticket::StationTicket;
 
-use audio_source::{AudioSource, FileSource};
-use broadcaster::RadioBroadcaster;
-use listener::RadioListener;
-use service::{ListenerInfo, RadioServiceClient, RadioServiceServer};
+#[cfg(feature = "live-input")]
+use zelfm::audio_source::LiveSource;
+#[cfg(feature = "live-input")]
+use zelfm::devices;
+#[cfg(feature = "http")]
+use zelfm::http;
+
+#[derive(Parser)]
+#[command(name = "zelfm")]
+#[command(about = "P2P Internet Radio - File & Live Streaming")]
+struct Cli {
+    /// Also write timestamped logs to this file (rotated at 10 MB; RUST_LOG sets verbosity)
+    #[arg(long, global = true)]
+    log_file: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    network: NetworkArgs,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Iroh endpoint networking, shared by every command (see `zelfm::network`)
+#[derive(Args)]
+struct NetworkArgs {
+    /// Use this relay server instead of the public n0 relays (repeatable)
+    #[arg(long = "relay-url", global = true, value_name = "URL")]
+    relay_urls: Vec<String>,
+
+    /// No relays and no DNS discovery: direct connections only, so peers must be
+    /// reachable on the local network and connect with a ticket, not a node ID
+    #[arg(long, global = true, conflicts_with = "relay_urls")]
+    lan_only: bool,
+
+    /// Local UDP address for the iroh endpoint [default: any interface, random port]
+    #[arg(long, global = true, value_name = "ADDR")]
+    bind: Option<std::net::SocketAddr>,
+
+    /// Keep this node's secret key in this file (created on first use) so its
+    /// node ID stays the same across restarts [default: a new ID every run]
+    #[arg(long, global = true, value_name = "PATH")]
+    identity: Option<std::path::PathBuf>,
+}
+
+impl NetworkArgs {
+    fn options(&self) -> anyhow::Result<NetworkOptions> {
+        let network = NetworkOptions::new(&self.relay_urls, self.lan_only, self.bind)?;
+        Ok(match &self.identity {
+            Some(path) => network.with_identity(path),
+            None => network,
+        })
+    }
+
+    /// Flags as a config layer, so they can override a config file
+    fn to_config(&self) -> BroadcastConfig {
+        BroadcastConfig {
+            relay_urls: (!self.relay_urls.is_empty()).then(|| self.relay_urls.clone()),
+            lan_only: self.lan_only.then_some(true),
+            bind: self.bind,
+            identity: self
+                .identity
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Start broadcasting a radio station
+    // Boxed: its flags outweigh every other subcommand's
+    Broadcast(Box<BroadcastArgs>),
+
+    /// List available input devices
+    #[cfg(feature = "live-input")]
+    ListDevices,
+
+    /// Listen to a radio station
+    Listen(ListenArgs),
+
+    /// Inspect an audio file's format and tags without broadcasting it
+    Probe {
+        /// File to inspect
+        file: std::path::PathBuf,
+    },
+
+    /// Run a station directory node that broadcasters can register with
+    Directory,
+
+    /// List stations registered with a directory
+    Browse {
+        /// Directory node ID
+        #[arg(short = 'D', long)]
+        directory: String,
+
+        /// Only show stations whose name or description matches
+        #[arg(short, long)]
+        search: Option<String>,
+    },
+
+    /// Print this node's ID and a connection ticket, then exit
+    Whoami,
+
+    /// Time Vorbis encoding and decoding of synthetic audio, without the
+    /// network or audio devices, to spot performance regressions
+    Bench {
+        /// Seconds of audio to encode [default: 60]
+        #[arg(long)]
+        seconds: Option<u64>,
+
+        /// Vorbis quality, -0.1 to 1.0 [default: 0.5, what listeners get]
+        #[arg(long, allow_hyphen_values = true)]
+        quality: Option<f32>,
+
+        /// 1 for mono, 2 for stereo [default: 2]
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=2))]
+        channels: Option<u8>,
+    },
+}
+
+#[derive(Args)]
+struct BroadcastArgs {
+    /// Load settings from a TOML config file (flags override file values)
+    #[arg(short, long)]
+    config: Option<std::path::PathBuf>,
+
+    /// Station name [default: ZelFM Demo]
+    #[arg(short, long)]
+    name: Option<String>,
+
+    /// Station description
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Station genre shown to listeners and directories
+    #[arg(long)]
+    genre: Option<String>,
+
+    /// Descriptive tag (repeatable)
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    /// Station website
+    #[arg(long)]
+    website: Option<String>,
+
+    /// JPEG or PNG logo listeners get as artwork when a track has no cover
+    #[arg(long)]
+    logo: Option<String>,
+
+    /// Encoded bytes buffered per send (smaller = lower latency, more writes)
+    /// [default: 8192, or 1024 with --low-latency-encode; at most a page with --flush-ms]
+    #[arg(long)]
+    chunk_size: Option<usize>,
+
+    /// Emit small OGG pages and chunks so audio leaves the encoder as soon as
+    /// possible (for live input), at some extra overhead per listener
+    #[arg(long)]
+    low_latency_encode: bool,
+
+    /// Finish OGG pages after about this many milliseconds of audio (10-1000),
+    /// trading a little bitrate for lower latency [default: libogg's ~4 KB pages]
+    #[arg(long)]
+    flush_ms: Option<u64>,
+
+    /// PCM broadcast channel capacity, in blocks [default: 100]
+    #[arg(long)]
+    pcm_capacity: Option<usize>,
+
+    /// Behavior when a listener's encoder falls behind [default: drop-oldest]
+    #[arg(long, value_enum)]
+    overflow: Option<OverflowPolicy>,
+
+    /// Level metering: sample peak/RMS, or add A-weighted RMS and true peak [default: basic]
+    #[arg(long, value_enum)]
+    meter_mode: Option<MeterMode>,
+
+    /// Broadcast a mono stream, summing the source's channels to center
+    #[arg(long)]
+    mono: bool,
+
+    /// Listener stream encoding; `flac` is lossless but uses several times the
+    /// bandwidth, so it suits LANs, and `pcm` skips encoding altogether for the
+    /// lowest latency at twice that again [default: vorbis]
+    #[arg(long, value_enum)]
+    codec: Option<StreamCodec>,
+
+    /// Lowest Vorbis quality (-0.1 to 1.0) listeners may pick with `listen --quality`;
+    /// each distinct quality in use runs one shared encoder
+    #[arg(long, allow_hyphen_values = true)]
+    min_quality: Option<f32>,
+
+    /// Highest Vorbis quality listeners may pick with `listen --quality`
+    #[arg(long, allow_hyphen_values = true)]
+    max_quality: Option<f32>,
+
+    /// Decode file, playlist, and --dir sources at playback speed instead of
+    /// racing ahead, for steadier latency and buffering
+    #[arg(long)]
+    realtime: bool,
+
+    /// Let file, playlist, and --dir sources decode at most this many seconds
+    /// ahead of playback, instead of as far as the buffers allow
+    #[arg(long, value_name = "SECS", conflicts_with = "realtime")]
+    max_decode_ahead_secs: Option<f32>,
+
+    /// Normalize file, playlist, and --dir tracks with their REPLAYGAIN_TRACK_GAIN tags
+    #[arg(long)]
+    replay_gain: bool,
+
+    /// Drop leading and trailing silence from file, playlist, and --dir tracks
+    /// so they follow each other without gaps (before any fade is applied)
+    #[arg(long)]
+    trim_silence: bool,
+
+    /// Audio below this level counts as silence for --trim-silence [default: -50]
+    #[arg(
+        long,
+        value_name = "DBFS",
+        requires = "trim_silence",
+        allow_hyphen_values = true
+    )]
+    silence_threshold_db: Option<f32>,
+
+    /// Refuse to start when the source's (or jingle's) sample rate differs from
+    /// the station's, instead of warning (mismatched audio plays at the wrong pitch).
+    /// Pipes and relays set the station's rate; stdin can't be checked
+    #[arg(long)]
+    strict_format: bool,
+
+    /// Stop broadcasting after this many seconds (optional)
+    #[arg(short, long)]
+    duration: Option<u64>,
+
+    /// Disconnect each listener after this many seconds (fair rotation on busy stations)
+    #[arg(long)]
+    max_session_secs: Option<u64>,
+
+    /// Refuse listeners beyond this many with "station is full". Each listener
+    /// runs its own encoder thread, so this also caps encoder CPU
+    #[arg(long)]
+    max_listeners: Option<usize>,
+
+    /// Cap total upload to listeners at this many kbps, admitting only as many
+    /// listeners as fit at the best quality each can get (the rest get
+    /// "station is full")
+    #[arg(long, value_name = "KBPS")]
+    max_bandwidth: Option<u32>,
+
+    /// Disconnect listeners whose connection accepts no data for this many seconds [default: 30]
+    #[arg(long)]
+    stall_timeout_secs: Option<u64>,
+
+    /// QUIC send priority of audio streams; chat, events, and other requests
+    /// go at 0, so a higher value sends audio first on a congested link and 0
+    /// shares the link evenly [default: 1]
+    #[arg(long, allow_hyphen_values = true)]
+    audio_priority: Option<i32>,
+
+    /// Keep counting a departed listener for this many seconds so quick
+    /// reconnects don't make the listener count flicker; 0 turns it off [default: 3]
+    #[arg(long)]
+    listener_grace_secs: Option<u64>,
+
+    /// Fade in over this many seconds when the broadcast starts and out when
+    /// it shuts down; 0 turns fades off [default: 1.5]
+    #[arg(long)]
+    fade_secs: Option<f32>,
+
+    /// Keep this many seconds of audio so listeners can start in the past (e.g. 300)
+    #[arg(long)]
+    rewind_secs: Option<u64>,
+
+    /// Buffer new listeners' header pages like any other data (slower join, fewer writes)
+    #[arg(long)]
+    no_fast_start: bool,
+
+    /// Also serve the stream over HTTP for ordinary media players (e.g. 0.0.0.0:8000);
+    /// `/healthz` there answers 200 while audio is flowing, 503 otherwise
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http_addr: Option<std::net::SocketAddr>,
+
+    /// Post a station announcement to chat every N seconds (off by default)
+    #[arg(long)]
+    announce_interval: Option<u64>,
+
+    /// Announcement text; `{station}` and `{listeners}` are filled in
+    /// [default: "You're listening to {station}"]
+    #[arg(long)]
+    announce_text: Option<String>,
+
+    /// Don't post "… joined" / "… left" to chat when listeners come and go
+    #[arg(long)]
+    quiet_joins: bool,
+
+    /// Chat messages buffered for each listener's live chat [default: 100]
+    #[arg(long)]
+    chat_capacity: Option<usize>,
+
+    /// Chat messages kept for listeners who join or resubscribe later [default: 100]
+    #[arg(long, value_name = "COUNT")]
+    chat_history_count: Option<usize>,
+
+    /// Memory chat history may use before the oldest messages are dropped,
+    /// however many there are [default: 262144]
+    #[arg(long, value_name = "BYTES")]
+    chat_history_bytes: Option<usize>,
+
+    /// Append chat to this JSONL file, and reload recent history from it on start
+    #[arg(long)]
+    chat_log: Option<String>,
+
+    /// Rotate the chat log to `<path>.1` once it reaches this size [default: 10]
+    #[arg(long, value_name = "MB", requires = "chat_log")]
+    chat_log_max_mb: Option<u64>,
+
+    /// Let listeners vote to skip a file, playlist, or dir track; it's skipped
+    /// once more than this share of listeners vote (0.5 = a majority)
+    #[arg(long, value_name = "SHARE")]
+    vote_skip: Option<f32>,
+
+    /// Step listeners whose sends keep stalling down to a lower encoder quality,
+    /// and back up once they keep pace. Starts a new chained OGG link on each
+    /// switch, so only listeners that ask for it (protocol 14 and later) adapt
+    #[arg(long)]
+    adaptive_bitrate: bool,
+
+    /// Register this station with a directory node so listeners can browse for it
+    #[arg(short = 'D', long)]
+    directory: Option<String>,
+
+    /// Node ID allowed to change the station's name, description, genre,
+    /// tags, and website on the air (repeatable; see `zelfm whoami`)
+    #[arg(long = "operator", value_name = "NODE_ID")]
+    operators: Vec<String>,
+
+    /// At the end of the file or playlist: `none` (stop), `one` (replay the current
+    /// track until skipped), `all` (loop), or a number of passes [default: all, or
+    /// none with --on-end or --fallback]
+    #[arg(long)]
+    repeat: Option<Repeat>,
+
+    /// Play the playlist or --dir in a random order, reshuffled each pass
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Shuffle seed, for a reproducible order
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Play this clip (e.g. a station ID) between playlist or --dir tracks
+    #[arg(long)]
+    jingle: Option<String>,
+
+    /// Play the jingle after every N tracks [default: 1]
+    #[arg(long, requires = "jingle")]
+    jingle_every: Option<u32>,
+
+    /// When the source runs out: `stop`, `loop`, `tone` (stay on the air with a
+    /// placeholder), or `fallback` [default: loop for files and playlists, else stop]
+    #[arg(long, value_enum)]
+    on_end: Option<OnEnd>,
+
+    /// Placeholder tone for `--on-end tone`, in Hz; 0 plays silence [default: 440]
+    #[arg(long)]
+    end_tone_hz: Option<f32>,
+
+    /// File or M3U/PLS playlist to loop once the source runs out (implies `--on-end fallback`)
+    #[arg(long)]
+    fallback: Option<String>,
+
+    /// Sidecar manifest (TOML or JSON) with per-track title, gain_db, and start/end trims
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// Sample rate of --pipe audio in Hz; the station runs at this rate [default: 44100]
+    #[arg(long, requires = "pipe", value_parser = clap::value_parser!(u32).range(8000..=192000))]
+    pcm_rate: Option<u32>,
+
+    /// Channels in --pipe audio [default: 2]
+    #[arg(long, requires = "pipe", value_parser = clap::value_parser!(u16).range(1..=8))]
+    pcm_channels: Option<u16>,
+
+    /// Sample encoding of --pipe audio (little-endian) [default: s16]
+    #[arg(long, value_enum, requires = "pipe")]
+    pcm_format: Option<RawPcmFormat>,
+
+    /// Order for --dir [default: name]
+    #[arg(long, value_enum, requires = "dir")]
+    order: Option<PlayOrder>,
+
+    /// Include subdirectories of --dir
+    #[arg(long, requires = "dir")]
+    recursive: bool,
+
+    /// Rescan --dir between tracks so added and removed files update the queue
+    #[arg(long, requires = "dir")]
+    watch: bool,
+
+    /// Check the config, source, and encoder, then exit without going on air
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Save the effective settings (config file and flags, defaults filled in)
+    /// to this TOML file, loadable with --config
+    #[arg(long)]
+    dump_config: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    source: AudioSourceArgs,
+}
+
+impl BroadcastArgs {
+    /// Flags as a config layer, so they can override a config file
+    fn to_config(&self) -> BroadcastConfig {
+        BroadcastConfig {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            genre: self.genre.clone(),
+            tags: (!self.tags.is_empty()).then(|| self.tags.clone()),
+            website: self.website.clone(),
+            logo: self.logo.clone(),
+            chunk_size: self.chunk_size,
+            low_latency_encode: self.low_latency_encode.then_some(true),
+            flush_ms: self.flush_ms,
+            pcm_capacity: self.pcm_capacity,
+            overflow: self.overflow,
+            meter_mode: self.meter_mode,
+            mono: self.mono.then_some(true),
+            codec: self.codec,
+            min_quality: self.min_quality,
+            max_quality: self.max_quality,
+            realtime: self.realtime.then_some(true),
+            max_decode_ahead_secs: self.max_decode_ahead_secs,
+            replay_gain: self.replay_gain.then_some(true),
+            trim_silence: self.trim_silence.then_some(true),
+            silence_threshold_db: self.silence_threshold_db,
+            strict_format: self.strict_format.then_some(true),
+            duration: self.duration,
+            max_session_secs: self.max_session_secs,
+            max_listeners: self.max_listeners,
+            max_bandwidth: self.max_bandwidth,
+            stall_timeout_secs: self.stall_timeout_secs,
+            audio_priority: self.audio_priority,
+            listener_grace_secs: self.listener_grace_secs,
+            fade_secs: self.fade_secs,
+            fast_start: self.no_fast_start.then_some(false),
+            rewind_secs: self.rewind_secs,
+            #[cfg(feature = "http")]
+            http_addr: self.http_addr,
+            #[cfg(not(feature = "http"))]
+            http_addr: None,
+            announce_interval: self.announce_interval,
+            announce_text: self.announce_text.clone(),
+            quiet_joins: self.quiet_joins.then_some(true),
+            chat_capacity: self.chat_capacity,
+            chat_history_count: self.chat_history_count,
+            chat_history_bytes: self.chat_history_bytes,
+            chat_log: self.chat_log.clone(),
+            chat_log_max_mb: self.chat_log_max_mb,
+            vote_skip: self.vote_skip,
+            adaptive_bitrate: self.adaptive_bitrate.then_some(true),
+            directory: self.directory.clone(),
+            operators: (!self.operators.is_empty()).then(|| self.operators.clone()),
+            file: self.source.file.clone(),
+            playlist: self.source.playlist.clone(),
+            dir: self.source.dir.clone(),
+            order: self.order,
+            recursive: self.recursive.then_some(true),
+            watch: self.watch.then_some(true),
+            repeat: self.repeat,
+            shuffle: self.shuffle.then_some(true),
+            seed: self.seed,
+            jingle: self.jingle.clone(),
+            jingle_every: self.jingle_every,
+            on_end: self.on_end,
+            end_tone_hz: self.end_tone_hz,
+            fallback: self.fallback.clone(),
+            manifest: self.manifest.clone(),
+            #[cfg(feature = "live-input")]
+            input: self.source.input.clone(),
+            #[cfg(not(feature = "live-input"))]
+            input: None,
+            stdin: self.source.stdin.then_some(true),
+            pipe: self.source.pipe.clone(),
+            pcm_rate: self.pcm_rate,
+            pcm_channels: self.pcm_channels,
+            pcm_format: self.pcm_format,
+            relay: self.source.relay.clone(),
+            // Endpoint settings come from the global NetworkArgs
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+struct ListenArgs {
+    /// Broadcaster node ID (always works; discovery finds the current address)
+    #[arg(short, long, required_unless_present_any = ["station", "ticket"])]
+    node_id: Option<String>,
+
+    /// Station ticket printed by the broadcaster (faster, more reliable through NAT)
+    #[arg(short, long, conflicts_with_all = ["node_id", "station"])]
+    ticket: Option<String>,
+
+    /// Station name to look up in a directory (instead of --node-id)
+    #[arg(short, long, conflicts_with = "node_id", requires = "directory")]
+    station: Option<String>,
+
+    /// Directory node ID used to resolve --station
+    #[arg(short = 'D', long)]
+    directory: Option<String>,
+
+    /// Max listening duration in seconds (optional)
+    #[arg(short, long)]
+    duration: Option<u64>,
+
+    /// Seconds to wait for the station to answer before giving up
+    #[arg(long, default_value_t = 15)]
+    connect_timeout: u64,
+
+    /// Start this many seconds in the past, if the station keeps a rewind buffer
+    #[arg(long)]
+    rewind: Option<u32>,
+
+    /// Ask for this Vorbis quality (-0.1 to 1.0), if the station offers a range
+    #[arg(long, conflicts_with = "rewind", allow_hyphen_values = true)]
+    quality: Option<f32>,
+
+    /// Prefix chat messages with the time they were sent
+    #[arg(long)]
+    timestamps: bool,
+
+    /// Show chat timestamps in UTC instead of local time
+    #[arg(long, requires = "timestamps")]
+    utc: bool,
+
+    /// Also show station events: track changes, joins and leaves, pauses
+    #[arg(long)]
+    events: bool,
+
+    /// Show a live text spectrum analyzer while listening
+    #[arg(long)]
+    spectrum: bool,
+
+    /// FFT size for the spectrum analyzer (power of two)
+    #[arg(long, default_value_t = zelfm::spectrum::DEFAULT_FFT_SIZE)]
+    fft_size: usize,
+
+    /// Record the stream to this file while listening
+    #[arg(long)]
+    record: Option<std::path::PathBuf>,
+
+    /// Recording format
+    #[arg(long, value_enum, default_value_t = RecordFormat::Ogg, requires = "record")]
+    record_format: RecordFormat,
+
+    /// Write decoded interleaved little-endian PCM to stdout instead of playing
+    /// (e.g. `zelfm listen -n <ID> --pcm-out | aplay -f S16_LE -r 44100 -c 2`).
+    /// The format line is printed to stderr; chat and commands are disabled.
+    #[arg(long, conflicts_with = "spectrum")]
+    pcm_out: bool,
+
+    /// Sample encoding for --pcm-out
+    #[arg(long, value_enum, default_value_t = PcmOutFormat::S16, requires = "pcm_out")]
+    pcm_format: PcmOutFormat,
+
+    /// With --pcm-out, play through the speakers as well. Each output runs on
+    /// its own, so a stalled pipe drops its audio without interrupting playback.
+    #[arg(long, requires = "pcm_out")]
+    also_play: bool,
+
+    /// Resample playback and --pcm-out to this rate in Hz, whatever the station
+    /// sends (recordings keep the station's rate)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(8000..=384000))]
+    output_rate: Option<u32>,
+
+    /// Tame loud passages and lift quiet ones on playback and --pcm-out, for
+    /// comfortable listening on headphones (recordings keep the station's audio)
+    #[arg(long)]
+    night_mode: bool,
+
+    /// Largest read from the stream, in bytes. Larger reads suit lossless and
+    /// PCM streams [default: from the station's bitrate, at least 8192]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(512..=1048576))]
+    read_chunk: Option<u64>,
+
+    /// Received chunks held for the decoder. Fewer keeps latency down if
+    /// decoding stalls, more rides out the stall; the player's 500 ms jitter
+    /// buffer comes after this [default: a second of audio, at least 10]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=4096))]
+    recv_queue: Option<u64>,
+}
+
+#[derive(Args)]
+#[group(multiple = false)]
+struct AudioSourceArgs {
+    /// Audio file to broadcast (loops)
+    #[arg(short, long)]
+    file: Option<String>,
+
+    /// M3U or PLS playlist to broadcast in order (loops)
+    #[arg(short, long)]
+    playlist: Option<String>,
+
+    /// Directory of audio files to broadcast as a playlist (loops)
+    #[arg(long)]
+    dir: Option<String>,
+
+    /// Live input device name (partial match, use list-devices to see options)
+    #[cfg(feature = "live-input")]
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Decode a media stream piped into stdin (e.g. `some-command | zelfm broadcast --stdin`)
+    #[arg(long)]
+    stdin: bool,
+
+    /// Send raw interleaved PCM from a named pipe or file as-is, at real-time
+    /// pace (see --pcm-rate, --pcm-channels, --pcm-format). For tools that can
+    /// write samples but not speak zelfm: `mkfifo /tmp/zelfm.pcm`, then point a
+    /// DAW or `ffmpeg -f s16le` at it
+    #[arg(long, value_name = "PATH")]
+    pipe: Option<String>,
+
+    /// Rebroadcast another station (node ID or ticket) as-is, without decoding or re-encoding
+    #[arg(long)]
+    relay: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    zelfm::logging::init(cli.log_file.as_deref())?;
+
+    match cli.command {
+        Commands::Broadcast(args) => broadcast_station(*args, &cli.network).await?,
+
+        #[cfg(feature = "live-input")]
+        Commands::ListDevices => {
+            devices::list_input_devices()?;
+        }
+
+        Commands::Listen(args) => listen_to_station(args, &cli.network.options()?).await?,
+
+        Commands::Probe { file } => probe(&file)?,
+
+        Commands::Directory => run_directory(&cli.network.options()?).await?,
+
+        Commands::Browse { directory, search } => {
+            browse_directory(directory, search, &cli.network.options()?).await?
+        }
+
+        Commands::Whoami => whoami(&cli.network.options()?).await?,
+
+        Commands::Bench {
+            seconds,
+            quality,
+            channels,
+        } => bench(seconds, quality, channels)?,
+    }
+
+    Ok(())
+}
+
+async fn broadcast_station(args: BroadcastArgs, network: &NetworkArgs) -> anyhow::Result<()> {
+    let file_config = match &args.config {
+        Some(path) => BroadcastConfig::load(path)?,
+        None => BroadcastConfig::default(),
+    };
+    let config = file_config
+        .merge(network.to_config())
+        .merge(args.to_config());
+    config.validate()?;
+    let network = config.network()?;
+
+    let name = config.name().to_string();
+    let overflow = config.overflow();
+    let pcm_capacity = config.pcm_capacity();
+
+    let options = BroadcastOptions {
+        chunk_size: config.chunk_size(),
+        pcm_capacity,
+        overflow,
+        max_session: config.max_session_secs.map(Duration::from_secs),
+        stall_timeout: config.stall_timeout(),
+        audio_priority: config.audio_priority(),
+        fast_start: config.fast_start(),
+        meter_mode: config.meter_mode(),
+        // A relay's rewind buffer is the relayed stream itself
+        rewind: config
+            .rewind_secs
+            .filter(|_| config.relay.is_none())
+            .map(Duration::from_secs),
+        announce_joins: !config.quiet_joins(),
+        chat_capacity: config.chat_capacity(),
+        chat_history_len: config.chat_history_count(),
+        chat_history_bytes: config.chat_history_bytes(),
+        adaptive_bitrate: config.adaptive_bitrate(),
+        max_listeners: config.max_listeners,
+        max_bandwidth: config.max_bandwidth,
+        codec: config.codec(),
+        quality_range: config.quality_range(),
+        listener_grace: config.listener_grace(),
+        vote_skip: config.vote_skip,
+        low_latency: config.low_latency_encode(),
+        flush_interval: config.flush_ms.map(Duration::from_millis),
+    };
+
+    if let Some(path) = &args.dump_config {
+        let resolved = config.resolved();
+        // Save the device actually matched, not the search string
+        #[cfg(feature = "live-input")]
+        let resolved = {
+            let mut resolved = resolved;
+            if let Some(search) = &config.input {
+                use cpal::traits::DeviceTrait;
+
+                let device =
+                    zelfm::devices::select_input_device(&cpal::default_host(), Some(search))?;
+                resolved.input = Some(device.name()?);
+            }
+            resolved
+        };
+        resolved.save(path)?;
+        println!("Saved config to {}", path.display());
+    }
+
+    if args.dry_run {
+        return dry_run(&config, options, &network).await;
+    }
+
+    println!("=== ZelFM Broadcaster ===\n");
+
+    // A relay takes its format from the upstream station
+    let upstream = match &config.relay {
+        Some(upstream) => Some(connect_upstream(upstream, &network).await?),
+        None => None,
+    };
+    let (sample_rate, channels) = match &upstream {
+        Some(upstream) => (upstream.info.sample_rate, upstream.info.channels),
+        // Raw PCM isn't resampled, so the station takes its rate
+        None if config.pipe.is_some() => (config.pcm_rate(), if config.mono() { 1 } else { 2 }),
+        None => (44100, if config.mono() { 1 } else { 2 }), // Target: 44.1 kHz
+    };
+    check_source_format(&config, sample_rate).await?;
+
+    // Create broadcaster
+    let (broadcaster, pcm_tx) = RadioBroadcaster::with_options(
+        name.clone(),
+        config.description(),
+        sample_rate,
+        channels,
+        options,
+    );
+
+    // Keep a clone to drop on shutdown
+    let pcm_tx_shutdown = pcm_tx.clone();
+
+    // Shared with the source so shutdown can fade it out
+    let fade = Duration::from_secs_f32(config.fade_secs());
+    let fader = Arc::new(Fader::new(fade, sample_rate));
+
+    // Leave headroom so the channel itself never evicts
+    let backpressure_limit = pcm_capacity.saturating_sub(1).max(1);
+    let backpressure = (overflow == OverflowPolicy::Backpressure).then_some(backpressure_limit);
+
+    // Operator console's skip/pause commands reach the source through this
+    let control = SourceControl::new();
+
+    // Relayed OGG pages, when rebroadcasting another station
+    let mut relay_pages = None;
+
+    if let Some(jingle) = &config.jingle {
+        println!("Jingle: {}", check_jingle(jingle, &config, sample_rate)?);
+    }
+
+    // Checked now so a bad fallback fails at startup, not when it's needed
+    let fallback = match &config.fallback {
+        Some(path) => Some(fallback_entries(path)?),
+        None => None,
+    };
+
+    // Determine and start audio source
+    let (capabilities, source_done) = if let Some(upstream) = &upstream {
+        println!("Source: Relay of '{}'", upstream.info.name);
+        let window = config
+            .rewind_secs
+            .map_or(zelfm::relay::RELAY_BUFFER, Duration::from_secs);
+        let pages = Arc::new(RewindBuffer::new(window));
+        relay_pages = Some(pages.clone());
+
+        let client = upstream.client.clone();
+        let forward = async move {
+            match zelfm::relay::forward(&client, &pages).await {
+                Ok(()) => SourceExit::Ended,
+                Err(e) => {
+                    error!("[Relay] {}", e);
+                    SourceExit::Failed
+                }
+            }
+        };
+        (
+            upstream.capabilities.clone(),
+            tokio::spawn(forward)
+                .map(|exit| exit.unwrap_or(SourceExit::Failed))
+                .boxed(),
+        )
+    } else if let Some(file_path) = config.file.clone() {
+        // File source
+        println!("Source: File ({})", file_path);
+        let mut audio_source = FileSource::new(file_path)
+            .with_meter(broadcaster.level_meter())
+            .with_fader(fader.clone())
+            .with_control(control.clone());
+        audio_source = with_decode_settings(audio_source, &config, backpressure);
+        // Repeating one track of a single file is just looping it
+        if let Some(passes) = config.repeat().passes() {
+            audio_source = audio_source.with_repeat(passes);
+        }
+        let capabilities = audio_source.capabilities();
+        (capabilities, supervise_source(audio_source, pcm_tx, true))
+    } else if let Some(playlist_path) = config.playlist.clone() {
+        // Playlist source
+        println!("Source: Playlist ({})", playlist_path);
+        let mut entries = zelfm::playlist::load(playlist_path.as_ref())?;
+        if let Some(manifest) = &config.manifest {
+            zelfm::playlist::apply_manifest(&mut entries, manifest.as_ref())?;
+        }
+        let mut audio_source = PlaylistSource::new(entries)
+            .with_meter(broadcaster.level_meter())
+            .with_fader(fader.clone())
+            .with_control(control.clone())
+            .with_repeat(config.repeat());
+        audio_source = with_decode_settings(audio_source, &config, backpressure);
+        if config.shuffle() {
+            audio_source = audio_source.with_shuffle(config.seed);
+        }
+        if let Some(jingle) = &config.jingle {
+            audio_source = audio_source.with_jingle(jingle, config.jingle_every());
+        }
+        let capabilities = audio_source.capabilities();
+        (capabilities, supervise_source(audio_source, pcm_tx, true))
+    } else if let Some(dir) = config.dir.clone() {
+        // Directory source, played like a playlist
+        println!("Source: Directory ({})", dir);
+        let scan = DirectoryScan {
+            root: dir.into(),
+            recursive: config.recursive(),
+            order: config.order(),
+        };
+        let entries = scan.scan()?;
+        if entries.is_empty() && !config.watch() {
+            anyhow::bail!("No audio files found in {}", scan.root.display());
+        }
+        let mut audio_source = PlaylistSource::new(entries)
+            .with_meter(broadcaster.level_meter())
+            .with_fader(fader.clone())
+            .with_control(control.clone())
+            .with_repeat(config.repeat());
+        audio_source = with_decode_settings(audio_source, &config, backpressure);
+        if config.shuffle() {
+            audio_source = audio_source.with_shuffle(config.seed);
+        }
+        if let Some(jingle) = &config.jingle {
+            audio_source = audio_source.with_jingle(jingle, config.jingle_every());
+        }
+        if config.watch() {
+            audio_source = audio_source.with_watch(scan);
+        }
+        let capabilities = audio_source.capabilities();
+        (capabilities, supervise_source(audio_source, pcm_tx, true))
+    } else if config.stdin() {
+        // Piped media stream; the broadcast ends with the input, and can't be reopened
+        println!("Source: Stdin");
+        let mut audio_source = StdinSource::new()
+            .with_meter(broadcaster.level_meter())
+            .with_fader(fader.clone());
+        if overflow == OverflowPolicy::Backpressure {
+            audio_source = audio_source.with_backpressure(backpressure_limit);
+        }
+        let capabilities = audio_source.capabilities();
+        (capabilities, supervise_source(audio_source, pcm_tx, false))
+    } else if let Some(pipe) = &config.pipe {
+        // Raw PCM; the broadcast ends when the writer closes the pipe
+        println!(
+            "Source: Pipe {} ({} Hz, {} ch, {})",
+            pipe,
+            config.pcm_rate(),
+            config.pcm_channels(),
+            config.pcm_format()
+        );
+        let mut audio_source = RawPcmSource::new(
+            pipe,
+            config.pcm_rate(),
+            config.pcm_channels(),
+            config.pcm_format(),
+        )
+        .with_meter(broadcaster.level_meter())
+        .with_fader(fader.clone())
+        .with_control(control.clone());
+        if overflow == OverflowPolicy::Backpressure {
+            audio_source = audio_source.with_backpressure(backpressure_limit);
+        }
+        let capabilities = audio_source.capabilities();
+        (capabilities, supervise_source(audio_source, pcm_tx, false))
+    } else {
+        #[cfg(feature = "live-input")]
+        if let Some(device_name) = config.input.clone() {
+            // Live input source
+            println!("Source: Live Input ({})", device_name);
+            let audio_source = LiveSource::new(Some(device_name))
+                .with_format(sample_rate, channels as u16)
+                .with_meter(broadcaster.level_meter())
+                .with_fader(fader.clone())
+                .with_control(control.clone());
+            let capabilities = audio_source.capabilities();
+            (capabilities, supervise_source(audio_source, pcm_tx, true))
+        } else {
+            anyhow::bail!("No audio source specified");
+        }
+
+        #[cfg(not(feature = "live-input"))]
+        anyhow::bail!("Live input requested but zelfm was built without the `live-input` feature");
+    };
+
+    let mut broadcaster = broadcaster
+        .with_capabilities(capabilities)
+        .with_metadata(config.genre.clone(), config.tags(), config.website.clone())
+        .with_operators(config.operators());
+    if let (Some(pages), Some(upstream)) = (relay_pages, &upstream) {
+        broadcaster =
+            broadcaster.with_relay(pages, upstream.info.bitrate, config.rewind_secs.is_some());
+        if let Some((station, signed)) = upstream.signed_info.clone() {
+            broadcaster = broadcaster.with_upstream_signature(station, signed);
+        }
+    }
+    // Tracks' cover art and skip votes
+    broadcaster = broadcaster.with_source_control(control.clone());
+    if let Some(path) = &config.logo {
+        let logo = zelfm::artwork::load(path)?;
+        println!("Logo: {} ({} KB)", path, logo.len().div_ceil(1024));
+        broadcaster = broadcaster.with_logo(logo);
+    }
+    if let Some(path) = &config.chat_log {
+        let log = zelfm::chat_log::ChatLog::open(path, config.chat_log_max_bytes())?;
+        println!(
+            "Chat log: {} (rotated at {} MB)",
+            path,
+            config.chat_log_max_bytes() / (1024 * 1024)
+        );
+        broadcaster = broadcaster.with_chat_log(log);
+    }
+    for operator in config.operators() {
+        println!("Operator: {} (may change station info)", operator);
+    }
+
+    // Optional HTTP endpoint for standard streaming clients
+    #[cfg(feature = "http")]
+    if let Some(addr) = config.http_addr {
+        let http_broadcaster = broadcaster.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(addr, http_broadcaster).await {
+                error!("[HTTP] Server error: {}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "http"))]
+    if config.http_addr.is_some() {
+        eprintln!("Warning: http_addr ignored, zelfm was built without the `http` feature");
+    }
+
+    // Setup Iroh
+    let endpoint = network.bind().await?;
+    let node_id = endpoint.id();
+    let broadcaster = broadcaster.with_signing_key(endpoint.secret_key().clone());
+
+    println!("Node ID: {}", node_id);
+    println!("Network: {}", network.describe());
+    println!("Station: {}", name);
+    println!(
+        "Overflow policy: {:?} (buffer {} blocks)",
+        overflow, pcm_capacity
+    );
+    if let Some(ms) = config.flush_ms {
+        println!(
+            "Encoder: pages every ~{} ms ({}+ bytes), {} byte chunks",
+            ms,
+            broadcaster.page_size().unwrap_or_default(),
+            broadcaster.chunk_size()
+        );
+    } else if config.low_latency_encode() {
+        println!(
+            "Encoder: low latency ({}+ byte pages, {} byte chunks)",
+            zelfm::broadcaster::LOW_LATENCY_PAGE_SIZE,
+            config.chunk_size()
+        );
+    }
+    match config.on_end() {
+        // Sources that end on their own are the only ones worth a line
+        OnEnd::Stop | OnEnd::Loop => {}
+        OnEnd::Tone if config.end_tone_hz() > 0.0 => {
+            println!("On end:  {} Hz tone", config.end_tone_hz())
+        }
+        OnEnd::Tone => println!("On end:  silence"),
+        OnEnd::Fallback => println!(
+            "On end:  fallback ({})",
+            config.fallback.as_deref().unwrap_or_default()
+        ),
+    }
+    if let Some(share) = config.vote_skip {
+        println!(
+            "Skip votes: on (more than {:.0}% of listeners)",
+            share * 100.0
+        );
+    }
+    let heavy_codec = match config.codec() {
+        StreamCodec::Vorbis => None,
+        // Roughly half the uncompressed rate per listener, and each one adds it again
+        StreamCodec::Flac => Some((
+            "FLAC",
+            sample_rate * channels as u32 * zelfm::service::FLAC_BITS_PER_SAMPLE / 2000,
+        )),
+        StreamCodec::Pcm => Some(("PCM", sample_rate * channels as u32 * 32 / 1000)),
+    };
+    if let Some((label, kbps)) = heavy_codec {
+        println!("Codec:   {} (about {} kbps per listener)", label, kbps);
+        match broadcaster.listener_limit() {
+            Some(max) => println!(
+                "         up to ~{} kbps at {} listeners",
+                kbps as usize * max,
+                max
+            ),
+            None => eprintln!(
+                "Warning: {} with no --max-listeners; upload grows by ~{} kbps per listener",
+                label, kbps
+            ),
+        }
+    }
+    if let Some(kbps) = config.max_bandwidth {
+        match broadcaster.listener_limit() {
+            Some(0) => eprintln!(
+                "Warning: --max-bandwidth {} kbps is below one stream; every listener will be refused",
+                kbps
+            ),
+            Some(max) => println!("Bandwidth: {} kbps cap, room for {} listener(s)", kbps, max),
+            None => {}
+        }
+    }
+
+    // Connection hook to assign unique listener IDs
+    let listener_id_counter = Arc::new(AtomicUsize::new(0));
+
+    // Build server with connection hook
+    let server = RpcServerBuilder::new(ALPN, endpoint.clone())
+        .with_connection_hook(move |_conn, _server_ext| {
+            let counter = listener_id_counter.clone();
+            Box::pin(async move {
+                let id = counter.fetch_add(1, Ordering::Relaxed);
+                info!("[Server] Assigned listener ID: {}", id);
+
+                Ok(Extensions::new().with(ListenerInfo { id, nickname: None }))
+            })
+        })
+        .service("radio");
+
+    let server = broadcaster
+        .clone()
+        .into_service_builder(server)
+        .build()
+        .build();
+    let server_bundle = zelfm::network::serve(endpoint, ALPN, server);
+
+    // Tickets carry relay/direct addresses; wait briefly for them to be known
+    let _ = tokio::time::timeout(Duration::from_secs(5), server_bundle.endpoint.online()).await;
+    let ticket = StationTicket::new(server_bundle.endpoint.addr());
+    println!("Ticket:  {}", ticket);
+    println!("  Share the ticket for the fastest connect (zelfm listen --ticket ...).");
+    println!("  The node ID stays valid across address changes (zelfm listen --node-id ...).");
+    println!("\nWaiting for listeners...\n");
+
+    // Operator console on stdin (unless stdin is the audio)
+    let console = async {
+        if config.stdin() {
+            return std::future::pending().await;
+        }
+        let can_skip = config.file.is_some() || config.playlist.is_some() || config.dir.is_some();
+        println!(
+            "Console: info, listeners, {}pause, resume, quit\n",
+            if can_skip { "skip, " } else { "" }
+        );
+        operator_console(&broadcaster, &control, can_skip).await
+    };
+
+    // Keep the station listed in a directory, if one was given
+    if let Some(directory) = &config.directory {
+        let directory = parse_node_id(directory)?;
+        let heartbeat_broadcaster = broadcaster.clone();
+        println!("Registering with directory {}", directory);
+        tokio::spawn(zelfm::directory::heartbeat(
+            server_bundle.endpoint.clone(),
+            directory,
+            move || StationEntry {
+                name: heartbeat_broadcaster.station_name(),
+                description: heartbeat_broadcaster.station_desc(),
+                node_id: String::new(),
+                listeners: heartbeat_broadcaster.listener_count(),
+            },
+        ));
+    }
+
+    // Periodic station announcements in chat
+    if let Some(secs) = config.announce_interval {
+        let announcer = broadcaster.clone();
+        let template = config.announce_text().to_string();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(secs));
+            interval.tick().await; // skip the immediate tick; nobody is listening yet
+            loop {
+                interval.tick().await;
+                announcer.announce(
+                    template
+                        .replace("{station}", &announcer.station_name())
+                        .replace("{listeners}", &announcer.listener_count().to_string()),
+                );
+            }
+        });
+    }
+
+    // What plays once the program runs out; a tone or fallback runs until shutdown
+    let end_pcm_tx = pcm_tx_shutdown.clone();
+    let source_done = async {
+        if source_done.await == SourceExit::Failed {
+            error!("[Audio] Source failed and can't be restarted");
+        }
+        match config.on_end() {
+            OnEnd::Stop | OnEnd::Loop => {}
+            OnEnd::Tone => {
+                println!("\nAudio source ended; staying on the air with a placeholder");
+                let tone = ToneSource::new(sample_rate, config.end_tone_hz())
+                    .with_meter(broadcaster.level_meter())
+                    .with_fader(fader.clone())
+                    .with_control(control.clone());
+                supervise_source(tone, end_pcm_tx, true).await;
+            }
+            OnEnd::Fallback => {
+                println!("\nAudio source ended; switching to the fallback");
+                let mut audio_source = PlaylistSource::new(fallback.unwrap_or_default())
+                    .with_meter(broadcaster.level_meter())
+                    .with_fader(fader.clone())
+                    .with_control(control.clone())
+                    .with_repeat(Repeat::All);
+                audio_source = with_decode_settings(audio_source, &config, backpressure);
+                supervise_source(audio_source, end_pcm_tx, true).await;
+            }
+        }
+    };
+
+    // Run until Ctrl+C or the scheduled end of the broadcast
+    let stop_after = async {
+        match config.duration {
+            Some(secs) => tokio::time::sleep(Duration::from_secs(secs)).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let source_running = tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            result?;
+            true
+        }
+        _ = stop_after => {
+            println!("\nBroadcast duration reached");
+            true
+        }
+        _ = source_done => {
+            println!("\nAudio source ended");
+            false
+        }
+        _ = console => {
+            println!("\nOperator quit");
+            true
+        }
+    };
+    println!("\nShutting down...");
+
+    // Fade out while the encoders are still running so listeners hear it
+    // (relayed pages pass through untouched, so there's nothing to fade)
+    if source_running && upstream.is_none() && !fade.is_zero() {
+        let limit = fade + queued_audio(pcm_capacity, sample_rate) + Duration::from_secs(1);
+        fade_out(&fader, &pcm_tx_shutdown, limit).await;
+    }
+
+    // Let encoders flush their final pages and close listener streams
+    broadcaster.shutdown();
+    broadcaster.wait_for_listeners(Duration::from_secs(2)).await;
+
+    // Drop the broadcast sender to signal audio thread to stop
+    drop(pcm_tx_shutdown);
+
+    server_bundle.shutdown(Duration::from_secs(1)).await?;
+
+    Ok(())
+}
+
+/// Connection quality readout for the listener's `netstats` command
+struct NetStats {
+    endpoint: iroh::Endpoint,
+    connection: iroh::endpoint::Connection,
+    node_id: iroh::PublicKey,
+    /// When and at what byte count the last readout happened, for throughput
+    last: (std::time::Instant, u64),
+}
+
+impl NetStats {
+    fn new(
+        endpoint: iroh::Endpoint,
+        connection: iroh::endpoint::Connection,
+        node_id: iroh::PublicKey,
+    ) -> Self {
+        let received = connection.stats().udp_rx.bytes;
+        Self {
+            endpoint,
+            connection,
+            node_id,
+            last: (std::time::Instant::now(), received),
+        }
+    }
+
+    fn print(&mut self) {
+        use iroh::endpoint::ConnectionType;
+        use iroh::Watcher;
+
+        let stats = self.connection.stats();
+        let now = std::time::Instant::now();
+        let (since, last_bytes) = self.last;
+        let elapsed = now.duration_since(since).as_secs_f64().max(0.001);
+        let kbps = (stats.udp_rx.bytes.saturating_sub(last_bytes) * 8) as f64 / elapsed / 1000.0;
+        self.last = (now, stats.udp_rx.bytes);
+
+        let path = match self.endpoint.conn_type(self.node_id).map(|mut w| w.get()) {
+            Some(ConnectionType::Direct(addr)) => format!("direct ({})", addr),
+            Some(ConnectionType::Relay(url)) => format!("relayed via {}", url),
+            Some(ConnectionType::Mixed(addr, url)) => {
+                format!("mixed (trying {}, relay {})", addr, url)
+            }
+            Some(ConnectionType::None) | None => "unknown".to_string(),
+        };
+
+        println!("\n=== Connection ===");
+        println!("Path:       {}", path);
+        println!("RTT:        {} ms", self.connection.rtt().as_millis());
+        println!("Throughput: {:.0} kbps (since last check)", kbps);
+        println!(
+            "Received:   {:.1} MB, lost {} of {} packets sent",
+            stats.udp_rx.bytes as f64 / 1_000_000.0,
+            stats.path.lost_packets,
+            stats.path.sent_packets
+        );
+        println!("==================\n");
+    }
+}
+
+/// Read broadcaster console commands; returns on `quit`
+///
+/// If stdin closes (e.g. running detached) the console just goes quiet and
+/// the station keeps broadcasting.
+async fn operator_console(broadcaster: &RadioBroadcaster, control: &SourceControl, can_skip: bool) {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match line.trim() {
+            "info" => {
+                let levels = broadcaster.level_meter().snapshot();
+                println!("\n=== {} ===", broadcaster.station_name());
+                println!("Description: {}", broadcaster.station_desc());
+                println!("Listeners:   {}", broadcaster.listener_count());
+                println!(
+                    "Outbound:    {} kbps",
+                    broadcaster.outbound_bytes_per_sec() * 8 / 1000
+                );
+                let nominal = broadcaster.bitrate() / 1000;
+                match broadcaster.encoded_bitrate() {
+                    0 => println!("Bitrate:     {} kbps nominal", nominal),
+                    achieved => println!(
+                        "Bitrate:     {} kbps nominal, {} kbps achieved",
+                        nominal,
+                        achieved / 1000
+                    ),
+                }
+                println!(
+                    "Source:      {}",
+                    if control.is_paused() {
+                        "paused"
+                    } else {
+                        "playing"
+                    }
+                );
+                println!("Peak:        {}", dbfs(&levels.peak));
+                println!("RMS:         {}", dbfs(&levels.rms));
+                if !levels.true_peak.is_empty() {
+                    println!("RMS (A):     {}", dbfs(&levels.rms_a));
+                    println!("True peak:   {}", dbfs(&levels.true_peak));
+                }
+                println!("Clipped:     {} samples\n", levels.clipped_samples);
+            }
+            "listeners" => {
+                let listeners = broadcaster.listeners();
+                if listeners.is_empty() {
+                    println!("No listeners connected");
+                }
+                for listener in listeners {
+                    println!(
+                        "  #{:<4} {} ({}s)",
+                        listener.id,
+                        listener.peer,
+                        listener.connected_at.elapsed().as_secs()
+                    );
+                }
+            }
+            "skip" if can_skip => {
+                control.skip();
+                println!("Skipping current track");
+            }
+            "skip" => println!("Nothing to skip on this source"),
+            "pause" => {
+                control.pause();
+                println!("Source paused");
+            }
+            "resume" => {
+                control.resume();
+                println!("Source resumed");
+            }
+            command if command.starts_with("set ") => {
+                let updated = info_update(&command["set ".len()..])
+                    .map_err(RadioError::InvalidRequest)
+                    .and_then(|update| broadcaster.update_station_info(update));
+                match updated {
+                    Ok(()) => println!("Station info updated"),
+                    Err(e) => println!("Not changed: {}", e.message()),
+                }
+            }
+            "quit" => return,
+            "" => {}
+            other => println!(
+                "Unknown command: '{}'. Try info, listeners, skip, pause, resume, set, quit",
+                other
+            ),
+        }
+    }
+
+    std::future::pending().await
+}
+
+/// Parse a console `set <field> <value>` into a station info change; tags
+/// are comma-separated, and an empty value clears genre, tags, or website
+fn info_update(args: &str) -> Result<StationInfoUpdate, String> {
+    let args = args.trim();
+    let (field, value) = args.split_once(' ').unwrap_or((args, ""));
+    let value = value.trim().to_string();
+    let mut update = StationInfoUpdate::default();
+    match field {
+        "name" => update.name = Some(value),
+        "description" => update.description = Some(value),
+        "genre" => update.genre = Some(value),
+        "website" => update.website = Some(value),
+        "tags" => update.tags = Some(value.split(',').map(str::to_string).collect()),
+        _ => {
+            return Err(format!(
+                "can't set '{}'; try name, description, genre, tags, or website",
+                field
+            ))
+        }
+    }
+    Ok(update)
+}
+
+/// Per-channel linear levels as dBFS, e.g. "-3.1 / -4.0 dBFS"
+fn dbfs(levels: &[f32]) -> String {
+    let channels: Vec<_> = levels
+        .iter()
+        .map(|&level| {
+            if level > 0.0 {
+                format!("{:.1}", 20.0 * level.log10())
+            } else {
+                "-inf".to_string()
+            }
+        })
+        .collect();
+    format!("{} dBFS", channels.join(" / "))
+}
+
+/// Go through broadcast setup without serving: the config has already been
+/// validated, so check that the source opens and the encoder builds
+async fn dry_run(
+    config: &BroadcastConfig,
+    options: BroadcastOptions,
+    network: &NetworkOptions,
+) -> anyhow::Result<()> {
+    println!("=== ZelFM Dry Run ===\n");
+    println!("Config:  ok");
+    println!("Network: {}", network.describe());
+
+    let (mut sample_rate, mut channels) = (44100, if config.mono() { 1 } else { 2 });
+    if let Some(upstream) = &config.relay {
+        let upstream = connect_upstream(upstream, network).await?;
+        println!(
+            "Source:  relay of '{}' ({} Hz, {} ch)",
+            upstream.info.name, upstream.info.sample_rate, upstream.info.channels
+        );
+        (sample_rate, channels) = (upstream.info.sample_rate, upstream.info.channels);
+    } else if let Some(file) = &config.file {
+        let report = zelfm::audio_source::probe_file(file)
+            .map_err(|e| anyhow::anyhow!("Can't decode {}: {}", file, e))?;
+        println!("Source:  file {} ({})", file, report.codec);
+    } else if let Some(playlist) = &config.playlist {
+        let mut entries = zelfm::playlist::load(playlist.as_ref())?;
+        if let Some(manifest) = &config.manifest {
+            zelfm::playlist::apply_manifest(&mut entries, manifest.as_ref())?;
+        }
+        let playable = probe_entries(&entries)?;
+        println!(
+            "Source:  playlist {} ({} of {} entries playable)",
+            playlist,
+            playable,
+            entries.len()
+        );
+    } else if let Some(dir) = &config.dir {
+        let scan = DirectoryScan {
+            root: dir.into(),
+            recursive: config.recursive(),
+            order: config.order(),
+        };
+        let entries = scan.scan()?;
+        if entries.is_empty() && !config.watch() {
+            anyhow::bail!("No audio files found in {}", scan.root.display());
+        }
+        let playable = probe_entries(&entries)?;
+        println!(
+            "Source:  directory {} ({} of {} files playable)",
+            dir,
+            playable,
+            entries.len()
+        );
+    } else if config.stdin() {
+        println!("Source:  stdin (not checked until audio is piped in)");
+    } else if let Some(pipe) = &config.pipe {
+        // Opening a FIFO would wait for a writer, so only check that it's there
+        std::fs::metadata(pipe).map_err(|e| anyhow::anyhow!("Can't use pipe {}: {}", pipe, e))?;
+        println!(
+            "Source:  pipe {} ({} Hz, {} ch, {})",
+            pipe,
+            config.pcm_rate(),
+            config.pcm_channels(),
+            config.pcm_format()
+        );
+        sample_rate = config.pcm_rate();
+    } else if let Some(device_name) = &config.input {
+        #[cfg(feature = "live-input")]
+        {
+            use cpal::traits::DeviceTrait;
+
+            let host = cpal::default_host();
+            let device = zelfm::devices::select_input_device(&host, Some(device_name))?;
+            let input = device.default_input_config()?;
+            println!(
+                "Source:  live input {} ({} Hz, {} ch)",
+                device.name()?,
+                input.sample_rate().0,
+                input.channels()
+            );
+        }
+        #[cfg(not(feature = "live-input"))]
+        anyhow::bail!(
+            "Live input '{}' requested but zelfm was built without the `live-input` feature",
+            device_name
+        );
+    }
+
+    check_source_format(config, sample_rate).await?;
+
+    if let Some(jingle) = &config.jingle {
+        println!("Jingle:  {}", check_jingle(jingle, config, sample_rate)?);
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(addr) = config.http_addr {
+        tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Can't bind HTTP address {}: {}", addr, e))?;
+        println!("HTTP:    {} is free", addr);
+    }
+
+    // No rewind encoder: nothing will be fed to it
+    let options = BroadcastOptions {
+        rewind: None,
+        ..options
+    };
+    let codec = options.codec;
+    let (broadcaster, _pcm_tx) = RadioBroadcaster::with_options(
+        config.name(),
+        config.description(),
+        sample_rate,
+        channels,
+        options,
+    );
+    let bytes = broadcaster
+        .check_encoder()
+        .map_err(|e| anyhow::anyhow!("Encoder: {}", e))?;
+    println!(
+        "Encoder: {} at {} Hz, {} ch ({} bytes for 1s of silence)",
+        codec, sample_rate, channels, bytes
+    );
+
+    println!("\nDry run OK: '{}' is ready to broadcast", config.name());
+    Ok(())
+}
+
+/// Compare the source's sample rate with the station's, warning about each
+/// mismatch (or refusing to start with `strict_format`)
+///
+/// Sources aren't resampled, so a 48 kHz file on a 44.1 kHz station plays
+/// slow and flat. Relays and pipes set the station's rate themselves, and
+/// stdin can't be checked before it's read (validation refuses it with
+/// `strict_format`). Channels are always conformed, so only the rate matters.
+async fn check_source_format(config: &BroadcastConfig, sample_rate: u32) -> anyhow::Result<()> {
+    /// Name this many mismatched files, then just count the rest
+    const MAX_LISTED: usize = 5;
+
+    let mut paths: Vec<std::path::PathBuf> = Vec::new();
+    if let Some(file) = &config.file {
+        paths.push(file.into());
+    } else if let Some(playlist) = &config.playlist {
+        paths.extend(
+            zelfm::playlist::load(playlist.as_ref())?
+                .into_iter()
+                .map(|entry| entry.path),
+        );
+    } else if let Some(dir) = &config.dir {
+        let scan = DirectoryScan {
+            root: dir.into(),
+            recursive: config.recursive(),
+            order: config.order(),
+        };
+        paths.extend(scan.scan()?.into_iter().map(|entry| entry.path));
+    }
+    if let Some(fallback) = &config.fallback {
+        paths.extend(fallback_entries(fallback)?.into_iter().map(|e| e.path));
+    }
+
+    let mut mismatched = mismatched_rates(paths, sample_rate).await;
+
+    #[cfg(feature = "live-input")]
+    if let Some(device_name) = &config.input {
+        use cpal::traits::DeviceTrait;
+
+        let device = zelfm::devices::select_input_device(&cpal::default_host(), Some(device_name))?;
+        let channels = if config.mono() { 1 } else { 2 };
+        let input = zelfm::devices::best_input_config(&device, sample_rate, channels)?;
+        if input.sample_rate().0 != sample_rate {
+            mismatched.push(format!(
+                "input {} runs at {} Hz",
+                device.name()?,
+                input.sample_rate().0
+            ));
+        }
+    }
+
+    if mismatched.is_empty() {
+        return Ok(());
+    }
+    let count = mismatched.len();
+    let more = count.saturating_sub(MAX_LISTED);
+    mismatched.truncate(MAX_LISTED);
+    if more > 0 {
+        mismatched.push(format!("and {} more", more));
+    }
+    let summary = format!(
+        "The station broadcasts at {} Hz and sources aren't resampled, so {} will play at the wrong speed and pitch:\n  {}",
+        sample_rate,
+        if count == 1 { "this" } else { "these" },
+        mismatched.join("\n  ")
+    );
+    if config.strict_format() {
+        anyhow::bail!("{}\nRefusing to start (--strict-format)", summary);
+    }
+    eprintln!("Warning: {}", summary);
+    eprintln!("Convert them first, or pass --strict-format to refuse to start instead");
+    Ok(())
+}
+
+/// Describe each of `paths` that isn't at `sample_rate`, in order
+///
+/// Probing reads and decodes the start of every file, so it runs on the
+/// blocking pool a few files at a time. Unreadable files are skipped; they're
+/// reported when they're played.
+async fn mismatched_rates(paths: Vec<std::path::PathBuf>, sample_rate: u32) -> Vec<String> {
+    use futures::StreamExt;
+
+    let workers = std::thread::available_parallelism().map_or(4, usize::from);
+    futures::stream::iter(paths)
+        .map(|path| {
+            tokio::task::spawn_blocking(move || {
+                let rate = zelfm::audio_source::probe_file(&path).ok()?.sample_rate?;
+                (rate != sample_rate).then(|| format!("{} is {} Hz", path.display(), rate))
+            })
+        })
+        .buffered(workers)
+        .filter_map(|probed| async move { probed.ok().flatten() })
+        .collect()
+        .await
+}
+
+/// Make sure the jingle decodes and matches the station's rate (refusing a
+/// mismatch under `--strict-format`, else warning); returns a one-line description
+fn check_jingle(path: &str, config: &BroadcastConfig, sample_rate: u32) -> anyhow::Result<String> {
+    let report = zelfm::audio_source::probe_file(path)
+        .map_err(|e| anyhow::anyhow!("Can't decode jingle {}: {}", path, e))?;
+    if let Some(rate) = report.sample_rate.filter(|&r| r != sample_rate) {
+        let problem = format!(
+            "jingle {} is {} Hz and isn't resampled to the station's {} Hz",
+            path, rate, sample_rate
+        );
+        if config.strict_format() {
+            anyhow::bail!("The {}\nRefusing to start (--strict-format)", problem);
+        }
+        eprintln!("Warning: {}", problem);
+    }
+    Ok(match config.jingle_every() {
+        1 => format!("{} between tracks", path),
+        n => format!("{} after every {} tracks", path, n),
+    })
+}
+
+/// Probe each entry, warning about ones that won't play; errors if none will
+fn probe_entries(entries: &[zelfm::playlist::PlaylistEntry]) -> anyhow::Result<usize> {
+    let mut playable = 0;
+    for entry in entries {
+        match zelfm::audio_source::probe_file(&entry.path) {
+            Ok(_) => playable += 1,
+            Err(e) => eprintln!("Warning: {} won't play: {}", entry.path.display(), e),
+        }
+    }
+    if playable == 0 && !entries.is_empty() {
+        anyhow::bail!("None of the {} entries can be decoded", entries.len());
+    }
+    Ok(playable)
+}
+
+fn probe(path: &std::path::Path) -> anyhow::Result<()> {
+    let report = zelfm::audio_source::probe_file(path)
+        .map_err(|e| anyhow::anyhow!("Can't decode {}: {}", path.display(), e))?;
+
+    let unknown = || "unknown".to_string();
+    println!("=== {} ===", path.display());
+    println!("Codec:       {} ({})", report.codec, report.sample_format);
+    println!(
+        "Sample Rate: {}",
+        report
+            .sample_rate
+            .map_or_else(unknown, |r| format!("{} Hz", r))
+    );
+    println!(
+        "Channels:    {}",
+        report.channels.map_or_else(unknown, |c| c.to_string())
+    );
+    println!(
+        "Duration:    {}",
+        report.duration_secs.map_or_else(unknown, |secs| {
+            let secs = secs.round() as u64;
+            format!("{}:{:02}", secs / 60, secs % 60)
+        })
+    );
+    if let Some(rate) = report.sample_rate.filter(|&r| r != 44100) {
+        println!(
+            "Note: stations broadcast at 44100 Hz; this file is {} Hz and isn't resampled",
+            rate
+        );
+    }
+
+    if !report.tags.is_empty() {
+        println!("\nTags:");
+        for (key, value) in &report.tags {
+            println!("  {}: {}", key, value);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_directory(network: &NetworkOptions) -> anyhow::Result<()> {
+    println!("=== ZelFM Directory ===\n");
+
+    let endpoint = network.bind().await?;
+    println!("Directory Node ID: {}", endpoint.id());
+    println!("Broadcasters register with: zelfm broadcast --directory <ID> ...");
+    println!("Listeners browse with:      zelfm browse --directory <ID>\n");
+
+    let server = RpcServerBuilder::new(DIRECTORY_ALPN, endpoint.clone()).service("directory");
+    let server = Directory::new()
+        .into_service_builder(server)
+        .build()
+        .build();
+    let bundle = zelfm::network::serve(endpoint, DIRECTORY_ALPN, server);
+
+    tokio::signal::ctrl_c().await?;
+    println!("\nShutting down...");
+    bundle.shutdown(Duration::from_secs(1)).await?;
+    Ok(())
+}
+
+fn bench(seconds: Option<u64>, quality: Option<f32>, channels: Option<u8>) -> anyhow::Result<()> {
+    let defaults = BenchOptions::default();
+    let options = BenchOptions {
+        duration: seconds.map_or(defaults.duration, Duration::from_secs),
+        quality: quality.unwrap_or(defaults.quality),
+        channels: channels.unwrap_or(defaults.channels),
+        ..defaults
+    };
+    if options.duration.is_zero() {
+        anyhow::bail!("--seconds must be at least 1");
+    }
+    if !(MIN_QUALITY..=MAX_QUALITY).contains(&options.quality) {
+        anyhow::bail!(
+            "--quality must be between {} and {}",
+            MIN_QUALITY,
+            MAX_QUALITY
+        );
+    }
+
+    println!(
+        "Encoding {} s of {} Hz {} at quality {}...",
+        options.duration.as_secs(),
+        options.sample_rate,
+        if options.channels == 1 {
+            "mono"
+        } else {
+            "stereo"
+        },
+        options.quality
+    );
+    let report = zelfm::bench::run(&options)?;
+
+    for (name, pass) in [("Encode", &report.encode), ("Decode", &report.decode)] {
+        println!(
+            "{}:  {:>7.2} s  {:>7.1}x realtime  {:>6.2} M samples/s  {:>7.1} MB/s PCM",
+            name,
+            pass.elapsed.as_secs_f64(),
+            pass.realtime(options.sample_rate),
+            pass.samples_per_sec(options.channels) / 1_000_000.0,
+            pass.pcm_mb_per_sec(options.channels)
+        );
+    }
+    println!(
+        "Output:  {} KB, {} kbps",
+        report.encoded_bytes / 1024,
+        report.bitrate(options.sample_rate) / 1000
+    );
+    Ok(())
+}
+
+async fn whoami(network: &NetworkOptions) -> anyhow::Result<()> {
+    let bundle = network.client_bundle().await?;
 
-#[cfg(feature = "live-input")]
-use audio_source::LiveSource;
+    // Tickets carry relay/direct addresses; wait briefly for them to be known
+    let _ = tokio::time::timeout(Duration::from_secs(5), bundle.endpoint.online()).await;
+    println!("Node ID: {}", bundle.endpoint.id());
+    println!("Ticket:  {}", StationTicket::new(bundle.endpoint.addr()));
+    match &network.identity {
+        Some(path) => println!("Identity: {}", path.display()),
+        None => println!("  (No --identity, so this ID is new every run and won't be reused.)"),
+    }
 
-#[derive(Parser)]
-#[command(name = "zelfm")]
-#[command(about = "P2P Internet Radio - File & Live Streaming")]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
+    bundle.shutdown(Duration::from_secs(1)).await?;
+    Ok(())
 }
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Start broadcasting a radio station
-    Broadcast {
-        /// Station name
-        #[arg(short, long, default_value = "ZelFM Demo")]
-        name: String,
-
-        #[command(flatten)]
-        source: AudioSourceArgs,
-    },
+async fn browse_directory(
+    directory: String,
+    search: Option<String>,
+    network: &NetworkOptions,
+) -> anyhow::Result<()> {
+    let directory = parse_node_id(&directory)?;
+    let bundle = network.client_bundle().await?;
+    let client = zelfm::directory::connect(&bundle.endpoint, directory).await?;
 
-    /// List available input devices
-    #[cfg(feature = "live-input")]
-    ListDevices,
+    let stations = match search {
+        Some(query) => client.search(query).await?,
+        None => client.list().await?,
+    };
 
-    /// Listen to a radio station
-    Listen {
-        /// Broadcaster node ID
-        #[arg(short, long)]
-        node_id: String,
+    if stations.is_empty() {
+        println!("No stations found.");
+    } else {
+        println!("\n=== Stations ({}) ===\n", stations.len());
+        for station in &stations {
+            println!("{} ({} listening)", station.name, station.listeners);
+            if !station.description.is_empty() {
+                println!("  {}", station.description);
+            }
+            println!("  zelfm listen --node-id {}\n", station.node_id);
+        }
+    }
 
-        /// Max listening duration in seconds (optional)
-        #[arg(short, long)]
-        duration: Option<u64>,
-    },
+    bundle.shutdown(Duration::from_secs(1)).await?;
+    Ok(())
 }
 
-#[derive(Args)]
-#[group(required = true, multiple = false)]
-struct AudioSourceArgs {
-    /// Audio file to broadcast (loops)
-    #[arg(short, long)]
-    file: Option<String>,
+/// Look up a station by name in a directory, returning its node ID
+async fn resolve_station(
+    endpoint: &iroh::Endpoint,
+    directory: &str,
+    station: &str,
+) -> anyhow::Result<iroh::PublicKey> {
+    let directory = parse_node_id(directory)?;
+    let client = zelfm::directory::connect(endpoint, directory).await?;
+    let matches = client.search(station.to_string()).await?;
 
-    /// Live input device name (partial match, use list-devices to see options)
-    #[cfg(feature = "live-input")]
-    #[arg(short, long)]
-    input: Option<String>,
+    // Prefer an exact (case-insensitive) name match over substring hits
+    let exact: Vec<_> = matches
+        .iter()
+        .filter(|s| s.name.eq_ignore_ascii_case(station))
+        .collect();
+    let chosen = match (exact.as_slice(), matches.as_slice()) {
+        ([only], _) => *only,
+        ([], [only]) => only,
+        ([], []) => anyhow::bail!("No station named '{}' in the directory", station),
+        _ => {
+            let names: Vec<_> = matches.iter().map(|s| s.name.as_str()).collect();
+            anyhow::bail!(
+                "'{}' matches several stations: {}. Be more specific or use --node-id",
+                station,
+                names.join(", ")
+            )
+        }
+    };
+
+    eprintln!("Found '{}' in directory", chosen.name);
+    parse_node_id(&chosen.node_id)
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    env_logger::init();
-    let cli = Cli::parse();
+/// Parse a node ID, explaining what was wrong instead of a bare decode error
+fn parse_node_id(input: &str) -> anyhow::Result<iroh::PublicKey> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        anyhow::bail!("Node ID is empty - copy the 'Node ID' line printed by `zelfm broadcast`");
+    }
 
-    match cli.command {
-        Commands::Broadcast { name, source } => broadcast_station(name, source).await?,
+    trimmed.parse().map_err(|e| {
+        anyhow::anyhow!(
+            "'{}' is not a valid node ID ({}). Expected the 64-character key \
+             printed as 'Node ID' by `zelfm broadcast`",
+            trimmed,
+            e
+        )
+    })
+}
 
-        #[cfg(feature = "live-input")]
-        Commands::ListDevices => {
-            devices::list_input_devices()?;
-        }
+/// Tracks the last chat message shown so replays after a resubscribe
+/// neither duplicate nor skip messages
+#[derive(Default)]
+struct ChatCursor {
+    last_seq: u64,
+    last_timestamp: Option<u64>,
+    /// Prefix messages with when they were sent, if set
+    clock: Option<ChatClock>,
+}
 
-        Commands::Listen { node_id, duration } => listen_to_station(node_id, duration).await?,
-    }
+/// Where the listener's `save` command notes tracks by default
+const LIKED_TRACKS_FILE: &str = "liked-tracks.txt";
 
-    Ok(())
+/// The station's current track, as far as its events have told us
+#[derive(Default)]
+enum NowPlaying {
+    /// The station hasn't said: it plays live input, or it predates events
+    /// (or replaying the current track to new subscribers)
+    #[default]
+    Unknown,
+    /// A track started, with its title if it has one
+    Track(Option<String>),
 }
 
-async fn broadcast_station(name: String, source: AudioSourceArgs) -> anyhow::Result<()> {
-    println!("=== ZelFM Broadcaster ===\n");
+/// Append `title` to the liked-tracks file at `path`, one line per track:
+/// local time, title, then the station (and its website) when known
+///
+/// Titles come from the station's track events, which fire as the station
+/// decodes a track. That is a little ahead of when it reaches the speakers.
+fn save_liked_track(
+    path: &std::path::Path,
+    title: &str,
+    station: Option<&StationInfo>,
+) -> std::io::Result<()> {
+    use std::io::Write;
 
-    // Create broadcaster
-    let (broadcaster, pcm_tx) = RadioBroadcaster::new(
-        name.clone(),
-        "Live P2P Radio Stream",
-        44100, // Target: 44.1 kHz
-        2,     // Target: Stereo
+    let mut line = format!(
+        "{}\t{}",
+        jiff::Zoned::now().strftime("%Y-%m-%d %H:%M:%S"),
+        title
     );
+    if let Some(station) = station {
+        line.push_str(&format!("\t{}", station.name));
+        if let Some(website) = &station.website {
+            line.push_str(&format!(" ({})", website));
+        }
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)
+}
 
-    // Keep a clone to drop on shutdown
-    let pcm_tx_shutdown = pcm_tx.clone();
+/// How chat timestamps are shown
+#[derive(Clone, Copy)]
+enum ChatClock {
+    Local,
+    Utc,
+}
 
-    // Determine and start audio source
-    std::thread::spawn(move || {
-        let result = if let Some(file_path) = source.file {
-            // File source
-            println!("Source: File ({})", file_path);
-            let audio_source = FileSource::new(file_path);
-            audio_source.start(pcm_tx)
+impl ChatClock {
+    /// `HH:MM` for messages sent today, with the date for anything older
+    ///
+    /// The timestamp comes from the broadcaster's clock, so one running ahead
+    /// of ours is shown as now rather than in the future. Returns `None` for a
+    /// missing (zero) or unrepresentable timestamp.
+    fn format(self, timestamp: u64) -> Option<String> {
+        use jiff::{tz::TimeZone, Timestamp};
+
+        if timestamp == 0 {
+            return None;
+        }
+        let now = Timestamp::now();
+        let sent = Timestamp::from_second(i64::try_from(timestamp).ok()?)
+            .ok()?
+            .min(now);
+        let zone = match self {
+            ChatClock::Local => TimeZone::system(),
+            ChatClock::Utc => TimeZone::UTC,
+        };
+        let (sent, now) = (sent.to_zoned(zone.clone()), now.to_zoned(zone));
+        let format = if sent.date() == now.date() {
+            "%H:%M"
         } else {
-            #[cfg(feature = "live-input")]
-            if let Some(device_name) = source.input {
-                // Live input source
-                println!("Source: Live Input ({})", device_name);
-                let audio_source = LiveSource::new(Some(device_name));
-                audio_source.start(pcm_tx)
-            } else {
-                Err(anyhow::anyhow!("No audio source specified"))
+            "%Y-%m-%d %H:%M"
+        };
+        Some(sent.strftime(format).to_string())
+    }
+}
+
+impl ChatCursor {
+    fn new(clock: Option<ChatClock>) -> Self {
+        Self {
+            clock,
+            ..Default::default()
+        }
+    }
+
+    /// Show history fetched after a resubscribe, skipping what was already shown
+    fn catch_up(&mut self, missed: Vec<zelfm::service::ChatMessage>) {
+        missed.into_iter().for_each(|chat| self.show(chat));
+    }
+
+    fn show(&mut self, chat: zelfm::service::ChatMessage) {
+        if !self.advance(&chat) {
+            return;
+        }
+
+        let display_name = chat
+            .nickname
+            .unwrap_or_else(|| format!("Listener {}", chat.listener_id));
+        match self.clock.and_then(|clock| clock.format(chat.timestamp)) {
+            Some(time) => println!("\r{} [{}]: {}", time, display_name, chat.message),
+            None => println!("\r[{}]: {}", display_name, chat.message),
+        }
+        print!("> ");
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+    }
+
+    /// Move past `chat`, or return false if it was already shown
+    fn advance(&mut self, chat: &zelfm::service::ChatMessage) -> bool {
+        // seq 0 means an older broadcaster without sequencing - can't dedupe
+        if chat.seq != 0 {
+            if chat.seq <= self.last_seq {
+                return false;
             }
+            self.last_seq = chat.seq;
+        }
+        self.last_timestamp = Some(chat.timestamp);
+        true
+    }
+}
 
-            #[cfg(not(feature = "live-input"))]
-            Err(anyhow::anyhow!("No audio source specified"))
+/// The station a relay rebroadcasts
+struct Upstream {
+    /// Keeps the upstream connection's endpoint alive
+    _bundle: IrohBundle,
+    client: RadioServiceClient,
+    info: StationInfo,
+    capabilities: SourceCapabilities,
+    /// The upstream's node ID and its own signed info, passed on to listeners
+    signed_info: Option<(iroh::PublicKey, SignedStationInfo)>,
+}
+
+/// Connect to the station to relay, given its node ID or ticket
+async fn connect_upstream(upstream: &str, network: &NetworkOptions) -> anyhow::Result<Upstream> {
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+    let target: iroh::EndpointAddr = match upstream.parse::<StationTicket>() {
+        Ok(ticket) => ticket.addr,
+        Err(_) => parse_node_id(upstream)?.into(),
+    };
+    let node_id = target.id;
+    println!("Connecting to upstream {}...", node_id);
+
+    let bundle = network.client_bundle().await?;
+    let connection =
+        match tokio::time::timeout(CONNECT_TIMEOUT, bundle.endpoint.connect(target, ALPN)).await {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(e)) => anyhow::bail!("Couldn't connect to upstream {}: {}", node_id, e),
+            Err(_) => anyhow::bail!(
+                "Upstream unreachable: no response from {} after {} seconds",
+                node_id,
+                CONNECT_TIMEOUT.as_secs()
+            ),
         };
 
-        if let Err(e) = result {
-            eprintln!("[Audio] Error: {}", e);
+    let rpc_client = zel_core::protocol::client::RpcClient::new(connection).await?;
+    let client = RadioServiceClient::new(rpc_client);
+    let info = client
+        .get_info()
+        .await
+        .map_err(|e| anyhow::anyhow!(RadioError::describe(&e)))?;
+    // Older stations don't expose capabilities
+    let capabilities = client.capabilities().await.unwrap_or_default();
+    if info.codec != StreamCodec::Vorbis {
+        anyhow::bail!(
+            "Upstream {} streams {}; only Vorbis stations can be relayed",
+            node_id,
+            info.codec
+        );
+    }
+    let signed_info = if info.supports(SIGNING_CONTEXT_VERSION) {
+        match client.get_signed_info().await {
+            Ok(signed) => match signed.verify(&node_id) {
+                Ok(_) => Some((node_id, signed)),
+                Err(e) => {
+                    warn!("[Relay] Upstream info failed verification: {}", e);
+                    None
+                }
+            },
+            // Stations run without a signing key
+            Err(_) => None,
         }
-    });
+    } else {
+        None
+    };
 
-    // Setup Iroh
-    let mut server_bundle = IrohBundle::builder(None).await?;
-    let node_id = server_bundle.endpoint().id();
+    Ok(Upstream {
+        _bundle: bundle,
+        client,
+        info,
+        capabilities,
+        signed_info,
+    })
+}
 
-    println!("Node ID: {}", node_id);
-    println!("Station: {}", name);
-    println!("\nWaiting for listeners...\n");
+/// `--fallback` as playlist entries: an M3U/PLS playlist, or a single file
+fn fallback_entries(path: &str) -> anyhow::Result<Vec<PlaylistEntry>> {
+    let path = std::path::Path::new(path);
+    let is_playlist = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["m3u", "m3u8", "pls"]
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+    if is_playlist {
+        return zelfm::playlist::load(path);
+    }
+    if !path.is_file() {
+        anyhow::bail!("Fallback {} not found", path.display());
+    }
+    Ok(vec![PlaylistEntry {
+        path: path.to_path_buf(),
+        title: None,
+        settings: Default::default(),
+    }])
+}
 
-    // Connection hook to assign unique listener IDs
-    let listener_id_counter = Arc::new(AtomicUsize::new(0));
+/// How the station's audio source finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceExit {
+    /// Ran out on its own (end of file, end of stdin)
+    Ended,
+    /// Kept failing, or failed where it can't be restarted
+    Failed,
+}
 
-    // Build server with connection hook
-    let server = RpcServerBuilder::new(b"zelfm/1", server_bundle.endpoint().clone())
-        .with_connection_hook(move |_conn, _server_ext| {
-            let counter = listener_id_counter.clone();
-            Box::pin(async move {
-                let id = counter.fetch_add(1, Ordering::Relaxed);
-                info!("[Server] Assigned listener ID: {}", id);
+/// Restarts allowed within [`RESTART_WINDOW`] before a failing source is given up on
+const MAX_SOURCE_RESTARTS: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
 
-                Ok(Extensions::new().with(ListenerInfo { id, nickname: None }))
-            })
-        })
-        .service("radio");
+/// Wait before a restart, times the restarts already in the window
+const RESTART_DELAY: Duration = Duration::from_secs(1);
 
-    let server = broadcaster.into_service_builder(server).build().build();
-    let server_bundle = server_bundle.accept(b"zelfm/1", server).finish().await;
+/// Run an audio source on its own thread, feeding the PCM broadcast channel;
+/// the returned receiver resolves with how it stopped, or with an error if
+/// the thread panicked
+fn spawn_source<S: AudioSource>(
+    source: S,
+    pcm_tx: tokio::sync::broadcast::Sender<Arc<Vec<Vec<f32>>>>,
+) -> tokio::sync::oneshot::Receiver<anyhow::Result<()>> {
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = done_tx.send(source.start(pcm_tx));
+    });
+    done_rx
+}
 
-    // Run until Ctrl+C
-    tokio::signal::ctrl_c().await?;
-    println!("\nShutting down...");
+/// Run `source` and, when `restart` is set, start a fresh copy whenever it
+/// errors or panics, backing off between tries (a playlist's copies share
+/// its position, so it carries on rather than starting over). Listeners
+/// stay connected throughout and just hear a gap. Resolves once the source ends on its own
+/// or fails more than [`MAX_SOURCE_RESTARTS`] times in [`RESTART_WINDOW`].
+fn supervise_source<S: AudioSource + Clone>(
+    source: S,
+    pcm_tx: tokio::sync::broadcast::Sender<Arc<Vec<Vec<f32>>>>,
+    restart: bool,
+) -> BoxFuture<'static, SourceExit> {
+    async move {
+        let mut restarts = std::collections::VecDeque::new();
+        loop {
+            let error = match spawn_source(source.clone(), pcm_tx.clone()).await {
+                Ok(Ok(())) => return SourceExit::Ended,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "the source thread panicked".to_string(),
+            };
+            error!("[Audio] Source stopped: {}", error);
 
-    // Drop the broadcast sender to signal audio thread to stop
-    drop(pcm_tx_shutdown);
+            let now = std::time::Instant::now();
+            restarts.retain(|at| now.duration_since(*at) < RESTART_WINDOW);
+            if !restart || restarts.len() >= MAX_SOURCE_RESTARTS {
+                return SourceExit::Failed;
+            }
+            restarts.push_back(now);
+            let delay = RESTART_DELAY * restarts.len() as u32;
+            error!(
+                "[Audio] Source failed ({}); restarting in {}s ({}/{} this minute)",
+                error,
+                delay.as_secs(),
+                restarts.len(),
+                MAX_SOURCE_RESTARTS
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+    .boxed()
+}
 
-    server_bundle.shutdown(Duration::from_secs(1)).await?;
+/// The decode settings shared by file and playlist sources
+trait DecodedSource: Sized {
+    fn with_realtime(self) -> Self;
+    fn with_max_decode_ahead(self, ahead: Duration) -> Self;
+    fn with_replay_gain(self) -> Self;
+    fn with_silence_trim(self, threshold_db: f32) -> Self;
+    fn with_backpressure(self, max_queued: usize) -> Self;
+}
 
-    Ok(())
+impl DecodedSource for FileSource {
+    fn with_realtime(self) -> Self {
+        FileSource::with_realtime(self)
+    }
+    fn with_max_decode_ahead(self, ahead: Duration) -> Self {
+        FileSource::with_max_decode_ahead(self, ahead)
+    }
+    fn with_replay_gain(self) -> Self {
+        FileSource::with_replay_gain(self)
+    }
+    fn with_silence_trim(self, threshold_db: f32) -> Self {
+        FileSource::with_silence_trim(self, threshold_db)
+    }
+    fn with_backpressure(self, max_queued: usize) -> Self {
+        FileSource::with_backpressure(self, max_queued)
+    }
+}
+
+impl DecodedSource for PlaylistSource {
+    fn with_realtime(self) -> Self {
+        PlaylistSource::with_realtime(self)
+    }
+    fn with_max_decode_ahead(self, ahead: Duration) -> Self {
+        PlaylistSource::with_max_decode_ahead(self, ahead)
+    }
+    fn with_replay_gain(self) -> Self {
+        PlaylistSource::with_replay_gain(self)
+    }
+    fn with_silence_trim(self, threshold_db: f32) -> Self {
+        PlaylistSource::with_silence_trim(self, threshold_db)
+    }
+    fn with_backpressure(self, max_queued: usize) -> Self {
+        PlaylistSource::with_backpressure(self, max_queued)
+    }
+}
+
+/// Apply the config's pacing, gain, and silence trim to a decoding source,
+/// and hold it to the slowest listener when `backpressure` is given
+fn with_decode_settings<S: DecodedSource>(
+    mut source: S,
+    config: &BroadcastConfig,
+    backpressure: Option<usize>,
+) -> S {
+    if config.realtime() {
+        source = source.with_realtime();
+    }
+    if let Some(ahead) = config.max_decode_ahead() {
+        source = source.with_max_decode_ahead(ahead);
+    }
+    if config.replay_gain() {
+        source = source.with_replay_gain();
+    }
+    if let Some(threshold_db) = config.trim_silence() {
+        source = source.with_silence_trim(threshold_db);
+    }
+    if let Some(max_queued) = backpressure {
+        source = source.with_backpressure(max_queued);
+    }
+    source
+}
+
+/// Longest a full PCM queue of `capacity` blocks takes to play out, so the
+/// fade-out wait covers audio queued ahead of the faded tail
+fn queued_audio(capacity: usize, sample_rate: u32) -> Duration {
+    let frames = capacity * zelfm::audio_source::DECODE_BLOCK_FRAMES;
+    Duration::from_secs_f64(frames as f64 / f64::from(sample_rate.max(1)))
+}
+
+/// Ramp the source down and wait for the faded tail to reach the encoders,
+/// giving up after `limit` (a paused source never gets there)
+async fn fade_out(
+    fader: &Fader,
+    pcm_tx: &tokio::sync::broadcast::Sender<Arc<Vec<Vec<f32>>>>,
+    limit: Duration,
+) {
+    fader.fade_out();
+    let drained = async {
+        while !fader.faded_out() || !pcm_tx.is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    let _ = tokio::time::timeout(limit, drained).await;
 }
 
-async fn listen_to_station(node_id_str: String, duration: Option<u64>) -> anyhow::Result<()> {
-    println!("=== ZelFM Listener ===\n");
+async fn listen_to_station(args: ListenArgs, network: &NetworkOptions) -> anyhow::Result<()> {
+    // With --pcm-out, stdout carries audio, so status goes to stderr
+    if args.pcm_out {
+        eprintln!("=== ZelFM Listener ===\n");
+    } else {
+        println!("=== ZelFM Listener ===\n");
+    }
+
+    let duration = args.duration;
+    let client_bundle = network.client_bundle().await?;
+
+    let target: iroh::EndpointAddr = match (&args.ticket, &args.node_id, &args.station) {
+        (Some(ticket), _, _) => {
+            let ticket: StationTicket = ticket
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid ticket: {}", e))?;
+            ticket.addr
+        }
+        (None, Some(node_id), _) => parse_node_id(node_id)?.into(),
+        (None, None, Some(station)) => {
+            let directory = args
+                .directory
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("--station needs --directory"))?;
+            resolve_station(&client_bundle.endpoint, directory, station)
+                .await?
+                .into()
+        }
+        _ => anyhow::bail!("Specify --ticket, --node-id, or --station with --directory"),
+    };
+    let node_id = target.id;
 
-    let node_id: iroh::PublicKey = node_id_str.parse()?;
-    let client_bundle = IrohBundle::builder(None).await?.finish().await;
+    if args.pcm_out {
+        eprintln!("Connecting to {}...", node_id);
+    } else {
+        println!("Connecting to {}...", node_id);
+    }
+    let connect_timeout = Duration::from_secs(args.connect_timeout);
+    let connection = match tokio::time::timeout(
+        connect_timeout,
+        client_bundle.endpoint.connect(target, ALPN),
+    )
+    .await
+    {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(e)) => anyhow::bail!("Couldn't connect to station {}: {}", node_id, e),
+        Err(_) => anyhow::bail!(
+            "Station unreachable: no response from {} after {} seconds \
+             (is the broadcaster running?)",
+            node_id,
+            connect_timeout.as_secs()
+        ),
+    };
+    info!("[Listener] Connected to {}", node_id);
 
-    info!("[Listener] Connecting to {}", node_id);
-    let connection = client_bundle.endpoint.connect(node_id, b"zelfm/1").await?;
+    // Kept for `netstats`; the RPC client takes ownership of the original
+    let mut net_stats = NetStats::new(client_bundle.endpoint.clone(), connection.clone(), node_id);
 
     let rpc_client = zel_core::protocol::client::RpcClient::new(connection).await?;
     let radio_client = RadioServiceClient::new(rpc_client);
 
     // Show initial station info
-    let listener = RadioListener::new(radio_client.clone());
+    let mut listener = RadioListener::new(radio_client.clone()).with_station_id(node_id);
+    if args.spectrum {
+        listener = listener.with_spectrum(args.fft_size);
+    }
+    if let Some(path) = args.record {
+        listener = listener.with_recording(path, args.record_format);
+    }
+    if let Some(seconds) = args.rewind {
+        listener = listener.with_rewind(seconds);
+    }
+    if let Some(quality) = args.quality {
+        listener = listener.with_quality(quality);
+    }
+    if let Some(rate) = args.output_rate {
+        listener = listener.with_output_rate(rate);
+    }
+    if args.night_mode {
+        listener = listener.with_night_mode();
+    }
+    if let Some(bytes) = args.read_chunk {
+        listener = listener.with_read_chunk(bytes as usize);
+    }
+    if let Some(chunks) = args.recv_queue {
+        listener = listener.with_recv_queue(chunks as usize);
+    }
+
+    if args.pcm_out {
+        // Pipe mode: no station info on stdout and no interactive prompt
+        return listener
+            .with_pcm_out(args.pcm_format)
+            .with_playback(args.also_play)
+            .listen(duration)
+            .await;
+    }
+
     listener.get_station_info().await?;
 
     // Start listening in background task
     let listen_task = tokio::spawn(async move {
         if let Err(e) = listener.listen(duration).await {
-            eprintln!("Listen error: {}", e);
+            error!("[Listener] {}", e);
         }
     });
 
-    // Subscribe to chat stream
-    use futures::StreamExt;
+    // Subscribe to chat stream, resubscribing (and catching up) if it drops
     let mut chat_stream = radio_client.chat_stream().await?;
+    let chat_client = radio_client.clone();
+    let chat_clock = match (args.timestamps, args.utc) {
+        (false, _) => None,
+        (true, false) => Some(ChatClock::Local),
+        (true, true) => Some(ChatClock::Utc),
+    };
     tokio::spawn(async move {
-        while let Some(result) = chat_stream.next().await {
-            match result {
-                Ok(chat) => {
-                    let display_name = chat
-                        .nickname
-                        .unwrap_or_else(|| format!("Listener {}", chat.listener_id));
-                    println!("\r[{}]: {}", display_name, chat.message);
-                    print!("> ");
-                    use std::io::Write;
-                    let _ = std::io::stdout().flush();
+        use futures::StreamExt;
+
+        const MAX_RESUBSCRIBE_ATTEMPTS: u32 = 5;
+        let mut cursor = ChatCursor::new(chat_clock);
+
+        loop {
+            while let Some(result) = chat_stream.next().await {
+                match result {
+                    Ok(chat) => cursor.show(chat),
+                    Err(e) => {
+                        warn!("[Chat] Stream error: {}", e);
+                        break;
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Chat error: {}", e);
-                    break;
+            }
+
+            // Resubscribe first, then fetch what was missed - the cursor drops overlap
+            let mut attempts = 0;
+            chat_stream = loop {
+                attempts += 1;
+                if attempts > MAX_RESUBSCRIBE_ATTEMPTS {
+                    eprintln!("Chat unavailable, giving up");
+                    return;
+                }
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                match chat_client.chat_stream().await {
+                    Ok(stream) => break stream,
+                    Err(e) => warn!("[Chat] Reconnect failed: {}", e),
                 }
+            };
+
+            // With nothing seen yet, everything in history may have been missed
+            match chat_client.get_chat_history(cursor.last_timestamp).await {
+                Ok(missed) => cursor.catch_up(missed),
+                Err(e) => warn!("[Chat] Couldn't fetch missed messages: {}", e),
             }
         }
     });
 
+    // Station events: track changes are kept for 'save', and everything is
+    // shown alongside chat if asked for
+    let now_playing: Arc<std::sync::Mutex<NowPlaying>> = Arc::default();
+    let event_stream = match radio_client.event_stream().await {
+        Ok(stream) => Some(stream),
+        // Older stations don't publish events; 'save' says so, and the audio
+        // and chat are still worth having
+        Err(e) => {
+            if args.events {
+                warn!(
+                    "[Events] Station events unavailable (needs protocol v{}): {}",
+                    EVENTS_VERSION, e
+                );
+            }
+            None
+        }
+    };
+    if let Some(mut event_stream) = event_stream {
+        let now_playing = now_playing.clone();
+        let show_events = args.events;
+        tokio::spawn(async move {
+            use futures::StreamExt;
+
+            while let Some(result) = event_stream.next().await {
+                match result {
+                    Ok(event) => {
+                        if let StationEvent::TrackChanged { title } = &event {
+                            *now_playing.lock().unwrap() = NowPlaying::Track(title.clone());
+                        }
+                        if show_events {
+                            println!("\r* {}", event);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("[Events] Stream error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
     // Interactive command loop
     println!("Commands:");
     println!("  'info'            - Show station info");
     println!("  'chat <message>'  - Send chat message");
+    println!("  'request <track>' - Ask the station to play something");
+    println!("  'requests'        - Show pending track requests");
+    println!("  'skip'            - Vote to skip the current track");
+    println!("  'cover <path>'    - Save the track's cover art (or station logo)");
+    println!(
+        "  'save [path]'     - Note the current track in {} (or path)",
+        LIKED_TRACKS_FILE
+    );
+    println!("  'netstats'        - Show connection quality (RTT, path, throughput)");
+    println!("  'set <field> <value>' - Change the station's name, description, genre,");
+    println!("                      tags, or website (operators only)");
     println!("  'quit'            - Exit");
     println!("Type command and press Enter:\n");
 
@@ -245,7 +2524,62 @@ async fn listen_to_station(node_id_str: String, duration: Option<u64>) -> anyhow
                     let message = cmd.strip_prefix("chat ").unwrap().to_string();
                     match radio_client.send_chat(message).await {
                         Ok(_) => {} // Message sent
-                        Err(e) => eprintln!("Error sending chat: {}", e),
+                        Err(e) => eprintln!("Error sending chat: {}", RadioError::describe(&e)),
+                    }
+                } else if let Some(path) = cmd.strip_prefix("cover ") {
+                    match radio_client.get_artwork().await {
+                        Ok(Some(image)) => {
+                            let kind = zelfm::artwork::ImageFormat::detect(&image)
+                                .map_or("image", zelfm::artwork::ImageFormat::name);
+                            match std::fs::write(path.trim(), &image) {
+                                Ok(()) => println!(
+                                    "Saved {} KB {} to {}",
+                                    image.len().div_ceil(1024),
+                                    kind,
+                                    path.trim()
+                                ),
+                                Err(e) => eprintln!("Couldn't save {}: {}", path.trim(), e),
+                            }
+                        }
+                        Ok(None) => println!("No artwork for this track"),
+                        Err(e) => eprintln!("Error: {}", RadioError::describe(&e)),
+                    }
+                } else if cmd == "save" || cmd.starts_with("save ") {
+                    let path = match cmd.strip_prefix("save").unwrap().trim() {
+                        "" => LIKED_TRACKS_FILE,
+                        path => path,
+                    };
+                    let title = match &*now_playing.lock().unwrap() {
+                        NowPlaying::Track(Some(title)) => Ok(title.clone()),
+                        NowPlaying::Track(None) => Err("This track has no title to save"),
+                        NowPlaying::Unknown => Err(
+                            "Nothing to save: the station hasn't said what's playing (live stations never do)",
+                        ),
+                    };
+                    match title {
+                        Ok(title) => {
+                            let station = radio_client.get_info().await.ok();
+                            match save_liked_track(path.as_ref(), &title, station.as_ref()) {
+                                Ok(()) => println!("Saved '{}' to {}", title, path),
+                                Err(e) => eprintln!("Couldn't save to {}: {}", path, e),
+                            }
+                        }
+                        Err(reason) => println!("{}", reason),
+                    }
+                } else if let Some(args) = cmd.strip_prefix("set ") {
+                    match info_update(args) {
+                        Ok(update) => match radio_client.set_station_info(update).await {
+                            Ok(info) => {
+                                println!("Station is now {}: {}", info.name, info.description)
+                            }
+                            Err(e) => eprintln!("Not changed: {}", RadioError::describe(&e)),
+                        },
+                        Err(reason) => eprintln!("Not changed: {}", reason),
+                    }
+                } else if let Some(query) = cmd.strip_prefix("request ") {
+                    match radio_client.request_track(query.to_string()).await {
+                        Ok(_) => println!("Request sent"),
+                        Err(e) => eprintln!("Request not accepted: {}", RadioError::describe(&e)),
                     }
                 } else {
                     match cmd {
@@ -256,7 +2590,27 @@ async fn listen_to_station(node_id_str: String, duration: Option<u64>) -> anyhow
                                 println!("Listeners: {}", info.listeners);
                                 println!("====================\n");
                             }
-                            Err(e) => eprintln!("Error: {}", e),
+                            Err(e) => eprintln!("Error: {}", RadioError::describe(&e)),
+                        },
+                        "netstats" => net_stats.print(),
+                        "skip" => match radio_client.vote_skip().await {
+                            Ok(vote) if vote.skipped => println!("Vote passed, skipping"),
+                            Ok(vote) => println!("Voted to skip ({}/{})", vote.votes, vote.needed),
+                            Err(e) => eprintln!("Vote not counted: {}", RadioError::describe(&e)),
+                        },
+                        "requests" => match radio_client.get_requests().await {
+                            Ok(requests) if requests.is_empty() => println!("No pending requests"),
+                            Ok(requests) => {
+                                println!("\n=== Requests ===");
+                                for request in requests {
+                                    let name = request.nickname.unwrap_or_else(|| {
+                                        format!("Listener {}", request.listener_id)
+                                    });
+                                    println!("{}: {}", name, request.query);
+                                }
+                                println!("================\n");
+                            }
+                            Err(e) => eprintln!("Error: {}", RadioError::describe(&e)),
                         },
                         "quit" | "exit" => {
                             println!("Disconnecting...");
@@ -265,7 +2619,7 @@ async fn listen_to_station(node_id_str: String, duration: Option<u64>) -> anyhow
                         "" => {} // Empty line, ignore
                         _ => {
                             println!(
-                                "Unknown command: '{}'. Try 'info', 'chat <message>', or 'quit'",
+                                "Unknown command: '{}'. Try 'info', 'chat <message>', 'request <track>', or 'quit'",
                                 cmd
                             );
                         }
@@ -284,3 +2638,282 @@ async fn listen_to_station(node_id_str: String, duration: Option<u64>) -> anyhow
     println!("\nDisconnected.");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zelfm::service::ChatMessage;
+
+    fn chat(seq: u64, timestamp: u64) -> ChatMessage {
+        ChatMessage {
+            listener_id: 0,
+            nickname: None,
+            message: format!("message {}", seq),
+            timestamp,
+            seq,
+        }
+    }
+
+    #[test]
+    fn reconnect_before_any_chat_catches_up_on_everything() {
+        let mut cursor = ChatCursor::new(None);
+        // Dropped before the first message arrived: fetch all of history
+        assert_eq!(cursor.last_timestamp, None);
+        cursor.catch_up(vec![chat(1, 100), chat(2, 101)]);
+        assert_eq!((cursor.last_seq, cursor.last_timestamp), (2, Some(101)));
+
+        // The next catch-up overlaps at the boundary second; nothing repeats
+        let overlap = chat(2, 101);
+        assert!(!cursor.advance(&overlap));
+        cursor.catch_up(vec![overlap, chat(3, 101)]);
+        assert_eq!(cursor.last_seq, 3);
+    }
+
+    #[test]
+    fn quality_range_flags_take_negative_values() {
+        let cli = Cli::try_parse_from([
+            "zelfm",
+            "broadcast",
+            "--file",
+            "a.ogg",
+            "--min-quality",
+            "-0.1",
+            "--max-quality",
+            "-0.1",
+        ])
+        .unwrap();
+        let Commands::Broadcast(args) = cli.command else {
+            panic!("parsed as another command");
+        };
+        let config = args.to_config();
+        assert_eq!(config.quality_range(), Some((-0.1, -0.1)));
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn an_end_action_plays_files_once_unless_repeat_says_otherwise() {
+        fn config(flags: &[&str]) -> BroadcastConfig {
+            let cli = Cli::try_parse_from(
+                ["zelfm", "broadcast", "--file", "a.ogg"]
+                    .iter()
+                    .chain(flags),
+            )
+            .unwrap();
+            let Commands::Broadcast(args) = cli.command else {
+                panic!("parsed as another command");
+            };
+            args.to_config()
+        }
+
+        let looped = config(&[]);
+        assert_eq!(looped.on_end(), OnEnd::Loop);
+        assert_eq!(looped.repeat(), Repeat::All);
+
+        for flags in [
+            &["--on-end", "stop"][..],
+            &["--on-end", "tone"][..],
+            &["--on-end", "fallback", "--fallback", "b.ogg"][..],
+            &["--fallback", "b.ogg"][..],
+        ] {
+            let config = config(flags);
+            assert_ne!(config.on_end(), OnEnd::Loop, "{flags:?}");
+            assert_eq!(config.repeat(), Repeat::None, "{flags:?}");
+            config.validate().unwrap();
+        }
+
+        let twice = config(&["--on-end", "tone", "--repeat", "2"]);
+        assert_eq!(twice.repeat(), Repeat::Times(2));
+        twice.validate().unwrap();
+        assert!(config(&["--on-end", "tone", "--repeat", "all"])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn liked_tracks_are_appended_one_per_line() {
+        let path = std::env::temp_dir().join(format!("zelfm-liked-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let station = StationInfo {
+            name: "Night FM".to_string(),
+            description: "test".to_string(),
+            bitrate: 128_000,
+            sample_rate: 44100,
+            channels: 2,
+            listeners: 1,
+            protocol_version: 0,
+            genre: None,
+            tags: Vec::new(),
+            website: Some("https://night.example".to_string()),
+            rewind_secs: 0,
+            codec: StreamCodec::Vorbis,
+            quality_range: None,
+        };
+
+        save_liked_track(&path, "The Zels - Night Drive", Some(&station)).unwrap();
+        save_liked_track(&path, "Untitled", None).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Vec<&str>> = saved
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        // Local time first, as YYYY-MM-DD HH:MM:SS
+        assert!(lines.iter().all(|fields| fields[0].len() == 19));
+        assert_eq!(
+            lines[0][1..],
+            ["The Zels - Night Drive", "Night FM (https://night.example)"]
+        );
+        assert_eq!(lines[1][1..], ["Untitled"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// A tenth of a second of mono 16-bit silence at `rate`
+    fn silent_wav(rate: u32) -> Vec<u8> {
+        let data = vec![0u8; rate as usize / 10 * 2];
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // channels
+        wav.extend_from_slice(&rate.to_le_bytes());
+        wav.extend_from_slice(&(rate * 2).to_le_bytes()); // byte rate
+        wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+        wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+
+    #[tokio::test]
+    async fn strict_format_refuses_files_at_another_rate() {
+        let dir = std::env::temp_dir().join(format!("zelfm-strict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.wav"), silent_wav(44100)).unwrap();
+        std::fs::write(dir.join("b.wav"), silent_wav(48000)).unwrap();
+        std::fs::write(dir.join("c.wav"), b"not audio").unwrap();
+
+        let paths = ["a.wav", "b.wav", "c.wav"]
+            .map(|name| dir.join(name))
+            .to_vec();
+        assert_eq!(
+            mismatched_rates(paths, 44100).await,
+            [format!("{} is 48000 Hz", dir.join("b.wav").display())]
+        );
+
+        let config = |strict: bool| {
+            let dir = dir.to_str().unwrap();
+            let mut argv = vec!["zelfm", "broadcast", "--dir", dir];
+            if strict {
+                argv.push("--strict-format");
+            }
+            let Commands::Broadcast(args) = Cli::try_parse_from(argv).unwrap().command else {
+                panic!("parsed as another command");
+            };
+            args.to_config()
+        };
+        // Without --strict-format it's only a warning
+        check_source_format(&config(false), 44100).await.unwrap();
+        let refused = check_source_format(&config(true), 44100).await.unwrap_err();
+        assert!(refused.to_string().contains("b.wav is 48000 Hz"));
+        check_source_format(&config(true), 48000).await.unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // stdin can't be checked up front, so strict mode won't take it
+        let cli =
+            Cli::try_parse_from(["zelfm", "broadcast", "--stdin", "--strict-format"]).unwrap();
+        let Commands::Broadcast(args) = cli.command else {
+            panic!("parsed as another command");
+        };
+        assert!(args.to_config().validate().is_err());
+    }
+
+    /// Fails its first `failures` starts, then ends cleanly
+    #[derive(Clone)]
+    struct FlakySource {
+        starts: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    impl AudioSource for FlakySource {
+        fn start(
+            self,
+            _pcm_tx: tokio::sync::broadcast::Sender<Arc<Vec<Vec<f32>>>>,
+        ) -> anyhow::Result<()> {
+            if self.starts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                anyhow::bail!("flaked");
+            }
+            Ok(())
+        }
+
+        fn capabilities(&self) -> SourceCapabilities {
+            SourceCapabilities::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_source_is_restarted_until_it_fails_too_often_in_the_window() {
+        for (failures, exit, starts) in [
+            (
+                MAX_SOURCE_RESTARTS,
+                SourceExit::Ended,
+                MAX_SOURCE_RESTARTS + 1,
+            ),
+            (usize::MAX, SourceExit::Failed, MAX_SOURCE_RESTARTS + 1),
+        ] {
+            let source = FlakySource {
+                starts: Arc::default(),
+                failures,
+            };
+            let (pcm_tx, _pcm_rx) = tokio::sync::broadcast::channel(1);
+            assert_eq!(supervise_source(source.clone(), pcm_tx, true).await, exit);
+            assert_eq!(source.starts.load(Ordering::SeqCst), starts);
+        }
+
+        // Without restarts, the first failure is the last
+        let source = FlakySource {
+            starts: Arc::default(),
+            failures: 1,
+        };
+        let (pcm_tx, _pcm_rx) = tokio::sync::broadcast::channel(1);
+        assert_eq!(
+            supervise_source(source.clone(), pcm_tx, false).await,
+            SourceExit::Failed
+        );
+        assert_eq!(source.starts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn network_flags_survive_the_broadcast_flags_merge() {
+        let cli = Cli::try_parse_from([
+            "zelfm",
+            "--identity",
+            "station.key",
+            "broadcast",
+            "--file",
+            "a.ogg",
+            "--lan-only",
+        ])
+        .unwrap();
+        let Commands::Broadcast(args) = cli.command else {
+            panic!("parsed as another command");
+        };
+        let config = BroadcastConfig::default()
+            .merge(cli.network.to_config())
+            .merge(args.to_config());
+        assert_eq!(config.identity.as_deref(), Some("station.key"));
+        assert_eq!(config.lan_only, Some(true));
+        assert_eq!(config.file.as_deref(), Some("a.ogg"));
+    }
+
+    #[test]
+    fn fade_out_waits_for_a_full_queue() {
+        // The default queue holds several seconds, well past a 1.5s fade
+        let queued = queued_audio(100, 44100);
+        assert!(queued > Duration::from_secs(4) && queued < Duration::from_secs(5));
+        assert_eq!(queued_audio(0, 44100), Duration::ZERO);
+    }
+}
@@ -0,0 +1,132 @@
+//! Endpoint networking options: which relays to use, and which local address
+//! to bind.
+//!
+//! Iroh connects through a relay server first, then upgrades to a direct
+//! (hole-punched) path when it can. Relays make connections work through
+//! almost any NAT or firewall, at the cost of routing (encrypted) traffic
+//! through a third-party server until the direct path is up, or for the whole
+//! session if it never is. `--relay-url` swaps in your own relays.
+//! `--lan-only` turns relays and DNS discovery off entirely: nothing leaves the
+//! local network, but peers must reach each other directly, and a bare node ID
+//! can't be resolved, so connect with a ticket (which carries direct
+//! addresses). `--identity` keeps the node ID the same across restarts (see
+//! [`crate::identity`]).
+
+use iroh::protocol::{DynProtocolHandler, Router};
+use iroh::{Endpoint, RelayMode, RelayUrl};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use zel_core::IrohBundle;
+
+#[derive(Debug, Clone, Default)]
+pub struct NetworkOptions {
+    /// Relay servers to use instead of the public n0 relays
+    pub relay_urls: Vec<RelayUrl>,
+    /// No relays and no DNS discovery; direct connections only
+    pub lan_only: bool,
+    /// Local UDP address to bind (default: any interface, random port)
+    pub bind: Option<SocketAddr>,
+    /// Secret key file for a stable node ID (default: a new key every run)
+    pub identity: Option<PathBuf>,
+}
+
+impl NetworkOptions {
+    /// Parse relay URLs given as strings (CLI flags or config)
+    pub fn new(
+        relay_urls: &[String],
+        lan_only: bool,
+        bind: Option<SocketAddr>,
+    ) -> anyhow::Result<Self> {
+        if lan_only && !relay_urls.is_empty() {
+            anyhow::bail!("LAN-only mode can't use relay URLs");
+        }
+        let relay_urls = relay_urls
+            .iter()
+            .map(|url| {
+                url.parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid relay URL {}: {}", url, e))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            relay_urls,
+            lan_only,
+            bind,
+            identity: None,
+        })
+    }
+
+    /// Load the node's key from `path`, creating it on first use
+    pub fn with_identity(mut self, path: impl Into<PathBuf>) -> Self {
+        self.identity = Some(path.into());
+        self
+    }
+
+    /// Bind an endpoint with these options
+    pub async fn bind(&self) -> anyhow::Result<Endpoint> {
+        let mut builder = if self.lan_only {
+            Endpoint::empty_builder(RelayMode::Disabled)
+        } else {
+            // n0 DNS discovery and relays, as `IrohBundle::builder` uses
+            Endpoint::builder()
+        };
+        if !self.relay_urls.is_empty() {
+            builder =
+                builder.relay_mode(RelayMode::Custom(self.relay_urls.iter().cloned().collect()));
+        }
+        if let Some(path) = &self.identity {
+            builder = builder.secret_key(crate::identity::load_or_create(path)?);
+        }
+        match self.bind {
+            Some(SocketAddr::V4(addr)) => builder = builder.bind_addr_v4(addr),
+            Some(SocketAddr::V6(addr)) => builder = builder.bind_addr_v6(addr),
+            None => {}
+        }
+        builder
+            .bind()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to bind iroh endpoint: {}", e))
+    }
+
+    /// A bundle that only dials out
+    pub async fn client_bundle(&self) -> anyhow::Result<IrohBundle> {
+        let endpoint = self.bind().await?;
+        Ok(bundle(endpoint, |router| router))
+    }
+
+    /// Human-readable summary for startup output
+    pub fn describe(&self) -> String {
+        let relays = if self.lan_only {
+            "LAN only (no relays, no discovery)".to_string()
+        } else if self.relay_urls.is_empty() {
+            "default relays".to_string()
+        } else {
+            let urls: Vec<String> = self.relay_urls.iter().map(|url| url.to_string()).collect();
+            format!("relays {}", urls.join(", "))
+        };
+        match self.bind {
+            Some(addr) => format!("{}, bound to {}", relays, addr),
+            None => relays,
+        }
+    }
+}
+
+/// A bundle serving `alpn` with `handler` on an endpoint from [`NetworkOptions::bind`]
+pub fn serve(
+    endpoint: Endpoint,
+    alpn: &[u8],
+    handler: impl Into<Box<dyn DynProtocolHandler>>,
+) -> IrohBundle {
+    bundle(endpoint, |router| router.accept(alpn, handler))
+}
+
+fn bundle(
+    endpoint: Endpoint,
+    protocols: impl FnOnce(iroh::protocol::RouterBuilder) -> iroh::protocol::RouterBuilder,
+) -> IrohBundle {
+    let router = protocols(Router::builder(endpoint.clone())).spawn();
+    IrohBundle {
+        endpoint,
+        router,
+        shutdown_subscribers: Vec::new(),
+    }
+}
@@ -0,0 +1,133 @@
+//! Listener-side night mode: gentle compression plus a peak limiter, so loud
+//! passages don't jump out on headphones late at night.
+//!
+//! [`NightMode::process`] works on decoded planar blocks in place, ahead of
+//! playback. The envelope carries over from one block to the next, so block
+//! boundaries don't pump. All channels share one gain, which keeps the stereo
+//! image still. Each frame costs a few multiplies, plus a log and an exp
+//! while it's over the threshold. That is cheap enough for the decode thread.
+
+use std::time::Duration;
+
+/// Level where compression starts
+const THRESHOLD_DB: f32 = -24.0;
+
+/// Decibels in over the threshold for each decibel out
+const RATIO: f32 = 3.0;
+
+/// Lift applied to everything, so quiet passages come up as loud ones come down
+const MAKEUP_DB: f32 = 6.0;
+
+/// Peak level nothing goes past (about -1 dBFS)
+const CEILING: f32 = 0.89;
+
+const ATTACK: Duration = Duration::from_millis(5);
+const RELEASE: Duration = Duration::from_millis(250);
+
+pub struct NightMode {
+    /// Per-frame smoothing of the envelope toward a louder / quieter peak
+    attack: f32,
+    release: f32,
+    /// Smoothed peak level across channels
+    envelope: f32,
+    threshold: f32,
+    makeup: f32,
+}
+
+impl NightMode {
+    pub fn new(sample_rate: u32) -> Self {
+        let coefficient = |time: Duration| (-1.0 / (time.as_secs_f32() * sample_rate as f32)).exp();
+        Self {
+            attack: coefficient(ATTACK),
+            release: coefficient(RELEASE),
+            envelope: 0.0,
+            threshold: db_to_gain(THRESHOLD_DB),
+            makeup: db_to_gain(MAKEUP_DB),
+        }
+    }
+
+    /// Compress and limit a planar block in place
+    pub fn process(&mut self, planar: &mut [Vec<f32>]) {
+        let frames = planar.iter().map(Vec::len).min().unwrap_or(0);
+        for frame in 0..frames {
+            let peak = planar
+                .iter()
+                .map(|channel| channel[frame].abs())
+                .fold(0.0, f32::max);
+            let smoothing = if peak > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope = peak + smoothing * (self.envelope - peak);
+
+            let mut gain = self.makeup;
+            if self.envelope > self.threshold {
+                let over_db = 20.0 * (self.envelope / self.threshold).log10();
+                gain *= db_to_gain(-over_db * (1.0 - 1.0 / RATIO));
+            }
+            // Whatever the envelope hasn't caught yet, the limiter does
+            if peak * gain > CEILING {
+                gain = CEILING / peak;
+            }
+
+            for channel in planar.iter_mut() {
+                channel[frame] *= gain;
+            }
+        }
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| amplitude * (i as f32 * 0.05).sin())
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn loud_passages_come_down_and_quiet_ones_come_up() {
+        let mut night = NightMode::new(44100);
+
+        let mut loud = vec![tone(1.0, 44100); 2];
+        night.process(&mut loud);
+        assert!(loud.iter().all(|c| peak(c) <= CEILING + 1e-6));
+        // Once the envelope settles, a full-scale tone sits well below the ceiling
+        assert!(peak(&loud[0][22050..]) < 0.5);
+        assert_eq!(loud[0], loud[1]);
+
+        let mut night = NightMode::new(44100);
+        let mut quiet = vec![tone(0.01, 44100)];
+        night.process(&mut quiet);
+        let lifted = peak(&quiet[0]) / 0.01;
+        assert!((lifted - db_to_gain(MAKEUP_DB)).abs() < 0.01);
+    }
+
+    #[test]
+    fn envelope_carries_across_blocks() {
+        let signal = tone(0.8, 4096);
+
+        let mut whole = vec![signal.clone()];
+        NightMode::new(44100).process(&mut whole);
+
+        let mut night = NightMode::new(44100);
+        let mut first = vec![signal[..1000].to_vec()];
+        let mut second = vec![signal[1000..].to_vec()];
+        night.process(&mut first);
+        night.process(&mut second);
+
+        assert_eq!(whole[0][..1000], first[0][..]);
+        assert_eq!(whole[0][1000..], second[0][..]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Framing for [`StreamCodec::Pcm`](crate::service::StreamCodec::Pcm) streams.
+//!
+//! Raw samples have no boundaries, so a reader that starts mid-stream (or
+//! loses bytes) couldn't tell where one block ends and the next begins. Each
+//! block goes out as one frame instead:
+//!
+//! | bytes | field                                            |
+//! |-------|--------------------------------------------------|
+//! | 2     | magic `ZP`                                       |
+//! | 4     | sequence number, u32 LE, +1 per frame (wraps)    |
+//! | 1     | channel count                                    |
+//! | 2     | frames (samples per channel), u16 LE             |
+//! | n     | `channels * frames` interleaved f32 LE samples   |
+//!
+//! A reader resynchronizes by scanning for the magic, and a jump in the
+//! sequence number counts blocks the station skipped for this listener or that
+//! never arrived. The sample rate is the station's (see `get_info`).
+
+use std::io::{ErrorKind, Read, Write};
+
+const MAGIC: [u8; 2] = *b"ZP";
+
+/// Bytes before each frame's samples
+pub const HEADER_LEN: usize = 9;
+
+/// Longest frame; longer blocks are split
+const MAX_FRAMES: usize = u16::MAX as usize;
+
+/// Most channels a frame may carry; a header claiming more is garbage that
+/// happened to follow a magic, and isn't worth allocating for
+pub const MAX_CHANNELS: usize = 8;
+
+/// Writes planar blocks as frames
+pub struct PcmFrameWriter<W: Write> {
+    writer: W,
+    channels: u8,
+    sequence: u32,
+}
+
+impl<W: Write> PcmFrameWriter<W> {
+    pub fn new(channels: u8, writer: W) -> Self {
+        Self {
+            writer,
+            channels,
+            sequence: 0,
+        }
+    }
+
+    pub fn encode_audio_block(&mut self, samples: &[&[f32]]) -> std::io::Result<()> {
+        let channels = samples.len().min(self.channels as usize).min(MAX_CHANNELS);
+        let frames = samples.iter().map(|c| c.len()).min().unwrap_or(0);
+
+        for start in (0..frames).step_by(MAX_FRAMES) {
+            let end = (start + MAX_FRAMES).min(frames);
+            let mut frame = Vec::with_capacity(HEADER_LEN + channels * (end - start) * 4);
+            frame.extend_from_slice(&MAGIC);
+            frame.extend_from_slice(&self.sequence.to_le_bytes());
+            frame.push(channels as u8);
+            frame.extend_from_slice(&((end - start) as u16).to_le_bytes());
+            for i in start..end {
+                for channel in &samples[..channels] {
+                    frame.extend_from_slice(&channel[i].to_le_bytes());
+                }
+            }
+            self.writer.write_all(&frame)?;
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    /// Account for `blocks` the listener never got, so its reader sees the gap
+    pub fn skip(&mut self, blocks: u64) {
+        self.sequence = self.sequence.wrapping_add(blocks as u32);
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// One decoded frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmFrame {
+    pub sequence: u32,
+    /// Blocks missing between the previous frame and this one
+    pub dropped: u32,
+    /// `[channel][frame]`
+    pub samples: Vec<Vec<f32>>,
+}
+
+/// Reads frames, resynchronizing after garbage and counting gaps
+pub struct PcmFrameReader<R: Read> {
+    reader: R,
+    next_sequence: Option<u32>,
+    /// Blocks missing so far
+    pub dropped: u64,
+    /// Bytes skipped looking for a frame boundary
+    pub skipped_bytes: u64,
+}
+
+impl<R: Read> PcmFrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            next_sequence: None,
+            dropped: 0,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// The next frame, or `None` at the end of the stream
+    pub fn next_frame(&mut self) -> std::io::Result<Option<PcmFrame>> {
+        loop {
+            if !self.find_magic()? {
+                return Ok(None);
+            }
+
+            let mut header = [0u8; HEADER_LEN - MAGIC.len()];
+            if !read_full(&mut self.reader, &mut header)? {
+                return Ok(None);
+            }
+            let sequence = u32::from_le_bytes(header[0..4].try_into().unwrap());
+            let channels = header[4] as usize;
+            let frames = u16::from_le_bytes(header[5..7].try_into().unwrap()) as usize;
+            if channels == 0 || channels > MAX_CHANNELS || frames == 0 {
+                // Not a real header; keep scanning past it
+                self.skipped_bytes += HEADER_LEN as u64;
+                continue;
+            }
+
+            let mut payload = vec![0u8; channels * frames * 4];
+            if !read_full(&mut self.reader, &mut payload)? {
+                return Ok(None);
+            }
+            let mut samples = vec![Vec::with_capacity(frames); channels];
+            for (i, bytes) in payload.chunks_exact(4).enumerate() {
+                samples[i % channels].push(f32::from_le_bytes(bytes.try_into().unwrap()));
+            }
+
+            let dropped = self
+                .next_sequence
+                .map_or(0, |expected| sequence.wrapping_sub(expected));
+            self.dropped += dropped as u64;
+            self.next_sequence = Some(sequence.wrapping_add(1));
+
+            return Ok(Some(PcmFrame {
+                sequence,
+                dropped,
+                samples,
+            }));
+        }
+    }
+
+    /// Consume bytes up to and including the next magic; false at end of stream
+    fn find_magic(&mut self) -> std::io::Result<bool> {
+        let mut window = [0u8; 2];
+        if !read_full(&mut self.reader, &mut window)? {
+            return Ok(false);
+        }
+        while window != MAGIC {
+            window[0] = window[1];
+            if !read_full(&mut self.reader, &mut window[1..])? {
+                return Ok(false);
+            }
+            self.skipped_bytes += 1;
+        }
+        Ok(true)
+    }
+}
+
+/// Fill `buf`; false if the stream ends first
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(value: f32, frames: usize) -> Vec<Vec<f32>> {
+        vec![vec![value; frames], vec![-value; frames]]
+    }
+
+    fn write(writer: &mut PcmFrameWriter<Vec<u8>>, planar: &[Vec<f32>]) {
+        let planar: Vec<&[f32]> = planar.iter().map(Vec::as_slice).collect();
+        writer.encode_audio_block(&planar).unwrap();
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut writer = PcmFrameWriter::new(2, Vec::new());
+        write(&mut writer, &block(0.25, 3));
+        write(&mut writer, &block(0.5, 2));
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes.len(), 2 * HEADER_LEN + (3 + 2) * 2 * 4);
+
+        let mut reader = PcmFrameReader::new(&bytes[..]);
+        let first = reader.next_frame().unwrap().unwrap();
+        assert_eq!(first.sequence, 0);
+        assert_eq!(first.samples, block(0.25, 3));
+        let second = reader.next_frame().unwrap().unwrap();
+        assert_eq!(second.sequence, 1);
+        assert_eq!(second.samples, block(0.5, 2));
+        assert_eq!(reader.next_frame().unwrap(), None);
+        assert_eq!(reader.dropped, 0);
+    }
+
+    #[test]
+    fn skipped_blocks_show_up_as_a_gap() {
+        let mut writer = PcmFrameWriter::new(2, Vec::new());
+        write(&mut writer, &block(0.1, 4));
+        writer.skip(3);
+        write(&mut writer, &block(0.2, 4));
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = PcmFrameReader::new(&bytes[..]);
+        assert_eq!(reader.next_frame().unwrap().unwrap().dropped, 0);
+        let after_gap = reader.next_frame().unwrap().unwrap();
+        assert_eq!(after_gap.sequence, 4);
+        assert_eq!(after_gap.dropped, 3);
+        assert_eq!(reader.dropped, 3);
+    }
+
+    #[test]
+    fn joining_mid_frame_resyncs_at_the_next_one() {
+        let mut writer = PcmFrameWriter::new(2, Vec::new());
+        write(&mut writer, &block(0.1, 4));
+        write(&mut writer, &block(0.2, 4));
+        let bytes = writer.finish().unwrap();
+
+        // Start partway through the first frame's samples
+        let mut reader = PcmFrameReader::new(&bytes[HEADER_LEN + 5..]);
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.sequence, 1);
+        assert_eq!(frame.samples, block(0.2, 4));
+        assert!(reader.skipped_bytes > 0);
+    }
+
+    #[test]
+    fn implausible_headers_are_skipped_without_allocating() {
+        let mut writer = PcmFrameWriter::new(2, Vec::new());
+        write(&mut writer, &block(0.3, 2));
+        let good = writer.finish().unwrap();
+
+        // 255 channels of 65535 frames would be a 64 MB payload
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&7u32.to_le_bytes());
+        bytes.push(255);
+        bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+        bytes.extend_from_slice(&good);
+
+        let mut reader = PcmFrameReader::new(&bytes[..]);
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.sequence, 0);
+        assert_eq!(frame.samples, block(0.3, 2));
+        assert!(reader.skipped_bytes >= HEADER_LEN as u64);
+    }
+}
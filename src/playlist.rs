@@ -0,0 +1,639 @@
+//! M3U and PLS playlist parsing, plus directory scanning for `--dir`, for
+//! [`PlaylistSource`](crate::audio_source::PlaylistSource).
+//!
+//! An optional sidecar manifest (TOML or JSON) adds per-track programming:
+//!
+//! ```toml
+//! [[tracks]]
+//! file = "music/opening.ogg"   # relative to the manifest, or just a file name
+//! title = "Station Ident"
+//! gain_db = -3.0
+//! start = 1.5                  # seconds trimmed from the intro
+//! end = 182.0                  # stop here instead of the end of the file
+//! ```
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// File extensions `--dir` treats as audio (anything Symphonia can usually open)
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "ogg", "oga", "flac", "wav", "m4a", "mp4", "aac", "mka", "webm", "caf",
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    pub path: PathBuf,
+    /// From `#EXTINF` (M3U), `TitleN=` (PLS), or the manifest
+    pub title: Option<String>,
+    pub settings: TrackSettings,
+}
+
+/// Per-track overrides from a sidecar manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackSettings {
+    pub gain_db: f32,
+    /// Start playback this far into the file
+    pub start_secs: Option<f64>,
+    /// Stop playback at this point in the file
+    pub end_secs: Option<f64>,
+}
+
+impl TrackSettings {
+    /// Linear gain factor
+    pub fn gain(&self) -> f32 {
+        10f32.powf(self.gain_db / 20.0)
+    }
+
+    pub fn is_trimmed(&self) -> bool {
+        self.start_secs.is_some() || self.end_secs.is_some()
+    }
+}
+
+impl PlaylistEntry {
+    /// Title if the playlist gave one, otherwise the file name
+    pub fn display_name(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.path.display().to_string())
+        })
+    }
+}
+
+/// Read a playlist file, keeping only entries whose files exist
+pub fn load(path: &Path) -> anyhow::Result<Vec<PlaylistEntry>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Can't read playlist {}: {}", path.display(), e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let is_pls = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+    let entries = if is_pls {
+        parse_pls(&text, base)
+    } else {
+        parse_m3u(&text, base)
+    };
+
+    let entries: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| {
+            let exists = entry.path.is_file();
+            if !exists {
+                warn!("[Playlist] Skipping missing file {}", entry.path.display());
+            }
+            exists
+        })
+        .collect();
+
+    if entries.is_empty() {
+        anyhow::bail!("Playlist {} has no playable files", path.display());
+    }
+    Ok(entries)
+}
+
+/// Parse M3U / extended M3U; relative paths resolve against `base`
+pub fn parse_m3u(text: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending_title = None;
+
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // "#EXTINF:<seconds>,<title>"
+            pending_title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(path) = resolve(line, base) {
+            entries.push(PlaylistEntry {
+                path,
+                title: pending_title.take(),
+                ..Default::default()
+            });
+        } else {
+            pending_title = None;
+        }
+    }
+
+    entries
+}
+
+/// Parse a PLS (`[playlist]`, `FileN=`, `TitleN=`); relative paths resolve against `base`
+pub fn parse_pls(text: &str, base: &Path) -> Vec<PlaylistEntry> {
+    // Entries are keyed by number and may appear in any order
+    let mut numbered: Vec<(u32, Option<PathBuf>, Option<String>)> = Vec::new();
+
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        let (is_file, index) = if let Some(n) = key.strip_prefix("file") {
+            (true, n)
+        } else if let Some(n) = key.strip_prefix("title") {
+            (false, n)
+        } else {
+            continue;
+        };
+        let Ok(index) = index.parse::<u32>() else {
+            continue;
+        };
+
+        let slot = match numbered.iter().position(|(n, _, _)| *n == index) {
+            Some(i) => &mut numbered[i],
+            None => {
+                numbered.push((index, None, None));
+                numbered.last_mut().unwrap()
+            }
+        };
+        if is_file {
+            slot.1 = resolve(value, base);
+        } else if !value.is_empty() {
+            slot.2 = Some(value.to_string());
+        }
+    }
+
+    numbered.sort_by_key(|(n, _, _)| *n);
+    numbered
+        .into_iter()
+        .filter_map(|(_, path, title)| {
+            path.map(|path| PlaylistEntry {
+                path,
+                title,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    tracks: Vec<ManifestTrack>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestTrack {
+    file: String,
+    title: Option<String>,
+    #[serde(default)]
+    gain_db: f32,
+    start: Option<f64>,
+    end: Option<f64>,
+}
+
+/// Apply a sidecar manifest's titles, gain, and trims to matching entries
+///
+/// A manifest `file` matches an entry by resolved path, or by file name when
+/// it's a bare name. Entries the manifest doesn't mention keep the defaults.
+pub fn apply_manifest(entries: &mut [PlaylistEntry], path: &Path) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Can't read manifest {}: {}", path.display(), e))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let manifest: Manifest = if is_json {
+        serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid manifest {}: {}", path.display(), e))?
+    } else {
+        toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid manifest {}: {}", path.display(), e))?
+    };
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    for track in manifest.tracks {
+        if let (Some(start), Some(end)) = (track.start, track.end) {
+            if end <= start {
+                anyhow::bail!("Manifest entry {}: end must be after start", track.file);
+            }
+        }
+
+        let resolved = base.join(&track.file);
+        let bare_name = Path::new(&track.file).components().count() == 1;
+        let mut matched = false;
+        for entry in entries.iter_mut().filter(|entry| {
+            entry.path == resolved
+                || (bare_name && entry.path.file_name() == Some(std::ffi::OsStr::new(&track.file)))
+        }) {
+            matched = true;
+            if track.title.is_some() {
+                entry.title = track.title.clone();
+            }
+            entry.settings = TrackSettings {
+                gain_db: track.gain_db,
+                start_secs: track.start,
+                end_secs: track.end,
+            };
+        }
+        if !matched {
+            warn!(
+                "[Playlist] Manifest entry {} isn't in the playlist",
+                track.file
+            );
+        }
+    }
+    Ok(())
+}
+
+/// How `--dir` orders the files it finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlayOrder {
+    /// Alphabetical by path
+    #[default]
+    Name,
+    /// Oldest modification time first
+    Mtime,
+    /// Random, reshuffled each pass
+    Shuffle,
+}
+
+/// What a file or playlist source does when it reaches the end
+///
+/// Parsed from `none`, `one`, `all`, or a pass count (`0` loops forever, as
+/// `all` does).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "RepeatValue", into = "RepeatValue")]
+pub enum Repeat {
+    /// Play through once, then end the broadcast
+    None,
+    /// Replay the current track until the operator skips it
+    One,
+    /// Loop the whole list forever
+    #[default]
+    All,
+    /// Play the whole list this many times, then end
+    Times(u32),
+}
+
+impl Repeat {
+    /// Passes over the whole list before the source ends (`None` = never ends)
+    pub fn passes(self) -> Option<u32> {
+        match self {
+            Repeat::None => Some(1),
+            Repeat::Times(times) => Some(times),
+            Repeat::One | Repeat::All => Option::None,
+        }
+    }
+}
+
+impl std::str::FromStr for Repeat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Repeat::None),
+            "one" => Ok(Repeat::One),
+            "all" => Ok(Repeat::All),
+            other => match other.parse::<u32>() {
+                Ok(0) => Ok(Repeat::All),
+                Ok(times) => Ok(Repeat::Times(times)),
+                Err(_) => Err(format!(
+                    "expected `none`, `one`, `all`, or a number of passes, got `{}`",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Repeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repeat::None => f.write_str("none"),
+            Repeat::One => f.write_str("one"),
+            Repeat::All => f.write_str("all"),
+            Repeat::Times(times) => write!(f, "{}", times),
+        }
+    }
+}
+
+/// `repeat` as written in a config file: a mode name or a pass count
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RepeatValue {
+    Times(u32),
+    Mode(String),
+}
+
+impl TryFrom<RepeatValue> for Repeat {
+    type Error = String;
+
+    fn try_from(value: RepeatValue) -> Result<Self, Self::Error> {
+        match value {
+            RepeatValue::Times(times) => times.to_string().parse(),
+            RepeatValue::Mode(mode) => mode.parse(),
+        }
+    }
+}
+
+impl From<Repeat> for RepeatValue {
+    fn from(repeat: Repeat) -> Self {
+        match repeat {
+            Repeat::Times(times) => RepeatValue::Times(times),
+            mode => RepeatValue::Mode(mode.to_string()),
+        }
+    }
+}
+
+/// What the broadcast does once its source runs out (`--on-end`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnEnd {
+    /// Shut the station down
+    Stop,
+    /// Start the file or playlist over (the same as `repeat = "all"`)
+    Loop,
+    /// Stay on the air with a placeholder tone (or silence)
+    Tone,
+    /// Switch to the configured `fallback` file or playlist, looped
+    Fallback,
+}
+
+/// A directory used as an automatic playlist
+#[derive(Debug, Clone)]
+pub struct DirectoryScan {
+    pub root: PathBuf,
+    pub recursive: bool,
+    pub order: PlayOrder,
+}
+
+impl DirectoryScan {
+    /// Every audio file under `root`, in name or mtime order (shuffling is the
+    /// player's job, since it happens per pass)
+    pub fn scan(&self) -> anyhow::Result<Vec<PlaylistEntry>> {
+        self.list(true)
+    }
+
+    /// Like [`scan`](Self::scan), without repeating warnings about skipped files
+    pub fn rescan(&self) -> anyhow::Result<Vec<PlaylistEntry>> {
+        self.list(false)
+    }
+
+    fn list(&self, warn_skipped: bool) -> anyhow::Result<Vec<PlaylistEntry>> {
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        self.collect(&self.root, &mut files, &mut visited, warn_skipped)
+            .map_err(|e| anyhow::anyhow!("Can't read directory {}: {}", self.root.display(), e))?;
+
+        match self.order {
+            PlayOrder::Mtime => {
+                files.sort_by(|(a, a_time), (b, b_time)| a_time.cmp(b_time).then_with(|| a.cmp(b)))
+            }
+            PlayOrder::Name | PlayOrder::Shuffle => files.sort(),
+        }
+
+        Ok(files
+            .into_iter()
+            .map(|(path, _)| PlaylistEntry {
+                path,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn collect(
+        &self,
+        dir: &Path,
+        files: &mut Vec<(PathBuf, std::time::SystemTime)>,
+        visited: &mut HashSet<PathBuf>,
+        warn_skipped: bool,
+    ) -> std::io::Result<()> {
+        // A symlink back up the tree would otherwise recurse forever
+        if !visited.insert(std::fs::canonicalize(dir)?) {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            // Follows symlinks, unlike the entry's own metadata
+            let metadata = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    if warn_skipped {
+                        warn!("[Playlist] Skipping {}: {}", path.display(), e);
+                    }
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                if self.recursive {
+                    if let Err(e) = self.collect(&path, files, visited, warn_skipped) {
+                        warn!("[Playlist] Skipping directory {}: {}", path.display(), e);
+                    }
+                }
+                continue;
+            }
+
+            let is_audio = path.extension().is_some_and(|ext| {
+                AUDIO_EXTENSIONS
+                    .iter()
+                    .any(|known| ext.eq_ignore_ascii_case(known))
+            });
+            if !is_audio {
+                if warn_skipped {
+                    warn!("[Playlist] Skipping non-audio file {}", path.display());
+                }
+                continue;
+            }
+
+            let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+            files.push((path, modified));
+        }
+        Ok(())
+    }
+}
+
+/// Local path for a playlist line; remote URLs aren't playable sources
+fn resolve(raw: &str, base: &Path) -> Option<PathBuf> {
+    let raw = raw.strip_prefix("file://").unwrap_or(raw);
+    if raw.contains("://") {
+        warn!("[Playlist] Skipping non-local entry {}", raw);
+        return None;
+    }
+
+    let path = PathBuf::from(raw);
+    Some(if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_m3u() {
+        let text = "#EXTM3U\n\
+            #EXTINF:215,Artist - Opening\n\
+            music/opening.ogg\n\
+            \n\
+            # a comment\n\
+            /abs/second.flac\n\
+            #EXTINF:-1,Stream\n\
+            http://example.com/live\n\
+            third.mp3\n";
+
+        let entries = parse_m3u(text, Path::new("/lists"));
+        assert_eq!(
+            entries,
+            vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/lists/music/opening.ogg"),
+                    title: Some("Artist - Opening".to_string()),
+                    ..Default::default()
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("/abs/second.flac"),
+                    title: None,
+                    ..Default::default()
+                },
+                // The URL's title must not leak onto the next file
+                PlaylistEntry {
+                    path: PathBuf::from("/lists/third.mp3"),
+                    title: None,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pls_with_titles_out_of_order() {
+        let text = "[playlist]\n\
+            File2=b.ogg\n\
+            Title1=First Track\n\
+            File1=file:///music/a.ogg\n\
+            Length1=180\n\
+            NumberOfEntries=2\n\
+            Version=2\n";
+
+        let entries = parse_pls(text, Path::new("/lists"));
+        assert_eq!(
+            entries,
+            vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/music/a.ogg"),
+                    title: Some("First Track".to_string()),
+                    ..Default::default()
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("/lists/b.ogg"),
+                    title: None,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_matches_by_path_or_bare_name() {
+        let dir = std::env::temp_dir().join(format!("zelfm-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = |path: PathBuf, title: Option<&str>| PlaylistEntry {
+            path,
+            title: title.map(str::to_string),
+            settings: TrackSettings::default(),
+        };
+        let mut entries = vec![
+            entry(dir.join("sub/opening.ogg"), Some("From the playlist")),
+            entry(dir.join("closer.ogg"), None),
+            entry(dir.join("untouched.ogg"), Some("Kept")),
+        ];
+
+        let manifest = dir.join("manifest.toml");
+        std::fs::write(
+            &manifest,
+            r#"
+            [[tracks]]
+            file = "sub/opening.ogg"
+            gain_db = -3.0
+            start = 1.5
+            end = 182.0
+
+            [[tracks]]
+            file = "closer.ogg"
+            title = "Closing Theme"
+
+            [[tracks]]
+            file = "missing.ogg"
+            "#,
+        )
+        .unwrap();
+        apply_manifest(&mut entries, &manifest).unwrap();
+
+        // No manifest title keeps the playlist's
+        assert_eq!(entries[0].title.as_deref(), Some("From the playlist"));
+        assert_eq!(
+            entries[0].settings,
+            TrackSettings {
+                gain_db: -3.0,
+                start_secs: Some(1.5),
+                end_secs: Some(182.0),
+            }
+        );
+        assert_eq!(entries[1].title.as_deref(), Some("Closing Theme"));
+        assert_eq!(entries[1].settings, TrackSettings::default());
+        assert_eq!(entries[2].title.as_deref(), Some("Kept"));
+
+        // JSON works too, and an end before the start is refused
+        let json = dir.join("manifest.json");
+        std::fs::write(
+            &json,
+            r#"{"tracks": [{"file": "closer.ogg", "start": 10, "end": 5}]}"#,
+        )
+        .unwrap();
+        assert!(apply_manifest(&mut entries, &json).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_scan_survives_symlink_loops() {
+        let root = std::env::temp_dir().join(format!("zelfm-scan-loop-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("album")).unwrap();
+        std::fs::write(root.join("album/one.mp3"), b"").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("album/loop")).unwrap();
+
+        let scan = DirectoryScan {
+            root: root.clone(),
+            recursive: true,
+            order: PlayOrder::Name,
+        };
+        let entries = scan.scan().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, root.join("album/one.mp3"));
+    }
+
+    #[test]
+    fn display_name_falls_back_to_file_name() {
+        let entry = PlaylistEntry {
+            path: PathBuf::from("/music/song.ogg"),
+            title: None,
+            ..Default::default()
+        };
+        assert_eq!(entry.display_name(), "song.ogg");
+    }
+}
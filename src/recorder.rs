@@ -0,0 +1,181 @@
+//! Recording sinks that write the decoded stream to standard audio files.
+
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::listener::{PcmSink, StreamFormat};
+
+/// Container written by `--record`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum RecordFormat {
+    /// Raw OGG/Vorbis bytes exactly as received (no re-encode)
+    #[default]
+    Ogg,
+    /// 16-bit PCM WAV
+    Wav,
+    /// Lossless FLAC (requires the `flac` feature)
+    Flac,
+}
+
+/// Build a PCM recording sink; OGG recording happens on the receive path instead
+pub fn pcm_recorder(
+    path: &Path,
+    format: RecordFormat,
+    stream: StreamFormat,
+) -> anyhow::Result<Option<Box<dyn PcmSink>>> {
+    match format {
+        RecordFormat::Ogg => Ok(None),
+        RecordFormat::Wav => Ok(Some(Box::new(WavRecorder::create(path, stream)?))),
+        #[cfg(feature = "flac")]
+        RecordFormat::Flac => Ok(Some(Box::new(FlacRecorder::create(path, stream)?))),
+        #[cfg(not(feature = "flac"))]
+        RecordFormat::Flac => {
+            anyhow::bail!("FLAC recording requires zelfm built with the `flac` feature")
+        }
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+// ============================================================================
+// WAV
+// ============================================================================
+
+/// Streams 16-bit PCM to disk and patches the RIFF sizes when finished
+pub struct WavRecorder {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    channels: u16,
+    data_bytes: u64,
+}
+
+impl WavRecorder {
+    const HEADER_LEN: u64 = 44;
+
+    pub fn create(path: &Path, format: StreamFormat) -> anyhow::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let channels = format.channels as u16;
+        let block_align = channels * 2;
+        let byte_rate = format.sample_rate * block_align as u32;
+
+        // Sizes are placeholders until finish()
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&format.sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?; // bits per sample
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        info!("[Record] Writing WAV to {}", path.display());
+
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            channels,
+            data_bytes: 0,
+        })
+    }
+
+    fn finalize(&mut self) -> std::io::Result<()> {
+        // WAV sizes are 32-bit; clamp rather than wrap for very long recordings
+        let data_len = self.data_bytes.min(u32::MAX as u64 - Self::HEADER_LEN) as u32;
+
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&(data_len + Self::HEADER_LEN as u32 - 8).to_le_bytes())?;
+        file.seek(SeekFrom::Start(40))?;
+        file.write_all(&data_len.to_le_bytes())?;
+        file.flush()
+    }
+}
+
+impl PcmSink for WavRecorder {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        let frames = samples.iter().map(|c| c.len()).min().unwrap_or(0);
+        for i in 0..frames {
+            for channel in samples.iter().take(self.channels as usize) {
+                self.writer.write_all(&to_i16(channel[i]).to_le_bytes())?;
+            }
+        }
+        self.data_bytes += (frames * self.channels as usize * 2) as u64;
+        Ok(true)
+    }
+
+    fn finish(&mut self) {
+        match self.finalize() {
+            Ok(()) => info!(
+                "[Record] Saved {} ({} bytes of audio)",
+                self.path.display(),
+                self.data_bytes
+            ),
+            Err(e) => error!("[Record] Failed to finalize {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+// ============================================================================
+// FLAC
+// ============================================================================
+
+/// Encodes losslessly as the audio arrives, writing each FLAC frame as soon
+/// as it's complete
+///
+/// The STREAMINFO block leaves the total length unknown, as a live FLAC
+/// broadcast does; players work it out from the frames.
+#[cfg(feature = "flac")]
+pub struct FlacRecorder {
+    path: PathBuf,
+    /// Taken by [`PcmSink::finish`] to write the last frame
+    encoder: Option<crate::flac_stream::FlacStreamEncoder<BufWriter<File>>>,
+}
+
+#[cfg(feature = "flac")]
+impl FlacRecorder {
+    pub fn create(path: &Path, format: StreamFormat) -> anyhow::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        let encoder = crate::flac_stream::FlacStreamEncoder::new(
+            format.sample_rate,
+            format.channels,
+            writer,
+        )?;
+        info!("[Record] Recording FLAC to {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            encoder: Some(encoder),
+        })
+    }
+}
+
+#[cfg(feature = "flac")]
+impl PcmSink for FlacRecorder {
+    fn write_block(&mut self, samples: &[&[f32]]) -> anyhow::Result<bool> {
+        if let Some(encoder) = &mut self.encoder {
+            encoder.encode_audio_block(samples)?;
+        }
+        Ok(true)
+    }
+
+    fn finish(&mut self) {
+        let Some(encoder) = self.encoder.take() else {
+            return;
+        };
+        // The header's total length is only known now
+        match encoder.finish_seekable() {
+            Ok(_) => info!("[Record] Saved {}", self.path.display()),
+            Err(e) => error!("[Record] Failed to write {}: {}", self.path.display(), e),
+        }
+    }
+}
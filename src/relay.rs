@@ -0,0 +1,42 @@
+//! Relay mode: rebroadcast another station's OGG stream without re-encoding.
+//!
+//! The upstream stream is split into pages and fed into a [`RewindBuffer`];
+//! local listeners are served from it at the live edge (see
+//! [`RadioBroadcaster::with_relay`](crate::broadcaster::RadioBroadcaster::with_relay)),
+//! so they receive the upstream's exact bytes.
+
+use log::info;
+use std::time::Duration;
+
+use crate::rewind::{PageSplitter, RewindBuffer};
+use crate::service::{RadioError, RadioServiceClient};
+
+/// Upstream audio kept when relaying without `--rewind-secs`
+pub const RELAY_BUFFER: Duration = Duration::from_secs(10);
+
+/// Pull the upstream station's stream into `pages` until it ends
+pub async fn forward(client: &RadioServiceClient, pages: &RewindBuffer) -> anyhow::Result<()> {
+    let (_send, mut recv) = client
+        .listen()
+        .await
+        .map_err(|e| anyhow::anyhow!(RadioError::describe(&e)))?;
+    info!("[Relay] Upstream stream opened");
+
+    let mut splitter = PageSplitter::default();
+    let mut chunk = vec![0u8; 8192];
+    let result = loop {
+        match recv.read(&mut chunk).await {
+            Ok(Some(n)) => {
+                for page in splitter.push(&chunk[..n]) {
+                    pages.push_page(page);
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(anyhow::anyhow!("Upstream stream failed: {}", e)),
+        }
+    };
+
+    info!("[Relay] Upstream stream ended");
+    pages.close();
+    result
+}
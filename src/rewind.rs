@@ -0,0 +1,265 @@
+//! Rolling buffer of recently encoded OGG pages for time-shifted listening.
+//!
+//! The station runs one extra encoder whose output is split into pages and
+//! kept for the configured window. A rewinding listener gets the stream's
+//! header pages, then every page from the requested point on, so it plays the
+//! same continuous Vorbis stream a fixed delay behind live.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// OGG page header length before the segment table
+const PAGE_HEADER_LEN: usize = 27;
+
+/// Header-type flag: page begins with the tail of a packet from the previous page
+const CONTINUED_PACKET: u8 = 0x01;
+
+struct Page {
+    seq: u64,
+    at: Instant,
+    data: Arc<[u8]>,
+    /// Safe to start decoding from (doesn't open mid-packet)
+    starts_packet: bool,
+}
+
+#[derive(Default)]
+struct RewindState {
+    headers: Vec<Arc<[u8]>>,
+    pages: VecDeque<Page>,
+    next_seq: u64,
+    closed: bool,
+}
+
+pub struct RewindBuffer {
+    window: Duration,
+    state: Mutex<RewindState>,
+    notify: Notify,
+}
+
+impl RewindBuffer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::default(),
+            notify: Notify::new(),
+        }
+    }
+
+    /// How far back listeners can rewind
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Store one complete OGG page, dropping pages older than the window
+    pub fn push_page(&self, page: Vec<u8>) {
+        let granule = page
+            .get(6..14)
+            .map_or(0, |g| i64::from_le_bytes(g.try_into().unwrap()));
+        let starts_packet = page
+            .get(5)
+            .is_some_and(|&flags| flags & CONTINUED_PACKET == 0);
+        let now = Instant::now();
+
+        let mut state = self.state.lock().unwrap();
+        // Vorbis header pages all come first with granule position 0
+        if state.pages.is_empty() && state.next_seq == 0 && granule == 0 {
+            state.headers.push(page.into());
+        } else {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.pages.push_back(Page {
+                seq,
+                at: now,
+                data: page.into(),
+                starts_packet,
+            });
+            while state
+                .pages
+                .front()
+                .is_some_and(|p| now.duration_since(p.at) > self.window)
+            {
+                state.pages.pop_front();
+            }
+        }
+        drop(state);
+        self.notify.notify_waiters();
+    }
+
+    /// Time since the newest page arrived (`None` before the first)
+    pub fn since_last_page(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state.pages.back().map(|page| page.at.elapsed())
+    }
+
+    /// The encoder stopped; readers drain what's left and finish
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_waiters();
+    }
+
+    /// Header pages plus a cursor at the first decodable page at most `ago` old
+    ///
+    /// With nothing that recent (e.g. `ago` of zero) it starts at the newest
+    /// decodable page, so playback begins at once instead of mid-packet.
+    pub fn start(&self, ago: Duration) -> (Vec<Arc<[u8]>>, u64) {
+        let state = self.state.lock().unwrap();
+        let since = Instant::now().checked_sub(ago);
+        let cursor = state
+            .pages
+            .iter()
+            .find(|p| p.starts_packet && since.is_none_or(|since| p.at >= since))
+            .or_else(|| state.pages.iter().rev().find(|p| p.starts_packet))
+            .map_or(state.next_seq, |p| p.seq);
+        (state.headers.clone(), cursor)
+    }
+
+    /// Pages from `cursor` on, waiting for new ones; empty once the buffer is closed
+    ///
+    /// A reader that falls further behind than the window skips to the oldest
+    /// page still kept.
+    pub async fn pages_from(&self, cursor: &mut u64) -> Vec<Arc<[u8]>> {
+        loop {
+            // Registered before checking so a push in between still wakes us
+            let notified = self.notify.notified();
+            {
+                let state = self.state.lock().unwrap();
+                if let Some(oldest) = state.pages.front() {
+                    *cursor = (*cursor).max(oldest.seq);
+                }
+                let pages: Vec<_> = state
+                    .pages
+                    .iter()
+                    .filter(|p| p.seq >= *cursor)
+                    .map(|p| p.data.clone())
+                    .collect();
+                if !pages.is_empty() {
+                    *cursor += pages.len() as u64;
+                    return pages;
+                }
+                if state.closed {
+                    return Vec::new();
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Reassembles complete OGG pages from arbitrarily chunked bytes
+#[derive(Default)]
+pub struct PageSplitter {
+    buffer: Vec<u8>,
+}
+
+impl PageSplitter {
+    /// Add bytes, returning any pages they completed
+    pub fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut pages = Vec::new();
+        loop {
+            // Resynchronize on the capture pattern if there's junk in front
+            match self.buffer.windows(4).position(|w| w == b"OggS") {
+                Some(0) => {}
+                Some(skip) => {
+                    self.buffer.drain(..skip);
+                }
+                None => {
+                    let keep = self.buffer.len().min(3);
+                    self.buffer.drain(..self.buffer.len() - keep);
+                    break;
+                }
+            }
+            if self.buffer.len() < PAGE_HEADER_LEN {
+                break;
+            }
+            let segments = self.buffer[26] as usize;
+            let table_end = PAGE_HEADER_LEN + segments;
+            if self.buffer.len() < table_end {
+                break;
+            }
+            let body: usize = self.buffer[PAGE_HEADER_LEN..table_end]
+                .iter()
+                .map(|&len| len as usize)
+                .sum();
+            let page_len = table_end + body;
+            if self.buffer.len() < page_len {
+                break;
+            }
+            pages.push(self.buffer.drain(..page_len).collect());
+        }
+        pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An OGG page with `body_len` bytes of payload in one segment
+    fn page(flags: u8, granule: i64, body_len: u8) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.push(0);
+        page.push(flags);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 12]); // serial, sequence, CRC
+        page.push(1);
+        page.push(body_len);
+        page.extend(std::iter::repeat_n(0xAB, body_len as usize));
+        page
+    }
+
+    #[test]
+    fn splitter_reassembles_pages_across_chunks() {
+        let (first, second) = (page(0, 0, 40), page(0, 1024, 7));
+        let mut stream = b"junk".to_vec();
+        stream.extend_from_slice(&first);
+        stream.extend_from_slice(&second);
+
+        let mut splitter = PageSplitter::default();
+        let pages: Vec<Vec<u8>> = stream.chunks(5).flat_map(|c| splitter.push(c)).collect();
+        assert_eq!(pages, [first, second]);
+        assert!(splitter.push(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn rewind_starts_on_a_packet_and_follows_new_pages() {
+        let buffer = RewindBuffer::new(Duration::from_secs(60));
+        buffer.push_page(page(0, 0, 1));
+        buffer.push_page(page(0, 0, 2));
+        buffer.push_page(page(0, 100, 3));
+        buffer.push_page(page(CONTINUED_PACKET, 200, 4));
+
+        // Headers apart; live skips the page that opens mid-packet
+        let (headers, mut cursor) = buffer.start(Duration::ZERO);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(cursor, 0);
+        let (_, from_start) = buffer.start(Duration::from_secs(60));
+        assert_eq!(from_start, 0);
+
+        assert_eq!(buffer.pages_from(&mut cursor).await.len(), 2);
+        buffer.push_page(page(0, 300, 5));
+        let pages = buffer.pages_from(&mut cursor).await;
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].len(), 28 + 5);
+
+        buffer.close();
+        assert!(buffer.pages_from(&mut cursor).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_reader_past_the_window_skips_to_the_oldest_page() {
+        let buffer = RewindBuffer::new(Duration::from_millis(50));
+        buffer.push_page(page(0, 100, 1));
+        std::thread::sleep(Duration::from_millis(100));
+        buffer.push_page(page(0, 200, 2));
+
+        let mut cursor = 0;
+        let pages = buffer.pages_from(&mut cursor).await;
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].len(), 28 + 2);
+        assert_eq!(cursor, 2);
+    }
+}
@@ -1,5 +1,76 @@
 use serde::{Deserialize, Serialize};
-use zel_core::protocol::zel_service;
+use std::fmt;
+use zel_core::protocol::{zel_service, ClientError, ResourceError};
+
+/// Radio protocol revision advertised in [`StationInfo::protocol_version`]
+///
+/// Bump when adding RPCs or fields a listener might want to gate on. Fields
+/// added to shared structs must carry `#[serde(default)]` so mixed versions
+/// still deserialize each other.
+pub const PROTOCOL_VERSION: u32 = 14;
+
+/// ALPN stations serve the radio protocol on
+pub const ALPN: &[u8] = b"zelfm/1";
+
+/// First protocol version with `signed_info`
+pub const SIGNED_INFO_VERSION: u32 = 3;
+
+/// First protocol version that advertises [`StationInfo::codec`]
+pub const CODEC_VERSION: u32 = 4;
+
+/// First protocol version with `listen_at`
+pub const QUALITY_VERSION: u32 = 5;
+
+/// First protocol version with `health`
+pub const HEALTH_VERSION: u32 = 6;
+
+/// First protocol version that can stream [`StreamCodec::Pcm`]
+pub const PCM_VERSION: u32 = 7;
+
+/// First protocol version with `vote_skip`
+pub const VOTE_SKIP_VERSION: u32 = 8;
+
+/// First protocol version with `artwork`
+pub const ARTWORK_VERSION: u32 = 9;
+
+/// First protocol version with `event_stream`
+pub const EVENTS_VERSION: u32 = 10;
+
+/// First protocol version with `set_station_info`
+pub const SET_INFO_VERSION: u32 = 11;
+
+/// First protocol version that reports [`ChannelLevels::encoded_bitrate`]
+pub const ENCODED_BITRATE_VERSION: u32 = 12;
+
+/// First protocol version that signs info under [`SIGNING_CONTEXT`] (and
+/// whose relays pass the upstream's signed info through)
+pub const SIGNING_CONTEXT_VERSION: u32 = 13;
+
+/// Prefixed to [`SignedStationInfo::payload`] before signing, so the
+/// signature can't stand in for anything else the node key signs
+pub const SIGNING_CONTEXT: &[u8] = b"zelfm station info v1\n";
+
+/// First protocol version with `listen_adaptive`; before it, `listen` could
+/// switch quality tiers mid-stream, and from it `listen` stays one OGG link
+pub const ADAPTIVE_VERSION: u32 = 14;
+
+/// Stream reset code sent when a listener reaches the station's max session length
+pub const RESET_SESSION_LIMIT: u32 = 1;
+
+/// Stream reset code sent when a listener makes no progress within the station's stall timeout
+pub const RESET_STALLED: u32 = 2;
+
+/// Stream stop code a listener sends when it's done listening (not an error)
+pub const STOP_LISTENER_DONE: u32 = 0;
+
+/// Why a `listen` stream was reset, for showing to the listener
+pub fn reset_reason(code: u64) -> String {
+    match u32::try_from(code) {
+        Ok(RESET_SESSION_LIMIT) => "the station's session time limit was reached".to_string(),
+        Ok(RESET_STALLED) => "you fell too far behind (connection too slow)".to_string(),
+        _ => format!("the station reset the stream (code {})", code),
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StationInfo {
@@ -9,16 +80,374 @@ pub struct StationInfo {
     pub sample_rate: u32, // e.g., 44100 Hz
     pub channels: u8,     // e.g., 2 (stereo)
     pub listeners: usize,
+    /// 0 for broadcasters that predate version negotiation
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// Optional discovery metadata (absent from older broadcasters)
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub website: Option<String>,
+    /// How far back `listen_from` can start (0 = live only)
+    #[serde(default)]
+    pub rewind_secs: u32,
+    /// How the `listen` stream is encoded (Vorbis from older broadcasters)
+    #[serde(default)]
+    pub codec: StreamCodec,
+    /// Vorbis quality range `listen_at` accepts (`None` = fixed quality)
+    #[serde(default)]
+    pub quality_range: Option<(f32, f32)>,
 }
 
+/// Encoding of the audio a station streams to listeners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StreamCodec {
+    /// Lossy OGG Vorbis
+    #[default]
+    Vorbis,
+    /// Lossless 16-bit FLAC (requires the `flac` feature to broadcast)
+    Flac,
+    /// Uncompressed 32-bit float in sequenced frames: no encoder delay, at
+    /// about twice FLAC's bandwidth
+    Pcm,
+}
+
+/// Sample depth of [`StreamCodec::Flac`] streams
+pub const FLAC_BITS_PER_SAMPLE: u32 = 16;
+
+impl fmt::Display for StreamCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vorbis => write!(f, "vorbis"),
+            Self::Flac => write!(f, "flac"),
+            Self::Pcm => write!(f, "pcm"),
+        }
+    }
+}
+
+/// `listener_id` of messages the station itself posts (announcements etc.)
+pub const STATION_LISTENER_ID: usize = usize::MAX;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub listener_id: usize,
     pub nickname: Option<String>,
     pub message: String,
     pub timestamp: u64,
+    /// Station-wide sequence number (starts at 1; 0 from older broadcasters)
+    #[serde(default)]
+    pub seq: u64,
+}
+
+/// Quick liveness summary for monitors and load balancers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthStatus {
+    pub uptime_secs: u64,
+    pub listeners: usize,
+    /// A source (or the upstream, when relaying) produced audio recently
+    pub audio_flowing: bool,
+    /// Milliseconds since the last audio arrived (`None` before the first)
+    pub last_audio_ms: Option<u64>,
+}
+
+/// Broadcaster output levels, linear full scale (1.0 = 0 dBFS)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelLevels {
+    /// Peak of the most recent block, per channel
+    pub peak: Vec<f32>,
+    /// RMS of the most recent block, per channel
+    pub rms: Vec<f32>,
+    /// Samples at or above full scale since the station started
+    pub clipped_samples: u64,
+    /// A-weighted RMS of the most recent block (empty unless loudness metering is on)
+    #[serde(default)]
+    pub rms_a: Vec<f32>,
+    /// 4x oversampled true peak of the most recent block (empty unless loudness metering is on)
+    #[serde(default)]
+    pub true_peak: Vec<f32>,
+    /// Encoded audio the station is currently sending to all listeners combined
+    #[serde(default)]
+    pub outbound_bytes_per_sec: u64,
+    /// Bits per second the encoder actually produced over the last 10 s of
+    /// audio, next to the nominal [`StationInfo::bitrate`] (0 when not measured)
+    #[serde(default)]
+    pub encoded_bitrate: u32,
+}
+
+/// A listener's track request, queued for the operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackRequest {
+    pub listener_id: usize,
+    pub nickname: Option<String>,
+    pub query: String,
+    pub timestamp: u64,
+}
+
+/// Station metadata to change with `set_station_info`; fields left `None`
+/// stay as they are, and an empty genre or website clears it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StationInfoUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub website: Option<String>,
+}
+
+impl StationInfoUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.genre.is_none()
+            && self.tags.is_none()
+            && self.website.is_none()
+    }
+}
+
+/// Where the vote to skip the current track stands after a `vote_skip`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkipVote {
+    pub votes: usize,
+    /// Votes the track needs, from the station's share and current listeners
+    pub needed: usize,
+    /// This vote carried it and the track is being skipped
+    pub skipped: bool,
+}
+
+/// Something that happened at the station, for `event_stream`
+///
+/// Serialized with an `event` tag, e.g. `{"event": "listener_joined", ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StationEvent {
+    /// The source started a track; `title` is the playlist's title or the
+    /// file's tags, when there is one
+    TrackChanged {
+        title: Option<String>,
+    },
+    ListenerJoined {
+        listener_id: usize,
+        nickname: Option<String>,
+        /// Listeners connected now, this one included
+        listeners: usize,
+    },
+    ListenerLeft {
+        listener_id: usize,
+        nickname: Option<String>,
+        listeners: usize,
+    },
+    /// The operator changed the station's name, description, or other metadata
+    InfoChanged {
+        name: String,
+        description: String,
+        genre: Option<String>,
+        tags: Vec<String>,
+        website: Option<String>,
+    },
+    /// The operator paused the source
+    Paused,
+    Resumed,
+    /// The station is shutting down; the stream ends after this
+    OffAir,
+    /// An event added by a newer broadcaster
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for StationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |nickname: &Option<String>, id: &usize| {
+            nickname
+                .clone()
+                .unwrap_or_else(|| format!("Listener {}", id))
+        };
+        match self {
+            Self::TrackChanged { title: Some(title) } => write!(f, "Now playing: {}", title),
+            Self::TrackChanged { title: None } => write!(f, "New track"),
+            Self::ListenerJoined {
+                listener_id,
+                nickname,
+                listeners,
+            } => write!(
+                f,
+                "{} joined ({} listening)",
+                name(nickname, listener_id),
+                listeners
+            ),
+            Self::ListenerLeft {
+                listener_id,
+                nickname,
+                listeners,
+            } => write!(
+                f,
+                "{} left ({} listening)",
+                name(nickname, listener_id),
+                listeners
+            ),
+            Self::InfoChanged {
+                name, description, ..
+            } => write!(f, "Station is now {}: {}", name, description),
+            Self::Paused => write!(f, "Paused"),
+            Self::Resumed => write!(f, "Resumed"),
+            Self::OffAir => write!(f, "Off the air"),
+            Self::Unknown => write!(f, "(unknown event)"),
+        }
+    }
+}
+
+/// Feature flags for the station's active audio source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceCapabilities {
+    pub seekable: bool,        // false for live input
+    pub has_metadata: bool,    // source carries track tags
+    pub codec: String,         // e.g., "vorbis", "mp3", "pcm"
+    pub sample_format: String, // e.g., "s16", "f32"
 }
 
+impl Default for SourceCapabilities {
+    fn default() -> Self {
+        Self {
+            seekable: false,
+            has_metadata: false,
+            codec: "unknown".to_string(),
+            sample_format: "unknown".to_string(),
+        }
+    }
+}
+
+impl StationInfo {
+    /// Whether the broadcaster speaks at least protocol `version`
+    pub fn supports(&self, version: u32) -> bool {
+        self.protocol_version >= version
+    }
+}
+
+/// [`StationInfo`] signed with the broadcaster's node key
+///
+/// The signature covers [`SIGNING_CONTEXT`] followed by the exact JSON bytes
+/// in `payload`, so verification doesn't depend on both sides serializing the
+/// struct identically. A relay or proxy can't pass off another station's info
+/// as its own: the signature only checks out against the node that made it.
+/// A relay signs its own info and carries the upstream's in `upstream`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedStationInfo {
+    /// JSON-encoded [`StationInfo`]
+    pub payload: String,
+    pub signature: iroh::Signature,
+    /// From a relay: the upstream station's node ID and its signed info,
+    /// passed through unchanged
+    #[serde(default)]
+    pub upstream: Option<(iroh::PublicKey, Box<SignedStationInfo>)>,
+}
+
+impl SignedStationInfo {
+    pub fn sign(info: &StationInfo, key: &iroh::SecretKey) -> Self {
+        let payload = serde_json::to_string(info).expect("StationInfo serializes");
+        let signature = key.sign(&Self::signed_bytes(&payload));
+        Self {
+            payload,
+            signature,
+            upstream: None,
+        }
+    }
+
+    /// The info, if `station` (the node we connected to) signed it
+    pub fn verify(&self, station: &iroh::PublicKey) -> anyhow::Result<StationInfo> {
+        station
+            .verify(&Self::signed_bytes(&self.payload), &self.signature)
+            .map_err(|_| anyhow::anyhow!("station info was not signed by {}", station))?;
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+
+    /// A relay's upstream and its info, if the upstream signed it
+    pub fn verify_upstream(&self) -> Option<anyhow::Result<(iroh::PublicKey, StationInfo)>> {
+        let (station, signed) = self.upstream.as_ref()?;
+        Some(signed.verify(station).map(|info| (*station, info)))
+    }
+
+    fn signed_bytes(payload: &str) -> Vec<u8> {
+        [SIGNING_CONTEXT, payload.as_bytes()].concat()
+    }
+}
+
+/// Structured failure returned by every [`RadioService`] method
+///
+/// zel_core forwards server errors as their `Display` text, so `Display` is the
+/// JSON wire form; clients recover the variant with [`RadioError::from_client_error`]
+/// and show people [`RadioError::message`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "error", content = "detail", rename_all = "snake_case")]
+pub enum RadioError {
+    /// The connection has no listener identity attached
+    UnknownListener,
+    /// Too many calls; try again after this many seconds
+    RateLimited {
+        retry_after_secs: u64,
+    },
+    /// The arguments were rejected
+    InvalidRequest(String),
+    /// The station isn't accepting more listeners
+    StationFull,
+    /// The caller isn't allowed to do this
+    Unauthorized,
+    /// The broadcaster is going off air
+    ShuttingDown,
+    Internal(String),
+}
+
+impl RadioError {
+    /// Human-readable description
+    pub fn message(&self) -> String {
+        match self {
+            Self::UnknownListener => "listener info not found".to_string(),
+            Self::RateLimited { retry_after_secs } => {
+                format!("too many requests, try again in {}s", retry_after_secs)
+            }
+            Self::InvalidRequest(reason) => format!("invalid request: {}", reason),
+            Self::StationFull => "station is full".to_string(),
+            Self::Unauthorized => "not authorized".to_string(),
+            Self::ShuttingDown => "station is shutting down".to_string(),
+            Self::Internal(reason) => format!("internal error: {}", reason),
+        }
+    }
+
+    /// Recover the station's error from a failed client call
+    ///
+    /// The server wraps the error's JSON in its own message ("Callback
+    /// execution failed: ..."), so this tries the text after each `": "`
+    /// as well as the whole of it.
+    pub fn from_client_error(error: &ClientError) -> Option<Self> {
+        match error {
+            ClientError::Resource(ResourceError::CallbackError(text)) => {
+                std::iter::once(text.as_str())
+                    .chain(text.match_indices(": ").map(|(at, _)| &text[at + 2..]))
+                    .find_map(|json| serde_json::from_str(json).ok())
+            }
+            _ => None,
+        }
+    }
+
+    /// Describe any client error, preferring the station's own message
+    pub fn describe(error: &ClientError) -> String {
+        Self::from_client_error(error)
+            .map(|e| e.message())
+            .unwrap_or_else(|| error.to_string())
+    }
+}
+
+impl fmt::Display for RadioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl std::error::Error for RadioError {}
+
 /// Connection-level extension to track listener identity
 #[derive(Debug, Clone)]
 pub struct ListenerInfo {
@@ -29,14 +458,207 @@ pub struct ListenerInfo {
 #[zel_service(name = "radio")]
 pub trait RadioService {
     #[method(name = "info")]
-    async fn get_info(&self) -> Result<StationInfo, String>;
+    async fn get_info(&self) -> Result<StationInfo, RadioError>;
+
+    /// [`StationInfo`] signed with the station's node key
+    #[method(name = "signed_info")]
+    async fn get_signed_info(&self) -> Result<SignedStationInfo, RadioError>;
+
+    #[method(name = "capabilities")]
+    async fn capabilities(&self) -> Result<SourceCapabilities, RadioError>;
 
     #[method(name = "send_chat")]
-    async fn send_chat(&self, message: String) -> Result<(), String>;
+    async fn send_chat(&self, message: String) -> Result<(), RadioError>;
+
+    /// Recent chat messages sent at or after `since_timestamp` (all if `None`)
+    #[method(name = "chat_history")]
+    async fn get_chat_history(
+        &self,
+        since_timestamp: Option<u64>,
+    ) -> Result<Vec<ChatMessage>, RadioError>;
+
+    /// Uptime, listener count, and whether audio is flowing; cheap enough to poll
+    #[method(name = "health")]
+    async fn health(&self) -> Result<HealthStatus, RadioError>;
+
+    /// Current output levels and clip count
+    #[method(name = "levels")]
+    async fn get_levels(&self) -> Result<ChannelLevels, RadioError>;
+
+    /// Ask the operator to play something (rate-limited per listener)
+    #[method(name = "request_track")]
+    async fn request_track(&self, query: String) -> Result<(), RadioError>;
+
+    /// Pending track requests, oldest first
+    #[method(name = "requests")]
+    async fn get_requests(&self) -> Result<Vec<TrackRequest>, RadioError>;
+
+    /// The current track's cover art, or else the station logo: JPEG or PNG
+    /// bytes, `None` when there's neither
+    #[method(name = "artwork")]
+    async fn get_artwork(&self) -> Result<Option<Vec<u8>>, RadioError>;
+
+    /// Vote to skip the current track; once more than the station's share of
+    /// listeners agree, it's skipped. One vote per listener per track.
+    #[method(name = "vote_skip")]
+    async fn vote_skip(&self) -> Result<SkipVote, RadioError>;
+
+    /// Change the station's name, description, genre, tags, or website on
+    /// the air (operators only: node IDs the station was started with
+    /// `--operator`); returns the updated info
+    #[method(name = "set_station_info")]
+    async fn set_station_info(&self, update: StationInfoUpdate) -> Result<StationInfo, RadioError>;
 
     #[subscription(name = "chat_stream", item = "ChatMessage")]
-    async fn chat_stream(&self) -> Result<(), String>;
+    async fn chat_stream(&self) -> Result<(), RadioError>;
+
+    /// Typed station events (track changes, joins and leaves, pause and
+    /// resume, going off the air) as they happen, for dashboards and tools
+    #[subscription(name = "event_stream", item = "StationEvent")]
+    async fn event_stream(&self) -> Result<(), RadioError>;
 
+    /// The station's encoded audio, in order, until the station ends it
+    ///
+    /// Audio streams are sent at a higher QUIC priority than chat, events,
+    /// and RPC replies on the same connection, so on a congested link those
+    /// wait behind audio rather than the other way round. Each stream arrives
+    /// in order, but nothing orders one stream against another: a chat
+    /// message can arrive before or after the audio playing when it was sent.
     #[stream(name = "listen")]
-    async fn listen(&self) -> Result<(), String>;
+    async fn listen(&self) -> Result<(), RadioError>;
+
+    /// Like `listen`, but a station with adaptive bitrate may step the stream
+    /// down (and back up) a quality tier, starting a new link of a chained
+    /// OGG stream each time; only for listeners that decode chained streams
+    #[stream(name = "listen_adaptive")]
+    async fn listen_adaptive(&self) -> Result<(), RadioError>;
+
+    /// Like `listen`, but start `seconds_ago` in the past (clamped to `rewind_secs`)
+    #[stream(name = "listen_from")]
+    async fn listen_from(&self, seconds_ago: u32) -> Result<(), RadioError>;
+
+    /// Listen live at a Vorbis quality within the station's `quality_range`;
+    /// listeners asking for the same quality share one encoder
+    #[stream(name = "listen_at")]
+    async fn listen_at(&self, quality: f32) -> Result<(), RadioError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn station_info_from_older_broadcaster() {
+        // Exactly what the first release serialized
+        let old = r#"{
+            "name": "Old FM",
+            "description": "Before metadata",
+            "bitrate": 128000,
+            "sample_rate": 44100,
+            "channels": 2,
+            "listeners": 3
+        }"#;
+
+        let info: StationInfo = serde_json::from_str(old).unwrap();
+        assert_eq!(info.name, "Old FM");
+        assert_eq!(info.listeners, 3);
+        assert_eq!(info.protocol_version, 0);
+        assert!(!info.supports(PROTOCOL_VERSION));
+        assert!(info.genre.is_none());
+        assert!(info.tags.is_empty());
+    }
+
+    #[test]
+    fn chat_message_without_seq() {
+        let old = r#"{"listener_id": 1, "nickname": null, "message": "hi", "timestamp": 5}"#;
+        let msg: ChatMessage = serde_json::from_str(old).unwrap();
+        assert_eq!(msg.seq, 0);
+    }
+
+    #[test]
+    fn station_events_are_tagged_and_tolerate_new_kinds() {
+        let joined = StationEvent::ListenerJoined {
+            listener_id: 4,
+            nickname: Some("ada".to_string()),
+            listeners: 2,
+        };
+        let json = serde_json::to_string(&joined).unwrap();
+        assert!(json.contains(r#""event":"listener_joined""#));
+        assert_eq!(serde_json::from_str::<StationEvent>(&json).unwrap(), joined);
+
+        let newer: StationEvent =
+            serde_json::from_str(r#"{"event": "encoder_restarted"}"#).unwrap();
+        assert_eq!(newer, StationEvent::Unknown);
+    }
+
+    #[test]
+    fn radio_error_survives_the_wire() {
+        let error = RadioError::RateLimited {
+            retry_after_secs: 12,
+        };
+        // Servers send the Display text of a ResourceError wrapping the JSON
+        let sent = ResourceError::CallbackError(error.to_string()).to_string();
+        assert!(sent.starts_with("Callback execution failed: "));
+        let client_error = ClientError::Resource(ResourceError::CallbackError(sent));
+        assert_eq!(RadioError::from_client_error(&client_error), Some(error));
+        assert!(RadioError::describe(&client_error).contains("12s"));
+
+        // A reason that itself contains ": " still comes back whole
+        let error = RadioError::Internal("encoder: out of memory".to_string());
+        let sent = ResourceError::CallbackError(error.to_string()).to_string();
+        let client_error = ClientError::Resource(ResourceError::CallbackError(sent));
+        assert_eq!(RadioError::from_client_error(&client_error), Some(error));
+    }
+
+    #[test]
+    fn signed_info_verifies_only_against_the_signer() {
+        let info: StationInfo = serde_json::from_str(
+            r#"{"name": "Night Shift", "description": "", "bitrate": 128000,
+                "sample_rate": 44100, "channels": 2, "listeners": 0}"#,
+        )
+        .unwrap();
+        let key = iroh::SecretKey::from_bytes(&[7; 32]);
+        let other = iroh::SecretKey::from_bytes(&[8; 32]);
+
+        let signed = SignedStationInfo::sign(&info, &key);
+        assert_eq!(signed.verify(&key.public()).unwrap().name, "Night Shift");
+        assert!(signed.verify(&other.public()).is_err());
+
+        let mut tampered = signed.clone();
+        tampered.payload = tampered.payload.replace("Night Shift", "Day Shift");
+        assert!(tampered.verify(&key.public()).is_err());
+    }
+
+    #[test]
+    fn signed_info_needs_the_context_and_carries_the_upstream() {
+        let info: StationInfo = serde_json::from_str(
+            r#"{"name": "Night Shift", "description": "", "bitrate": 128000,
+                "sample_rate": 44100, "channels": 2, "listeners": 0}"#,
+        )
+        .unwrap();
+        let station = iroh::SecretKey::from_bytes(&[7; 32]);
+        let relay = iroh::SecretKey::from_bytes(&[8; 32]);
+
+        // A bare signature over the payload, as made outside the context
+        let mut bare = SignedStationInfo::sign(&info, &station);
+        bare.signature = station.sign(bare.payload.as_bytes());
+        assert!(bare.verify(&station.public()).is_err());
+
+        let mut relayed = SignedStationInfo::sign(&info, &relay);
+        assert!(relayed.verify_upstream().is_none());
+        relayed.upstream = Some((
+            station.public(),
+            Box::new(SignedStationInfo::sign(&info, &station)),
+        ));
+        let json = serde_json::to_string(&relayed).unwrap();
+        let mut relayed: SignedStationInfo = serde_json::from_str(&json).unwrap();
+        assert!(relayed.verify(&relay.public()).is_ok());
+        let (upstream, upstream_info) = relayed.verify_upstream().unwrap().unwrap();
+        assert_eq!(upstream, station.public());
+        assert_eq!(upstream_info.name, "Night Shift");
+
+        // The relay can't swap in a node ID the upstream info wasn't signed by
+        relayed.upstream.as_mut().unwrap().0 = relay.public();
+        assert!(relayed.verify_upstream().unwrap().is_err());
+    }
 }
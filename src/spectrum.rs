@@ -0,0 +1,137 @@
+//! Lightweight FFT spectrum analyzer for visualizing decoded audio.
+
+use std::f32::consts::PI;
+
+/// Default FFT window length (samples, after decimation)
+pub const DEFAULT_FFT_SIZE: usize = 512;
+
+/// Every Nth mono sample is analyzed, keeping the work off the playback path
+pub const DECIMATION: usize = 4;
+
+const BAR_GLYPHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Accumulates mono samples and emits magnitude bins once per full window
+pub struct SpectrumAnalyzer {
+    fft_size: usize,
+    window: Vec<f32>,
+    pending: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// `fft_size` must be a power of two
+    pub fn new(fft_size: usize) -> anyhow::Result<Self> {
+        if fft_size < 8 || !fft_size.is_power_of_two() {
+            anyhow::bail!("FFT size must be a power of two >= 8 (got {})", fft_size);
+        }
+
+        // Hann window to reduce spectral leakage
+        let window = (0..fft_size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / fft_size as f32).cos())
+            .collect();
+
+        Ok(Self {
+            fft_size,
+            window,
+            pending: Vec::with_capacity(fft_size),
+        })
+    }
+
+    /// Feed mono samples; returns `fft_size / 2` magnitude bins when a window fills
+    pub fn push(&mut self, samples: &[f32]) -> Option<Vec<f32>> {
+        let mut result = None;
+
+        for &sample in samples {
+            self.pending.push(sample);
+            if self.pending.len() == self.fft_size {
+                result = Some(self.analyze());
+                self.pending.clear();
+            }
+        }
+
+        result
+    }
+
+    fn analyze(&self) -> Vec<f32> {
+        let mut re: Vec<f32> = self
+            .pending
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| s * w)
+            .collect();
+        let mut im = vec![0.0; self.fft_size];
+
+        fft_in_place(&mut re, &mut im);
+
+        let scale = 2.0 / self.fft_size as f32;
+        (0..self.fft_size / 2)
+            .map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt() * scale)
+            .collect()
+    }
+}
+
+/// Iterative radix-2 Cooley-Tukey FFT; lengths must be equal powers of two
+fn fft_in_place(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0f32, 0.0f32);
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Render bins as a row of `bands` bar glyphs on a log-frequency scale
+pub fn render_bars(bins: &[f32], bands: usize) -> String {
+    if bins.len() < 2 || bands == 0 {
+        return String::new();
+    }
+
+    let max_bin = bins.len() as f32;
+    (0..bands)
+        .map(|band| {
+            // Log spacing so bass isn't squeezed into a single bar
+            let lo = max_bin.powf(band as f32 / bands as f32) as usize;
+            let hi = (max_bin.powf((band + 1) as f32 / bands as f32) as usize).max(lo + 1);
+            let peak = bins[lo.min(bins.len() - 1)..hi.min(bins.len())]
+                .iter()
+                .fold(0.0f32, |acc, &m| acc.max(m));
+
+            // Map -60..0 dBFS onto the glyph range
+            let db = 20.0 * peak.max(1e-6).log10();
+            let level = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+            BAR_GLYPHS[(level * (BAR_GLYPHS.len() - 1) as f32).round() as usize]
+        })
+        .collect()
+}
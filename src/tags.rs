@@ -0,0 +1,66 @@
+//! Track metadata from file tags, for the "now playing" name and ReplayGain.
+//!
+//! OGG Vorbis and FLAC files carry Vorbis comments (`TITLE`, `ARTIST`,
+//! `ALBUM`, `REPLAYGAIN_TRACK_GAIN`, ...); Symphonia maps those, like ID3
+//! frames, onto its standard keys. Keys it doesn't map are matched by name,
+//! case-insensitively, as Vorbis comments are.
+
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// `REPLAYGAIN_TRACK_GAIN`, in dB
+    pub replay_gain_db: Option<f32>,
+    /// Embedded cover art (see [`crate::artwork`]); filled in from the
+    /// file's pictures, not its tags
+    pub artwork: Option<Vec<u8>>,
+}
+
+impl TrackTags {
+    /// Pick out the fields zelfm uses; the first non-empty value of each wins
+    pub fn from_tags(tags: &[Tag]) -> Self {
+        let find = |std_key: StandardTagKey, name: &str| {
+            tags.iter()
+                .filter(|tag| tag.std_key == Some(std_key) || tag.key.eq_ignore_ascii_case(name))
+                .map(|tag| tag.value.to_string().trim().to_string())
+                .find(|value| !value.is_empty())
+        };
+
+        Self {
+            title: find(StandardTagKey::TrackTitle, "TITLE"),
+            artist: find(StandardTagKey::Artist, "ARTIST"),
+            album: find(StandardTagKey::Album, "ALBUM"),
+            replay_gain_db: find(StandardTagKey::ReplayGainTrackGain, "REPLAYGAIN_TRACK_GAIN")
+                .and_then(|value| parse_gain(&value)),
+            artwork: None,
+        }
+    }
+
+    /// "Artist - Title", or just the title; `None` without a title
+    pub fn now_playing(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        Some(match &self.artist {
+            Some(artist) => format!("{} - {}", artist, title),
+            None => title.clone(),
+        })
+    }
+}
+
+/// Parse a ReplayGain value such as `-6.48 dB` or `+1.2`
+fn parse_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .len()
+        .checked_sub(2)
+        .filter(|&split| value.is_char_boundary(split))
+        .filter(|&split| value[split..].eq_ignore_ascii_case("db"))
+        .map_or(value, |split| &value[..split]);
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|gain| gain.is_finite())
+}
@@ -0,0 +1,86 @@
+//! Shareable station tickets.
+//!
+//! A ticket bundles the broadcaster's node ID with its current relay URL and
+//! direct addresses, so listeners can dial straight away instead of waiting on
+//! discovery. Node IDs still work; tickets are just faster and more reliable
+//! through NAT. Tickets go stale if the broadcaster's addresses change, while
+//! a node ID stays valid for as long as the node keeps its key.
+
+use iroh::EndpointAddr;
+use std::fmt;
+use std::str::FromStr;
+
+const PREFIX: &str = "zelfm";
+const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StationTicket {
+    pub addr: EndpointAddr,
+}
+
+impl StationTicket {
+    pub fn new(addr: EndpointAddr) -> Self {
+        Self { addr }
+    }
+}
+
+impl fmt::Display for StationTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = serde_json::to_vec(&self.addr).map_err(|_| fmt::Error)?;
+        write!(f, "{}{}", PREFIX, base32_encode(&bytes))
+    }
+}
+
+impl FromStr for StationTicket {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = s.trim().strip_prefix(PREFIX).ok_or_else(|| {
+            anyhow::anyhow!("not a zelfm ticket (should start with '{}')", PREFIX)
+        })?;
+        let bytes = base32_decode(body)
+            .ok_or_else(|| anyhow::anyhow!("ticket is corrupted (invalid characters)"))?;
+        let addr = serde_json::from_slice(&bytes)
+            .map_err(|e| anyhow::anyhow!("ticket is corrupted: {}", e))?;
+        Ok(Self { addr })
+    }
+}
+
+/// RFC 4648 base32, lowercase, no padding
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in text.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c.to_ascii_lowercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}